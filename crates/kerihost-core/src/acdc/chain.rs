@@ -0,0 +1,739 @@
+//! Chained credential verification
+//!
+//! An ACDC's edge section (`e`) links it to other credentials. Each edge
+//! names the far node SAID (`n`), optionally the schema that node must have
+//! (`s`), and an operator (`o`) constraining who may chain to it:
+//! - `I2I`: the issuer of this credential must be the issuee of the far node
+//! - `NI2I`: no issuer/issuee constraint
+//! - `DI2I`: like `I2I`, but the issuer may also be a delegate of the issuee
+//!
+//! The verifier walks edges recursively and returns a proof tree recording,
+//! for every node, what was checked and which violations were found.
+//! Credentials, their status (from their TEL), their issuance (issuer
+//! signatures and KEL anchors) and delegation relationships (from KELs) are
+//! supplied by a [`CredentialResolver`].
+
+use super::Acdc;
+use serde::Serialize;
+use std::collections::HashSet;
+
+/// Default limit on chain depth
+pub const DEFAULT_MAX_CHAIN_DEPTH: usize = 16;
+
+/// Edge operator constraining issuer/issuee relationships
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub enum EdgeOperator {
+    /// Issuer-to-issuee
+    I2I,
+    /// Not issuer-to-issuee
+    NI2I,
+    /// Delegated issuer-to-issuee
+    DI2I,
+}
+
+impl EdgeOperator {
+    /// Parse operator from its code
+    pub fn parse(code: &str) -> Option<Self> {
+        match code {
+            "I2I" => Some(EdgeOperator::I2I),
+            "NI2I" => Some(EdgeOperator::NI2I),
+            "DI2I" => Some(EdgeOperator::DI2I),
+            _ => None,
+        }
+    }
+}
+
+/// Credential status as recorded in its registry (TEL)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CredentialStatus {
+    /// Issued and not revoked
+    Issued,
+    /// Revoked by its issuer
+    Revoked,
+    /// No status information available
+    Unknown,
+}
+
+/// Source of credentials, credential status and delegation relationships
+pub trait CredentialResolver {
+    /// Look up a credential by SAID
+    fn resolve(&self, said: &str) -> Option<Acdc>;
+
+    /// Get the current status of a credential from its registry
+    fn status(&self, credential: &Acdc) -> CredentialStatus;
+
+    /// Get the delegator of a delegated AID, from its KEL
+    fn delegator(&self, aid: &str) -> Option<String>;
+
+    /// Check that the issuer signed the credential and anchored it in its
+    /// KEL, returning why not otherwise
+    fn verify_issuance(&self, credential: &Acdc) -> Result<(), String>;
+}
+
+/// Problem found while verifying a credential chain
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ChainViolation {
+    /// Linked credential could not be found
+    Unresolved { said: String },
+    /// Credential has been revoked
+    Revoked { said: String },
+    /// Credential status could not be determined
+    StatusUnknown { said: String },
+    /// Issuer signature or KEL anchor of the credential did not verify
+    IssuanceUnverified { said: String, reason: String },
+    /// Linked credential has a different schema than the edge requires
+    SchemaMismatch {
+        said: String,
+        expected: String,
+        actual: String,
+    },
+    /// `I2I`: issuer is not the issuee of the linked credential
+    IssuerNotIssuee {
+        said: String,
+        issuer: String,
+        issuee: Option<String>,
+    },
+    /// `DI2I`: issuer is neither the issuee nor one of its delegates
+    DelegationNotFound {
+        said: String,
+        issuer: String,
+        issuee: Option<String>,
+    },
+    /// Credential links back to itself
+    Cycle { said: String },
+    /// Chain is deeper than the verifier allows
+    DepthExceeded { said: String },
+    /// Edge section is malformed or not disclosed
+    InvalidEdge { said: String, reason: String },
+}
+
+impl ChainViolation {
+    /// Check whether this violation breaks the chain of authority
+    ///
+    /// These are the illegitimate delegation cases: authority derived from
+    /// a revoked or missing credential, from a credential outside the scope
+    /// the edge requires, or from a party that never held it.
+    pub fn is_illegitimate_delegation(&self) -> bool {
+        matches!(
+            self,
+            ChainViolation::Unresolved { .. }
+                | ChainViolation::Revoked { .. }
+                | ChainViolation::IssuanceUnverified { .. }
+                | ChainViolation::SchemaMismatch { .. }
+                | ChainViolation::IssuerNotIssuee { .. }
+                | ChainViolation::DelegationNotFound { .. }
+        )
+    }
+}
+
+/// Verified edge in a proof tree
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct ProofEdge {
+    /// Edge label
+    pub label: String,
+    /// Operator applied
+    pub operator: EdgeOperator,
+    /// Far node SAID
+    pub node: String,
+    /// Required far node schema
+    pub schema: Option<String>,
+    /// Proof of the far node, if it was resolved
+    pub proof: Option<Box<ProofNode>>,
+}
+
+/// Verified credential in a proof tree
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct ProofNode {
+    /// Credential SAID
+    pub said: String,
+    /// Issuer AID
+    pub issuer: String,
+    /// Issuee AID, if disclosed
+    pub issuee: Option<String>,
+    /// Schema SAID
+    pub schema: String,
+    /// Registry status
+    pub status: CredentialStatus,
+    /// Outgoing edges
+    pub edges: Vec<ProofEdge>,
+    /// Violations found at this node and its edges
+    pub violations: Vec<ChainViolation>,
+}
+
+impl ProofNode {
+    /// Check whether the whole chain below this node verified
+    pub fn is_valid(&self) -> bool {
+        self.all_violations().is_empty()
+    }
+
+    /// Get violations from this node and every node below it
+    pub fn all_violations(&self) -> Vec<&ChainViolation> {
+        let mut violations: Vec<&ChainViolation> = self.violations.iter().collect();
+        for edge in &self.edges {
+            if let Some(ref proof) = edge.proof {
+                violations.extend(proof.all_violations());
+            }
+        }
+        violations
+    }
+
+    /// Get violations that make the chain an illegitimate delegation
+    pub fn illegitimate_delegations(&self) -> Vec<&ChainViolation> {
+        self.all_violations()
+            .into_iter()
+            .filter(|v| v.is_illegitimate_delegation())
+            .collect()
+    }
+}
+
+/// Recursive verifier for chained credentials
+pub struct ChainVerifier<'a, R: CredentialResolver> {
+    resolver: &'a R,
+    max_depth: usize,
+}
+
+impl<'a, R: CredentialResolver> ChainVerifier<'a, R> {
+    /// Create new chain verifier
+    pub fn new(resolver: &'a R) -> Self {
+        ChainVerifier {
+            resolver,
+            max_depth: DEFAULT_MAX_CHAIN_DEPTH,
+        }
+    }
+
+    /// Set the maximum chain depth
+    pub fn with_max_depth(mut self, max_depth: usize) -> Self {
+        self.max_depth = max_depth;
+        self
+    }
+
+    /// Verify a credential and everything it chains to
+    pub fn verify(&self, credential: &Acdc) -> ProofNode {
+        let mut path = HashSet::new();
+        self.verify_node(credential, &mut path, 0)
+    }
+
+    fn verify_node(&self, credential: &Acdc, path: &mut HashSet<String>, depth: usize) -> ProofNode {
+        let status = self.resolver.status(credential);
+        let mut node = ProofNode {
+            said: credential.said.clone(),
+            issuer: credential.issuer.clone(),
+            issuee: credential.issuee().map(|s| s.to_string()),
+            schema: credential.schema.clone(),
+            status,
+            edges: vec![],
+            violations: vec![],
+        };
+
+        match status {
+            CredentialStatus::Issued => {}
+            CredentialStatus::Revoked => node.violations.push(ChainViolation::Revoked {
+                said: credential.said.clone(),
+            }),
+            CredentialStatus::Unknown => node.violations.push(ChainViolation::StatusUnknown {
+                said: credential.said.clone(),
+            }),
+        }
+        if let Err(reason) = self.resolver.verify_issuance(credential) {
+            node.violations.push(ChainViolation::IssuanceUnverified {
+                said: credential.said.clone(),
+                reason,
+            });
+        }
+
+        let edges = match parse_edges(credential) {
+            Ok(edges) => edges,
+            Err(reason) => {
+                node.violations.push(ChainViolation::InvalidEdge {
+                    said: credential.said.clone(),
+                    reason,
+                });
+                return node;
+            }
+        };
+        if edges.is_empty() {
+            return node;
+        }
+
+        if depth >= self.max_depth {
+            node.violations.push(ChainViolation::DepthExceeded {
+                said: credential.said.clone(),
+            });
+            return node;
+        }
+
+        path.insert(credential.said.clone());
+        for edge in edges {
+            let (operator, proof) =
+                self.verify_edge(credential, &edge, &mut node.violations, path, depth);
+            node.edges.push(ProofEdge {
+                label: edge.label,
+                operator,
+                node: edge.node,
+                schema: edge.schema,
+                proof,
+            });
+        }
+        path.remove(&credential.said);
+
+        node
+    }
+
+    fn verify_edge(
+        &self,
+        credential: &Acdc,
+        edge: &Edge,
+        violations: &mut Vec<ChainViolation>,
+        path: &mut HashSet<String>,
+        depth: usize,
+    ) -> (EdgeOperator, Option<Box<ProofNode>>) {
+        let declared = edge.operator.unwrap_or(EdgeOperator::I2I);
+
+        if path.contains(&edge.node) {
+            violations.push(ChainViolation::Cycle {
+                said: edge.node.clone(),
+            });
+            return (declared, None);
+        }
+
+        let Some(far) = self.resolver.resolve(&edge.node) else {
+            violations.push(ChainViolation::Unresolved {
+                said: edge.node.clone(),
+            });
+            return (declared, None);
+        };
+
+        if let Some(ref expected) = edge.schema {
+            if &far.schema != expected {
+                violations.push(ChainViolation::SchemaMismatch {
+                    said: far.said.clone(),
+                    expected: expected.clone(),
+                    actual: far.schema.clone(),
+                });
+            }
+        }
+
+        // Unspecified operator defaults to I2I when the far node has an issuee
+        let issuee = far.issuee();
+        let operator = edge.operator.unwrap_or(match issuee {
+            Some(_) => EdgeOperator::I2I,
+            None => EdgeOperator::NI2I,
+        });
+
+        match operator {
+            EdgeOperator::I2I if issuee != Some(credential.issuer.as_str()) => {
+                violations.push(ChainViolation::IssuerNotIssuee {
+                    said: far.said.clone(),
+                    issuer: credential.issuer.clone(),
+                    issuee: issuee.map(|s| s.to_string()),
+                });
+            }
+            EdgeOperator::DI2I if !self.is_delegate(&credential.issuer, issuee) => {
+                violations.push(ChainViolation::DelegationNotFound {
+                    said: far.said.clone(),
+                    issuer: credential.issuer.clone(),
+                    issuee: issuee.map(|s| s.to_string()),
+                });
+            }
+            _ => {}
+        }
+
+        let proof = self.verify_node(&far, path, depth + 1);
+        (operator, Some(Box::new(proof)))
+    }
+
+    /// Check whether `issuer` is `issuee` or delegated (transitively) by it
+    fn is_delegate(&self, issuer: &str, issuee: Option<&str>) -> bool {
+        let Some(issuee) = issuee else {
+            return false;
+        };
+
+        let mut current = issuer.to_string();
+        for _ in 0..=self.max_depth {
+            if current == issuee {
+                return true;
+            }
+            match self.resolver.delegator(&current) {
+                Some(delegator) => current = delegator,
+                None => return false,
+            }
+        }
+        false
+    }
+}
+
+/// Edge parsed from the edge section
+struct Edge {
+    label: String,
+    node: String,
+    schema: Option<String>,
+    operator: Option<EdgeOperator>,
+}
+
+/// Get the SAIDs of the credentials a credential's edges link to
+///
+/// A malformed or undisclosed edge section links to nothing.
+pub fn linked_credentials(credential: &Acdc) -> Vec<String> {
+    parse_edges(credential)
+        .map(|edges| edges.into_iter().map(|edge| edge.node).collect())
+        .unwrap_or_default()
+}
+
+/// Parse the edges of a credential, flattening edge groups
+fn parse_edges(credential: &Acdc) -> Result<Vec<Edge>, String> {
+    match credential.edges {
+        None => Ok(vec![]),
+        Some(serde_json::Value::Object(ref section)) => {
+            let mut edges = vec![];
+            collect_edges(section, "", &mut edges)?;
+            Ok(edges)
+        }
+        Some(_) => Err("edge section is not disclosed".to_string()),
+    }
+}
+
+fn collect_edges(
+    section: &serde_json::Map<String, serde_json::Value>,
+    prefix: &str,
+    edges: &mut Vec<Edge>,
+) -> Result<(), String> {
+    for (label, value) in section {
+        if label == "d" || label == "u" || label == "o" {
+            continue;
+        }
+        let Some(map) = value.as_object() else {
+            return Err(format!("edge '{}' is not a map", label));
+        };
+        let label = format!("{}{}", prefix, label);
+
+        // An edge without a node is a group of edges
+        let Some(node) = map.get("n") else {
+            collect_edges(map, &format!("{}.", label), edges)?;
+            continue;
+        };
+        let node = node
+            .as_str()
+            .ok_or_else(|| format!("edge '{}' node is not a SAID", label))?
+            .to_string();
+
+        let codes: Vec<&str> = match map.get("o") {
+            None => vec![],
+            Some(serde_json::Value::String(code)) => vec![code.as_str()],
+            Some(serde_json::Value::Array(codes)) => codes
+                .iter()
+                .map(|c| c.as_str())
+                .collect::<Option<_>>()
+                .ok_or_else(|| format!("edge '{}' operator is malformed", label))?,
+            Some(_) => return Err(format!("edge '{}' operator is malformed", label)),
+        };
+        let mut operator = None;
+        for code in codes {
+            let op = EdgeOperator::parse(code)
+                .ok_or_else(|| format!("edge '{}' has unknown operator '{}'", label, code))?;
+            if operator.is_some() {
+                return Err(format!("edge '{}' has conflicting operators", label));
+            }
+            operator = Some(op);
+        }
+
+        edges.push(Edge {
+            label,
+            node,
+            schema: map.get("s").and_then(|s| s.as_str()).map(|s| s.to_string()),
+            operator,
+        });
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::acdc::test_support::create_credential;
+    use serde_json::{json, Value};
+    use std::collections::HashMap;
+
+    const COOP_SCHEMA: &str = "ECoop_Schema_00000000000000000000000000000000";
+    const LICENSE_SCHEMA: &str = "ELicense_Schema_00000000000000000000000000000";
+
+    #[derive(Default)]
+    struct TestResolver {
+        credentials: HashMap<String, Acdc>,
+        revoked: HashSet<String>,
+        forged: HashSet<String>,
+        delegators: HashMap<String, String>,
+    }
+
+    impl TestResolver {
+        fn add(&mut self, credential: &Acdc) {
+            self.credentials
+                .insert(credential.said.clone(), credential.clone());
+        }
+    }
+
+    impl CredentialResolver for TestResolver {
+        fn resolve(&self, said: &str) -> Option<Acdc> {
+            self.credentials.get(said).cloned()
+        }
+
+        fn status(&self, credential: &Acdc) -> CredentialStatus {
+            if self.revoked.contains(&credential.said) {
+                CredentialStatus::Revoked
+            } else {
+                CredentialStatus::Issued
+            }
+        }
+
+        fn delegator(&self, aid: &str) -> Option<String> {
+            self.delegators.get(aid).cloned()
+        }
+
+        fn verify_issuance(&self, credential: &Acdc) -> Result<(), String> {
+            if self.forged.contains(&credential.said) {
+                Err("issuer signature does not verify".to_string())
+            } else {
+                Ok(())
+            }
+        }
+    }
+
+    fn credential(issuer: &str, issuee: &str, schema: &str, edges: Option<Value>) -> Acdc {
+        let mut sections = vec![("a", json!({"d": "", "i": issuee}))];
+        sections.extend(edges.map(|e| ("e", e)));
+        Acdc::from_value(create_credential(issuer, schema, &sections)).unwrap()
+    }
+
+    fn membership_edge(node: &Acdc, operator: &str) -> Value {
+        json!({"d": "", "membership": {"n": node.said, "s": COOP_SCHEMA, "o": operator}})
+    }
+
+    #[test]
+    fn test_valid_i2i_chain() {
+        let mut resolver = TestResolver::default();
+        let membership = credential("ECoop", "EMember", COOP_SCHEMA, None);
+        let license = credential(
+            "EMember",
+            "EApprentice",
+            LICENSE_SCHEMA,
+            Some(membership_edge(&membership, "I2I")),
+        );
+        resolver.add(&membership);
+
+        let proof = ChainVerifier::new(&resolver).verify(&license);
+
+        assert!(proof.is_valid());
+        assert_eq!(proof.edges.len(), 1);
+        assert_eq!(proof.edges[0].label, "membership");
+        assert_eq!(proof.edges[0].proof.as_ref().unwrap().said, membership.said);
+    }
+
+    #[test]
+    fn test_i2i_broken_chain_of_authority() {
+        let mut resolver = TestResolver::default();
+        let membership = credential("ECoop", "EMember", COOP_SCHEMA, None);
+        let license = credential(
+            "EImposter",
+            "EApprentice",
+            LICENSE_SCHEMA,
+            Some(membership_edge(&membership, "I2I")),
+        );
+        resolver.add(&membership);
+
+        let proof = ChainVerifier::new(&resolver).verify(&license);
+
+        assert!(!proof.is_valid());
+        assert!(matches!(
+            proof.illegitimate_delegations()[0],
+            ChainViolation::IssuerNotIssuee { .. }
+        ));
+    }
+
+    #[test]
+    fn test_ni2i_allows_any_issuer() {
+        let mut resolver = TestResolver::default();
+        let membership = credential("ECoop", "EMember", COOP_SCHEMA, None);
+        let license = credential(
+            "EThirdParty",
+            "EApprentice",
+            LICENSE_SCHEMA,
+            Some(membership_edge(&membership, "NI2I")),
+        );
+        resolver.add(&membership);
+
+        assert!(ChainVerifier::new(&resolver).verify(&license).is_valid());
+    }
+
+    #[test]
+    fn test_di2i_delegated_issuer() {
+        let mut resolver = TestResolver::default();
+        let membership = credential("ECoop", "EMember", COOP_SCHEMA, None);
+        let license = credential(
+            "EMemberDelegate",
+            "EApprentice",
+            LICENSE_SCHEMA,
+            Some(membership_edge(&membership, "DI2I")),
+        );
+        resolver.add(&membership);
+
+        let proof = ChainVerifier::new(&resolver).verify(&license);
+        assert!(matches!(
+            proof.violations[0],
+            ChainViolation::DelegationNotFound { .. }
+        ));
+
+        resolver
+            .delegators
+            .insert("EMemberDelegate".to_string(), "EMember".to_string());
+        assert!(ChainVerifier::new(&resolver).verify(&license).is_valid());
+    }
+
+    #[test]
+    fn test_revoked_parent_is_illegitimate() {
+        let mut resolver = TestResolver::default();
+        let membership = credential("ECoop", "EMember", COOP_SCHEMA, None);
+        let license = credential(
+            "EMember",
+            "EApprentice",
+            LICENSE_SCHEMA,
+            Some(membership_edge(&membership, "I2I")),
+        );
+        resolver.add(&membership);
+        resolver.revoked.insert(membership.said.clone());
+
+        let proof = ChainVerifier::new(&resolver).verify(&license);
+
+        assert!(proof.violations.is_empty());
+        let violations = proof.illegitimate_delegations();
+        assert_eq!(violations.len(), 1);
+        assert!(matches!(violations[0], ChainViolation::Revoked { .. }));
+    }
+
+    #[test]
+    fn test_unverified_issuance_is_illegitimate() {
+        let mut resolver = TestResolver::default();
+        let membership = credential("ECoop", "EMember", COOP_SCHEMA, None);
+        let license = credential(
+            "EMember",
+            "EApprentice",
+            LICENSE_SCHEMA,
+            Some(membership_edge(&membership, "I2I")),
+        );
+        resolver.add(&membership);
+        resolver.forged.insert(membership.said.clone());
+
+        let proof = ChainVerifier::new(&resolver).verify(&license);
+
+        let violations = proof.illegitimate_delegations();
+        assert_eq!(violations.len(), 1);
+        assert!(matches!(
+            violations[0],
+            ChainViolation::IssuanceUnverified { said, .. } if *said == membership.said
+        ));
+    }
+
+    #[test]
+    fn test_unknown_operator_rejected() {
+        let mut resolver = TestResolver::default();
+        let membership = credential("ECoop", "EMember", COOP_SCHEMA, None);
+        resolver.add(&membership);
+
+        for operator in [json!("X2I"), json!(["I2I", "X2I"]), json!(["I2I", 1])] {
+            let edges = json!({"d": "", "membership": {"n": membership.said, "o": operator}});
+            let license = credential("EMember", "EApprentice", LICENSE_SCHEMA, Some(edges));
+
+            let proof = ChainVerifier::new(&resolver).verify(&license);
+            assert!(matches!(
+                proof.violations[0],
+                ChainViolation::InvalidEdge { .. }
+            ));
+            assert!(proof.edges.is_empty());
+        }
+    }
+
+    #[test]
+    fn test_out_of_scope_schema() {
+        let mut resolver = TestResolver::default();
+        let other = credential("ECoop", "EMember", "EOther_Schema_000000000000000000000000000000", None);
+        let license = credential(
+            "EMember",
+            "EApprentice",
+            LICENSE_SCHEMA,
+            Some(membership_edge(&other, "I2I")),
+        );
+        resolver.add(&other);
+
+        let proof = ChainVerifier::new(&resolver).verify(&license);
+        assert!(matches!(
+            proof.violations[0],
+            ChainViolation::SchemaMismatch { .. }
+        ));
+    }
+
+    #[test]
+    fn test_unresolved_edge() {
+        let resolver = TestResolver::default();
+        let membership = credential("ECoop", "EMember", COOP_SCHEMA, None);
+        let license = credential(
+            "EMember",
+            "EApprentice",
+            LICENSE_SCHEMA,
+            Some(membership_edge(&membership, "I2I")),
+        );
+
+        let proof = ChainVerifier::new(&resolver).verify(&license);
+        assert!(matches!(proof.violations[0], ChainViolation::Unresolved { .. }));
+        assert!(proof.edges[0].proof.is_none());
+    }
+
+    #[test]
+    fn test_multi_level_chain_and_depth_limit() {
+        let mut resolver = TestResolver::default();
+        let root = credential("EAuthority", "ECoop", COOP_SCHEMA, None);
+        let membership = credential(
+            "ECoop",
+            "EMember",
+            COOP_SCHEMA,
+            Some(json!({"d": "", "charter": {"n": root.said}})),
+        );
+        let license = credential(
+            "EMember",
+            "EApprentice",
+            LICENSE_SCHEMA,
+            Some(membership_edge(&membership, "I2I")),
+        );
+        resolver.add(&root);
+        resolver.add(&membership);
+
+        let proof = ChainVerifier::new(&resolver).verify(&license);
+        assert!(proof.is_valid());
+        let charter = &proof.edges[0].proof.as_ref().unwrap().edges[0];
+        assert_eq!(charter.operator, EdgeOperator::I2I);
+        assert!(charter.proof.is_some());
+
+        let shallow = ChainVerifier::new(&resolver).with_max_depth(1).verify(&license);
+        assert!(shallow
+            .all_violations()
+            .iter()
+            .any(|v| matches!(v, ChainViolation::DepthExceeded { .. })));
+    }
+
+    #[test]
+    fn test_compact_edges_not_verifiable() {
+        let resolver = TestResolver::default();
+        let membership = credential("ECoop", "EMember", COOP_SCHEMA, None);
+        let license = credential(
+            "EMember",
+            "EApprentice",
+            LICENSE_SCHEMA,
+            Some(membership_edge(&membership, "I2I")),
+        );
+        let compact = Acdc::from_value(license.compact().unwrap()).unwrap();
+
+        let proof = ChainVerifier::new(&resolver).verify(&compact);
+        assert!(matches!(proof.violations[0], ChainViolation::InvalidEdge { .. }));
+        assert!(linked_credentials(&compact).is_empty());
+        assert_eq!(linked_credentials(&license), vec![membership.said]);
+    }
+}
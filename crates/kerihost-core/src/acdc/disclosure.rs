@@ -0,0 +1,424 @@
+//! Graduated and selective disclosure of ACDC attributes
+//!
+//! Graduated disclosure presents a credential in compact, partial, or full
+//! form by compacting sections to their SAIDs. Selective disclosure goes one
+//! level further with an aggregate attribute section (`A`): each attribute
+//! lives in its own blinded block `{d, u, <label>}` whose salty nonce `u`
+//! prevents the SAID from leaking the value. The section is a list whose
+//! first element is the aggregate identifier (AGID): the SAID of the list of
+//! block SAIDs, derived as the ACDC specification does with the AGID slot
+//! itself as the placeholder (see `said::compute_list_said`). Any block may
+//! be replaced by its SAID without changing the AGID, and therefore without
+//! changing the signed credential SAID.
+
+use super::{section_said, Acdc};
+use crate::error::{CoreError, CoreResult};
+use crate::said::{compute_list_said, saidify, verify_said};
+use cesride::{Matter, Salter};
+use serde_json::{Map, Value};
+
+/// Label of the aggregate attribute section
+pub const AGGREGATE_LABEL: &str = "A";
+
+/// Fields of a blinded block that are not attribute data
+const BLOCK_FIELDS: [&str; 2] = ["d", "u"];
+
+/// How much of a credential is disclosed
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DisclosureLevel {
+    /// Every section is compacted to its SAID
+    Compact,
+    /// Some sections or attribute blocks are expanded
+    Partial,
+    /// Every section and attribute block is expanded
+    Full,
+}
+
+/// Aggregate attribute section with blinded attribute blocks
+#[derive(Debug, Clone, PartialEq)]
+pub struct AttributeAggregate {
+    /// Aggregate identifier
+    pub agid: String,
+    /// Blinded attribute blocks, in order
+    pub blocks: Vec<Value>,
+}
+
+impl AttributeAggregate {
+    /// Blind each attribute into its own block with a fresh salty nonce
+    pub fn new(attributes: &Map<String, Value>) -> CoreResult<Self> {
+        let blocks = attributes
+            .iter()
+            .map(|(label, value)| blind_attribute(label, value, &new_nonce()?))
+            .collect::<CoreResult<Vec<_>>>()?;
+        Self::from_blocks(blocks)
+    }
+
+    /// Build an aggregate from existing blinded blocks
+    pub fn from_blocks(blocks: Vec<Value>) -> CoreResult<Self> {
+        for block in &blocks {
+            verify_said(block, "d")?;
+        }
+        let agid = aggregate_id(&blocks)?;
+        Ok(AttributeAggregate { agid, blocks })
+    }
+
+    /// Fully disclosed section value
+    pub fn full(&self) -> Value {
+        let mut list = vec![Value::String(self.agid.clone())];
+        list.extend(self.blocks.iter().cloned());
+        Value::Array(list)
+    }
+
+    /// Section value disclosing only blocks that carry one of `labels`
+    pub fn select(&self, labels: &[&str]) -> Value {
+        select_blocks(&self.agid, &self.blocks, labels)
+    }
+}
+
+/// Create a blinded attribute block `{d, u, <label>: value}`
+pub fn blind_attribute(label: &str, value: &Value, nonce: &str) -> CoreResult<Value> {
+    if BLOCK_FIELDS.contains(&label) {
+        return Err(CoreError::InvalidCredential(format!(
+            "'{}' is reserved in attribute blocks",
+            label
+        )));
+    }
+
+    let mut block = Map::new();
+    block.insert("d".to_string(), Value::String(String::new()));
+    block.insert("u".to_string(), Value::String(nonce.to_string()));
+    block.insert(label.to_string(), value.clone());
+    saidify(&Value::Object(block), "d")
+}
+
+/// Generate a random salty nonce
+pub fn new_nonce() -> CoreResult<String> {
+    let salter = Salter::new_with_defaults(None)
+        .map_err(|e| CoreError::CesrParse(format!("Failed to create nonce: {}", e)))?;
+    salter
+        .qb64()
+        .map_err(|e| CoreError::CesrParse(format!("Failed to encode nonce: {}", e)))
+}
+
+/// Compute the aggregate identifier over the SAIDs of the blocks
+///
+/// Blocks may be expanded or already blinded to their SAID. The AGID is the
+/// SAID of the list `[AGID, SAID1, SAID2, ...]`, as in the ACDC
+/// specification's selectively disclosable attribute aggregate.
+pub fn aggregate_id(blocks: &[Value]) -> CoreResult<String> {
+    let saids = blocks
+        .iter()
+        .map(|b| {
+            section_said(b).ok_or_else(|| {
+                CoreError::InvalidCredential("Aggregate element has no SAID".to_string())
+            })
+        })
+        .collect::<CoreResult<Vec<_>>>()?;
+
+    compute_list_said(&saids)
+}
+
+/// Verify an aggregate section and return its AGID
+///
+/// The section is either the compact AGID or a list of the AGID followed by
+/// expanded or blinded blocks.
+pub(crate) fn verify_aggregate(section: &Value) -> CoreResult<String> {
+    match section {
+        Value::String(agid) => Ok(agid.clone()),
+        Value::Array(list) => {
+            let (agid, blocks) = list
+                .split_first()
+                .and_then(|(first, rest)| first.as_str().map(|a| (a, rest)))
+                .ok_or_else(|| {
+                    CoreError::InvalidCredential("Aggregate must start with its AGID".to_string())
+                })?;
+
+            for block in blocks {
+                match block {
+                    Value::Object(_) => verify_said(block, "d")?,
+                    Value::String(_) => {}
+                    _ => {
+                        return Err(CoreError::InvalidCredential(
+                            "Aggregate element must be a block or a SAID".to_string(),
+                        ))
+                    }
+                }
+            }
+
+            let computed = aggregate_id(blocks)?;
+            if computed != agid {
+                return Err(CoreError::SaidMismatch {
+                    expected: agid.to_string(),
+                    computed,
+                });
+            }
+            Ok(computed)
+        }
+        _ => Err(CoreError::InvalidCredential(
+            "Section 'A' must be a list or an AGID".to_string(),
+        )),
+    }
+}
+
+/// Verify a presented credential against the SAID its issuer signed
+///
+/// Whatever subset is disclosed, it must compact to `expected_said`.
+pub fn verify_disclosure(presentation: &Value, expected_said: &str) -> CoreResult<Acdc> {
+    let credential = Acdc::from_value(presentation.clone())?;
+    if credential.said != expected_said {
+        return Err(CoreError::SaidMismatch {
+            expected: expected_said.to_string(),
+            computed: credential.said,
+        });
+    }
+    Ok(credential)
+}
+
+fn select_blocks(agid: &str, blocks: &[Value], labels: &[&str]) -> Value {
+    let mut list = vec![Value::String(agid.to_string())];
+    for block in blocks {
+        let disclose = block
+            .as_object()
+            .map(|b| labels.iter().any(|l| b.contains_key(*l)))
+            .unwrap_or(false);
+
+        if disclose {
+            list.push(block.clone());
+        } else if let Some(said) = section_said(block) {
+            list.push(Value::String(said));
+        }
+    }
+    Value::Array(list)
+}
+
+impl Acdc {
+    /// Get the disclosure level of this presentation
+    pub fn disclosure_level(&self) -> DisclosureLevel {
+        let sections = [&self.attributes, &self.aggregate, &self.edges, &self.rules];
+        let present: Vec<&Value> = sections.iter().filter_map(|s| s.as_ref()).collect();
+
+        let expanded = |s: &Value| match s {
+            Value::Object(_) => true,
+            Value::Array(list) => list.iter().skip(1).all(|b| b.is_object()),
+            _ => false,
+        };
+
+        if present.iter().all(|s| s.is_string()) {
+            DisclosureLevel::Compact
+        } else if present.iter().all(|s| expanded(s)) {
+            DisclosureLevel::Full
+        } else {
+            DisclosureLevel::Partial
+        }
+    }
+
+    /// Present with only the named sections expanded, others compacted
+    pub fn disclose_sections(&self, sections: &[&str]) -> CoreResult<Value> {
+        let compact = self.compact()?;
+        let mut presentation = self.sad.clone();
+        for label in ["a", AGGREGATE_LABEL, "e", "r"] {
+            if presentation.get(label).is_some() && !sections.contains(&label) {
+                presentation[label] = compact[label].clone();
+            }
+        }
+        Ok(presentation)
+    }
+
+    /// Present with only the aggregate blocks carrying `labels` disclosed
+    pub fn select_attributes(&self, labels: &[&str]) -> CoreResult<Value> {
+        let list = self
+            .aggregate
+            .as_ref()
+            .and_then(|a| a.as_array())
+            .ok_or_else(|| {
+                CoreError::InvalidCredential("Credential has no expanded aggregate".to_string())
+            })?;
+
+        let (agid, blocks) = list
+            .split_first()
+            .and_then(|(first, rest)| first.as_str().map(|a| (a, rest)))
+            .ok_or_else(|| {
+                CoreError::InvalidCredential("Aggregate must start with its AGID".to_string())
+            })?;
+
+        for label in labels {
+            let held = blocks
+                .iter()
+                .any(|b| b.as_object().map(|b| b.contains_key(*label)).unwrap_or(false));
+            if !held {
+                return Err(CoreError::InvalidCredential(format!(
+                    "Attribute '{}' is not disclosed in this credential",
+                    label
+                )));
+            }
+        }
+
+        let mut presentation = self.sad.clone();
+        presentation[AGGREGATE_LABEL] = select_blocks(agid, blocks, labels);
+        Ok(presentation)
+    }
+
+    /// Get the disclosed attribute values from `a` and `A`
+    pub fn disclosed_attributes(&self) -> Map<String, Value> {
+        let mut attributes = Map::new();
+        let mut collect = |block: &Map<String, Value>| {
+            for (label, value) in block {
+                if !BLOCK_FIELDS.contains(&label.as_str()) {
+                    attributes.insert(label.clone(), value.clone());
+                }
+            }
+        };
+
+        if let Some(block) = self.attribute_block() {
+            collect(block);
+        }
+        if let Some(list) = self.aggregate.as_ref().and_then(|a| a.as_array()) {
+            list.iter().filter_map(|b| b.as_object()).for_each(&mut collect);
+        }
+        attributes
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::acdc::test_support::{create_credential, ISSUER, SCHEMA};
+    use serde_json::json;
+
+    fn create_aggregate() -> AttributeAggregate {
+        let attributes = json!({"name": "Alice", "age": 30, "country": "NZ"});
+        AttributeAggregate::new(attributes.as_object().unwrap()).unwrap()
+    }
+
+    fn aggregate_credential(aggregate: &AttributeAggregate) -> Acdc {
+        let sad = create_credential(ISSUER, SCHEMA, &[(AGGREGATE_LABEL, aggregate.full())]);
+        Acdc::from_value(sad).unwrap()
+    }
+
+    #[test]
+    fn test_blinded_blocks_differ_for_same_value() {
+        let a = blind_attribute("name", &json!("Alice"), &new_nonce().unwrap()).unwrap();
+        let b = blind_attribute("name", &json!("Alice"), &new_nonce().unwrap()).unwrap();
+        assert_ne!(a["d"], b["d"]);
+    }
+
+    #[test]
+    fn test_blind_attribute_reserved_label() {
+        let result = blind_attribute("d", &json!("x"), "0Anonce");
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn test_agid_stable_under_blinding() {
+        let aggregate = create_aggregate();
+        let blinded: Vec<Value> = aggregate
+            .blocks
+            .iter()
+            .map(|b| Value::String(section_said(b).unwrap()))
+            .collect();
+
+        assert_eq!(aggregate_id(&blinded).unwrap(), aggregate.agid);
+    }
+
+    #[test]
+    fn test_agid_is_said_of_block_list() {
+        let aggregate = create_aggregate();
+        let saids: Vec<String> = aggregate
+            .blocks
+            .iter()
+            .map(|b| section_said(b).unwrap())
+            .collect();
+
+        assert_eq!(aggregate.agid, compute_list_said(&saids).unwrap());
+        // Order is significant
+        let reversed: Vec<String> = saids.iter().rev().cloned().collect();
+        assert_ne!(aggregate.agid, compute_list_said(&reversed).unwrap());
+    }
+
+    #[test]
+    fn test_disclosure_levels() {
+        let aggregate = create_aggregate();
+        let full = aggregate_credential(&aggregate);
+        assert_eq!(full.disclosure_level(), DisclosureLevel::Full);
+
+        let partial = Acdc::from_value(full.select_attributes(&["age"]).unwrap()).unwrap();
+        assert_eq!(partial.disclosure_level(), DisclosureLevel::Partial);
+
+        let compact = Acdc::from_value(full.compact().unwrap()).unwrap();
+        assert_eq!(compact.disclosure_level(), DisclosureLevel::Compact);
+    }
+
+    #[test]
+    fn test_selective_disclosure_verifies() {
+        let aggregate = create_aggregate();
+        let full = aggregate_credential(&aggregate);
+
+        let presentation = full.select_attributes(&["age"]).unwrap();
+        let verified = verify_disclosure(&presentation, &full.said).unwrap();
+
+        let disclosed = verified.disclosed_attributes();
+        assert_eq!(disclosed.get("age"), Some(&json!(30)));
+        assert!(!disclosed.contains_key("name"));
+        assert!(!disclosed.contains_key("country"));
+    }
+
+    #[test]
+    fn test_selective_disclosure_tampered_value() {
+        let aggregate = create_aggregate();
+        let full = aggregate_credential(&aggregate);
+
+        let mut presentation = full.select_attributes(&["age"]).unwrap();
+        let block = presentation[AGGREGATE_LABEL]
+            .as_array_mut()
+            .unwrap()
+            .iter_mut()
+            .find(|b| b.is_object())
+            .unwrap();
+        block["age"] = json!(21);
+
+        let result = verify_disclosure(&presentation, &full.said);
+        assert!(matches!(result, Err(CoreError::SaidMismatch { .. })));
+    }
+
+    #[test]
+    fn test_selective_disclosure_substituted_block() {
+        let aggregate = create_aggregate();
+        let full = aggregate_credential(&aggregate);
+
+        // A validly SAIDified block that was never part of the aggregate
+        let forged = blind_attribute("age", &json!(21), &new_nonce().unwrap()).unwrap();
+        let mut presentation = full.select_attributes(&[]).unwrap();
+        presentation[AGGREGATE_LABEL].as_array_mut().unwrap()[1] = forged;
+
+        let result = verify_disclosure(&presentation, &full.said);
+        assert!(matches!(result, Err(CoreError::SaidMismatch { .. })));
+    }
+
+    #[test]
+    fn test_verify_disclosure_wrong_said() {
+        let aggregate = create_aggregate();
+        let full = aggregate_credential(&aggregate);
+
+        let result = verify_disclosure(&full.sad, "EOther_SAID_0000000000000000000000000000000000");
+        assert!(matches!(result, Err(CoreError::SaidMismatch { .. })));
+    }
+
+    #[test]
+    fn test_select_undisclosed_attribute_fails() {
+        let aggregate = create_aggregate();
+        let full = aggregate_credential(&aggregate);
+        let partial = Acdc::from_value(full.select_attributes(&["age"]).unwrap()).unwrap();
+
+        assert!(partial.select_attributes(&["name"]).is_err());
+    }
+
+    #[test]
+    fn test_disclose_sections() {
+        let aggregate = create_aggregate();
+        let full = aggregate_credential(&aggregate);
+
+        let presentation = full.disclose_sections(&[]).unwrap();
+        assert_eq!(presentation[AGGREGATE_LABEL], json!(aggregate.agid));
+        assert!(verify_disclosure(&presentation, &full.said).is_ok());
+    }
+}
@@ -0,0 +1,233 @@
+//! Authentic Chained Data Containers (ACDC)
+//!
+//! ACDCs are the credentials of the KERI ecosystem. An ACDC is a SAIDified
+//! JSON map with these top-level fields:
+//! - `v` version string, `d` SAID, `u` optional salty nonce
+//! - `i` issuer AID, `ri` optional registry (TEL) identifier
+//! - `s` schema SAID
+//! - `a` attributes or `A` aggregate attributes, `e` edges, `r` rules
+//!
+//! Each section may be expanded (a nested SAIDified map) or compacted to its
+//! SAID. The top-level SAID is computed over the most compact form, so the
+//! same SAID identifies every disclosure variant of a credential.
+
+mod chain;
+mod disclosure;
+mod schema;
+mod signed;
+#[cfg(test)]
+pub(crate) mod test_support;
+
+pub use chain::*;
+pub use disclosure::*;
+pub use schema::*;
+pub use signed::*;
+
+use crate::error::{CoreError, CoreResult};
+use crate::said::{compute_said, verify_said};
+use serde_json::{Map, Value};
+
+/// Labels of the sections that may be compacted to their SAID
+const SECTION_LABELS: [&str; 3] = ["a", "e", "r"];
+
+/// Parsed ACDC credential
+#[derive(Debug, Clone)]
+pub struct Acdc {
+    /// Credential SAID (`d`)
+    pub said: String,
+    /// Issuer AID (`i`)
+    pub issuer: String,
+    /// Schema SAID (`s`)
+    pub schema: String,
+    /// Registry identifier for status (`ri`)
+    pub registry: Option<String>,
+    /// Salty nonce (`u`)
+    pub uuid: Option<String>,
+    /// Attribute section (`a`), expanded map or SAID
+    pub attributes: Option<Value>,
+    /// Aggregate attribute section (`A`), block list or AGID
+    pub aggregate: Option<Value>,
+    /// Edge section (`e`), expanded map or SAID
+    pub edges: Option<Value>,
+    /// Rule section (`r`), expanded map or SAID
+    pub rules: Option<Value>,
+    /// The credential as presented
+    pub sad: Value,
+}
+
+impl Acdc {
+    /// Parse credential from raw JSON bytes
+    pub fn from_json(raw: &[u8]) -> CoreResult<Self> {
+        let sad: Value = serde_json::from_slice(raw)
+            .map_err(|e| CoreError::InvalidCredential(format!("JSON parse error: {}", e)))?;
+        Self::from_value(sad)
+    }
+
+    /// Parse credential from a JSON value, verifying its SAIDs
+    pub fn from_value(sad: Value) -> CoreResult<Self> {
+        let version = required_str(&sad, "v")?;
+        if !version.starts_with("ACDC") || version.get(6..10) != Some("JSON") {
+            return Err(CoreError::InvalidCredential(format!(
+                "Unsupported version string: {}",
+                version
+            )));
+        }
+
+        let said = required_str(&sad, "d")?.to_string();
+        let issuer = required_str(&sad, "i")?.to_string();
+        let schema = required_str(&sad, "s")?.to_string();
+
+        // Expanded sections must match their own SAIDs
+        for label in SECTION_LABELS {
+            if let Some(section) = sad.get(label) {
+                if section.is_object() {
+                    verify_said(section, "d")?;
+                } else if !section.is_string() {
+                    return Err(CoreError::InvalidCredential(format!(
+                        "Section '{}' must be a map or a SAID",
+                        label
+                    )));
+                }
+            }
+        }
+
+        if let Some(aggregate) = sad.get(AGGREGATE_LABEL) {
+            if sad.get("a").is_some() {
+                return Err(CoreError::InvalidCredential(
+                    "Sections 'a' and 'A' are mutually exclusive".to_string(),
+                ));
+            }
+            verify_aggregate(aggregate)?;
+        }
+
+        // The top-level SAID is bound to the most compact form
+        let computed = compute_said(&compact_sections(&sad)?, "d")?;
+        if computed != said {
+            return Err(CoreError::SaidMismatch {
+                expected: said,
+                computed,
+            });
+        }
+
+        Ok(Acdc {
+            said,
+            issuer,
+            schema,
+            registry: sad.get("ri").and_then(|v| v.as_str()).map(|s| s.to_string()),
+            uuid: sad.get("u").and_then(|v| v.as_str()).map(|s| s.to_string()),
+            attributes: sad.get("a").cloned(),
+            aggregate: sad.get(AGGREGATE_LABEL).cloned(),
+            edges: sad.get("e").cloned(),
+            rules: sad.get("r").cloned(),
+            sad,
+        })
+    }
+
+    /// Get the expanded attribute block, if disclosed
+    pub fn attribute_block(&self) -> Option<&Map<String, Value>> {
+        self.attributes.as_ref().and_then(|a| a.as_object())
+    }
+
+    /// Get the issuee (subject) AID from the attributes, if disclosed
+    pub fn issuee(&self) -> Option<&str> {
+        let from_block = self.attribute_block().and_then(|a| a.get("i"));
+        let from_aggregate = || {
+            self.aggregate
+                .as_ref()
+                .and_then(|a| a.as_array())
+                .and_then(|list| list.iter().find_map(|b| b.get("i")))
+        };
+
+        from_block.or_else(from_aggregate).and_then(|v| v.as_str())
+    }
+
+    /// Get the most compact form of this credential
+    pub fn compact(&self) -> CoreResult<Value> {
+        compact_sections(&self.sad)
+    }
+}
+
+/// Replace every expanded section with its SAID
+fn compact_sections(sad: &Value) -> CoreResult<Value> {
+    let mut compact = sad.clone();
+    for label in SECTION_LABELS {
+        if let Some(section) = compact.get(label) {
+            let said = section_said(section).ok_or_else(|| {
+                CoreError::InvalidCredential(format!("Section '{}' has no SAID", label))
+            })?;
+            compact[label] = Value::String(said);
+        }
+    }
+    if let Some(aggregate) = compact.get(AGGREGATE_LABEL) {
+        compact[AGGREGATE_LABEL] = Value::String(verify_aggregate(aggregate)?);
+    }
+    Ok(compact)
+}
+
+/// Get the SAID of a section, whether expanded or compact
+pub fn section_said(section: &Value) -> Option<String> {
+    match section {
+        Value::String(said) => Some(said.clone()),
+        Value::Object(map) => map.get("d").and_then(|v| v.as_str()).map(|s| s.to_string()),
+        _ => None,
+    }
+}
+
+fn required_str<'a>(sad: &'a Value, label: &str) -> CoreResult<&'a str> {
+    sad.get(label)
+        .and_then(|v| v.as_str())
+        .ok_or_else(|| CoreError::InvalidCredential(format!("missing '{}' field", label)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::test_support::*;
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_acdc_from_value_expanded() {
+        let sad = create_test_credential(SCHEMA, json!({"d": "", "i": "EIssuee", "name": "Alice"}));
+        let acdc = Acdc::from_value(sad).unwrap();
+
+        assert_eq!(acdc.issuer, ISSUER);
+        assert_eq!(acdc.schema, SCHEMA);
+        assert_eq!(acdc.issuee(), Some("EIssuee"));
+        assert!(acdc.attribute_block().is_some());
+    }
+
+    #[test]
+    fn test_acdc_compact_and_expanded_share_said() {
+        let sad = create_test_credential(SCHEMA, json!({"d": "", "name": "Alice"}));
+        let expanded = Acdc::from_value(sad).unwrap();
+
+        let compact = Acdc::from_value(expanded.compact().unwrap()).unwrap();
+        assert_eq!(compact.said, expanded.said);
+        assert!(compact.attribute_block().is_none());
+    }
+
+    #[test]
+    fn test_acdc_tampered_attributes_rejected() {
+        let mut sad = create_test_credential(SCHEMA, json!({"d": "", "name": "Alice"}));
+        sad["a"]["name"] = json!("Mallory");
+
+        let result = Acdc::from_value(sad);
+        assert!(matches!(result, Err(CoreError::SaidMismatch { .. })));
+    }
+
+    #[test]
+    fn test_acdc_rejects_keri_version() {
+        let mut sad = create_test_credential(SCHEMA, json!({"d": "", "name": "Alice"}));
+        sad["v"] = json!("KERI10JSON000000_");
+
+        let result = Acdc::from_value(sad);
+        assert!(matches!(result, Err(CoreError::InvalidCredential(_))));
+    }
+
+    #[test]
+    fn test_section_said() {
+        assert_eq!(section_said(&json!("EAbc")), Some("EAbc".to_string()));
+        assert_eq!(section_said(&json!({"d": "EAbc"})), Some("EAbc".to_string()));
+        assert_eq!(section_said(&json!(42)), None);
+    }
+}
@@ -0,0 +1,340 @@
+//! SAIDified JSON Schemas for ACDC credentials
+//!
+//! A credential schema is a JSON Schema whose `$id` is the SAID of the schema
+//! itself. Credentials reference their schema by that SAID (`s` field), so a
+//! schema fetched from anywhere can be verified before it is trusted.
+
+use super::Acdc;
+use crate::error::{CoreError, CoreResult};
+use crate::said::verify_said;
+use jsonschema::Validator;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use serde_json::Value;
+use std::sync::Arc;
+
+/// Label of the SAID field in a schema
+pub const SCHEMA_SAID_LABEL: &str = "$id";
+
+/// Outcome of validating a credential against its schema
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SchemaValidation {
+    /// The credential and its attribute block conform to the schema
+    Valid,
+    /// The credential conforms, but its attribute block is compacted to a
+    /// SAID, so the attributes themselves were not checked
+    AttributesUnverified,
+}
+
+/// Verified credential schema with compiled validators
+#[derive(Clone)]
+pub struct CredentialSchema {
+    /// Schema SAID (`$id`)
+    pub said: String,
+    /// Human-readable title
+    pub title: Option<String>,
+    /// Credential type name
+    pub credential_type: Option<String>,
+    /// Schema version
+    pub version: Option<String>,
+    /// The schema document
+    pub schema: Value,
+    /// Validator for whole credentials
+    validator: Arc<Validator>,
+    /// Validator for expanded attribute blocks, if the schema defines one
+    attributes_validator: Option<Arc<Validator>>,
+}
+
+impl CredentialSchema {
+    /// Parse schema from raw JSON bytes
+    pub fn from_json(raw: &[u8]) -> CoreResult<Self> {
+        let schema: Value = serde_json::from_slice(raw)
+            .map_err(|e| CoreError::InvalidSchema(format!("JSON parse error: {}", e)))?;
+        Self::from_value(schema)
+    }
+
+    /// Build schema from a JSON value, verifying its SAID and compiling it
+    pub fn from_value(schema: Value) -> CoreResult<Self> {
+        let said = schema
+            .get(SCHEMA_SAID_LABEL)
+            .and_then(|v| v.as_str())
+            .ok_or_else(|| CoreError::InvalidSchema("missing '$id' field".to_string()))?
+            .to_string();
+
+        verify_said(&schema, SCHEMA_SAID_LABEL)?;
+
+        let validator = compile(&schema)?;
+        let attributes_validator = match attribute_subschema(&schema) {
+            Some(subschema) => Some(Arc::new(compile(&subschema)?)),
+            None => None,
+        };
+
+        let text = |label: &str| {
+            schema
+                .get(label)
+                .and_then(|v| v.as_str())
+                .map(|s| s.to_string())
+        };
+
+        Ok(CredentialSchema {
+            said,
+            title: text("title"),
+            credential_type: text("credentialType"),
+            version: text("version"),
+            validator: Arc::new(validator),
+            attributes_validator,
+            schema,
+        })
+    }
+
+    /// Serialize schema to JSON
+    pub fn to_json(&self) -> CoreResult<String> {
+        Ok(serde_json::to_string(&self.schema)?)
+    }
+
+    /// Validate a credential against this schema
+    ///
+    /// The credential must reference this schema and its disclosed form must
+    /// conform to it. An expanded attribute block is also checked against
+    /// the schema's attribute block on its own, so a `oneOf` that admits the
+    /// compact SAID cannot stand in for it. A compacted block only yields
+    /// `SchemaValidation::AttributesUnverified`.
+    pub fn validate(&self, credential: &Acdc) -> CoreResult<SchemaValidation> {
+        if credential.schema != self.said {
+            return Err(CoreError::SchemaViolation(format!(
+                "credential references schema {}, not {}",
+                credential.schema, self.said
+            )));
+        }
+
+        check(&self.validator, &credential.sad)?;
+
+        match (&self.attributes_validator, credential.sad.get("a")) {
+            (Some(validator), Some(attributes)) if attributes.is_object() => {
+                check(validator, attributes)?;
+                Ok(SchemaValidation::Valid)
+            }
+            (Some(_), Some(Value::String(_))) => Ok(SchemaValidation::AttributesUnverified),
+            _ => Ok(SchemaValidation::Valid),
+        }
+    }
+
+    /// Validate an expanded attribute block against this schema
+    pub fn validate_attributes(&self, attributes: &Value) -> CoreResult<()> {
+        let validator = self.attributes_validator.as_ref().ok_or_else(|| {
+            CoreError::SchemaViolation(format!(
+                "schema {} does not define an attribute block",
+                self.said
+            ))
+        })?;
+
+        check(validator, attributes)
+    }
+}
+
+impl std::fmt::Debug for CredentialSchema {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("CredentialSchema")
+            .field("said", &self.said)
+            .field("title", &self.title)
+            .field("credential_type", &self.credential_type)
+            .field("version", &self.version)
+            .finish_non_exhaustive()
+    }
+}
+
+impl PartialEq for CredentialSchema {
+    fn eq(&self, other: &Self) -> bool {
+        self.said == other.said
+    }
+}
+
+impl Serialize for CredentialSchema {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        self.schema.serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for CredentialSchema {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let schema = Value::deserialize(deserializer)?;
+        CredentialSchema::from_value(schema).map_err(serde::de::Error::custom)
+    }
+}
+
+fn compile(schema: &Value) -> CoreResult<Validator> {
+    jsonschema::validator_for(schema).map_err(|e| CoreError::InvalidSchema(e.to_string()))
+}
+
+fn check(validator: &Validator, instance: &Value) -> CoreResult<()> {
+    let errors: Vec<String> = validator
+        .iter_errors(instance)
+        .map(|e| {
+            let path = e.instance_path().to_string();
+            let path = if path.is_empty() { "/".to_string() } else { path };
+            format!("{}: {}", path, e)
+        })
+        .collect();
+
+    if errors.is_empty() {
+        Ok(())
+    } else {
+        Err(CoreError::SchemaViolation(errors.join("; ")))
+    }
+}
+
+/// Extract the schema for the expanded attribute block (`properties.a`)
+///
+/// ACDC schemas usually declare `a` as `oneOf` the compact SAID string or the
+/// expanded map; the map variant is the one attributes are checked against.
+fn attribute_subschema(schema: &Value) -> Option<Value> {
+    let section = schema.get("properties")?.get("a")?;
+
+    let mut subschema = match section.get("oneOf").and_then(|v| v.as_array()) {
+        Some(variants) => variants
+            .iter()
+            .find(|v| v.get("type").and_then(|t| t.as_str()) == Some("object"))?
+            .clone(),
+        None => section.clone(),
+    };
+
+    // Carry shared definitions so local references still resolve
+    if let Some(map) = subschema.as_object_mut() {
+        for label in ["definitions", "$defs"] {
+            if let Some(defs) = schema.get(label) {
+                map.entry(label).or_insert_with(|| defs.clone());
+            }
+        }
+    }
+
+    Some(subschema)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::acdc::test_support::create_test_credential;
+    use crate::said::saidify;
+    use serde_json::json;
+
+    fn create_test_schema() -> Value {
+        saidify(
+            &json!({
+                "$id": "",
+                "$schema": "http://json-schema.org/draft-07/schema#",
+                "title": "Test Credential",
+                "credentialType": "TestCredential",
+                "version": "1.0.0",
+                "type": "object",
+                "properties": {
+                    "v": {"type": "string"},
+                    "d": {"type": "string"},
+                    "i": {"type": "string"},
+                    "s": {"type": "string"},
+                    "a": {
+                        "oneOf": [
+                            {"type": "string"},
+                            {
+                                "type": "object",
+                                "properties": {
+                                    "d": {"type": "string"},
+                                    "name": {"type": "string"},
+                                    "age": {"type": "integer", "minimum": 0}
+                                },
+                                "required": ["d", "name"]
+                            }
+                        ]
+                    }
+                },
+                "required": ["v", "d", "i", "s", "a"]
+            }),
+            SCHEMA_SAID_LABEL,
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn test_schema_from_value() {
+        let schema = CredentialSchema::from_value(create_test_schema()).unwrap();
+        assert_eq!(schema.title.as_deref(), Some("Test Credential"));
+        assert_eq!(schema.credential_type.as_deref(), Some("TestCredential"));
+        assert_eq!(schema.said.len(), 44);
+    }
+
+    #[test]
+    fn test_schema_said_mismatch_rejected() {
+        let mut raw = create_test_schema();
+        raw["title"] = json!("Tampered");
+
+        let result = CredentialSchema::from_value(raw);
+        assert!(matches!(result, Err(CoreError::SaidMismatch { .. })));
+    }
+
+    #[test]
+    fn test_schema_serde_roundtrip() {
+        let schema = CredentialSchema::from_value(create_test_schema()).unwrap();
+        let json = schema.to_json().unwrap();
+        let parsed: CredentialSchema = serde_json::from_str(&json).unwrap();
+        assert_eq!(parsed, schema);
+    }
+
+    #[test]
+    fn test_validate_credential() {
+        let schema = CredentialSchema::from_value(create_test_schema()).unwrap();
+        let sad = create_test_credential(&schema.said, json!({"d": "", "name": "Alice", "age": 30}));
+        let credential = Acdc::from_value(sad).unwrap();
+
+        assert_eq!(
+            schema.validate(&credential).unwrap(),
+            SchemaValidation::Valid
+        );
+    }
+
+    #[test]
+    fn test_validate_compacted_attributes_unverified() {
+        let schema = CredentialSchema::from_value(create_test_schema()).unwrap();
+        // Attributes that violate the schema, hidden behind their SAID
+        let sad =
+            create_test_credential(&schema.said, json!({"d": "", "name": "Alice", "age": -1}));
+        let compact = Acdc::from_value(sad).unwrap().compact().unwrap();
+        let credential = Acdc::from_value(compact).unwrap();
+
+        assert_eq!(
+            schema.validate(&credential).unwrap(),
+            SchemaValidation::AttributesUnverified
+        );
+    }
+
+    #[test]
+    fn test_validate_credential_violation() {
+        let schema = CredentialSchema::from_value(create_test_schema()).unwrap();
+        let sad = create_test_credential(&schema.said, json!({"d": "", "name": "Alice", "age": -1}));
+        let credential = Acdc::from_value(sad).unwrap();
+
+        let result = schema.validate(&credential);
+        assert!(matches!(result, Err(CoreError::SchemaViolation(_))));
+    }
+
+    #[test]
+    fn test_validate_credential_wrong_schema() {
+        let schema = CredentialSchema::from_value(create_test_schema()).unwrap();
+        let sad = create_test_credential(
+            "EOther_Schema_0000000000000000000000000000000",
+            json!({"d": "", "name": "Alice"}),
+        );
+        let credential = Acdc::from_value(sad).unwrap();
+
+        assert!(schema.validate(&credential).is_err());
+    }
+
+    #[test]
+    fn test_validate_attributes() {
+        let schema = CredentialSchema::from_value(create_test_schema()).unwrap();
+
+        assert!(schema
+            .validate_attributes(&json!({"d": "EAbc", "name": "Alice"}))
+            .is_ok());
+
+        let result = schema.validate_attributes(&json!({"d": "EAbc"}));
+        assert!(matches!(result, Err(CoreError::SchemaViolation(_))));
+    }
+}
@@ -0,0 +1,212 @@
+//! Credentials with their issuer's signatures
+//!
+//! Issuer signatures cover the most compact form of a credential, the form
+//! its SAID is computed over, so they verify for every disclosure variant.
+
+use super::Acdc;
+use crate::error::{CoreError, CoreResult};
+use crate::event::IndexedSignature;
+use crate::state::KeyState;
+use crate::validation::count_valid_signatures;
+use cesride::{counter, Counter, Matter};
+use parside::{CesrGroup, Message};
+
+/// ACDC with its issuer's indexed signatures
+#[derive(Debug, Clone)]
+pub struct SignedCredential {
+    /// The credential as presented
+    pub credential: Acdc,
+    /// Issuer signatures over the compact credential
+    pub signatures: Vec<IndexedSignature>,
+}
+
+impl SignedCredential {
+    /// Create signed credential from credential and signatures
+    pub fn new(credential: Acdc, signatures: Vec<IndexedSignature>) -> Self {
+        SignedCredential {
+            credential,
+            signatures,
+        }
+    }
+
+    /// Parse signed credential from CESR stream
+    ///
+    /// Signatures are taken from controller indexed signature (`-A`) groups
+    /// and from transferable indexed signature (`-F`) groups of the issuer.
+    pub fn from_cesr(raw: &[u8]) -> CoreResult<Self> {
+        let (after_body, first_msg) = Message::from_stream_bytes(raw)
+            .map_err(|e| CoreError::CesrParse(format!("parside: {}", e)))?;
+        if !matches!(first_msg, Message::Custom { .. }) {
+            return Err(CoreError::CesrParse(
+                "Expected JSON credential as first message".into(),
+            ));
+        }
+        let credential = Acdc::from_json(&raw[..raw.len() - after_body.len()])?;
+
+        let mut signatures = Vec::new();
+        let mut rest = after_body;
+        while !rest.is_empty() {
+            let (remaining, msg) = Message::from_stream_bytes(rest)
+                .map_err(|e| CoreError::CesrParse(format!("parside attachment: {}", e)))?;
+
+            match msg {
+                Message::Group {
+                    value: CesrGroup::ControllerIdxSigsVariant { value: sigs },
+                } => {
+                    for sig in &sigs.value {
+                        signatures.push(IndexedSignature::from_siger(&sig.siger)?);
+                    }
+                }
+                Message::Group {
+                    value: CesrGroup::TransIdxSigGroupsVariant { value: groups },
+                } => {
+                    for group in &groups.value {
+                        let signer = group
+                            .prefixer
+                            .qb64()
+                            .map_err(|e| CoreError::CesrParse(e.to_string()))?;
+                        if signer != credential.issuer {
+                            return Err(CoreError::InvalidCredential(format!(
+                                "Signature group for {} on credential from {}",
+                                signer, credential.issuer
+                            )));
+                        }
+                        for sig in &group.isigers.value {
+                            signatures.push(IndexedSignature::from_siger(&sig.siger)?);
+                        }
+                    }
+                }
+                _ => {}
+            }
+
+            if remaining.len() == rest.len() {
+                break; // No progress, avoid infinite loop
+            }
+            rest = remaining;
+        }
+
+        Ok(SignedCredential {
+            credential,
+            signatures,
+        })
+    }
+
+    /// Serialize to a CESR stream: the credential as presented followed by
+    /// its signatures
+    pub fn to_cesr(&self) -> CoreResult<Vec<u8>> {
+        let mut cesr = serde_json::to_vec(&self.credential.sad)?;
+        if !self.signatures.is_empty() {
+            let counter = Counter::new_with_code_and_count(
+                counter::Codex::ControllerIdxSigs,
+                self.signatures.len() as u32,
+            )
+            .map_err(|e| CoreError::CesrParse(e.to_string()))?;
+            cesr.extend(
+                counter
+                    .qb64b()
+                    .map_err(|e| CoreError::CesrParse(e.to_string()))?,
+            );
+            for sig in &self.signatures {
+                cesr.extend(sig.signature.as_bytes());
+            }
+        }
+
+        Ok(cesr)
+    }
+
+    /// Get the bytes issuer signatures are made over
+    pub fn signed_bytes(credential: &Acdc) -> CoreResult<Vec<u8>> {
+        Ok(serde_json::to_vec(&credential.compact()?)?)
+    }
+
+    /// Verify the signatures against the issuer's key state
+    ///
+    /// Fails unless `state` belongs to the issuer and enough signatures
+    /// verify to meet its signing threshold.
+    pub fn verify(&self, state: &KeyState) -> CoreResult<()> {
+        if state.prefix != self.credential.issuer {
+            return Err(CoreError::InvalidCredential(format!(
+                "Key state for {} does not belong to issuer {}",
+                state.prefix, self.credential.issuer
+            )));
+        }
+
+        let have = count_valid_signatures(
+            &state.signing_keys,
+            &self.signatures,
+            &Self::signed_bytes(&self.credential)?,
+        )?;
+        let need = state.min_signatures();
+        if have < need {
+            return Err(CoreError::ThresholdNotMet { have, need });
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::test_support::*;
+    use super::*;
+    use crate::test_util::{key_state, signer};
+    use cesride::Signer;
+    use serde_json::json;
+
+    fn sign(signer: &Signer, credential: Acdc) -> SignedCredential {
+        let raw = SignedCredential::signed_bytes(&credential).unwrap();
+        let siger = signer.sign_indexed(&raw, false, 0, None).unwrap();
+        SignedCredential::new(
+            credential,
+            vec![IndexedSignature::from_siger(&siger).unwrap()],
+        )
+    }
+
+    fn credential(issuer: &str) -> Acdc {
+        let sad = create_credential(issuer, SCHEMA, &[("a", json!({"d": "", "name": "Alice"}))]);
+        Acdc::from_value(sad).unwrap()
+    }
+
+    #[test]
+    fn test_signed_credential_roundtrip_and_verify() {
+        let signer = signer(5);
+        let state = key_state(&signer);
+
+        let signed = sign(&signer, credential(&state.prefix));
+        let parsed = SignedCredential::from_cesr(&signed.to_cesr().unwrap()).unwrap();
+        assert_eq!(parsed.credential.said, signed.credential.said);
+        assert_eq!(parsed.signatures.len(), 1);
+        assert!(parsed.verify(&state).is_ok());
+
+        // Signatures cover every disclosure variant
+        let compact = Acdc::from_value(signed.credential.compact().unwrap()).unwrap();
+        let compact = SignedCredential::new(compact, signed.signatures.clone());
+        assert!(compact.verify(&state).is_ok());
+    }
+
+    #[test]
+    fn test_signed_credential_rejects_forged_and_unsigned() {
+        let issuer = signer(5);
+        let mallory = signer(6);
+        let state = key_state(&issuer);
+
+        let forged = sign(&mallory, credential(&state.prefix));
+        assert!(matches!(
+            forged.verify(&state),
+            Err(CoreError::ThresholdNotMet { have: 0, need: 1 })
+        ));
+
+        let unsigned = SignedCredential::new(credential(&state.prefix), vec![]);
+        assert!(matches!(
+            unsigned.verify(&state),
+            Err(CoreError::ThresholdNotMet { have: 0, need: 1 })
+        ));
+
+        // Signatures cannot be verified against another identifier's state
+        let signed = sign(&issuer, credential(&state.prefix));
+        assert!(matches!(
+            signed.verify(&key_state(&mallory)),
+            Err(CoreError::InvalidCredential(_))
+        ));
+    }
+}
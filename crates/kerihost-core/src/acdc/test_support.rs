@@ -0,0 +1,49 @@
+//! Shared fixtures for ACDC tests
+
+use crate::said::saidify;
+use serde_json::{json, Value};
+
+/// Issuer AID of test credentials
+pub(crate) const ISSUER: &str = "EIssuer_AID_000000000000000000000000000000000";
+
+/// Schema SAID of test credentials
+pub(crate) const SCHEMA: &str = "ESchema_SAID_00000000000000000000000000000000";
+
+/// Build an expanded credential from its sections
+///
+/// Map sections are SAIDified; an aggregate (`A`) list is taken as built,
+/// its first element being the AGID. The top-level SAID is computed over
+/// the compact form.
+pub(crate) fn create_credential(issuer: &str, schema: &str, sections: &[(&str, Value)]) -> Value {
+    let mut compact = json!({
+        "v": "ACDC10JSON000000_",
+        "d": "",
+        "i": issuer,
+        "s": schema,
+    });
+
+    let mut expanded = Vec::new();
+    for (label, section) in sections {
+        let section = match section {
+            Value::Object(_) => saidify(section, "d").unwrap(),
+            _ => section.clone(),
+        };
+        compact[*label] = match &section {
+            Value::Object(map) => map["d"].clone(),
+            Value::Array(list) => list[0].clone(),
+            other => other.clone(),
+        };
+        expanded.push((*label, section));
+    }
+
+    let mut sad = saidify(&compact, "d").unwrap();
+    for (label, section) in expanded {
+        sad[label] = section;
+    }
+    sad
+}
+
+/// Build a credential from `ISSUER` with an expanded attribute block
+pub(crate) fn create_test_credential(schema: &str, attributes: Value) -> Value {
+    create_credential(ISSUER, schema, &[("a", attributes)])
+}
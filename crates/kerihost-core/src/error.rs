@@ -60,6 +60,30 @@ pub enum CoreError {
     /// Serialization error
     #[error("Serialization error: {0}")]
     Serialization(String),
+
+    /// SAID does not match content
+    #[error("SAID mismatch: expected {expected}, computed {computed}")]
+    SaidMismatch { expected: String, computed: String },
+
+    /// Invalid credential (ACDC) structure
+    #[error("Invalid credential: {0}")]
+    InvalidCredential(String),
+
+    /// Invalid credential schema
+    #[error("Invalid schema: {0}")]
+    InvalidSchema(String),
+
+    /// Data does not conform to its schema
+    #[error("Schema validation failed: {0}")]
+    SchemaViolation(String),
+
+    /// Invalid exchange (`exn`) message
+    #[error("Invalid exchange message: {0}")]
+    InvalidExchange(String),
+
+    /// Exchange message not allowed in the current exchange state
+    #[error("Invalid IPEX transition from {from} to {to}")]
+    InvalidTransition { from: String, to: String },
 }
 
 impl From<anyhow::Error> for CoreError {
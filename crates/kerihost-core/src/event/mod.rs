@@ -9,14 +9,16 @@
 
 mod inception;
 mod interaction;
+mod replay;
 mod rotation;
 
 pub use inception::*;
 pub use interaction::*;
+pub use replay::*;
 pub use rotation::*;
 
 use crate::error::{CoreError, CoreResult};
-use cesride::{Diger, Indexer, Matter, Prefixer, Siger, Verfer};
+use cesride::{counter, Counter, Diger, Indexer, Matter, Prefixer, Siger, Verfer};
 use parside::{CesrGroup, Message};
 use serde::{Deserialize, Serialize};
 
@@ -38,6 +40,7 @@ pub enum EventType {
 
 impl EventType {
     /// Parse event type from string
+    #[allow(clippy::should_implement_trait)]
     pub fn from_str(s: &str) -> CoreResult<Self> {
         match s {
             "icp" => Ok(EventType::Icp),
@@ -403,6 +406,10 @@ pub struct SignedEvent {
     pub event: KeyEvent,
     /// Controller signatures
     pub signatures: Vec<IndexedSignature>,
+    /// Attachment groups other than controller signatures, such as witness
+    /// signatures and delegation seals, as received
+    #[serde(skip)]
+    pub attachments: Vec<u8>,
 }
 
 /// Indexed signature with key index
@@ -433,7 +440,11 @@ impl IndexedSignature {
 impl SignedEvent {
     /// Create signed event from event and signatures
     pub fn new(event: KeyEvent, signatures: Vec<IndexedSignature>) -> Self {
-        SignedEvent { event, signatures }
+        SignedEvent {
+            event,
+            signatures,
+            attachments: Vec::new(),
+        }
     }
 
     /// Parse signed event from CESR stream
@@ -456,29 +467,72 @@ impl SignedEvent {
         let event_size = raw.len() - after_event.len();
         let event = KeyEvent::from_cesr(&raw[..event_size])?;
 
-        // Parse attachment groups from remaining bytes
+        // Parse attachment groups from remaining bytes. Controller signatures
+        // are decoded; every other group is kept verbatim.
         let mut signatures = Vec::new();
+        let mut attachments = Vec::new();
         let mut rest = after_event;
         while !rest.is_empty() {
             let (remaining, msg) = Message::from_stream_bytes(rest)
                 .map_err(|e| CoreError::CesrParse(format!("parside attachment: {}", e)))?;
+            if remaining.len() == rest.len() {
+                break; // No progress, avoid infinite loop
+            }
+            let group = &rest[..rest.len() - remaining.len()];
 
-            if let Message::Group { value } = msg {
-                if let CesrGroup::ControllerIdxSigsVariant { value: sigs } = value {
+            match msg {
+                Message::Group {
+                    value: CesrGroup::ControllerIdxSigsVariant { value: sigs },
+                } => {
                     for sig in &sigs.value {
                         signatures.push(IndexedSignature::from_siger(&sig.siger)?);
                     }
                 }
-                // Other group types (witness sigs, receipts, etc.) silently ignored for now
+                Message::Group { .. } => attachments.extend_from_slice(group),
+                // The next message starts here
+                _ => break,
             }
+            rest = remaining;
+        }
 
-            if remaining.len() == rest.len() {
-                break; // No progress, avoid infinite loop
+        Ok(SignedEvent {
+            event,
+            signatures,
+            attachments,
+        })
+    }
+
+    /// Serialize to a CESR stream: the raw event, its controller signatures,
+    /// then its other attachment groups
+    ///
+    /// This is the inverse of `from_cesr` and is the canonical stored form.
+    pub fn to_cesr(&self) -> CoreResult<Vec<u8>> {
+        if self.event.raw.is_empty() {
+            return Err(CoreError::InvalidEvent(format!(
+                "Event {} has no raw bytes",
+                self.event.digest
+            )));
+        }
+
+        let mut cesr = self.event.raw.clone();
+        if !self.signatures.is_empty() {
+            let counter = Counter::new_with_code_and_count(
+                counter::Codex::ControllerIdxSigs,
+                self.signatures.len() as u32,
+            )
+            .map_err(|e| CoreError::CesrParse(e.to_string()))?;
+            cesr.extend(
+                counter
+                    .qb64b()
+                    .map_err(|e| CoreError::CesrParse(e.to_string()))?,
+            );
+            for sig in &self.signatures {
+                cesr.extend(sig.signature.as_bytes());
             }
-            rest = remaining;
         }
+        cesr.extend(&self.attachments);
 
-        Ok(SignedEvent { event, signatures })
+        Ok(cesr)
     }
 
     /// Get signature count
@@ -836,12 +890,111 @@ mod tests {
                 index: 0,
                 signature: "AASig".to_string(),
             }],
+            attachments: Vec::new(),
         };
 
         let json = serde_json::to_string(&signed).unwrap();
         assert!(json.contains("\"prefix\":\"DTest123\""));
     }
 
+    #[test]
+    fn test_signed_event_cesr_roundtrip() {
+        let signer = cesride::Signer::new_with_raw(&[7u8; 32], Some(true), None).unwrap();
+        let key = signer.verfer().qb64().unwrap();
+        let ked = crate::said::saidify(
+            &serde_json::json!({
+                "v": "KERI10JSON000000_", "t": "icp", "d": "", "i": key, "s": "0",
+                "kt": "1", "k": [key], "nt": "0", "n": [], "bt": "0", "b": [], "c": [], "a": []
+            }),
+            "d",
+        )
+        .unwrap();
+        let raw = serde_json::to_vec(&ked).unwrap();
+        let siger = signer.sign_indexed(&raw, false, 0, None).unwrap();
+
+        let signed = SignedEvent::new(
+            KeyEvent::from_cesr(&raw).unwrap(),
+            vec![IndexedSignature::from_siger(&siger).unwrap()],
+        );
+        let parsed = SignedEvent::from_cesr(&signed.to_cesr().unwrap()).unwrap();
+
+        assert_eq!(parsed.event.raw, raw);
+        assert_eq!(parsed.event.digest, signed.event.digest);
+        assert_eq!(parsed.signatures.len(), 1);
+        assert_eq!(parsed.signatures[0].signature, signed.signatures[0].signature);
+        assert_eq!(parsed.to_cesr().unwrap(), signed.to_cesr().unwrap());
+    }
+
+    #[test]
+    fn test_delegated_event_cesr_roundtrip_keeps_attachments() {
+        let signer = cesride::Signer::new_with_raw(&[7u8; 32], Some(true), None).unwrap();
+        let witness = cesride::Signer::new_with_raw(&[8u8; 32], Some(false), None).unwrap();
+        let key = signer.verfer().qb64().unwrap();
+        let delegator = Diger::new_with_ser(b"delegator", None)
+            .unwrap()
+            .qb64()
+            .unwrap();
+        let ked = crate::said::saidify(
+            &serde_json::json!({
+                "v": "KERI10JSON000000_", "t": "dip", "d": "", "i": key, "s": "0",
+                "kt": "1", "k": [key], "nt": "0", "n": [], "bt": "1",
+                "b": [witness.verfer().qb64().unwrap()], "c": [], "a": [], "di": delegator
+            }),
+            "d",
+        )
+        .unwrap();
+        let raw = serde_json::to_vec(&ked).unwrap();
+        let counter = |code: &str| {
+            Counter::new_with_code_and_count(code, 1)
+                .unwrap()
+                .qb64()
+                .unwrap()
+        };
+
+        // Controller and witness signatures, then the delegating event's seal
+        let mut stream = raw.clone();
+        stream.extend(counter(counter::Codex::ControllerIdxSigs).as_bytes());
+        let siger = signer.sign_indexed(&raw, false, 0, None).unwrap();
+        stream.extend(siger.qb64b().unwrap());
+        let mut attachments = counter(counter::Codex::WitnessIdxSigs).into_bytes();
+        let wiger = witness.sign_indexed(&raw, false, 0, None).unwrap();
+        attachments.extend(wiger.qb64b().unwrap());
+        attachments.extend(counter(counter::Codex::SealSourceCouples).as_bytes());
+        attachments.extend(cesride::Seqner::new_with_sn(3).unwrap().qb64b().unwrap());
+        let anchoring = Diger::new_with_ser(b"anchoring", None).unwrap();
+        attachments.extend(anchoring.qb64b().unwrap());
+        stream.extend(&attachments);
+
+        let parsed = SignedEvent::from_cesr(&stream).unwrap();
+        assert_eq!(parsed.event.event_type, EventType::Dip);
+        assert_eq!(parsed.event.delegator.as_deref(), Some(delegator.as_str()));
+        assert_eq!(parsed.signatures.len(), 1);
+        assert_eq!(parsed.attachments, attachments);
+        assert_eq!(parsed.to_cesr().unwrap(), stream);
+
+        // Groups before the controller signatures are kept too, and written
+        // after them
+        let mut reordered = raw.clone();
+        reordered.extend(&attachments);
+        reordered.extend(&stream[raw.len()..stream.len() - attachments.len()]);
+        let parsed = SignedEvent::from_cesr(&reordered).unwrap();
+        assert_eq!(parsed.attachments, attachments);
+        assert_eq!(parsed.to_cesr().unwrap(), stream);
+    }
+
+    #[test]
+    fn test_signed_event_to_cesr_requires_raw() {
+        let json = r#"{"v":"KERI10JSON000000_","t":"icp","d":"","i":"DTest","s":"0"}"#;
+        let ked = crate::said::saidify(&serde_json::from_str(json).unwrap(), "d").unwrap();
+        let event = KeyEvent::from_cesr(&serde_json::to_vec(&ked).unwrap()).unwrap();
+
+        let mut signed = SignedEvent::new(event, vec![]);
+        assert_eq!(signed.to_cesr().unwrap(), signed.event.raw);
+
+        signed.event.raw.clear();
+        assert!(signed.to_cesr().is_err());
+    }
+
     // --- SAID verification tests ---
 
     #[test]
@@ -0,0 +1,305 @@
+//! First-seen replay streams
+//!
+//! A replay stream is keripy's clone format for moving KELs between nodes:
+//! each event message is the raw event followed by its controller
+//! signatures (`-A`), the witness receipt couples it has collected (`-C`)
+//! and a first-seen replay couple (`-E`) carrying the ordinal and datetime
+//! at which the source first saw it. Messages are concatenated in
+//! first-seen order.
+
+use super::{IndexedSignature, KeyEvent, SignedEvent};
+use crate::error::{CoreError, CoreResult};
+use crate::receipt::NontransferableReceipt;
+use cesride::{counter, Counter, Dater, Matter, Seqner};
+use parside::{CesrGroup, Message};
+
+/// When the source node first saw an event
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FirstSeenCouple {
+    /// First-seen ordinal within the event's KEL
+    pub ordinal: u64,
+    /// First-seen datetime (RFC 3339)
+    pub datetime: String,
+}
+
+/// An event with everything a replay stream carries for it
+#[derive(Debug, Clone)]
+pub struct ReplayEvent {
+    /// The event and its controller signatures
+    pub signed: SignedEvent,
+    /// Witness receipts collected for the event
+    pub receipts: Vec<NontransferableReceipt>,
+    /// First-seen data, absent for streams without `-E` groups
+    pub first_seen: Option<FirstSeenCouple>,
+}
+
+impl ReplayEvent {
+    /// Serialize to one replay stream message
+    pub fn to_cesr(&self) -> CoreResult<Vec<u8>> {
+        let mut cesr = self.signed.to_cesr()?;
+
+        if !self.receipts.is_empty() {
+            cesr.extend(counter_qb64b(
+                counter::Codex::NonTransReceiptCouples,
+                self.receipts.len(),
+            )?);
+            for receipt in &self.receipts {
+                cesr.extend(receipt.witness_prefix.as_bytes());
+                cesr.extend(receipt.signature.as_bytes());
+            }
+        }
+
+        if let Some(first_seen) = &self.first_seen {
+            cesr.extend(counter_qb64b(counter::Codex::FirstSeenReplayCouples, 1)?);
+            let seqner = Seqner::new_with_sn(first_seen.ordinal as u128).map_err(cesr_err)?;
+            let dater =
+                Dater::new_with_dts(&to_dts(&first_seen.datetime)?, None).map_err(cesr_err)?;
+            cesr.extend(seqner.qb64b().map_err(cesr_err)?);
+            cesr.extend(dater.qb64b().map_err(cesr_err)?);
+        }
+
+        Ok(cesr)
+    }
+
+    /// Iterate over the event messages of a replay stream
+    pub fn parse_stream(stream: &[u8]) -> ReplayStream<'_> {
+        ReplayStream { rest: stream }
+    }
+}
+
+/// Iterator over the events of a replay stream
+///
+/// Stops after the first error, since the stream cannot be resynchronized.
+pub struct ReplayStream<'a> {
+    rest: &'a [u8],
+}
+
+impl<'a> ReplayStream<'a> {
+    /// Parse the next event message off the front of the stream
+    fn next_event(&mut self) -> CoreResult<ReplayEvent> {
+        let raw = self.rest;
+        let (after_event, message) = Message::from_stream_bytes(raw)
+            .map_err(|e| CoreError::CesrParse(format!("parside: {}", e)))?;
+        if !matches!(message, Message::Custom { .. }) {
+            return Err(CoreError::CesrParse(
+                "Expected JSON event at start of message".into(),
+            ));
+        }
+        let event = KeyEvent::from_cesr(&raw[..raw.len() - after_event.len()])?;
+
+        let mut replayed = ReplayEvent {
+            signed: SignedEvent::new(event, vec![]),
+            receipts: vec![],
+            first_seen: None,
+        };
+        let mut rest = after_event;
+        while !rest.is_empty() {
+            let (remaining, message) = Message::from_stream_bytes(rest)
+                .map_err(|e| CoreError::CesrParse(format!("parside attachment: {}", e)))?;
+            let group = match message {
+                // The next event's message starts here
+                Message::Custom { .. } => break,
+                Message::Group { value } => value,
+            };
+            replayed.attach(group, &rest[..rest.len() - remaining.len()])?;
+            rest = remaining;
+        }
+
+        self.rest = rest;
+        Ok(replayed)
+    }
+}
+
+impl Iterator for ReplayStream<'_> {
+    type Item = CoreResult<ReplayEvent>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.rest.is_empty() {
+            return None;
+        }
+        let result = self.next_event();
+        if result.is_err() {
+            self.rest = &[];
+        }
+        Some(result)
+    }
+}
+
+impl ReplayEvent {
+    /// Add an attachment group parsed from `bytes` after the event
+    ///
+    /// Other group types are kept verbatim, as `SignedEvent::from_cesr`
+    /// does.
+    fn attach(&mut self, group: CesrGroup, bytes: &[u8]) -> CoreResult<()> {
+        let event = &self.signed.event;
+        match group {
+            CesrGroup::ControllerIdxSigsVariant { value } => {
+                for sig in &value.value {
+                    self.signed
+                        .signatures
+                        .push(IndexedSignature::from_siger(&sig.siger)?);
+                }
+            }
+            CesrGroup::NonTransReceiptCouplesVariant { value } => {
+                for couple in &value.value {
+                    self.receipts.push(NontransferableReceipt::new(
+                        event.digest.clone(),
+                        event.sn,
+                        event.prefix.clone(),
+                        couple.cigar.verfer().qb64().map_err(cesr_err)?,
+                        couple.cigar.qb64().map_err(cesr_err)?,
+                    ));
+                }
+            }
+            CesrGroup::FirstSeenReplayCouplesVariant { value } => {
+                if let Some(couple) = value.value.first() {
+                    let ordinal = couple.firner.sn().map_err(cesr_err)?;
+                    self.first_seen = Some(FirstSeenCouple {
+                        ordinal: u64::try_from(ordinal).map_err(|_| {
+                            CoreError::CesrParse(format!(
+                                "First-seen ordinal {} too large",
+                                ordinal
+                            ))
+                        })?,
+                        datetime: couple.dater.dts().map_err(cesr_err)?,
+                    });
+                }
+            }
+            _ => self.signed.attachments.extend_from_slice(bytes),
+        }
+        Ok(())
+    }
+}
+
+fn cesr_err(e: impl std::fmt::Display) -> CoreError {
+    CoreError::CesrParse(e.to_string())
+}
+
+fn counter_qb64b(code: &str, count: usize) -> CoreResult<Vec<u8>> {
+    Counter::new_with_code_and_count(code, count as u32)
+        .and_then(|counter| counter.qb64b())
+        .map_err(cesr_err)
+}
+
+/// Convert an RFC 3339 datetime to the fixed 32-character form `Dater`
+/// encodes, e.g. `2020-08-22T17:50:09.988921+00:00`
+fn to_dts(datetime: &str) -> CoreResult<String> {
+    let datetime = chrono::DateTime::parse_from_rfc3339(datetime)
+        .map_err(|e| CoreError::CesrParse(format!("Invalid datetime {}: {}", datetime, e)))?;
+    Ok(datetime
+        .with_timezone(&chrono::Utc)
+        .format("%Y-%m-%dT%H:%M:%S%.6f+00:00")
+        .to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use cesride::{Signer, Verfer};
+
+    fn signed_event(signer: &Signer, sn: u64, prior: Option<&str>) -> SignedEvent {
+        let key = signer.verfer().qb64().unwrap();
+        let mut ked = serde_json::json!({
+            "v": "KERI10JSON000000_", "t": "icp", "d": "", "i": key, "s": format!("{:x}", sn),
+            "kt": "1", "k": [key], "nt": "0", "n": [], "bt": "0", "b": [], "c": [], "a": []
+        });
+        if let Some(prior) = prior {
+            ked = serde_json::json!({
+                "v": "KERI10JSON000000_", "t": "ixn", "d": "", "i": key,
+                "s": format!("{:x}", sn), "p": prior, "a": []
+            });
+        }
+        let ked = crate::said::saidify(&ked, "d").unwrap();
+        let raw = serde_json::to_vec(&ked).unwrap();
+        let siger = signer.sign_indexed(&raw, false, 0, None).unwrap();
+        SignedEvent::new(
+            KeyEvent::from_cesr(&raw).unwrap(),
+            vec![IndexedSignature::from_siger(&siger).unwrap()],
+        )
+    }
+
+    #[test]
+    fn test_replay_stream_roundtrip() {
+        let signer = Signer::new_with_raw(&[7u8; 32], Some(true), None).unwrap();
+        let witness = Signer::new_with_raw(&[9u8; 32], Some(false), None).unwrap();
+        let witness_prefix = witness.verfer().qb64().unwrap();
+
+        let icp = signed_event(&signer, 0, None);
+        let ixn = signed_event(&signer, 1, Some(&icp.event.digest));
+        let receipt = NontransferableReceipt::sign(
+            icp.event.digest.clone(),
+            0,
+            icp.event.prefix.clone(),
+            witness_prefix.clone(),
+            &witness,
+            &icp.event.raw,
+        )
+        .unwrap();
+
+        let events = vec![
+            ReplayEvent {
+                signed: icp.clone(),
+                receipts: vec![receipt.clone()],
+                first_seen: Some(FirstSeenCouple {
+                    ordinal: 0,
+                    datetime: "2024-01-02T03:04:05.123456789+00:00".to_string(),
+                }),
+            },
+            ReplayEvent {
+                signed: ixn.clone(),
+                receipts: vec![],
+                first_seen: None,
+            },
+        ];
+        let mut stream = Vec::new();
+        for event in &events {
+            stream.extend(event.to_cesr().unwrap());
+        }
+
+        let parsed: Vec<ReplayEvent> = ReplayEvent::parse_stream(&stream)
+            .collect::<CoreResult<_>>()
+            .unwrap();
+        assert_eq!(parsed.len(), 2);
+
+        assert_eq!(parsed[0].signed.event.raw, icp.event.raw);
+        assert_eq!(parsed[0].signed.signatures.len(), 1);
+        assert_eq!(parsed[0].receipts.len(), 1);
+        assert_eq!(parsed[0].receipts[0].witness_prefix, witness_prefix);
+        assert_eq!(parsed[0].receipts[0].signature, receipt.signature);
+        assert_eq!(parsed[0].receipts[0].event_digest, icp.event.digest);
+        assert_eq!(
+            parsed[0].first_seen,
+            Some(FirstSeenCouple {
+                ordinal: 0,
+                datetime: "2024-01-02T03:04:05.123456+00:00".to_string(),
+            })
+        );
+
+        assert_eq!(parsed[1].signed.event.digest, ixn.event.digest);
+        assert_eq!(parsed[1].signed.signatures.len(), 1);
+        assert!(parsed[1].receipts.is_empty());
+        assert!(parsed[1].first_seen.is_none());
+
+        let verfer = Verfer::new_with_qb64(&witness_prefix).unwrap();
+        let cigar = parsed[0].receipts[0].cigar(Some(&verfer)).unwrap();
+        assert!(verfer.verify(&cigar.raw(), &icp.event.raw).unwrap());
+    }
+
+    #[test]
+    fn test_replay_stream_stops_at_garbage() {
+        let signer = Signer::new_with_raw(&[7u8; 32], Some(true), None).unwrap();
+        let icp = signed_event(&signer, 0, None);
+        let mut stream = ReplayEvent {
+            signed: icp,
+            receipts: vec![],
+            first_seen: None,
+        }
+        .to_cesr()
+        .unwrap();
+        stream.extend(b"not cesr");
+
+        let results: Vec<_> = ReplayEvent::parse_stream(&stream).collect();
+        assert_eq!(results.len(), 1);
+        assert!(results[0].is_err());
+    }
+}
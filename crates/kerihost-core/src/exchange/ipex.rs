@@ -0,0 +1,316 @@
+//! Issuance and Presentation EXchange (IPEX) protocol
+//!
+//! IPEX is a conversation of `exn` messages routed under `/ipex/`. Each
+//! response names the SAID of the message it answers, so an exchange is a
+//! SAID chain starting at its first message. Allowed transitions:
+//!
+//! ```text
+//! apply -> offer -> agree -> grant -> admit
+//!   \        \        \        \
+//!    `--------`--------`--------`--> spurn
+//! ```
+//!
+//! An exchange may also start at `offer` or `grant`, skipping earlier steps.
+//! `admit` and `spurn` complete the exchange.
+
+use super::ExchangeMessage;
+use crate::error::{CoreError, CoreResult};
+use serde::{Deserialize, Serialize};
+
+/// Route prefix for IPEX messages
+pub const IPEX_ROUTE_PREFIX: &str = "/ipex/";
+
+/// Label of the embedded credential in offer and grant messages
+pub const IPEX_CREDENTIAL_LABEL: &str = "acdc";
+
+/// IPEX message verbs
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum IpexVerb {
+    /// Disclosee asks for a credential
+    Apply,
+    /// Discloser offers a (partially disclosed) credential
+    Offer,
+    /// Disclosee agrees to the offer
+    Agree,
+    /// Discloser grants the credential
+    Grant,
+    /// Disclosee admits the granted credential
+    Admit,
+    /// Either party rejects the last message
+    Spurn,
+}
+
+impl IpexVerb {
+    /// Parse verb from an `exn` route
+    pub fn from_route(route: &str) -> CoreResult<Self> {
+        let verb = route
+            .strip_prefix(IPEX_ROUTE_PREFIX)
+            .ok_or_else(|| CoreError::InvalidExchange(format!("Not an IPEX route: {}", route)))?;
+
+        match verb {
+            "apply" => Ok(IpexVerb::Apply),
+            "offer" => Ok(IpexVerb::Offer),
+            "agree" => Ok(IpexVerb::Agree),
+            "grant" => Ok(IpexVerb::Grant),
+            "admit" => Ok(IpexVerb::Admit),
+            "spurn" => Ok(IpexVerb::Spurn),
+            _ => Err(CoreError::InvalidExchange(format!(
+                "Unknown IPEX verb: {}",
+                verb
+            ))),
+        }
+    }
+
+    /// Get the `exn` route for this verb
+    pub fn route(&self) -> String {
+        format!("{}{}", IPEX_ROUTE_PREFIX, self)
+    }
+
+    /// Check whether this verb may follow `prior` (`None` starts an exchange)
+    pub fn can_follow(&self, prior: Option<IpexVerb>) -> bool {
+        use IpexVerb::*;
+
+        matches!(
+            (prior, self),
+            (None, Apply | Offer | Grant)
+                | (Some(Apply), Offer)
+                | (Some(Offer), Agree)
+                | (Some(Agree), Grant)
+                | (Some(Grant), Admit)
+                | (Some(Apply | Offer | Agree | Grant), Spurn)
+        )
+    }
+
+    /// Check whether this verb completes the exchange
+    pub fn is_terminal(&self) -> bool {
+        matches!(self, IpexVerb::Admit | IpexVerb::Spurn)
+    }
+}
+
+impl std::fmt::Display for IpexVerb {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            IpexVerb::Apply => write!(f, "apply"),
+            IpexVerb::Offer => write!(f, "offer"),
+            IpexVerb::Agree => write!(f, "agree"),
+            IpexVerb::Grant => write!(f, "grant"),
+            IpexVerb::Admit => write!(f, "admit"),
+            IpexVerb::Spurn => write!(f, "spurn"),
+        }
+    }
+}
+
+/// State of one IPEX exchange
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct IpexExchange {
+    /// Exchange identifier (SAID of the first message)
+    pub id: String,
+    /// AID that sent the first message
+    pub initiator: String,
+    /// The other party, once known
+    pub counterparty: Option<String>,
+    /// Verb of the latest message
+    pub state: IpexVerb,
+    /// SAIDs of accepted messages, in order
+    pub chain: Vec<String>,
+    /// Sender of the latest message
+    pub last_sender: String,
+    /// Recipient of the latest message
+    pub last_recipient: Option<String>,
+    /// SAID of the credential offered or granted, if any
+    pub credential: Option<String>,
+}
+
+impl IpexExchange {
+    /// Start a new exchange from its first message
+    pub fn start(message: &ExchangeMessage) -> CoreResult<Self> {
+        let verb = IpexVerb::from_route(&message.route)?;
+        if message.prior.is_some() || !verb.can_follow(None) {
+            return Err(CoreError::InvalidTransition {
+                from: "start".to_string(),
+                to: verb.to_string(),
+            });
+        }
+
+        Ok(IpexExchange {
+            id: message.said.clone(),
+            initiator: message.sender.clone(),
+            counterparty: message.recipient.clone(),
+            state: verb,
+            chain: vec![message.said.clone()],
+            last_sender: message.sender.clone(),
+            last_recipient: message.recipient.clone(),
+            credential: embedded_credential(message),
+        })
+    }
+
+    /// SAID of the latest accepted message
+    pub fn last_said(&self) -> &str {
+        self.chain.last().map(|s| s.as_str()).unwrap_or(&self.id)
+    }
+
+    /// Check whether the exchange is complete
+    pub fn is_complete(&self) -> bool {
+        self.state.is_terminal()
+    }
+
+    /// Advance the exchange with a response message
+    pub fn advance(&mut self, message: &ExchangeMessage) -> CoreResult<()> {
+        let verb = IpexVerb::from_route(&message.route)?;
+
+        if message.prior.as_deref() != Some(self.last_said()) {
+            return Err(CoreError::InvalidExchange(format!(
+                "Message {} does not respond to {}",
+                message.said,
+                self.last_said()
+            )));
+        }
+
+        if !verb.can_follow(Some(self.state)) {
+            return Err(CoreError::InvalidTransition {
+                from: self.state.to_string(),
+                to: verb.to_string(),
+            });
+        }
+
+        // Parties alternate: a response comes from the prior message's recipient
+        let expected_sender = self.last_recipient.as_deref();
+        if message.sender == self.last_sender
+            || expected_sender.is_some_and(|s| s != message.sender)
+        {
+            return Err(CoreError::InvalidExchange(format!(
+                "{} may not respond to its own or another party's message",
+                message.sender
+            )));
+        }
+        if message
+            .recipient
+            .as_deref()
+            .is_some_and(|r| r != self.last_sender)
+        {
+            return Err(CoreError::InvalidExchange(format!(
+                "Response must be addressed to {}",
+                self.last_sender
+            )));
+        }
+
+        if self.counterparty.is_none() && message.sender != self.initiator {
+            self.counterparty = Some(message.sender.clone());
+        }
+        if let Some(credential) = embedded_credential(message) {
+            self.credential = Some(credential);
+        }
+
+        self.state = verb;
+        self.chain.push(message.said.clone());
+        self.last_sender = message.sender.clone();
+        self.last_recipient = message.recipient.clone();
+        Ok(())
+    }
+}
+
+/// Get the SAID of an embedded credential
+fn embedded_credential(message: &ExchangeMessage) -> Option<String> {
+    message
+        .embedded(IPEX_CREDENTIAL_LABEL)
+        .and_then(|acdc| acdc.get("d"))
+        .and_then(|d| d.as_str())
+        .map(|s| s.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    const HOLDER: &str = "EHolder";
+    const ISSUER: &str = "EIssuer";
+
+    fn message(sender: &str, recipient: &str, verb: IpexVerb, prior: Option<&str>) -> ExchangeMessage {
+        ExchangeMessage::new(sender, Some(recipient), &verb.route(), prior, json!({}), None).unwrap()
+    }
+
+    #[test]
+    fn test_verb_from_route() {
+        assert_eq!(IpexVerb::from_route("/ipex/grant").unwrap(), IpexVerb::Grant);
+        assert!(IpexVerb::from_route("/ipex/steal").is_err());
+        assert!(IpexVerb::from_route("/credential/issue").is_err());
+        assert_eq!(IpexVerb::Admit.route(), "/ipex/admit");
+    }
+
+    #[test]
+    fn test_transitions() {
+        assert!(IpexVerb::Apply.can_follow(None));
+        assert!(IpexVerb::Grant.can_follow(None));
+        assert!(!IpexVerb::Admit.can_follow(None));
+        assert!(IpexVerb::Offer.can_follow(Some(IpexVerb::Apply)));
+        assert!(!IpexVerb::Grant.can_follow(Some(IpexVerb::Apply)));
+        assert!(IpexVerb::Spurn.can_follow(Some(IpexVerb::Grant)));
+        assert!(!IpexVerb::Spurn.can_follow(Some(IpexVerb::Admit)));
+        assert!(!IpexVerb::Offer.can_follow(Some(IpexVerb::Spurn)));
+    }
+
+    #[test]
+    fn test_full_exchange() {
+        let apply = message(HOLDER, ISSUER, IpexVerb::Apply, None);
+        let mut exchange = IpexExchange::start(&apply).unwrap();
+
+        let offer = message(ISSUER, HOLDER, IpexVerb::Offer, Some(&apply.said));
+        exchange.advance(&offer).unwrap();
+        let agree = message(HOLDER, ISSUER, IpexVerb::Agree, Some(&offer.said));
+        exchange.advance(&agree).unwrap();
+        let grant = ExchangeMessage::new(
+            ISSUER,
+            Some(HOLDER),
+            &IpexVerb::Grant.route(),
+            Some(&agree.said),
+            json!({}),
+            Some(json!({"acdc": {"d": "ECredential"}})),
+        )
+        .unwrap();
+        exchange.advance(&grant).unwrap();
+        let admit = message(HOLDER, ISSUER, IpexVerb::Admit, Some(&grant.said));
+        exchange.advance(&admit).unwrap();
+
+        assert!(exchange.is_complete());
+        assert_eq!(exchange.chain.len(), 5);
+        assert_eq!(exchange.counterparty.as_deref(), Some(ISSUER));
+        assert_eq!(exchange.credential.as_deref(), Some("ECredential"));
+    }
+
+    #[test]
+    fn test_invalid_transition() {
+        let apply = message(HOLDER, ISSUER, IpexVerb::Apply, None);
+        let mut exchange = IpexExchange::start(&apply).unwrap();
+
+        let grant = message(ISSUER, HOLDER, IpexVerb::Grant, Some(&apply.said));
+        let result = exchange.advance(&grant);
+        assert!(matches!(result, Err(CoreError::InvalidTransition { .. })));
+    }
+
+    #[test]
+    fn test_wrong_prior_rejected() {
+        let apply = message(HOLDER, ISSUER, IpexVerb::Apply, None);
+        let mut exchange = IpexExchange::start(&apply).unwrap();
+
+        let offer = message(ISSUER, HOLDER, IpexVerb::Offer, Some("EOther"));
+        assert!(exchange.advance(&offer).is_err());
+    }
+
+    #[test]
+    fn test_same_party_cannot_respond() {
+        let apply = message(HOLDER, ISSUER, IpexVerb::Apply, None);
+        let mut exchange = IpexExchange::start(&apply).unwrap();
+
+        let offer = message(HOLDER, ISSUER, IpexVerb::Offer, Some(&apply.said));
+        assert!(exchange.advance(&offer).is_err());
+    }
+
+    #[test]
+    fn test_start_requires_no_prior() {
+        let admit = message(HOLDER, ISSUER, IpexVerb::Admit, None);
+        let result = IpexExchange::start(&admit);
+        assert!(matches!(result, Err(CoreError::InvalidTransition { .. })));
+    }
+}
@@ -0,0 +1,422 @@
+//! Peer-to-peer exchange (`exn`) messages
+//!
+//! Exchange messages carry protocol conversations between AIDs, such as the
+//! IPEX credential exchange. Each message names its sender (`i`), optional
+//! recipient (`rp`), the SAID of the message it responds to (`p`) and a route
+//! (`r`) that selects the protocol and verb.
+
+mod ipex;
+
+pub use ipex::*;
+
+use crate::error::{CoreError, CoreResult};
+use crate::event::IndexedSignature;
+use crate::said::{saidify, verify_said};
+use crate::state::KeyState;
+use crate::validation::count_valid_signatures;
+use cesride::{counter, Counter, Matter};
+use parside::{CesrGroup, Message};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use serde_json::{Map, Value};
+
+/// Parsed `exn` message
+#[derive(Debug, Clone, PartialEq)]
+pub struct ExchangeMessage {
+    /// Message SAID (`d`)
+    pub said: String,
+    /// Sender AID (`i`)
+    pub sender: String,
+    /// Recipient AID (`rp`)
+    pub recipient: Option<String>,
+    /// SAID of the prior message in the conversation (`p`)
+    pub prior: Option<String>,
+    /// Route (`r`)
+    pub route: String,
+    /// Datetime (`dt`)
+    pub datetime: String,
+    /// Attributes (`a`)
+    pub attributes: Value,
+    /// Embedded SADs (`e`)
+    pub embeds: Option<Value>,
+    /// The full message
+    pub sad: Value,
+}
+
+impl ExchangeMessage {
+    /// Create and SAIDify a new exchange message
+    pub fn new(
+        sender: &str,
+        recipient: Option<&str>,
+        route: &str,
+        prior: Option<&str>,
+        attributes: Value,
+        embeds: Option<Value>,
+    ) -> CoreResult<Self> {
+        let mut sad = Map::new();
+        sad.insert("v".to_string(), Value::String("KERI10JSON000000_".to_string()));
+        sad.insert("t".to_string(), Value::String("exn".to_string()));
+        sad.insert("d".to_string(), Value::String(String::new()));
+        sad.insert("i".to_string(), Value::String(sender.to_string()));
+        if let Some(recipient) = recipient {
+            sad.insert("rp".to_string(), Value::String(recipient.to_string()));
+        }
+        sad.insert(
+            "p".to_string(),
+            Value::String(prior.unwrap_or_default().to_string()),
+        );
+        sad.insert(
+            "dt".to_string(),
+            Value::String(chrono::Utc::now().to_rfc3339()),
+        );
+        sad.insert("r".to_string(), Value::String(route.to_string()));
+        sad.insert("q".to_string(), Value::Object(Map::new()));
+        sad.insert("a".to_string(), attributes);
+
+        let embeds = match embeds {
+            Some(Value::Object(embeds)) => {
+                let mut block = Map::new();
+                block.insert("d".to_string(), Value::String(String::new()));
+                block.extend(embeds);
+                saidify(&Value::Object(block), "d")?
+            }
+            Some(_) => {
+                return Err(CoreError::InvalidExchange(
+                    "Embeds must be a map".to_string(),
+                ))
+            }
+            None => Value::Object(Map::new()),
+        };
+        sad.insert("e".to_string(), embeds);
+
+        Self::from_value(saidify(&Value::Object(sad), "d")?)
+    }
+
+    /// Parse message from raw JSON bytes
+    pub fn from_json(raw: &[u8]) -> CoreResult<Self> {
+        let sad: Value = serde_json::from_slice(raw)
+            .map_err(|e| CoreError::InvalidExchange(format!("JSON parse error: {}", e)))?;
+        Self::from_value(sad)
+    }
+
+    /// Parse message from a JSON value, verifying its SAID
+    pub fn from_value(sad: Value) -> CoreResult<Self> {
+        let text = |label: &str| sad.get(label).and_then(|v| v.as_str());
+        let required = |label: &str| {
+            text(label)
+                .map(|s| s.to_string())
+                .ok_or_else(|| CoreError::InvalidExchange(format!("missing '{}' field", label)))
+        };
+
+        if !required("v")?.starts_with("KERI") {
+            return Err(CoreError::InvalidExchange(
+                "Unsupported version string".to_string(),
+            ));
+        }
+        if text("t") != Some("exn") {
+            return Err(CoreError::InvalidExchange(format!(
+                "Expected 'exn' message, got {:?}",
+                text("t")
+            )));
+        }
+
+        verify_said(&sad, "d")?;
+        if let Some(embeds) = sad.get("e").filter(|e| e.get("d").is_some()) {
+            verify_said(embeds, "d")?;
+        }
+
+        Ok(ExchangeMessage {
+            said: required("d")?,
+            sender: required("i")?,
+            recipient: text("rp").filter(|s| !s.is_empty()).map(|s| s.to_string()),
+            prior: text("p").filter(|s| !s.is_empty()).map(|s| s.to_string()),
+            route: required("r")?,
+            datetime: text("dt").unwrap_or_default().to_string(),
+            attributes: sad.get("a").cloned().unwrap_or(Value::Null),
+            embeds: sad.get("e").cloned(),
+            sad,
+        })
+    }
+
+    /// Serialize message to JSON
+    pub fn to_json(&self) -> CoreResult<String> {
+        Ok(serde_json::to_string(&self.sad)?)
+    }
+
+    /// Get an embedded SAD by label
+    pub fn embedded(&self, label: &str) -> Option<&Value> {
+        self.embeds.as_ref().and_then(|e| e.get(label))
+    }
+}
+
+impl Serialize for ExchangeMessage {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        self.sad.serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for ExchangeMessage {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let sad = Value::deserialize(deserializer)?;
+        ExchangeMessage::from_value(sad).map_err(serde::de::Error::custom)
+    }
+}
+
+/// `exn` message with its sender's indexed signatures
+#[derive(Debug, Clone)]
+pub struct SignedExchange {
+    /// The message
+    pub message: ExchangeMessage,
+    /// Sender signatures over the serialized message
+    pub signatures: Vec<IndexedSignature>,
+}
+
+impl SignedExchange {
+    /// Create signed message from message and signatures
+    pub fn new(message: ExchangeMessage, signatures: Vec<IndexedSignature>) -> Self {
+        SignedExchange {
+            message,
+            signatures,
+        }
+    }
+
+    /// Parse signed message from CESR stream
+    ///
+    /// Signatures are taken from controller indexed signature (`-A`) groups
+    /// and from transferable indexed signature (`-F`) groups of the sender.
+    /// The message body must be in its canonical serialization, as that is
+    /// the form signatures are verified against.
+    pub fn from_cesr(raw: &[u8]) -> CoreResult<Self> {
+        let (after_body, first_msg) = Message::from_stream_bytes(raw)
+            .map_err(|e| CoreError::CesrParse(format!("parside: {}", e)))?;
+        if !matches!(first_msg, Message::Custom { .. }) {
+            return Err(CoreError::CesrParse(
+                "Expected JSON message as first message".into(),
+            ));
+        }
+
+        let body = &raw[..raw.len() - after_body.len()];
+        let message = ExchangeMessage::from_json(body)?;
+        if message.to_json()?.as_bytes() != body {
+            return Err(CoreError::InvalidExchange(
+                "Message is not in canonical serialization".to_string(),
+            ));
+        }
+
+        let mut signatures = Vec::new();
+        let mut rest = after_body;
+        while !rest.is_empty() {
+            let (remaining, msg) = Message::from_stream_bytes(rest)
+                .map_err(|e| CoreError::CesrParse(format!("parside attachment: {}", e)))?;
+
+            match msg {
+                Message::Group {
+                    value: CesrGroup::ControllerIdxSigsVariant { value: sigs },
+                } => {
+                    for sig in &sigs.value {
+                        signatures.push(IndexedSignature::from_siger(&sig.siger)?);
+                    }
+                }
+                Message::Group {
+                    value: CesrGroup::TransIdxSigGroupsVariant { value: groups },
+                } => {
+                    for group in &groups.value {
+                        let signer = group
+                            .prefixer
+                            .qb64()
+                            .map_err(|e| CoreError::CesrParse(e.to_string()))?;
+                        if signer != message.sender {
+                            return Err(CoreError::InvalidExchange(format!(
+                                "Signature group for {} on message from {}",
+                                signer, message.sender
+                            )));
+                        }
+                        for sig in &group.isigers.value {
+                            signatures.push(IndexedSignature::from_siger(&sig.siger)?);
+                        }
+                    }
+                }
+                _ => {}
+            }
+
+            if remaining.len() == rest.len() {
+                break; // No progress, avoid infinite loop
+            }
+            rest = remaining;
+        }
+
+        Ok(SignedExchange {
+            message,
+            signatures,
+        })
+    }
+
+    /// Serialize to a CESR stream: the message followed by its signatures
+    pub fn to_cesr(&self) -> CoreResult<Vec<u8>> {
+        let mut cesr = self.message.to_json()?.into_bytes();
+        if !self.signatures.is_empty() {
+            let counter = Counter::new_with_code_and_count(
+                counter::Codex::ControllerIdxSigs,
+                self.signatures.len() as u32,
+            )
+            .map_err(|e| CoreError::CesrParse(e.to_string()))?;
+            cesr.extend(
+                counter
+                    .qb64b()
+                    .map_err(|e| CoreError::CesrParse(e.to_string()))?,
+            );
+            for sig in &self.signatures {
+                cesr.extend(sig.signature.as_bytes());
+            }
+        }
+
+        Ok(cesr)
+    }
+
+    /// Verify the signatures against the sender's current key state
+    ///
+    /// Fails unless `state` belongs to the sender and enough signatures
+    /// verify to meet its signing threshold.
+    pub fn verify(&self, state: &KeyState) -> CoreResult<()> {
+        if state.prefix != self.message.sender {
+            return Err(CoreError::InvalidExchange(format!(
+                "Key state for {} does not belong to sender {}",
+                state.prefix, self.message.sender
+            )));
+        }
+
+        let have = count_valid_signatures(
+            &state.signing_keys,
+            &self.signatures,
+            self.message.to_json()?.as_bytes(),
+        )?;
+        let need = state.min_signatures();
+        if have < need {
+            return Err(CoreError::ThresholdNotMet { have, need });
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::{key_state, signer};
+    use cesride::Signer;
+    use serde_json::json;
+
+    #[test]
+    fn test_exchange_message_new() {
+        let msg = ExchangeMessage::new(
+            "EHolder",
+            Some("EIssuer"),
+            "/ipex/apply",
+            None,
+            json!({"m": "please"}),
+            None,
+        )
+        .unwrap();
+
+        assert_eq!(msg.sender, "EHolder");
+        assert_eq!(msg.recipient.as_deref(), Some("EIssuer"));
+        assert_eq!(msg.prior, None);
+        assert_eq!(msg.said.len(), 44);
+    }
+
+    #[test]
+    fn test_exchange_message_roundtrip() {
+        let msg = ExchangeMessage::new(
+            "EIssuer",
+            Some("EHolder"),
+            "/ipex/grant",
+            Some("EPrior"),
+            json!({}),
+            Some(json!({"acdc": {"d": "ECredential"}})),
+        )
+        .unwrap();
+
+        let parsed = ExchangeMessage::from_json(msg.to_json().unwrap().as_bytes()).unwrap();
+        assert_eq!(parsed, msg);
+        assert_eq!(parsed.prior.as_deref(), Some("EPrior"));
+        assert_eq!(parsed.embedded("acdc"), Some(&json!({"d": "ECredential"})));
+    }
+
+    #[test]
+    fn test_exchange_message_tampered() {
+        let msg =
+            ExchangeMessage::new("EHolder", None, "/ipex/apply", None, json!({}), None).unwrap();
+        let mut sad = msg.sad.clone();
+        sad["i"] = json!("EMallory");
+
+        let result = ExchangeMessage::from_value(sad);
+        assert!(matches!(result, Err(CoreError::SaidMismatch { .. })));
+    }
+
+    #[test]
+    fn test_exchange_message_wrong_type() {
+        let sad = saidify(&json!({"v": "KERI10JSON000000_", "t": "rct", "d": ""}), "d").unwrap();
+        let result = ExchangeMessage::from_value(sad);
+        assert!(matches!(result, Err(CoreError::InvalidExchange(_))));
+    }
+
+    fn sign(signer: &Signer, message: ExchangeMessage) -> SignedExchange {
+        let raw = message.to_json().unwrap();
+        let siger = signer.sign_indexed(raw.as_bytes(), false, 0, None).unwrap();
+        SignedExchange::new(message, vec![IndexedSignature::from_siger(&siger).unwrap()])
+    }
+
+    #[test]
+    fn test_signed_exchange_roundtrip_and_verify() {
+        let signer = signer(3);
+        let state = key_state(&signer);
+        let message =
+            ExchangeMessage::new(&state.prefix, None, "/ipex/apply", None, json!({}), None)
+                .unwrap();
+
+        let signed = sign(&signer, message);
+        let parsed = SignedExchange::from_cesr(&signed.to_cesr().unwrap()).unwrap();
+        assert_eq!(parsed.message, signed.message);
+        assert_eq!(parsed.signatures.len(), 1);
+        assert!(parsed.verify(&state).is_ok());
+    }
+
+    #[test]
+    fn test_signed_exchange_rejects_forged_and_unsigned() {
+        let sender = signer(3);
+        let mallory = signer(4);
+        let state = key_state(&sender);
+        let message =
+            ExchangeMessage::new(&state.prefix, None, "/ipex/admit", None, json!({}), None)
+                .unwrap();
+
+        let forged = sign(&mallory, message.clone());
+        assert!(matches!(
+            forged.verify(&state),
+            Err(CoreError::ThresholdNotMet { have: 0, need: 1 })
+        ));
+
+        let unsigned = SignedExchange::from_cesr(message.to_json().unwrap().as_bytes()).unwrap();
+        assert!(unsigned.signatures.is_empty());
+        assert!(matches!(
+            unsigned.verify(&state),
+            Err(CoreError::ThresholdNotMet { have: 0, need: 1 })
+        ));
+
+        // Signatures cannot be verified against another identifier's state
+        let signed = sign(&sender, message);
+        assert!(matches!(
+            signed.verify(&key_state(&mallory)),
+            Err(CoreError::InvalidExchange(_))
+        ));
+    }
+
+    #[test]
+    fn test_signed_exchange_non_canonical_rejected() {
+        let message =
+            ExchangeMessage::new("EHolder", None, "/ipex/apply", None, json!({}), None).unwrap();
+        let pretty = serde_json::to_vec_pretty(&message.sad).unwrap();
+
+        let result = SignedExchange::from_cesr(&pretty);
+        assert!(matches!(result, Err(CoreError::InvalidExchange(_))));
+    }
+}
@@ -5,6 +5,8 @@
 //! - Key state computation
 //! - Event validation
 //! - Receipt types
+//! - ACDC credentials and their schemas
+//! - Exchange (`exn`) messages and the IPEX protocol
 //!
 //! # KERI-Honest Design
 //!
@@ -14,15 +16,23 @@
 //! - Cryptographic eventual finality
 //! - Explicit confidence qualifiers
 
+pub mod acdc;
 pub mod error;
 pub mod event;
+pub mod exchange;
 pub mod receipt;
+pub mod said;
 pub mod state;
+#[cfg(any(test, feature = "test-util"))]
+pub mod test_util;
 pub mod validation;
 
+pub use acdc::*;
 pub use error::*;
 pub use event::*;
+pub use exchange::*;
 pub use receipt::*;
+pub use said::*;
 pub use state::*;
 pub use validation::*;
 
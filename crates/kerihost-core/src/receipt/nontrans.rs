@@ -303,7 +303,7 @@ mod tests {
 
     #[test]
     fn test_receipt_builder_missing_field() {
-        let builder = NontransReceiptBuilder::new()
+        let _builder = NontransReceiptBuilder::new()
             .event_digest("EDigest123".to_string());
         // Missing required fields
 
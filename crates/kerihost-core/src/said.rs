@@ -0,0 +1,180 @@
+//! Self-Addressing IDentifier (SAID) derivation for JSON data
+//!
+//! Events are verified against their original raw bytes (see `event`), but
+//! schemas, credentials and exchange messages are handled as parsed JSON.
+//! These helpers derive a SAID from a JSON value using the standard procedure:
+//! fill the SAID field with `#` placeholders of the final length, fix up the
+//! version string size (if any), serialize compactly, and digest.
+
+use crate::error::{CoreError, CoreResult};
+use cesride::{Diger, Matter};
+use serde_json::Value;
+
+/// Placeholder character for the SAID field during derivation
+const DUMMY: char = '#';
+
+/// Default digest code (Blake3-256)
+const DEFAULT_DIGEST_CODE: &str = "E";
+
+/// Length of a qb64 encoded 256-bit digest
+const DIGEST_LENGTH: usize = 44;
+
+/// Compute the SAID of a self-addressed JSON map
+///
+/// The digest algorithm is taken from the current value of `label` when it
+/// holds a valid digest, otherwise Blake3-256 is used.
+pub fn compute_said(sad: &Value, label: &str) -> CoreResult<String> {
+    let code = match sad.get(label).and_then(|v| v.as_str()) {
+        Some(current) if !current.is_empty() && !current.starts_with(DUMMY) => {
+            let diger = Diger::new_with_qb64(current)
+                .map_err(|e| CoreError::CesrParse(format!("Invalid SAID: {}", e)))?;
+            Matter::code(&diger)
+        }
+        _ => DEFAULT_DIGEST_CODE.to_string(),
+    };
+
+    let dummied = with_placeholder(sad, label)?;
+    let ser = serde_json::to_vec(&dummied)?;
+
+    let diger = Diger::new_with_ser(&ser, Some(&code))
+        .map_err(|e| CoreError::CesrParse(format!("Failed to compute SAID: {}", e)))?;
+    diger
+        .qb64()
+        .map_err(|e| CoreError::CesrParse(format!("Failed to encode SAID: {}", e)))
+}
+
+/// Return a copy of `sad` with its SAID field (and version size) populated
+pub fn saidify(sad: &Value, label: &str) -> CoreResult<Value> {
+    let said = compute_said(sad, label)?;
+    let mut sad = with_placeholder(sad, label)?;
+    sad[label] = Value::String(said);
+    Ok(sad)
+}
+
+/// Verify that the SAID field of `sad` matches its content
+pub fn verify_said(sad: &Value, label: &str) -> CoreResult<()> {
+    let expected = sad
+        .get(label)
+        .and_then(|v| v.as_str())
+        .ok_or_else(|| CoreError::InvalidEvent(format!("missing SAID field '{}'", label)))?;
+
+    let computed = compute_said(sad, label)?;
+    if computed != expected {
+        return Err(CoreError::SaidMismatch {
+            expected: expected.to_string(),
+            computed,
+        });
+    }
+
+    Ok(())
+}
+
+/// Compute the SAID of a list whose zeroth element is its own SAID
+///
+/// This is how the ACDC specification derives the aggregate identifier
+/// (AGID) of a selectively disclosable attribute section: the zeroth
+/// element is filled with placeholders, the list `[AGID, SAID1, SAID2, ...]`
+/// is serialized compactly, and the result digested with Blake3-256.
+pub fn compute_list_said(items: &[String]) -> CoreResult<String> {
+    let mut list = vec![Value::String(DUMMY.to_string().repeat(DIGEST_LENGTH))];
+    list.extend(items.iter().cloned().map(Value::String));
+    let ser = serde_json::to_vec(&list)?;
+
+    let diger = Diger::new_with_ser(&ser, Some(DEFAULT_DIGEST_CODE))
+        .map_err(|e| CoreError::CesrParse(format!("Failed to compute SAID: {}", e)))?;
+    diger
+        .qb64()
+        .map_err(|e| CoreError::CesrParse(format!("Failed to encode SAID: {}", e)))
+}
+
+/// Replace the SAID field with placeholders and size the version string
+fn with_placeholder(sad: &Value, label: &str) -> CoreResult<Value> {
+    let mut sad = sad.clone();
+    let map = sad
+        .as_object_mut()
+        .ok_or_else(|| CoreError::InvalidEvent("SAD must be a JSON object".to_string()))?;
+
+    if !map.contains_key(label) {
+        return Err(CoreError::InvalidEvent(format!(
+            "missing SAID field '{}'",
+            label
+        )));
+    }
+    map.insert(
+        label.to_string(),
+        Value::String(DUMMY.to_string().repeat(DIGEST_LENGTH)),
+    );
+
+    // Versioned SADs carry their own serialized size in the version string
+    let version = match map.get("v") {
+        Some(Value::String(v)) if v.len() == 17 && v.ends_with('_') => Some(v[..10].to_string()),
+        _ => None,
+    };
+    if let Some(version) = version {
+        let size = serde_json::to_vec(&sad)?.len();
+        sad["v"] = Value::String(format!("{}{:06x}_", version, size));
+    }
+
+    Ok(sad)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_saidify_and_verify() {
+        let sad = json!({"d": "", "name": "test"});
+        let saidified = saidify(&sad, "d").unwrap();
+
+        let said = saidified["d"].as_str().unwrap();
+        assert_eq!(said.len(), 44);
+        assert!(said.starts_with('E'));
+        assert!(verify_said(&saidified, "d").is_ok());
+    }
+
+    #[test]
+    fn test_verify_detects_tampering() {
+        let mut saidified = saidify(&json!({"d": "", "name": "test"}), "d").unwrap();
+        saidified["name"] = json!("tampered");
+
+        let result = verify_said(&saidified, "d");
+        assert!(matches!(result, Err(CoreError::SaidMismatch { .. })));
+    }
+
+    #[test]
+    fn test_saidify_custom_label() {
+        let schema = json!({"$id": "", "type": "object"});
+        let saidified = saidify(&schema, "$id").unwrap();
+        assert!(verify_said(&saidified, "$id").is_ok());
+    }
+
+    #[test]
+    fn test_saidify_sizes_version_string() {
+        let sad = json!({"v": "ACDC10JSON000000_", "d": "", "i": "EIssuer"});
+        let saidified = saidify(&sad, "d").unwrap();
+
+        let size = serde_json::to_vec(&saidified).unwrap().len();
+        let expected = format!("ACDC10JSON{:06x}_", size);
+        assert_eq!(saidified["v"], json!(expected));
+    }
+
+    #[test]
+    fn test_compute_list_said() {
+        let items = vec!["EFirst".to_string(), "ESecond".to_string()];
+        let said = compute_list_said(&items).unwrap();
+
+        let placeholder = "#".repeat(44);
+        let ser = format!(r#"["{}","EFirst","ESecond"]"#, placeholder);
+        let expected = Diger::new_with_ser(ser.as_bytes(), None).unwrap();
+        assert_eq!(said, expected.qb64().unwrap());
+        assert_ne!(said, compute_list_said(&items[..1]).unwrap());
+    }
+
+    #[test]
+    fn test_saidify_missing_label() {
+        let result = saidify(&json!({"name": "test"}), "d");
+        assert!(result.is_err());
+    }
+}
@@ -0,0 +1,54 @@
+//! Key event fixtures for tests
+//!
+//! Built for this crate's own tests and, behind the `test-util` feature,
+//! for the tests of crates that depend on it.
+
+use crate::{saidify, IndexedSignature, KeyEvent, KeyState, SignedEvent};
+use cesride::{Matter, Signer};
+use serde_json::{json, Value};
+
+/// Transferable signer whose key is derived from `seed` repeated
+pub fn signer(seed: u8) -> Signer {
+    Signer::new_with_raw(&[seed; 32], Some(true), None).unwrap()
+}
+
+/// SAIDify an event and sign it with `signer` as its only controller key
+pub fn sign_event(signer: &Signer, ked: Value) -> SignedEvent {
+    let raw = serde_json::to_vec(&saidify(&ked, "d").unwrap()).unwrap();
+    let siger = signer.sign_indexed(&raw, false, 0, None).unwrap();
+    SignedEvent::new(
+        KeyEvent::from_cesr(&raw).unwrap(),
+        vec![IndexedSignature::from_siger(&siger).unwrap()],
+    )
+}
+
+/// Inception of a single-key identifier whose prefix is its key,
+/// requiring a receipt from each of `witnesses`
+pub fn inception(signer: &Signer, witnesses: &[&str]) -> SignedEvent {
+    let key = signer.verfer().qb64().unwrap();
+    sign_event(
+        signer,
+        json!({
+            "v": "KERI10JSON000000_", "t": "icp", "d": "", "i": key, "s": "0",
+            "kt": "1", "k": [key], "nt": "0", "n": [],
+            "bt": format!("{:x}", witnesses.len()), "b": witnesses, "c": [], "a": []
+        }),
+    )
+}
+
+/// Interaction at `sn` of `signer`'s identifier, chained to `prior`
+pub fn interaction(signer: &Signer, sn: u64, prior: &str, anchors: Value) -> SignedEvent {
+    let key = signer.verfer().qb64().unwrap();
+    sign_event(
+        signer,
+        json!({
+            "v": "KERI10JSON000000_", "t": "ixn", "d": "", "i": key,
+            "s": format!("{:x}", sn), "p": prior, "a": anchors
+        }),
+    )
+}
+
+/// Key state of `signer`'s unwitnessed single-key identifier
+pub fn key_state(signer: &Signer) -> KeyState {
+    KeyState::from_inception(&inception(signer, &[]).event).unwrap()
+}
@@ -0,0 +1,57 @@
+-- Key event log: one row per accepted event, stored as its original CESR
+CREATE TABLE kel (
+    aid TEXT NOT NULL,
+    sn BIGINT NOT NULL,
+    digest TEXT NOT NULL,
+    prior_digest TEXT,
+    cesr BYTEA NOT NULL,
+    created TIMESTAMPTZ NOT NULL DEFAULT now(),
+    PRIMARY KEY (aid, sn)
+);
+
+CREATE INDEX kel_digest_idx ON kel (digest);
+
+-- Anchored seal index: seal digest -> anchoring event, in append order
+CREATE TABLE anchors (
+    id BIGSERIAL PRIMARY KEY,
+    digest TEXT NOT NULL,
+    aid TEXT NOT NULL,
+    sn BIGINT NOT NULL,
+    event_digest TEXT NOT NULL,
+    seal TEXT NOT NULL,
+    anchored TIMESTAMPTZ NOT NULL DEFAULT now()
+);
+
+CREATE INDEX anchors_digest_idx ON anchors (digest);
+
+-- Current key state per identifier, versioned by (sn, digest)
+CREATE TABLE states (
+    aid TEXT PRIMARY KEY,
+    sn BIGINT NOT NULL,
+    digest TEXT NOT NULL,
+    state TEXT NOT NULL
+);
+
+-- Witness receipts, one per (event, witness)
+CREATE TABLE receipts (
+    event_digest TEXT NOT NULL,
+    witness_aid TEXT NOT NULL,
+    event_aid TEXT NOT NULL,
+    event_sn BIGINT NOT NULL,
+    signature TEXT NOT NULL,
+    receipt TEXT NOT NULL,
+    PRIMARY KEY (event_digest, witness_aid)
+);
+
+-- Escrowed events awaiting promotion
+CREATE TABLE escrows (
+    digest TEXT PRIMARY KEY,
+    seq BIGSERIAL NOT NULL,
+    aid TEXT NOT NULL,
+    reason TEXT NOT NULL,
+    escrowed TEXT NOT NULL,
+    cesr BYTEA NOT NULL,
+    ttl BIGINT NOT NULL
+);
+
+CREATE INDEX escrows_aid_idx ON escrows (aid);
@@ -0,0 +1,3 @@
+-- Per-reason escrow queries, soonest-expiring first
+
+CREATE INDEX escrows_reason_ttl_idx ON escrows (reason, ttl, digest);
@@ -0,0 +1,17 @@
+-- First-seen log: the order (fn) and time each event was first accepted
+CREATE TABLE first_seen (
+    aid TEXT NOT NULL,
+    fn BIGINT NOT NULL,
+    sn BIGINT NOT NULL,
+    digest TEXT NOT NULL,
+    datetime TEXT NOT NULL,
+    PRIMARY KEY (aid, fn)
+);
+
+CREATE INDEX first_seen_digest_idx ON first_seen (digest);
+
+-- Events accepted before the log existed were seen in KEL order
+INSERT INTO first_seen (aid, fn, sn, digest, datetime)
+SELECT aid, sn, sn, digest,
+       to_char(created AT TIME ZONE 'UTC', 'YYYY-MM-DD"T"HH24:MI:SS.US"+00:00"')
+FROM kel;
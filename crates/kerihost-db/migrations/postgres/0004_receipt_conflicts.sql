@@ -0,0 +1,9 @@
+-- Receipts rejected because the witness had already receipted the event
+-- with a different signature: evidence of witness duplicity
+CREATE TABLE receipt_conflicts (
+    witness_aid TEXT NOT NULL,
+    event_digest TEXT NOT NULL,
+    conflicting_signature TEXT NOT NULL,
+    conflict TEXT NOT NULL,
+    PRIMARY KEY (witness_aid, event_digest, conflicting_signature)
+);
@@ -0,0 +1,29 @@
+-- Tenant namespaces: every row belongs to a tenant, '' being the default
+-- namespace where rows written before tenants existed stay
+
+ALTER TABLE kel ADD COLUMN tenant TEXT NOT NULL DEFAULT '';
+ALTER TABLE kel DROP CONSTRAINT kel_pkey, ADD PRIMARY KEY (tenant, aid, sn);
+
+ALTER TABLE anchors ADD COLUMN tenant TEXT NOT NULL DEFAULT '';
+
+ALTER TABLE states ADD COLUMN tenant TEXT NOT NULL DEFAULT '';
+ALTER TABLE states DROP CONSTRAINT states_pkey, ADD PRIMARY KEY (tenant, aid);
+
+ALTER TABLE receipts ADD COLUMN tenant TEXT NOT NULL DEFAULT '';
+ALTER TABLE receipts DROP CONSTRAINT receipts_pkey,
+    ADD PRIMARY KEY (tenant, event_digest, witness_aid);
+
+ALTER TABLE escrows ADD COLUMN tenant TEXT NOT NULL DEFAULT '';
+ALTER TABLE escrows DROP CONSTRAINT escrows_pkey, ADD PRIMARY KEY (tenant, digest);
+DROP INDEX escrows_aid_idx;
+CREATE INDEX escrows_aid_idx ON escrows (tenant, aid);
+DROP INDEX escrows_reason_ttl_idx;
+CREATE INDEX escrows_reason_ttl_idx ON escrows (tenant, reason, ttl, digest);
+
+ALTER TABLE first_seen ADD COLUMN tenant TEXT NOT NULL DEFAULT '';
+ALTER TABLE first_seen DROP CONSTRAINT first_seen_pkey,
+    ADD PRIMARY KEY (tenant, aid, fn);
+
+ALTER TABLE receipt_conflicts ADD COLUMN tenant TEXT NOT NULL DEFAULT '';
+ALTER TABLE receipt_conflicts DROP CONSTRAINT receipt_conflicts_pkey,
+    ADD PRIMARY KEY (tenant, witness_aid, event_digest, conflicting_signature);
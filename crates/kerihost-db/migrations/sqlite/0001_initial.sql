@@ -0,0 +1,56 @@
+-- Key event log: one row per accepted event, stored as its original CESR
+CREATE TABLE kel (
+    aid TEXT NOT NULL,
+    sn INTEGER NOT NULL,
+    digest TEXT NOT NULL,
+    prior_digest TEXT,
+    cesr BLOB NOT NULL,
+    created TEXT NOT NULL,
+    PRIMARY KEY (aid, sn)
+);
+
+CREATE INDEX kel_digest_idx ON kel (digest);
+
+-- Anchored seal index: seal digest -> anchoring event, in append order
+CREATE TABLE anchors (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    digest TEXT NOT NULL,
+    aid TEXT NOT NULL,
+    sn INTEGER NOT NULL,
+    event_digest TEXT NOT NULL,
+    seal TEXT NOT NULL,
+    anchored TEXT NOT NULL
+);
+
+CREATE INDEX anchors_digest_idx ON anchors (digest);
+
+-- Current key state per identifier, versioned by (sn, digest)
+CREATE TABLE states (
+    aid TEXT PRIMARY KEY NOT NULL,
+    sn INTEGER NOT NULL,
+    digest TEXT NOT NULL,
+    state TEXT NOT NULL
+);
+
+-- Witness receipts, one per (event, witness)
+CREATE TABLE receipts (
+    event_digest TEXT NOT NULL,
+    witness_aid TEXT NOT NULL,
+    event_aid TEXT NOT NULL,
+    event_sn INTEGER NOT NULL,
+    signature TEXT NOT NULL,
+    receipt TEXT NOT NULL,
+    PRIMARY KEY (event_digest, witness_aid)
+);
+
+-- Escrowed events awaiting promotion
+CREATE TABLE escrows (
+    digest TEXT PRIMARY KEY NOT NULL,
+    aid TEXT NOT NULL,
+    reason TEXT NOT NULL,
+    escrowed TEXT NOT NULL,
+    cesr BLOB NOT NULL,
+    ttl INTEGER NOT NULL
+);
+
+CREATE INDEX escrows_aid_idx ON escrows (aid);
@@ -0,0 +1,15 @@
+-- First-seen log: the order (fn) and time each event was first accepted
+CREATE TABLE first_seen (
+    aid TEXT NOT NULL,
+    fn INTEGER NOT NULL,
+    sn INTEGER NOT NULL,
+    digest TEXT NOT NULL,
+    datetime TEXT NOT NULL,
+    PRIMARY KEY (aid, fn)
+);
+
+CREATE INDEX first_seen_digest_idx ON first_seen (digest);
+
+-- Events accepted before the log existed were seen in KEL order
+INSERT INTO first_seen (aid, fn, sn, digest, datetime)
+SELECT aid, sn, sn, digest, created FROM kel;
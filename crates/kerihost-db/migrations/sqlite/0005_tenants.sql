@@ -0,0 +1,99 @@
+-- Tenant namespaces: every row belongs to a tenant, '' being the default
+-- namespace where rows written before tenants existed stay. SQLite cannot
+-- change a primary key in place, so keyed tables are rebuilt.
+
+CREATE TABLE kel_tenants (
+    tenant TEXT NOT NULL DEFAULT '',
+    aid TEXT NOT NULL,
+    sn INTEGER NOT NULL,
+    digest TEXT NOT NULL,
+    prior_digest TEXT,
+    cesr BLOB NOT NULL,
+    created TEXT NOT NULL,
+    PRIMARY KEY (tenant, aid, sn)
+);
+INSERT INTO kel_tenants (aid, sn, digest, prior_digest, cesr, created)
+SELECT aid, sn, digest, prior_digest, cesr, created FROM kel;
+DROP TABLE kel;
+ALTER TABLE kel_tenants RENAME TO kel;
+CREATE INDEX kel_digest_idx ON kel (digest);
+
+ALTER TABLE anchors ADD COLUMN tenant TEXT NOT NULL DEFAULT '';
+
+CREATE TABLE states_tenants (
+    tenant TEXT NOT NULL DEFAULT '',
+    aid TEXT NOT NULL,
+    sn INTEGER NOT NULL,
+    digest TEXT NOT NULL,
+    state TEXT NOT NULL,
+    PRIMARY KEY (tenant, aid)
+);
+INSERT INTO states_tenants (aid, sn, digest, state)
+SELECT aid, sn, digest, state FROM states;
+DROP TABLE states;
+ALTER TABLE states_tenants RENAME TO states;
+
+CREATE TABLE receipts_tenants (
+    tenant TEXT NOT NULL DEFAULT '',
+    event_digest TEXT NOT NULL,
+    witness_aid TEXT NOT NULL,
+    event_aid TEXT NOT NULL,
+    event_sn INTEGER NOT NULL,
+    signature TEXT NOT NULL,
+    receipt TEXT NOT NULL,
+    PRIMARY KEY (tenant, event_digest, witness_aid)
+);
+INSERT INTO receipts_tenants
+    (event_digest, witness_aid, event_aid, event_sn, signature, receipt)
+SELECT event_digest, witness_aid, event_aid, event_sn, signature, receipt
+FROM receipts;
+DROP TABLE receipts;
+ALTER TABLE receipts_tenants RENAME TO receipts;
+
+-- Escrows are read in insertion (rowid) order, which the copy keeps
+CREATE TABLE escrows_tenants (
+    tenant TEXT NOT NULL DEFAULT '',
+    digest TEXT NOT NULL,
+    aid TEXT NOT NULL,
+    reason TEXT NOT NULL,
+    escrowed TEXT NOT NULL,
+    cesr BLOB NOT NULL,
+    ttl INTEGER NOT NULL,
+    PRIMARY KEY (tenant, digest)
+);
+INSERT INTO escrows_tenants (digest, aid, reason, escrowed, cesr, ttl)
+SELECT digest, aid, reason, escrowed, cesr, ttl FROM escrows ORDER BY rowid;
+DROP TABLE escrows;
+ALTER TABLE escrows_tenants RENAME TO escrows;
+CREATE INDEX escrows_aid_idx ON escrows (tenant, aid);
+CREATE INDEX escrows_reason_ttl_idx ON escrows (tenant, reason, ttl, digest);
+
+CREATE TABLE first_seen_tenants (
+    tenant TEXT NOT NULL DEFAULT '',
+    aid TEXT NOT NULL,
+    fn INTEGER NOT NULL,
+    sn INTEGER NOT NULL,
+    digest TEXT NOT NULL,
+    datetime TEXT NOT NULL,
+    PRIMARY KEY (tenant, aid, fn)
+);
+INSERT INTO first_seen_tenants (aid, fn, sn, digest, datetime)
+SELECT aid, fn, sn, digest, datetime FROM first_seen;
+DROP TABLE first_seen;
+ALTER TABLE first_seen_tenants RENAME TO first_seen;
+CREATE INDEX first_seen_digest_idx ON first_seen (digest);
+
+CREATE TABLE receipt_conflicts_tenants (
+    tenant TEXT NOT NULL DEFAULT '',
+    witness_aid TEXT NOT NULL,
+    event_digest TEXT NOT NULL,
+    conflicting_signature TEXT NOT NULL,
+    conflict TEXT NOT NULL,
+    PRIMARY KEY (tenant, witness_aid, event_digest, conflicting_signature)
+);
+INSERT INTO receipt_conflicts_tenants
+    (witness_aid, event_digest, conflicting_signature, conflict)
+SELECT witness_aid, event_digest, conflicting_signature, conflict
+FROM receipt_conflicts;
+DROP TABLE receipt_conflicts;
+ALTER TABLE receipt_conflicts_tenants RENAME TO receipt_conflicts;
@@ -0,0 +1,721 @@
+//! Read-through cache over any backend
+//!
+//! [`CachedDatabase`] keeps bounded LRU caches of key states, latest events
+//! and receipt counts, the reads behind every `/process` call and state
+//! query. Writes through the wrapper update or drop the entries they touch,
+//! and every entry expires after a TTL.
+//!
+//! Other processes writing the same prefix can leave a cached state behind
+//! the stored one, but never ahead of it. That is safe because commits are
+//! conditional on the stored state: a commit validated against a stale state
+//! fails with the retryable `DbError::StateConflict`, the wrapper drops the
+//! prefix's entries, and the retry reads the stored state. A stale state can
+//! also get an event escrowed as out of order; escrowing drops the prefix's
+//! entries too, so the next escrow pass sees the stored state. Receipt
+//! counts written elsewhere show up once their entry expires, or at once
+//! when the cache follows a change feed (see [`CachedDatabase::follow`]).
+//! Absent states and events are not cached.
+//!
+//! Each tenant namespace has caches of its own, shared by every handle
+//! scoped to the tenant.
+
+use crate::error::DbResult;
+use crate::traits::{
+    AnchorLocation, Change, ChangeFeed, ChangeStream, EscrowReason, EscrowStore, EscrowedEvent,
+    EscrowedMessage, ExchangeStore, FirstSeen, KelStore, MigrationReport, MigrationStore, Page,
+    ReceiptConflict, ReceiptStore, SchemaStore, StateStore, TenantStore,
+};
+use async_trait::async_trait;
+use futures::StreamExt;
+use kerihost_core::{
+    CredentialSchema, ExchangeMessage, IpexExchange, KeyState, NontransferableReceipt, SignedEvent,
+};
+use lru::LruCache;
+use std::collections::HashMap;
+use std::num::NonZeroUsize;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+/// Default entries kept per cache
+const DEFAULT_CAPACITY: usize = 10_000;
+
+/// Default time an entry is served before it is read again
+const DEFAULT_TTL: Duration = Duration::from_secs(30);
+
+/// One bounded cache of values by key
+struct Cache<V> {
+    inner: Mutex<CacheInner<V>>,
+    ttl: Duration,
+}
+
+struct CacheInner<V> {
+    entries: LruCache<String, (V, Instant)>,
+    /// Bumped by every write, so a read that raced one does not fill
+    epoch: u64,
+}
+
+impl<V: Clone> Cache<V> {
+    fn new(capacity: usize, ttl: Duration) -> Self {
+        let capacity = NonZeroUsize::new(capacity).unwrap_or(NonZeroUsize::MIN);
+        Cache {
+            inner: Mutex::new(CacheInner {
+                entries: LruCache::new(capacity),
+                epoch: 0,
+            }),
+            ttl,
+        }
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, CacheInner<V>> {
+        self.inner.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Get an unexpired value
+    fn get(&self, key: &str) -> Option<V> {
+        let mut inner = self.lock();
+        match inner.entries.get(key) {
+            Some((value, cached)) if cached.elapsed() < self.ttl => Some(value.clone()),
+            Some(_) => {
+                inner.entries.pop(key);
+                None
+            }
+            None => None,
+        }
+    }
+
+    /// Epoch to pass to `fill` after reading the backend
+    fn epoch(&self) -> u64 {
+        self.lock().epoch
+    }
+
+    /// Cache a value read from the backend, unless a write came in since
+    /// `epoch`
+    fn fill(&self, key: &str, value: V, epoch: u64) {
+        let mut inner = self.lock();
+        if inner.epoch == epoch {
+            inner.entries.put(key.to_string(), (value, Instant::now()));
+        }
+    }
+
+    /// Cache a value just written
+    fn put(&self, key: &str, value: V) {
+        let mut inner = self.lock();
+        inner.epoch += 1;
+        inner.entries.put(key.to_string(), (value, Instant::now()));
+    }
+
+    /// Drop a value
+    fn invalidate(&self, key: &str) {
+        let mut inner = self.lock();
+        inner.epoch += 1;
+        inner.entries.pop(key);
+    }
+
+    /// Drop every value
+    fn clear(&self) {
+        let mut inner = self.lock();
+        inner.epoch += 1;
+        inner.entries.clear();
+    }
+}
+
+/// The caches of a `CachedDatabase`, shared with its change feed task
+struct Caches {
+    /// Key state by prefix
+    states: Cache<KeyState>,
+    /// Latest event by prefix
+    latest: Cache<SignedEvent>,
+    /// Receipt count by event digest
+    receipt_counts: Cache<usize>,
+}
+
+impl Caches {
+    fn new(capacity: usize, ttl: Duration) -> Self {
+        Caches {
+            states: Cache::new(capacity, ttl),
+            latest: Cache::new(capacity, ttl),
+            receipt_counts: Cache::new(capacity, ttl),
+        }
+    }
+
+    /// Drop everything cached for a prefix
+    fn forget_prefix(&self, prefix: &str) {
+        self.states.invalidate(prefix);
+        self.latest.invalidate(prefix);
+    }
+
+    /// Drop the entries a change made stale
+    fn apply(&self, change: &Change) {
+        match change {
+            Change::EventAccepted { prefix, .. } => self.forget_prefix(prefix),
+            Change::ReceiptAdded { event_digest, .. } => {
+                self.receipt_counts.invalidate(event_digest)
+            }
+            Change::Escrowed { .. } | Change::EscrowRemoved { .. } => {}
+        }
+    }
+
+    fn clear(&self) {
+        self.states.clear();
+        self.latest.clear();
+        self.receipt_counts.clear();
+    }
+}
+
+/// Caching decorator for a database
+///
+/// Implements every store trait its backend does, so it can stand in for
+/// the backend anywhere, including in `Witness`.
+pub struct CachedDatabase<D> {
+    inner: D,
+    caches: Arc<Caches>,
+    capacity: usize,
+    ttl: Duration,
+    /// Caches of each tenant namespace
+    tenants: Arc<Mutex<HashMap<String, Arc<Caches>>>>,
+}
+
+impl<D> CachedDatabase<D> {
+    /// Wrap a backend with the default capacity and TTL
+    pub fn new(inner: D) -> Self {
+        Self::with_config(inner, DEFAULT_CAPACITY, DEFAULT_TTL)
+    }
+
+    /// Wrap a backend, keeping up to `capacity` entries per cache for at
+    /// most `ttl` each
+    pub fn with_config(inner: D, capacity: usize, ttl: Duration) -> Self {
+        CachedDatabase {
+            inner,
+            caches: Arc::new(Caches::new(capacity, ttl)),
+            capacity,
+            ttl,
+            tenants: Arc::default(),
+        }
+    }
+
+    /// The wrapped backend
+    pub fn inner(&self) -> &D {
+        &self.inner
+    }
+
+    /// Drop every cached entry
+    pub fn clear(&self) {
+        self.caches.clear();
+    }
+
+    /// Drop entries as changes from other processes arrive
+    ///
+    /// Spawns a task that runs until the stream ends. A lagged or failed
+    /// feed clears the whole cache, since changes may have been missed.
+    pub fn follow(&self, mut changes: ChangeStream) -> tokio::task::JoinHandle<()> {
+        let caches = Arc::clone(&self.caches);
+        tokio::spawn(async move {
+            while let Some(change) = changes.next().await {
+                match change {
+                    Ok(change) => caches.apply(&change),
+                    Err(_) => caches.clear(),
+                }
+            }
+        })
+    }
+}
+
+impl<D: TenantStore> TenantStore for CachedDatabase<D> {
+    fn tenant(&self) -> Option<&str> {
+        self.inner.tenant()
+    }
+
+    fn for_tenant(&self, tenant: &str) -> DbResult<Self> {
+        let inner = self.inner.for_tenant(tenant)?;
+        let mut tenants = self.tenants.lock().unwrap_or_else(|e| e.into_inner());
+        let caches = tenants
+            .entry(tenant.to_string())
+            .or_insert_with(|| Arc::new(Caches::new(self.capacity, self.ttl)));
+        Ok(CachedDatabase {
+            inner,
+            caches: Arc::clone(caches),
+            capacity: self.capacity,
+            ttl: self.ttl,
+            tenants: Arc::clone(&self.tenants),
+        })
+    }
+}
+
+#[async_trait]
+impl<D: KelStore> KelStore for CachedDatabase<D> {
+    async fn append_event(&self, event: &SignedEvent) -> DbResult<()> {
+        let result = self.inner.append_event(event).await;
+        self.caches.latest.invalidate(&event.event.prefix);
+        result
+    }
+
+    async fn commit_event_seen_at(
+        &self,
+        event: &SignedEvent,
+        new_state: &KeyState,
+        datetime: &str,
+    ) -> DbResult<()> {
+        let prefix = &event.event.prefix;
+        match self
+            .inner
+            .commit_event_seen_at(event, new_state, datetime)
+            .await
+        {
+            Ok(()) => {
+                self.caches.states.put(prefix, new_state.clone());
+                self.caches.latest.put(prefix, event.clone());
+                Ok(())
+            }
+            Err(e) => {
+                // Most likely validated against a stale state
+                self.caches.forget_prefix(prefix);
+                Err(e)
+            }
+        }
+    }
+
+    async fn get_event(&self, prefix: &str, sn: u64) -> DbResult<Option<SignedEvent>> {
+        self.inner.get_event(prefix, sn).await
+    }
+
+    async fn get_events(
+        &self,
+        prefix: &str,
+        start_sn: u64,
+        end_sn: Option<u64>,
+    ) -> DbResult<Vec<SignedEvent>> {
+        self.inner.get_events(prefix, start_sn, end_sn).await
+    }
+
+    async fn get_events_page(
+        &self,
+        prefix: &str,
+        start_sn: u64,
+        end_sn: Option<u64>,
+        limit: usize,
+        cursor: Option<&str>,
+    ) -> DbResult<Page<SignedEvent>> {
+        self.inner
+            .get_events_page(prefix, start_sn, end_sn, limit, cursor)
+            .await
+    }
+
+    async fn get_prefixes_page(
+        &self,
+        limit: usize,
+        cursor: Option<&str>,
+    ) -> DbResult<Page<String>> {
+        self.inner.get_prefixes_page(limit, cursor).await
+    }
+
+    async fn get_latest(&self, prefix: &str) -> DbResult<Option<SignedEvent>> {
+        if let Some(event) = self.caches.latest.get(prefix) {
+            return Ok(Some(event));
+        }
+        let epoch = self.caches.latest.epoch();
+        let event = self.inner.get_latest(prefix).await?;
+        if let Some(event) = &event {
+            self.caches.latest.fill(prefix, event.clone(), epoch);
+        }
+        Ok(event)
+    }
+
+    async fn get_event_by_digest(
+        &self,
+        prefix: &str,
+        digest: &str,
+    ) -> DbResult<Option<SignedEvent>> {
+        self.inner.get_event_by_digest(prefix, digest).await
+    }
+
+    async fn get_first_seen(&self, prefix: &str, digest: &str) -> DbResult<Option<FirstSeen>> {
+        self.inner.get_first_seen(prefix, digest).await
+    }
+
+    async fn get_first_seen_range(
+        &self,
+        prefix: &str,
+        start_fn: u64,
+        end_fn: Option<u64>,
+    ) -> DbResult<Vec<FirstSeen>> {
+        self.inner
+            .get_first_seen_range(prefix, start_fn, end_fn)
+            .await
+    }
+
+    async fn find_anchor(&self, digest: &str) -> DbResult<Option<AnchorLocation>> {
+        self.inner.find_anchor(digest).await
+    }
+
+    async fn find_event_seal(
+        &self,
+        prefix: &str,
+        sn: u64,
+        digest: &str,
+    ) -> DbResult<Option<AnchorLocation>> {
+        self.inner.find_event_seal(prefix, sn, digest).await
+    }
+}
+
+#[async_trait]
+impl<D: StateStore> StateStore for CachedDatabase<D> {
+    async fn get_state(&self, prefix: &str) -> DbResult<Option<KeyState>> {
+        if let Some(state) = self.caches.states.get(prefix) {
+            return Ok(Some(state));
+        }
+        let epoch = self.caches.states.epoch();
+        let state = self.inner.get_state(prefix).await?;
+        if let Some(state) = &state {
+            self.caches.states.fill(prefix, state.clone(), epoch);
+        }
+        Ok(state)
+    }
+
+    async fn put_state(&self, state: &KeyState) -> DbResult<()> {
+        let result = self.inner.put_state(state).await;
+        match result {
+            Ok(()) => self.caches.states.put(&state.prefix, state.clone()),
+            Err(_) => self.caches.states.invalidate(&state.prefix),
+        }
+        result
+    }
+
+    async fn delete_state(&self, prefix: &str) -> DbResult<()> {
+        let result = self.inner.delete_state(prefix).await;
+        self.caches.states.invalidate(prefix);
+        result
+    }
+}
+
+#[async_trait]
+impl<D: ReceiptStore> ReceiptStore for CachedDatabase<D> {
+    async fn add_receipt(&self, receipt: &NontransferableReceipt) -> DbResult<()> {
+        let result = self.inner.add_receipt(receipt).await;
+        self.caches.receipt_counts.invalidate(&receipt.event_digest);
+        result
+    }
+
+    async fn get_receipts(&self, event_digest: &str) -> DbResult<Vec<NontransferableReceipt>> {
+        self.inner.get_receipts(event_digest).await
+    }
+
+    async fn get_receipt(
+        &self,
+        event_digest: &str,
+        witness_prefix: &str,
+    ) -> DbResult<Option<NontransferableReceipt>> {
+        self.inner.get_receipt(event_digest, witness_prefix).await
+    }
+
+    async fn count_receipts(&self, event_digest: &str) -> DbResult<usize> {
+        if let Some(count) = self.caches.receipt_counts.get(event_digest) {
+            return Ok(count);
+        }
+        let epoch = self.caches.receipt_counts.epoch();
+        let count = self.inner.count_receipts(event_digest).await?;
+        self.caches.receipt_counts.fill(event_digest, count, epoch);
+        Ok(count)
+    }
+
+    async fn get_receipt_conflicts(&self, witness_prefix: &str) -> DbResult<Vec<ReceiptConflict>> {
+        self.inner.get_receipt_conflicts(witness_prefix).await
+    }
+}
+
+#[async_trait]
+impl<D: EscrowStore> EscrowStore for CachedDatabase<D> {
+    async fn escrow_event_with_ttl(
+        &self,
+        event: &SignedEvent,
+        reason: EscrowReason,
+        ttl_seconds: u64,
+    ) -> DbResult<()> {
+        // The decision to escrow may rest on a stale state
+        self.caches.forget_prefix(&event.event.prefix);
+        self.inner
+            .escrow_event_with_ttl(event, reason, ttl_seconds)
+            .await
+    }
+
+    async fn record_escrow_attempt(&self, escrowed: &EscrowedEvent) -> DbResult<()> {
+        self.inner.record_escrow_attempt(escrowed).await
+    }
+
+    async fn get_escrowed_page(
+        &self,
+        limit: usize,
+        cursor: Option<&str>,
+    ) -> DbResult<Page<EscrowedEvent>> {
+        self.inner.get_escrowed_page(limit, cursor).await
+    }
+
+    async fn get_escrowed_by_prefix(
+        &self,
+        prefix: &str,
+        limit: usize,
+        cursor: Option<&str>,
+    ) -> DbResult<Page<EscrowedEvent>> {
+        self.inner
+            .get_escrowed_by_prefix(prefix, limit, cursor)
+            .await
+    }
+
+    async fn get_escrowed_by_reason(
+        &self,
+        reason: EscrowReason,
+        limit: usize,
+        cursor: Option<&str>,
+    ) -> DbResult<Page<EscrowedEvent>> {
+        self.inner
+            .get_escrowed_by_reason(reason, limit, cursor)
+            .await
+    }
+
+    async fn promote_escrowed(&self, event_digest: &str) -> DbResult<Option<SignedEvent>> {
+        self.inner.promote_escrowed(event_digest).await
+    }
+
+    async fn remove_escrowed(&self, event_digest: &str) -> DbResult<()> {
+        self.inner.remove_escrowed(event_digest).await
+    }
+}
+
+#[async_trait]
+impl<D: SchemaStore> SchemaStore for CachedDatabase<D> {
+    async fn put_schema(&self, schema: &CredentialSchema) -> DbResult<()> {
+        self.inner.put_schema(schema).await
+    }
+
+    async fn get_schema(&self, said: &str) -> DbResult<Option<CredentialSchema>> {
+        self.inner.get_schema(said).await
+    }
+}
+
+#[async_trait]
+impl<D: ExchangeStore> ExchangeStore for CachedDatabase<D> {
+    async fn put_exchange(
+        &self,
+        exchange: &IpexExchange,
+        message: &ExchangeMessage,
+    ) -> DbResult<()> {
+        self.inner.put_exchange(exchange, message).await
+    }
+
+    async fn get_exchange(&self, id: &str) -> DbResult<Option<IpexExchange>> {
+        self.inner.get_exchange(id).await
+    }
+
+    async fn get_message(&self, said: &str) -> DbResult<Option<ExchangeMessage>> {
+        self.inner.get_message(said).await
+    }
+
+    async fn get_exchange_by_message(&self, said: &str) -> DbResult<Option<IpexExchange>> {
+        self.inner.get_exchange_by_message(said).await
+    }
+
+    async fn escrow_message_with_ttl(
+        &self,
+        message: &ExchangeMessage,
+        ttl_seconds: u64,
+    ) -> DbResult<()> {
+        self.inner
+            .escrow_message_with_ttl(message, ttl_seconds)
+            .await
+    }
+
+    async fn get_escrowed_messages(&self, prior: &str) -> DbResult<Vec<EscrowedMessage>> {
+        self.inner.get_escrowed_messages(prior).await
+    }
+
+    async fn get_escrowed_messages_page(
+        &self,
+        limit: usize,
+        cursor: Option<&str>,
+    ) -> DbResult<Page<EscrowedMessage>> {
+        self.inner.get_escrowed_messages_page(limit, cursor).await
+    }
+
+    async fn promote_escrowed_message(&self, said: &str) -> DbResult<Option<ExchangeMessage>> {
+        self.inner.promote_escrowed_message(said).await
+    }
+
+    async fn remove_escrowed_message(&self, said: &str) -> DbResult<()> {
+        self.inner.remove_escrowed_message(said).await
+    }
+}
+
+#[async_trait]
+impl<D: MigrationStore> MigrationStore for CachedDatabase<D> {
+    async fn migrate_records(&self) -> DbResult<MigrationReport> {
+        // Migrations rewrite records without changing what they decode to
+        self.inner.migrate_records().await
+    }
+}
+
+#[async_trait]
+impl<D: ChangeFeed> ChangeFeed for CachedDatabase<D> {
+    async fn subscribe(&self, prefix_filter: Option<&str>) -> DbResult<ChangeStream> {
+        self.inner.subscribe(prefix_filter).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error::DbError;
+    use crate::memory::InMemoryDatabase;
+    use crate::test_support::*;
+
+    crate::conformance::conformance_tests!(Some(CachedDatabase::new(InMemoryDatabase::new())));
+
+    #[tokio::test]
+    async fn test_tenant_isolation() {
+        crate::conformance::tenant_isolation(&CachedDatabase::new(InMemoryDatabase::new())).await;
+    }
+
+    #[tokio::test]
+    async fn test_tenant_caches_shared_by_handles() {
+        let db = CachedDatabase::new(InMemoryDatabase::new());
+        let alpha = db.for_tenant("alpha").unwrap();
+        let icp = create_test_event("DTest123", 0, None);
+        alpha.commit_event(&icp, &state_for(&icp)).await.unwrap();
+        assert_eq!(alpha.get_state("DTest123").await.unwrap().unwrap().sn, 0);
+        assert!(db.get_state("DTest123").await.unwrap().is_none());
+
+        // A commit through one handle is seen through another at once
+        let again = db.for_tenant("alpha").unwrap();
+        let ixn = create_test_event("DTest123", 1, Some(icp.event.digest.clone()));
+        again.commit_event(&ixn, &state_for(&ixn)).await.unwrap();
+        assert_eq!(alpha.get_state("DTest123").await.unwrap().unwrap().sn, 1);
+        assert!(db
+            .for_tenant("beta")
+            .unwrap()
+            .get_state("DTest123")
+            .await
+            .unwrap()
+            .is_none());
+    }
+
+    /// State for `event` as the next state after `sn - 1`
+    fn state_for(event: &SignedEvent) -> KeyState {
+        let mut state = create_test_state(&event.event.prefix, event.event.sn);
+        state.latest_digest = event.event.digest.clone();
+        state
+    }
+
+    #[tokio::test]
+    async fn test_cache_serves_reads_until_ttl() {
+        let backend = InMemoryDatabase::new();
+        let db = CachedDatabase::with_config(backend.clone(), 10, Duration::from_millis(50));
+        let icp = create_test_event("DTest123", 0, None);
+        db.commit_event(&icp, &state_for(&icp)).await.unwrap();
+        assert_eq!(db.count_receipts(&icp.event.digest).await.unwrap(), 0);
+
+        // Written behind the cache's back, as another process would
+        let ixn = create_test_event("DTest123", 1, Some(icp.event.digest.clone()));
+        backend.commit_event(&ixn, &state_for(&ixn)).await.unwrap();
+        backend
+            .add_receipt(&create_test_receipt(&icp.event.digest, "BWitness1"))
+            .await
+            .unwrap();
+        assert_eq!(db.get_state("DTest123").await.unwrap().unwrap().sn, 0);
+        assert_eq!(
+            db.get_latest("DTest123").await.unwrap().unwrap().event.sn,
+            0
+        );
+        assert_eq!(db.count_receipts(&icp.event.digest).await.unwrap(), 0);
+
+        tokio::time::sleep(Duration::from_millis(60)).await;
+        assert_eq!(db.get_state("DTest123").await.unwrap().unwrap().sn, 1);
+        assert_eq!(
+            db.get_latest("DTest123").await.unwrap().unwrap().event.sn,
+            1
+        );
+        assert_eq!(db.count_receipts(&icp.event.digest).await.unwrap(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_cache_updates_on_write() {
+        let backend = InMemoryDatabase::new();
+        let db = CachedDatabase::new(backend.clone());
+        let icp = create_test_event("DTest123", 0, None);
+        db.commit_event(&icp, &state_for(&icp)).await.unwrap();
+        assert_eq!(db.count_receipts(&icp.event.digest).await.unwrap(), 0);
+
+        db.add_receipt(&create_test_receipt(&icp.event.digest, "BWitness1"))
+            .await
+            .unwrap();
+        assert_eq!(db.count_receipts(&icp.event.digest).await.unwrap(), 1);
+
+        db.put_state(&create_test_state("DTest123", 7))
+            .await
+            .unwrap();
+        assert_eq!(db.get_state("DTest123").await.unwrap().unwrap().sn, 7);
+        db.delete_state("DTest123").await.unwrap();
+        assert!(db.get_state("DTest123").await.unwrap().is_none());
+    }
+
+    #[tokio::test]
+    async fn test_cache_stale_commit_conflicts_then_rereads() {
+        let backend = InMemoryDatabase::new();
+        let first = CachedDatabase::new(backend.clone());
+        let second = CachedDatabase::new(backend.clone());
+        let icp = create_test_event("DTest123", 0, None);
+        first.commit_event(&icp, &state_for(&icp)).await.unwrap();
+        assert_eq!(second.get_state("DTest123").await.unwrap().unwrap().sn, 0);
+
+        let ixn = create_test_event("DTest123", 1, Some(icp.event.digest.clone()));
+        first.commit_event(&ixn, &state_for(&ixn)).await.unwrap();
+
+        // The second process validates against its stale state and loses
+        let mut other = create_test_event("DTest123", 1, Some(icp.event.digest.clone()));
+        other.event.digest = "EOtherIxn".to_string();
+        let result = second.commit_event(&other, &state_for(&other)).await;
+        assert!(matches!(result, Err(DbError::StateConflict(_))));
+
+        let state = second.get_state("DTest123").await.unwrap().unwrap();
+        assert_eq!(state.latest_digest, ixn.event.digest);
+        assert_eq!(backend.event_count("DTest123").await, 2);
+    }
+
+    #[tokio::test]
+    async fn test_cache_follows_change_feed() {
+        let backend = InMemoryDatabase::new();
+        let db = CachedDatabase::new(backend.clone());
+        let icp = create_test_event("DTest123", 0, None);
+        db.commit_event(&icp, &state_for(&icp)).await.unwrap();
+        assert_eq!(db.count_receipts(&icp.event.digest).await.unwrap(), 0);
+        let task = db.follow(backend.subscribe(None).await.unwrap());
+
+        let ixn = create_test_event("DTest123", 1, Some(icp.event.digest.clone()));
+        backend.commit_event(&ixn, &state_for(&ixn)).await.unwrap();
+        backend
+            .add_receipt(&create_test_receipt(&icp.event.digest, "BWitness1"))
+            .await
+            .unwrap();
+        // Let the feed task catch up
+        tokio::task::yield_now().await;
+        tokio::task::yield_now().await;
+
+        assert_eq!(db.get_state("DTest123").await.unwrap().unwrap().sn, 1);
+        assert_eq!(db.count_receipts(&icp.event.digest).await.unwrap(), 1);
+        task.abort();
+    }
+
+    #[test]
+    fn test_cache_evicts_least_recently_used() {
+        let cache = Cache::new(2, DEFAULT_TTL);
+        cache.put("a", 1);
+        cache.put("b", 2);
+        assert_eq!(cache.get("a"), Some(1));
+        cache.put("c", 3);
+        assert_eq!(cache.get("b"), None);
+        assert_eq!(cache.get("a"), Some(1));
+
+        // A read that raced a write does not overwrite it
+        let epoch = cache.epoch();
+        cache.put("a", 10);
+        cache.fill("a", 1, epoch);
+        assert_eq!(cache.get("a"), Some(10));
+    }
+}
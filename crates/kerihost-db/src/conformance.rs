@@ -0,0 +1,936 @@
+//! Storage conformance suite
+//!
+//! Every backend must behave identically through the store traits. Each
+//! check below takes a fresh, empty database, and `conformance_tests!`
+//! expands to one test per check for a backend's test module:
+//!
+//! ```ignore
+//! crate::conformance::conformance_tests!(Some(InMemoryDatabase::new()));
+//! ```
+//!
+//! The argument is evaluated once per test and yields `Option<database>`;
+//! `None` skips the test, for backends whose server is not configured.
+//!
+//! Backends with tenant namespaces also run `tenant_isolation`, and those
+//! storing IPEX exchanges run `exchange_store`.
+
+use crate::error::DbError;
+use crate::test_support::*;
+use crate::traits::{EscrowPolicy, EscrowReason, ExchangeStore, TenantStore, WitnessDatabase};
+use cesride::{Diger, Matter};
+use kerihost_core::{Anchor, ExchangeMessage, IpexExchange};
+use serde_json::json;
+use std::collections::HashSet;
+
+/// Expand to a `#[tokio::test]` per conformance check
+macro_rules! conformance_tests {
+    ($db:expr) => {
+        $crate::conformance::conformance_tests!(@checks $db;
+            kel_append_and_read,
+            kel_append_rejections,
+            kel_attachments,
+            kel_pagination,
+            commit_event,
+            commit_event_conflict,
+            commit_event_seen_at,
+            first_seen_log,
+            prefixes_page,
+            anchors,
+            anchors_many_seals,
+            state_store,
+            receipt_store,
+            duplicate_receipt_conflicts,
+            escrow_store,
+            escrow_pagination,
+            escrow_attempts,
+        );
+    };
+    (@checks $db:expr; $($check:ident),* $(,)?) => {
+        mod conformance {
+            use super::*;
+
+            $(
+                #[tokio::test]
+                async fn $check() {
+                    let Some(db) = $db else { return };
+                    $crate::conformance::$check(&db).await;
+                }
+            )*
+        }
+    };
+}
+pub(crate) use conformance_tests;
+
+/// Appended events read back unchanged by sn, range, digest and latest
+pub(crate) async fn kel_append_and_read<D: WitnessDatabase>(db: &D) {
+    let events = append_test_kel(db, "DTest123", 3).await;
+
+    let event = db.get_event("DTest123", 1).await.unwrap().unwrap();
+    assert_eq!(event.event.digest, events[1].event.digest);
+    assert_eq!(event.event.raw, events[1].event.raw);
+    assert_eq!(event.signatures.len(), 1);
+    assert_eq!(event.signatures[0].index, events[1].signatures[0].index);
+    assert_eq!(
+        event.signatures[0].signature,
+        events[1].signatures[0].signature
+    );
+    assert!(db.get_event("DTest123", 3).await.unwrap().is_none());
+    assert!(db.get_event("DOther", 0).await.unwrap().is_none());
+
+    let range = db.get_events("DTest123", 1, None).await.unwrap();
+    let sns: Vec<u64> = range.iter().map(|e| e.event.sn).collect();
+    assert_eq!(sns, vec![1, 2]);
+    assert_eq!(
+        db.get_events("DTest123", 0, Some(1)).await.unwrap().len(),
+        2
+    );
+    assert!(db.get_events("DOther", 0, None).await.unwrap().is_empty());
+
+    let latest = db.get_latest("DTest123").await.unwrap().unwrap();
+    assert_eq!(latest.event.digest, events[2].event.digest);
+    assert!(db.get_latest("DOther").await.unwrap().is_none());
+
+    let found = db
+        .get_event_by_digest("DTest123", &events[2].event.digest)
+        .await
+        .unwrap();
+    assert_eq!(found.map(|e| e.event.sn), Some(2));
+    assert!(db
+        .get_event_by_digest("DOther", &events[2].event.digest)
+        .await
+        .unwrap()
+        .is_none());
+}
+
+/// Appends that would fork or gap the KEL fail with the matching error
+/// Attachment groups after an event's signatures survive storage and escrow
+pub(crate) async fn kel_attachments<D: WitnessDatabase>(db: &D) {
+    let icp = create_test_event("DTest123", 0, None);
+    let anchoring = Diger::new_with_ser(b"anchoring", None)
+        .unwrap()
+        .qb64()
+        .unwrap();
+    let icp = with_seal_source(icp, 3, &anchoring);
+    db.append_event(&icp).await.unwrap();
+
+    let cesr = icp.to_cesr().unwrap();
+    let stored = db.get_event("DTest123", 0).await.unwrap().unwrap();
+    assert_eq!(stored.attachments, icp.attachments);
+    assert_eq!(stored.to_cesr().unwrap(), cesr);
+    let latest = db.get_latest("DTest123").await.unwrap().unwrap();
+    assert_eq!(latest.to_cesr().unwrap(), cesr);
+    let events = db.get_events("DTest123", 0, None).await.unwrap();
+    assert_eq!(events[0].to_cesr().unwrap(), cesr);
+
+    // Escrowed delegated events keep their seal until promoted
+    let ixn = with_seal_source(
+        create_test_event("DTest123", 2, Some("EMissing".to_string())),
+        4,
+        &anchoring,
+    );
+    db.escrow_event(&ixn, EscrowReason::MissingDelegator)
+        .await
+        .unwrap();
+    let escrowed = db.get_escrowed("DTest123").await.unwrap().remove(0);
+    assert_eq!(escrowed.event.to_cesr().unwrap(), ixn.to_cesr().unwrap());
+    let promoted = db
+        .promote_escrowed(&ixn.event.digest)
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(promoted.to_cesr().unwrap(), ixn.to_cesr().unwrap());
+}
+
+pub(crate) async fn kel_append_rejections<D: WitnessDatabase>(db: &D) {
+    let icp = create_test_event("DTest123", 0, None);
+    db.append_event(&icp).await.unwrap();
+
+    let result = db.append_event(&icp).await;
+    assert!(matches!(result, Err(DbError::Duplicate(_))), "{:?}", result);
+
+    let ixn = create_test_event("DTest123", 1, Some("EWrongDigest".to_string()));
+    let result = db.append_event(&ixn).await;
+    assert!(
+        matches!(result, Err(DbError::PriorDigestMismatch { .. })),
+        "{:?}",
+        result
+    );
+
+    let gap = create_test_event("DTest123", 5, Some(icp.event.digest.clone()));
+    let result = db.append_event(&gap).await;
+    assert!(matches!(result, Err(DbError::NotFound(_))), "{:?}", result);
+
+    let ixn = create_test_event("DTest123", 1, Some(icp.event.digest.clone()));
+    db.append_event(&ixn).await.unwrap();
+    let result = db.append_event(&ixn).await;
+    assert!(matches!(result, Err(DbError::Duplicate(_))), "{:?}", result);
+
+    assert_eq!(db.get_events("DTest123", 0, None).await.unwrap().len(), 2);
+}
+
+/// KEL pages cover the range exactly once and reject foreign cursors
+pub(crate) async fn kel_pagination<D: WitnessDatabase>(db: &D) {
+    let events = append_test_kel(db, "DTest123", 7).await;
+
+    let mut seen = Vec::new();
+    let mut cursor: Option<String> = None;
+    loop {
+        let page = db
+            .get_events_page("DTest123", 1, Some(5), 2, cursor.as_deref())
+            .await
+            .unwrap();
+        assert!(page.items.len() <= 2);
+        seen.extend(page.items.into_iter().map(|e| e.event.digest));
+        match page.next {
+            Some(next) => cursor = Some(next),
+            None => break,
+        }
+    }
+    let expected: Vec<String> = events[1..=5]
+        .iter()
+        .map(|e| e.event.digest.clone())
+        .collect();
+    assert_eq!(seen, expected);
+
+    let result = db
+        .get_events_page("DTest123", 0, None, 2, Some("not a cursor"))
+        .await;
+    assert!(
+        matches!(result, Err(DbError::InvalidCursor(_))),
+        "{:?}",
+        result
+    );
+}
+
+/// Committed events advance the KEL and the state together
+pub(crate) async fn commit_event<D: WitnessDatabase>(db: &D) {
+    let icp = create_test_event("DTest123", 0, None);
+    let mut state = create_test_state("DTest123", 0);
+    state.latest_digest = icp.event.digest.clone();
+    db.commit_event(&icp, &state).await.unwrap();
+
+    let ixn = create_test_event("DTest123", 1, Some(icp.event.digest.clone()));
+    let mut state = create_test_state("DTest123", 1);
+    state.latest_digest = ixn.event.digest.clone();
+    db.commit_event(&ixn, &state).await.unwrap();
+
+    assert_eq!(db.get_events("DTest123", 0, None).await.unwrap().len(), 2);
+    let stored = db.get_state("DTest123").await.unwrap().unwrap();
+    assert_eq!(stored.sn, 1);
+    assert_eq!(stored.latest_digest, ixn.event.digest);
+}
+
+/// A commit against stale state writes neither the event nor the state
+pub(crate) async fn commit_event_conflict<D: WitnessDatabase>(db: &D) {
+    let icp = create_test_event("DTest123", 0, None);
+    db.append_event(&icp).await.unwrap();
+
+    // State was never written for the inception
+    let ixn = create_test_event("DTest123", 1, Some(icp.event.digest.clone()));
+    let result = db
+        .commit_event(&ixn, &create_test_state("DTest123", 1))
+        .await;
+    assert!(
+        matches!(result, Err(DbError::StateConflict(_))),
+        "{:?}",
+        result
+    );
+    assert!(result.unwrap_err().is_retryable());
+
+    assert_eq!(db.get_events("DTest123", 0, None).await.unwrap().len(), 1);
+    assert!(db.get_state("DTest123").await.unwrap().is_none());
+    assert_eq!(
+        db.get_first_seen_range("DTest123", 0, None)
+            .await
+            .unwrap()
+            .len(),
+        1
+    );
+}
+
+/// Accepted events get consecutive first-seen ordinals per prefix
+pub(crate) async fn first_seen_log<D: WitnessDatabase>(db: &D) {
+    let events = append_test_kel(db, "DTest123", 3).await;
+    append_test_kel(db, "DOther", 1).await;
+
+    let entries = db.get_first_seen_range("DTest123", 0, None).await.unwrap();
+    let ordinals: Vec<u64> = entries.iter().map(|e| e.ordinal).collect();
+    assert_eq!(ordinals, vec![0, 1, 2]);
+    assert_eq!(entries[1].digest, events[1].event.digest);
+    assert_eq!(entries[1].prefix, "DTest123");
+
+    let range = db
+        .get_first_seen_range("DTest123", 1, Some(1))
+        .await
+        .unwrap();
+    assert_eq!(range.len(), 1);
+    assert_eq!(range[0].sn, 1);
+
+    let entry = db
+        .get_first_seen("DTest123", &events[2].event.digest)
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(entry.ordinal, 2);
+    assert!(chrono::DateTime::parse_from_rfc3339(&entry.datetime).is_ok());
+    assert!(db
+        .get_first_seen("DOther", &events[2].event.digest)
+        .await
+        .unwrap()
+        .is_none());
+
+    let other = db.get_first_seen_range("DOther", 0, None).await.unwrap();
+    assert_eq!(other.len(), 1);
+    assert_eq!(other[0].ordinal, 0);
+}
+
+/// A replayed commit keeps the datetime it was first seen at elsewhere
+pub(crate) async fn commit_event_seen_at<D: WitnessDatabase>(db: &D) {
+    let icp = create_test_event("DTest123", 0, None);
+    let mut state = create_test_state("DTest123", 0);
+    state.latest_digest = icp.event.digest.clone();
+    let seen_at = "2024-01-02T03:04:05.000006+00:00";
+    db.commit_event_seen_at(&icp, &state, seen_at)
+        .await
+        .unwrap();
+
+    let entry = db
+        .get_first_seen("DTest123", &icp.event.digest)
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(entry.ordinal, 0);
+    assert_eq!(entry.datetime, seen_at);
+    assert_eq!(db.get_state("DTest123").await.unwrap().unwrap().sn, 0);
+}
+
+/// Prefix pages list every KEL once across continuation tokens
+pub(crate) async fn prefixes_page<D: WitnessDatabase>(db: &D) {
+    let page = db.get_prefixes_page(10, None).await.unwrap();
+    assert!(page.items.is_empty());
+    assert!(page.next.is_none());
+
+    let mut expected = HashSet::new();
+    for i in 0..5 {
+        let prefix = format!("DTest{}", i);
+        append_test_kel(db, &prefix, 2).await;
+        expected.insert(prefix);
+    }
+
+    let mut seen = HashSet::new();
+    let mut cursor: Option<String> = None;
+    loop {
+        let page = db.get_prefixes_page(2, cursor.as_deref()).await.unwrap();
+        assert!(page.items.len() <= 2);
+        for prefix in page.items {
+            assert!(seen.insert(prefix), "prefix listed twice");
+        }
+        match page.next {
+            Some(next) => cursor = Some(next),
+            None => break,
+        }
+    }
+    assert_eq!(seen, expected);
+}
+
+/// Seals anchored by appended events are found by digest
+pub(crate) async fn anchors<D: WitnessDatabase>(db: &D) {
+    let icp = create_test_event("DTest123", 0, None);
+    db.append_event(&icp).await.unwrap();
+    let ixn = create_anchoring_event(
+        "DTest123",
+        1,
+        Some(icp.event.digest.clone()),
+        vec![
+            Anchor::digest("ECredential"),
+            Anchor::event("EDelegate", "b", "EDelegated"),
+        ],
+    );
+    db.append_event(&ixn).await.unwrap();
+
+    let location = db.find_anchor("ECredential").await.unwrap().unwrap();
+    assert_eq!(location.prefix, "DTest123");
+    assert_eq!(location.sn, 1);
+    assert_eq!(location.event_digest, ixn.event.digest);
+    assert!(db.find_anchor("ENotAnchored").await.unwrap().is_none());
+
+    let seal = db
+        .find_event_seal("EDelegate", 11, "EDelegated")
+        .await
+        .unwrap();
+    assert_eq!(seal.map(|l| l.sn), Some(1));
+    assert!(db
+        .find_event_seal("EDelegate", 12, "EDelegated")
+        .await
+        .unwrap()
+        .is_none());
+}
+
+/// Events anchoring more seals than one DynamoDB transaction holds are
+/// stored with every seal indexed, whether appended or committed
+pub(crate) async fn anchors_many_seals<D: WitnessDatabase>(db: &D) {
+    let seals = |label: &str| -> Vec<Anchor> {
+        (0..150)
+            .map(|i| Anchor::digest(&format!("E{}Seal{:03}", label, i)))
+            .collect()
+    };
+
+    let icp = create_test_event("DTest123", 0, None);
+    let mut state = create_test_state("DTest123", 0);
+    state.latest_digest = icp.event.digest.clone();
+    db.commit_event(&icp, &state).await.unwrap();
+
+    let appended = create_anchoring_event(
+        "DTest123",
+        1,
+        Some(icp.event.digest.clone()),
+        seals("Appended"),
+    );
+    db.append_event(&appended).await.unwrap();
+    let mut state = create_test_state("DTest123", 1);
+    state.latest_digest = appended.event.digest.clone();
+    db.put_state(&state).await.unwrap();
+
+    let committed = create_anchoring_event(
+        "DTest123",
+        2,
+        Some(appended.event.digest.clone()),
+        seals("Committed"),
+    );
+    let mut state = create_test_state("DTest123", 2);
+    state.latest_digest = committed.event.digest.clone();
+    db.commit_event(&committed, &state).await.unwrap();
+
+    for (label, event) in [("Appended", &appended), ("Committed", &committed)] {
+        for i in [0, 99, 149] {
+            let location = db
+                .find_anchor(&format!("E{}Seal{:03}", label, i))
+                .await
+                .unwrap()
+                .unwrap();
+            assert_eq!(location.event_digest, event.event.digest);
+        }
+    }
+
+    // Appending the event again reports the duplicate and keeps its index
+    let result = db.append_event(&appended).await;
+    assert!(matches!(result, Err(DbError::Duplicate(_))), "{:?}", result);
+    assert!(db.find_anchor("EAppendedSeal149").await.unwrap().is_some());
+}
+
+/// States are written, replaced and deleted per prefix
+pub(crate) async fn state_store<D: WitnessDatabase>(db: &D) {
+    assert!(db.get_state("DTest123").await.unwrap().is_none());
+
+    db.put_state(&create_test_state("DTest123", 0))
+        .await
+        .unwrap();
+    db.put_state(&create_test_state("DTest123", 4))
+        .await
+        .unwrap();
+    db.put_state(&create_test_state("DOther", 1)).await.unwrap();
+    let state = db.get_state("DTest123").await.unwrap().unwrap();
+    assert_eq!(state.sn, 4);
+    assert_eq!(state.latest_digest, "EDigestDTest123_4");
+
+    db.delete_state("DTest123").await.unwrap();
+    assert!(db.get_state("DTest123").await.unwrap().is_none());
+    assert!(db.get_state("DOther").await.unwrap().is_some());
+}
+
+/// Receipts are kept per event and witness, listed in witness order
+pub(crate) async fn receipt_store<D: WitnessDatabase>(db: &D) {
+    for witness in ["BWitness2", "BWitness1", "BWitness3"] {
+        db.add_receipt(&create_test_receipt("EDigest123", witness))
+            .await
+            .unwrap();
+    }
+    db.add_receipt(&create_test_receipt("EOther", "BWitness1"))
+        .await
+        .unwrap();
+
+    let receipts = db.get_receipts("EDigest123").await.unwrap();
+    let witnesses: Vec<&str> = receipts.iter().map(|r| r.witness_prefix.as_str()).collect();
+    assert_eq!(witnesses, vec!["BWitness1", "BWitness2", "BWitness3"]);
+    assert_eq!(db.count_receipts("EDigest123").await.unwrap(), 3);
+
+    let receipt = db
+        .get_receipt("EDigest123", "BWitness2")
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(receipt.event_prefix, "DTest123");
+    assert!(db
+        .get_receipt("EDigest123", "BWitness4")
+        .await
+        .unwrap()
+        .is_none());
+    assert!(db.get_receipts("ENone").await.unwrap().is_empty());
+    assert_eq!(db.count_receipts("ENone").await.unwrap(), 0);
+}
+
+/// A witness's first receipt for an event stands; a different one is
+/// rejected and recorded as a conflict
+pub(crate) async fn duplicate_receipt_conflicts<D: WitnessDatabase>(db: &D) {
+    db.add_receipt(&create_test_receipt("EDigest123", "BWitness1"))
+        .await
+        .unwrap();
+    db.add_receipt(&create_test_receipt("EDigest123", "BWitness1"))
+        .await
+        .unwrap();
+    assert_eq!(db.count_receipts("EDigest123").await.unwrap(), 1);
+    assert!(db
+        .get_receipt_conflicts("BWitness1")
+        .await
+        .unwrap()
+        .is_empty());
+
+    let mut resent = create_test_receipt("EDigest123", "BWitness1");
+    resent.signature = "0BSig456".to_string();
+    for _ in 0..2 {
+        let result = db.add_receipt(&resent).await;
+        assert!(matches!(result, Err(DbError::ConflictingReceipt { .. })));
+    }
+
+    let receipts = db.get_receipts("EDigest123").await.unwrap();
+    assert_eq!(receipts.len(), 1);
+    assert_eq!(receipts[0].signature, "0BSig123");
+    let receipt = db
+        .get_receipt("EDigest123", "BWitness1")
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(receipt.signature, "0BSig123");
+
+    // Recording the same conflict again keeps one record
+    let conflicts = db.get_receipt_conflicts("BWitness1").await.unwrap();
+    assert_eq!(conflicts.len(), 1);
+    assert_eq!(conflicts[0].event_digest, "EDigest123");
+    assert_eq!(conflicts[0].event_prefix, "DTest123");
+    assert_eq!(conflicts[0].signature, "0BSig123");
+    assert_eq!(conflicts[0].conflicting_signature, "0BSig456");
+    let (stored, rejected) = conflicts[0].receipts();
+    assert_eq!(stored.signature, receipt.signature);
+    assert_eq!(rejected.signature, resent.signature);
+    assert!(db
+        .get_receipt_conflicts("BWitness2")
+        .await
+        .unwrap()
+        .is_empty());
+}
+
+/// Escrowed events are listed until promoted or removed
+pub(crate) async fn escrow_store<D: WitnessDatabase>(db: &D) {
+    let event1 = create_test_event("DTest1", 5, Some("EP1".to_string()));
+    let event2 = create_test_event("DTest2", 3, Some("EP2".to_string()));
+    db.escrow_event(&event1, EscrowReason::OutOfOrder)
+        .await
+        .unwrap();
+    db.escrow_event(&event2, EscrowReason::PartiallySigned)
+        .await
+        .unwrap();
+
+    let escrowed = db.get_escrowed("DTest1").await.unwrap();
+    assert_eq!(escrowed.len(), 1);
+    assert_eq!(escrowed[0].reason, EscrowReason::OutOfOrder);
+    assert_eq!(escrowed[0].event.event.digest, event1.event.digest);
+    assert_eq!(escrowed[0].event.signatures.len(), 1);
+    assert!(!escrowed[0].is_expired());
+    assert_eq!(db.get_all_escrowed().await.unwrap().len(), 2);
+
+    // Re-escrowing replaces the earlier escrow
+    db.escrow_event(&event1, EscrowReason::MissingReceipts)
+        .await
+        .unwrap();
+    let escrowed = db.get_escrowed("DTest1").await.unwrap();
+    assert_eq!(escrowed.len(), 1);
+    assert_eq!(escrowed[0].reason, EscrowReason::MissingReceipts);
+
+    let promoted = db.promote_escrowed(&event1.event.digest).await.unwrap();
+    assert_eq!(
+        promoted.map(|e| e.event.digest),
+        Some(event1.event.digest.clone())
+    );
+    assert!(db.get_escrowed("DTest1").await.unwrap().is_empty());
+    assert!(db
+        .promote_escrowed(&event1.event.digest)
+        .await
+        .unwrap()
+        .is_none());
+
+    db.remove_escrowed(&event2.event.digest).await.unwrap();
+    assert!(db.get_all_escrowed().await.unwrap().is_empty());
+    db.remove_escrowed(&event2.event.digest).await.unwrap();
+}
+
+/// Escrow pages cover every escrow once, overall and per reason
+pub(crate) async fn escrow_pagination<D: WitnessDatabase>(db: &D) {
+    let mut digests = HashSet::new();
+    for i in 0..5 {
+        let event = create_test_event(&format!("DTest{}", i), 5, Some("EP".to_string()));
+        db.escrow_event(&event, EscrowReason::OutOfOrder)
+            .await
+            .unwrap();
+        digests.insert(event.event.digest);
+    }
+    let signed = create_test_event("DSigned", 1, Some("EP".to_string()));
+    db.escrow_event(&signed, EscrowReason::PartiallySigned)
+        .await
+        .unwrap();
+
+    let mut seen = HashSet::new();
+    let mut cursor: Option<String> = None;
+    loop {
+        let page = db.get_escrowed_page(2, cursor.as_deref()).await.unwrap();
+        assert!(page.items.len() <= 2);
+        for escrowed in page.items {
+            assert!(seen.insert(escrowed.event.event.digest));
+        }
+        match page.next {
+            Some(next) => cursor = Some(next),
+            None => break,
+        }
+    }
+    assert_eq!(seen.len(), 6);
+
+    let mut seen = HashSet::new();
+    let mut cursor: Option<String> = None;
+    loop {
+        let page = db
+            .get_escrowed_by_reason(EscrowReason::OutOfOrder, 2, cursor.as_deref())
+            .await
+            .unwrap();
+        for escrowed in page.items {
+            assert_eq!(escrowed.reason, EscrowReason::OutOfOrder);
+            assert!(seen.insert(escrowed.event.event.digest));
+        }
+        match page.next {
+            Some(next) => cursor = Some(next),
+            None => break,
+        }
+    }
+    assert_eq!(seen, digests);
+
+    let mut digests = HashSet::new();
+    for sn in 3..6 {
+        let event = create_test_event("DSigned", sn, Some("EP".to_string()));
+        db.escrow_event(&event, EscrowReason::OutOfOrder)
+            .await
+            .unwrap();
+        digests.insert(event.event.digest);
+    }
+    digests.insert(signed.event.digest);
+
+    let mut seen = HashSet::new();
+    let mut cursor: Option<String> = None;
+    loop {
+        let page = db
+            .get_escrowed_by_prefix("DSigned", 2, cursor.as_deref())
+            .await
+            .unwrap();
+        assert!(page.items.len() <= 2);
+        for escrowed in page.items {
+            assert!(seen.insert(escrowed.event.event.digest));
+        }
+        match page.next {
+            Some(next) => cursor = Some(next),
+            None => break,
+        }
+    }
+    assert_eq!(seen, digests);
+}
+
+/// Escrow TTLs and attempt schedules round-trip until the event is re-escrowed
+pub(crate) async fn escrow_attempts<D: WitnessDatabase>(db: &D) {
+    let event = create_test_event("DTest1", 5, Some("EP1".to_string()));
+    let now = chrono::Utc::now().timestamp() as u64;
+    db.escrow_event_with_ttl(&event, EscrowReason::PartiallySigned, 86400)
+        .await
+        .unwrap();
+
+    let mut escrowed = db.get_escrowed("DTest1").await.unwrap().remove(0);
+    assert!(escrowed.ttl >= now + 86400);
+    assert_eq!(escrowed.attempts, 0);
+    assert!(escrowed.is_due());
+
+    escrowed.record_attempt(&EscrowPolicy::default());
+    db.record_escrow_attempt(&escrowed).await.unwrap();
+    let stored = db.get_escrowed("DTest1").await.unwrap().remove(0);
+    assert_eq!(stored.attempts, 1);
+    assert_eq!(stored.last_attempt, escrowed.last_attempt);
+    assert_eq!(stored.next_attempt, escrowed.next_attempt);
+    assert_eq!(stored.ttl, escrowed.ttl);
+    assert!(!stored.is_due());
+
+    // An attempt read under another reason is not stored
+    let mut other = stored.clone();
+    other.reason = EscrowReason::OutOfOrder;
+    other.attempts = 5;
+    db.record_escrow_attempt(&other).await.unwrap();
+    assert_eq!(db.get_escrowed("DTest1").await.unwrap()[0].attempts, 1);
+
+    // Re-escrowing starts the attempts over
+    db.escrow_event_with_ttl(&event, EscrowReason::PartiallySigned, 86400)
+        .await
+        .unwrap();
+    let escrowed = db.get_escrowed("DTest1").await.unwrap().remove(0);
+    assert_eq!(escrowed.attempts, 0);
+    assert!(escrowed.is_due());
+
+    db.promote_escrowed(&event.event.digest).await.unwrap();
+    db.record_escrow_attempt(&stored).await.unwrap();
+    assert!(db.get_all_escrowed().await.unwrap().is_empty());
+}
+
+/// Tenants sharing storage never see each other's data, even under the
+/// same prefixes and digests
+pub(crate) async fn tenant_isolation<D: WitnessDatabase + TenantStore>(db: &D) {
+    assert_eq!(db.tenant(), None);
+    assert!(matches!(
+        db.for_tenant("alpha#beta"),
+        Err(DbError::InvalidTenant(_))
+    ));
+    let alpha = db.for_tenant("alpha").unwrap();
+    let beta = db.for_tenant("beta").unwrap();
+    assert_eq!(alpha.tenant(), Some("alpha"));
+
+    // The same KEL in two namespaces, one event longer in alpha
+    let events = append_test_kel(&alpha, "DTest123", 2).await;
+    append_test_kel(&beta, "DTest123", 1).await;
+    let ixn = create_anchoring_event(
+        "DTest123",
+        2,
+        Some(events[1].event.digest.clone()),
+        vec![Anchor::digest("ECredential")],
+    );
+    alpha.append_event(&ixn).await.unwrap();
+    alpha
+        .put_state(&create_test_state("DTest123", 2))
+        .await
+        .unwrap();
+
+    assert!(db.get_event("DTest123", 0).await.unwrap().is_none());
+    assert!(db
+        .get_prefixes_page(10, None)
+        .await
+        .unwrap()
+        .items
+        .is_empty());
+    assert_eq!(
+        beta.get_latest("DTest123").await.unwrap().unwrap().event.sn,
+        0
+    );
+    assert!(beta.get_event("DTest123", 1).await.unwrap().is_none());
+    assert_eq!(
+        alpha.get_prefixes_page(10, None).await.unwrap().items,
+        vec!["DTest123".to_string()]
+    );
+    assert!(beta
+        .get_event_by_digest("DTest123", &ixn.event.digest)
+        .await
+        .unwrap()
+        .is_none());
+
+    // First-seen ordinals count per namespace
+    let seen = alpha
+        .get_first_seen("DTest123", &ixn.event.digest)
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!((seen.prefix.as_str(), seen.ordinal), ("DTest123", 2));
+    assert_eq!(
+        beta.get_first_seen_range("DTest123", 0, None)
+            .await
+            .unwrap()
+            .len(),
+        1
+    );
+
+    assert!(alpha.find_anchor("ECredential").await.unwrap().is_some());
+    assert!(beta.find_anchor("ECredential").await.unwrap().is_none());
+
+    assert_eq!(alpha.get_state("DTest123").await.unwrap().unwrap().sn, 2);
+    assert!(beta.get_state("DTest123").await.unwrap().is_none());
+    beta.delete_state("DTest123").await.unwrap();
+    assert!(alpha.get_state("DTest123").await.unwrap().is_some());
+
+    // Receipts on an event both tenants hold
+    let digest = &events[0].event.digest;
+    alpha
+        .add_receipt(&create_test_receipt(digest, "BWitness1"))
+        .await
+        .unwrap();
+    assert_eq!(alpha.count_receipts(digest).await.unwrap(), 1);
+    assert_eq!(beta.count_receipts(digest).await.unwrap(), 0);
+    assert!(beta.get_receipts(digest).await.unwrap().is_empty());
+    assert!(beta
+        .get_receipt(digest, "BWitness1")
+        .await
+        .unwrap()
+        .is_none());
+
+    // A witness's receipt in one namespace does not conflict with another's
+    let mut other = create_test_receipt(digest, "BWitness1");
+    other.signature = "0BSig456".to_string();
+    beta.add_receipt(&other).await.unwrap();
+    assert!(alpha.add_receipt(&other).await.is_err());
+    assert_eq!(
+        alpha
+            .get_receipt_conflicts("BWitness1")
+            .await
+            .unwrap()
+            .len(),
+        1
+    );
+    assert!(beta
+        .get_receipt_conflicts("BWitness1")
+        .await
+        .unwrap()
+        .is_empty());
+
+    // The same escrowed event in two namespaces is two escrows
+    let escrowed = create_test_event("DTest123", 5, Some("EPrior".to_string()));
+    alpha
+        .escrow_event(&escrowed, EscrowReason::OutOfOrder)
+        .await
+        .unwrap();
+    beta.escrow_event(&escrowed, EscrowReason::OutOfOrder)
+        .await
+        .unwrap();
+    assert!(db.get_all_escrowed().await.unwrap().is_empty());
+    assert!(db
+        .get_escrowed_page(10, None)
+        .await
+        .unwrap()
+        .items
+        .is_empty());
+    assert!(db
+        .get_escrowed_by_reason(EscrowReason::OutOfOrder, 10, None)
+        .await
+        .unwrap()
+        .items
+        .is_empty());
+    let promoted = beta.promote_escrowed(&escrowed.event.digest).await.unwrap();
+    assert!(promoted.is_some());
+    assert!(beta.get_all_escrowed().await.unwrap().is_empty());
+    assert_eq!(alpha.get_escrowed("DTest123").await.unwrap().len(), 1);
+    assert_eq!(
+        alpha
+            .get_escrowed_by_reason(EscrowReason::OutOfOrder, 10, None)
+            .await
+            .unwrap()
+            .items
+            .len(),
+        1
+    );
+    db.remove_escrowed(&escrowed.event.digest).await.unwrap();
+    assert_eq!(alpha.get_all_escrowed().await.unwrap().len(), 1);
+
+    // A handle scoped again reaches the same namespace
+    let again = beta.for_tenant("alpha").unwrap();
+    assert_eq!(
+        again
+            .get_latest("DTest123")
+            .await
+            .unwrap()
+            .unwrap()
+            .event
+            .sn,
+        2
+    );
+}
+
+/// Exchanges advance one response at a time; escrowed messages round-trip
+pub(crate) async fn exchange_store<D: ExchangeStore>(db: &D) {
+    let message = |sender: &str, route: &str, prior: Option<&ExchangeMessage>| {
+        let prior = prior.map(|p| p.said.as_str());
+        ExchangeMessage::new(sender, None, route, prior, json!({}), None).unwrap()
+    };
+    let apply = message("EHolder", "/ipex/apply", None);
+    let exchange = IpexExchange::start(&apply).unwrap();
+    db.put_exchange(&exchange, &apply).await.unwrap();
+    assert_eq!(
+        db.get_exchange(&apply.said).await.unwrap(),
+        Some(exchange.clone())
+    );
+    assert_eq!(
+        db.get_message(&apply.said).await.unwrap(),
+        Some(apply.clone())
+    );
+
+    // Starting the same exchange again loses
+    let result = db.put_exchange(&exchange, &apply).await;
+    assert!(
+        matches!(result, Err(DbError::StateConflict(_))),
+        "{:?}",
+        result
+    );
+
+    // Two responses to the same message: only the first is stored
+    let offer = message("EIssuer", "/ipex/offer", Some(&apply));
+    let spurn = message("EIssuer", "/ipex/spurn", Some(&apply));
+    let mut offered = exchange.clone();
+    offered.advance(&offer).unwrap();
+    let mut spurned = exchange.clone();
+    spurned.advance(&spurn).unwrap();
+
+    db.put_exchange(&offered, &offer).await.unwrap();
+    let result = db.put_exchange(&spurned, &spurn).await;
+    assert!(
+        matches!(result, Err(DbError::StateConflict(_))),
+        "{:?}",
+        result
+    );
+    assert!(result.unwrap_err().is_retryable());
+    assert_eq!(db.get_exchange(&apply.said).await.unwrap(), Some(offered));
+    assert_eq!(
+        db.get_exchange_by_message(&offer.said)
+            .await
+            .unwrap()
+            .map(|e| e.id),
+        Some(apply.said.clone())
+    );
+    assert!(db.get_message(&spurn.said).await.unwrap().is_none());
+
+    // Escrowed messages wait on their prior
+    let agree = message("EHolder", "/ipex/agree", Some(&offer));
+    db.escrow_message(&agree).await.unwrap();
+    let escrowed = db.get_escrowed_messages(&offer.said).await.unwrap();
+    assert_eq!(escrowed.len(), 1);
+    assert_eq!(escrowed[0].message, agree);
+    assert_eq!(db.get_all_escrowed_messages().await.unwrap().len(), 1);
+    assert_eq!(
+        db.promote_escrowed_message(&agree.said).await.unwrap(),
+        Some(agree.clone())
+    );
+    assert!(db
+        .promote_escrowed_message(&agree.said)
+        .await
+        .unwrap()
+        .is_none());
+
+    // Escrowed message pages cover every message once
+    let mut saids = HashSet::new();
+    for route in ["/ipex/grant", "/ipex/admit", "/ipex/spurn"] {
+        let escrowed = message("EHolder", route, Some(&agree));
+        db.escrow_message(&escrowed).await.unwrap();
+        saids.insert(escrowed.said);
+    }
+    let mut seen = HashSet::new();
+    let mut cursor: Option<String> = None;
+    loop {
+        let page = db
+            .get_escrowed_messages_page(2, cursor.as_deref())
+            .await
+            .unwrap();
+        assert!(page.items.len() <= 2);
+        for escrowed in page.items {
+            assert!(seen.insert(escrowed.message.said));
+        }
+        match page.next {
+            Some(next) => cursor = Some(next),
+            None => break,
+        }
+    }
+    assert_eq!(seen, saids);
+}
@@ -0,0 +1,103 @@
+//! Consumed capacity reporting
+//!
+//! [`CapacityInterceptor`] asks DynamoDB for the capacity every request
+//! consumes and adds it to the instrumented call in progress, so
+//! `InstrumentedDatabase` spans show what each store call cost.
+
+use crate::instrument::add_consumed_capacity;
+use aws_sdk_dynamodb::config::interceptors::{
+    AfterDeserializationInterceptorContextRef, BeforeSerializationInterceptorContextMut,
+};
+use aws_sdk_dynamodb::config::{ConfigBag, Intercept, RuntimeComponents};
+use aws_sdk_dynamodb::operation::delete_item::{DeleteItemInput, DeleteItemOutput};
+use aws_sdk_dynamodb::operation::get_item::{GetItemInput, GetItemOutput};
+use aws_sdk_dynamodb::operation::put_item::{PutItemInput, PutItemOutput};
+use aws_sdk_dynamodb::operation::query::{QueryInput, QueryOutput};
+use aws_sdk_dynamodb::operation::scan::{ScanInput, ScanOutput};
+use aws_sdk_dynamodb::operation::transact_write_items::{
+    TransactWriteItemsInput, TransactWriteItemsOutput,
+};
+use aws_sdk_dynamodb::operation::update_item::{UpdateItemInput, UpdateItemOutput};
+use aws_sdk_dynamodb::types::{ConsumedCapacity, ReturnConsumedCapacity};
+
+type BoxError = Box<dyn std::error::Error + Send + Sync>;
+
+/// Request `TOTAL` consumed capacity on whichever input type `$input` holds
+macro_rules! request_capacity {
+    ($input:expr; $($kind:ty),*) => {
+        $(
+            if let Some(input) = $input.downcast_mut::<$kind>() {
+                input
+                    .return_consumed_capacity
+                    .get_or_insert(ReturnConsumedCapacity::Total);
+                return Ok(());
+            }
+        )*
+    };
+}
+
+/// Report the capacity of whichever output type `$output` holds
+macro_rules! report_capacity {
+    ($output:expr; $($kind:ty),*) => {
+        $(
+            if let Some(output) = $output.downcast_ref::<$kind>() {
+                output.consumed_capacity.iter().for_each(report);
+                return Ok(());
+            }
+        )*
+    };
+}
+
+/// Adds each request's consumed capacity to the instrumented call
+#[derive(Debug)]
+pub(super) struct CapacityInterceptor;
+
+/// Add one table's consumed capacity to the call in progress
+fn report(capacity: &ConsumedCapacity) {
+    if let Some(units) = capacity.capacity_units {
+        tracing::trace!(
+            table = capacity.table_name.as_deref().unwrap_or_default(),
+            units,
+            "consumed capacity"
+        );
+        add_consumed_capacity(units);
+    }
+}
+
+impl Intercept for CapacityInterceptor {
+    fn name(&self) -> &'static str {
+        "CapacityInterceptor"
+    }
+
+    fn modify_before_serialization(
+        &self,
+        context: &mut BeforeSerializationInterceptorContextMut<'_>,
+        _runtime_components: &RuntimeComponents,
+        _cfg: &mut ConfigBag,
+    ) -> Result<(), BoxError> {
+        request_capacity!(context.input_mut();
+            PutItemInput, GetItemInput, UpdateItemInput, DeleteItemInput,
+            QueryInput, ScanInput, TransactWriteItemsInput
+        );
+        Ok(())
+    }
+
+    fn read_after_deserialization(
+        &self,
+        context: &AfterDeserializationInterceptorContextRef<'_>,
+        _runtime_components: &RuntimeComponents,
+        _cfg: &mut ConfigBag,
+    ) -> Result<(), BoxError> {
+        let Ok(output) = context.output_or_error() else {
+            return Ok(());
+        };
+        report_capacity!(output;
+            PutItemOutput, GetItemOutput, UpdateItemOutput, DeleteItemOutput,
+            QueryOutput, ScanOutput
+        );
+        if let Some(output) = output.downcast_ref::<TransactWriteItemsOutput>() {
+            output.consumed_capacity.iter().flatten().for_each(report);
+        }
+        Ok(())
+    }
+}
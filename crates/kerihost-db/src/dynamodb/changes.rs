@@ -0,0 +1,969 @@
+//! Change feed from DynamoDB Streams
+//!
+//! The KEL, receipts and escrows tables need streams enabled with the
+//! `NEW_AND_OLD_IMAGES` view. [`DynamoDbChangeFeed`] follows every shard of
+//! the three streams and turns their records into [`Change`]s. Records are
+//! read through a [`StreamSource`], so the consumer can be driven by a
+//! local fake in tests.
+//!
+//! DynamoDB Streams keeps each item's records in order but may deliver a
+//! record more than once, so subscribers must tolerate repeated changes.
+//! Escrow items deleted by TTL arrive as removals like any other.
+//!
+//! The streams carry every tenant's writes. A feed follows one namespace,
+//! the default unless scoped with `TenantStore::for_tenant`.
+
+use super::client::split_scoped;
+use super::kel::sk_to_sn;
+use super::TableConfig;
+use crate::error::{DbError, DbResult};
+use crate::traits::{validate_tenant, Change, ChangeFeed, ChangeStream, EscrowReason, TenantStore};
+use async_trait::async_trait;
+use aws_sdk_dynamodb::error::{DisplayErrorContext, SdkError};
+use aws_sdk_dynamodb::types::AttributeValue;
+use aws_sdk_dynamodbstreams::types::{
+    AttributeValue as StreamAttributeValue, OperationType, ShardIteratorType,
+};
+use futures::StreamExt;
+use std::collections::{HashMap, HashSet};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::mpsc;
+
+type Item = HashMap<String, AttributeValue>;
+
+/// Default wait between polls of idle shards
+const DEFAULT_POLL_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Polls between looks for new shards while no shard has closed
+const SHARD_REFRESH_POLLS: u64 = 60;
+
+/// Changes buffered for a subscriber before the consumer waits on it
+const CHANGE_BUFFER: usize = 256;
+
+/// Kind of write a stream record describes
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Operation {
+    /// A new item was put
+    Insert,
+    /// An existing item was overwritten or updated
+    Modify,
+    /// An item was deleted
+    Remove,
+}
+
+/// A stream record with the attributes the change feed reads
+#[derive(Debug, Clone)]
+pub struct StreamRecord {
+    /// Kind of write
+    pub operation: Operation,
+    /// Table key of the item
+    pub keys: Item,
+    /// Item after the write (`None` for removals)
+    pub new_image: Option<Item>,
+    /// Item before the write (`None` for inserts)
+    pub old_image: Option<Item>,
+}
+
+/// A shard of a table stream
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ShardInfo {
+    /// Shard ID
+    pub id: String,
+    /// Shard this one was split from, whose records come first
+    pub parent: Option<String>,
+}
+
+/// Reads records from DynamoDB Streams
+#[async_trait]
+pub trait StreamSource: Send + Sync + 'static {
+    /// ARN of the stream enabled on a table
+    async fn stream_arn(&self, table: &str) -> DbResult<String>;
+
+    /// Every shard of a stream, open or closed
+    async fn shards(&self, stream_arn: &str) -> DbResult<Vec<ShardInfo>>;
+
+    /// Open an iterator on a shard at its oldest record, or just after its
+    /// newest one
+    async fn shard_iterator(
+        &self,
+        stream_arn: &str,
+        shard_id: &str,
+        from_start: bool,
+    ) -> DbResult<String>;
+
+    /// Read the records at an iterator and the iterator to read next, which
+    /// is `None` once the shard is closed and drained
+    async fn records(&self, iterator: &str) -> DbResult<(Vec<StreamRecord>, Option<String>)>;
+}
+
+/// Map a DynamoDB Streams request failure onto the storage error kinds
+fn streams_err<E, R>(err: SdkError<E, R>) -> DbError
+where
+    E: std::error::Error + Send + Sync + 'static,
+    R: std::fmt::Debug,
+    aws_sdk_dynamodbstreams::Error: From<E>,
+{
+    use aws_sdk_dynamodbstreams::Error as StreamsError;
+    let message = DisplayErrorContext(&err).to_string();
+    let err = match err {
+        SdkError::ServiceError(context) => StreamsError::from(context.into_err()),
+        SdkError::TimeoutError(_) | SdkError::DispatchFailure(_) | SdkError::ResponseError(_) => {
+            return DbError::Transient(message)
+        }
+        _ => return DbError::DynamoDb(message),
+    };
+    match err {
+        StreamsError::LimitExceededException(_) => DbError::Throttled(message),
+        StreamsError::InternalServerError(_) => DbError::Transient(message),
+        StreamsError::ResourceNotFoundException(_) => DbError::NotFound(message),
+        _ => DbError::DynamoDb(message),
+    }
+}
+
+/// Convert a stream image, keeping the string and number attributes
+/// changes are built from
+fn convert_image(image: HashMap<String, StreamAttributeValue>) -> Item {
+    image
+        .into_iter()
+        .filter_map(|(name, value)| match value {
+            StreamAttributeValue::S(s) => Some((name, AttributeValue::S(s))),
+            StreamAttributeValue::N(n) => Some((name, AttributeValue::N(n))),
+            _ => None,
+        })
+        .collect()
+}
+
+#[async_trait]
+impl StreamSource for aws_sdk_dynamodbstreams::Client {
+    async fn stream_arn(&self, table: &str) -> DbResult<String> {
+        let output = self
+            .list_streams()
+            .table_name(table)
+            .send()
+            .await
+            .map_err(streams_err)?;
+        output
+            .streams
+            .unwrap_or_default()
+            .into_iter()
+            .find_map(|stream| stream.stream_arn)
+            .ok_or_else(|| DbError::NotFound(format!("No stream enabled on {}", table)))
+    }
+
+    async fn shards(&self, stream_arn: &str) -> DbResult<Vec<ShardInfo>> {
+        let mut shards = Vec::new();
+        let mut start_shard = None;
+        loop {
+            let description = self
+                .describe_stream()
+                .stream_arn(stream_arn)
+                .set_exclusive_start_shard_id(start_shard)
+                .send()
+                .await
+                .map_err(streams_err)?
+                .stream_description
+                .ok_or_else(|| DbError::NotFound(format!("Stream {}", stream_arn)))?;
+
+            shards.extend(
+                description
+                    .shards
+                    .unwrap_or_default()
+                    .into_iter()
+                    .filter_map(|shard| {
+                        Some(ShardInfo {
+                            id: shard.shard_id?,
+                            parent: shard.parent_shard_id,
+                        })
+                    }),
+            );
+            start_shard = description.last_evaluated_shard_id;
+            if start_shard.is_none() {
+                return Ok(shards);
+            }
+        }
+    }
+
+    async fn shard_iterator(
+        &self,
+        stream_arn: &str,
+        shard_id: &str,
+        from_start: bool,
+    ) -> DbResult<String> {
+        let iterator_type = if from_start {
+            ShardIteratorType::TrimHorizon
+        } else {
+            ShardIteratorType::Latest
+        };
+        self.get_shard_iterator()
+            .stream_arn(stream_arn)
+            .shard_id(shard_id)
+            .shard_iterator_type(iterator_type)
+            .send()
+            .await
+            .map_err(streams_err)?
+            .shard_iterator
+            .ok_or_else(|| DbError::NotFound(format!("Shard {}", shard_id)))
+    }
+
+    async fn records(&self, iterator: &str) -> DbResult<(Vec<StreamRecord>, Option<String>)> {
+        let output = self
+            .get_records()
+            .shard_iterator(iterator)
+            .send()
+            .await
+            .map_err(streams_err)?;
+
+        let records = output
+            .records
+            .unwrap_or_default()
+            .into_iter()
+            .filter_map(|record| {
+                let operation = match record.event_name? {
+                    OperationType::Insert => Operation::Insert,
+                    OperationType::Modify => Operation::Modify,
+                    OperationType::Remove => Operation::Remove,
+                    _ => return None,
+                };
+                let data = record.dynamodb?;
+                Some(StreamRecord {
+                    operation,
+                    keys: convert_image(data.keys.unwrap_or_default()),
+                    new_image: data.new_image.map(convert_image),
+                    old_image: data.old_image.map(convert_image),
+                })
+            })
+            .collect();
+        Ok((records, output.next_shard_iterator))
+    }
+}
+
+/// Table a stream record came from
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Table {
+    Kel,
+    Receipts,
+    Escrows,
+}
+
+/// Get a stream record's image of the item
+fn image(image: &Option<Item>) -> DbResult<&Item> {
+    image.as_ref().ok_or_else(|| {
+        DbError::DynamoDb("Stream record without image, expected NEW_AND_OLD_IMAGES".into())
+    })
+}
+
+/// Build the change a stream record describes, if any, with the tenant
+/// whose namespace the item is in
+///
+/// Rewrites that change nothing a subscriber sees, such as record
+/// migrations, produce no change.
+fn change_from_record(
+    table: Table,
+    record: &StreamRecord,
+) -> DbResult<Option<(Option<String>, Change)>> {
+    let field = |item: &Item, name: &str| {
+        item.get(name)
+            .and_then(|v| v.as_s().ok())
+            .cloned()
+            .ok_or_else(|| DbError::Corruption(format!("Missing {} field", name)))
+    };
+
+    // Partition keys carry the tenant; other attributes are stored bare
+    let key_field = |item: &Item, name: &str| -> DbResult<(Option<String>, String)> {
+        let stored = field(item, name)?;
+        let (tenant, key) = split_scoped(&stored);
+        Ok((tenant.map(str::to_string), key.to_string()))
+    };
+
+    let change = match (table, record.operation) {
+        (Table::Kel, Operation::Insert) => {
+            let item = image(&record.new_image)?;
+            let (tenant, prefix) = key_field(item, "aid")?;
+            let change = Change::EventAccepted {
+                prefix,
+                sn: sk_to_sn(&field(item, "sn")?)
+                    .ok_or_else(|| DbError::Corruption("Invalid sn field".to_string()))?,
+                digest: field(item, "digest")?,
+            };
+            (tenant, change)
+        }
+        (Table::Receipts, Operation::Insert | Operation::Modify) => {
+            let item = image(&record.new_image)?;
+            let signature = field(item, "signature")?;
+            let old_signature = record
+                .old_image
+                .as_ref()
+                .and_then(|old| field(old, "signature").ok());
+            if old_signature.as_ref() == Some(&signature) {
+                return Ok(None);
+            }
+            let (tenant, event_digest) = key_field(item, "event_digest")?;
+            let change = Change::ReceiptAdded {
+                prefix: field(item, "event_aid")?,
+                sn: item
+                    .get("event_sn")
+                    .and_then(|v| v.as_n().ok())
+                    .and_then(|n| n.parse().ok())
+                    .ok_or_else(|| DbError::Corruption("Missing event_sn field".to_string()))?,
+                event_digest,
+                witness_prefix: field(item, "witness_aid")?,
+            };
+            (tenant, change)
+        }
+        (Table::Escrows, Operation::Insert | Operation::Modify) => {
+            let item = image(&record.new_image)?;
+            let reason = field(item, "reason")?;
+            let old_reason = record
+                .old_image
+                .as_ref()
+                .and_then(|old| field(old, "reason").ok());
+            if old_reason.as_ref() == Some(&reason) {
+                return Ok(None);
+            }
+            let (sn, digest) = parse_escrow_sk(&field(item, "sn_digest")?)?;
+            let (tenant, prefix) = key_field(item, "aid")?;
+            let reason = split_scoped(&reason).1.to_string();
+            let change = Change::Escrowed {
+                prefix,
+                sn,
+                digest,
+                reason: serde_json::from_value::<EscrowReason>(reason.into())?,
+            };
+            (tenant, change)
+        }
+        (Table::Escrows, Operation::Remove) => {
+            let (sn, digest) = parse_escrow_sk(&field(&record.keys, "sn_digest")?)?;
+            let (tenant, prefix) = key_field(&record.keys, "aid")?;
+            (tenant, Change::EscrowRemoved { prefix, sn, digest })
+        }
+        _ => return Ok(None),
+    };
+    Ok(Some(change))
+}
+
+impl TableConfig {
+    /// Build the change a record from one of the tables' streams describes,
+    /// with the tenant whose namespace the item is in
+    ///
+    /// For consumers handed records by a Lambda event source mapping rather
+    /// than following the streams with [`DynamoDbChangeFeed`]. Records from
+    /// other tables, and rewrites that change nothing a subscriber sees,
+    /// produce no change.
+    pub fn change_from_stream(
+        &self,
+        table: &str,
+        record: &StreamRecord,
+    ) -> DbResult<Option<(Option<String>, Change)>> {
+        let table = if table == self.kel_table {
+            Table::Kel
+        } else if table == self.receipts_table {
+            Table::Receipts
+        } else if table == self.escrows_table {
+            Table::Escrows
+        } else {
+            return Ok(None);
+        };
+        change_from_record(table, record)
+    }
+}
+
+/// Split an escrow sort key into its sequence number and digest
+fn parse_escrow_sk(sk: &str) -> DbResult<(u64, String)> {
+    sk.split_once('#')
+        .and_then(|(sn, digest)| Some((sk_to_sn(sn)?, digest.to_string())))
+        .ok_or_else(|| DbError::Corruption(format!("Invalid escrow key {}", sk)))
+}
+
+/// DynamoDB change feed, read from the tables' streams
+pub struct DynamoDbChangeFeed<S = aws_sdk_dynamodbstreams::Client> {
+    source: Arc<S>,
+    config: TableConfig,
+    poll_interval: Duration,
+    tenant: Option<String>,
+}
+
+impl DynamoDbChangeFeed {
+    /// Create from environment
+    pub async fn from_env() -> Self {
+        let aws_config = aws_config::defaults(aws_config::BehaviorVersion::latest())
+            .load()
+            .await;
+        let client = aws_sdk_dynamodbstreams::Client::new(&aws_config);
+        DynamoDbChangeFeed::new(client, TableConfig::from_env())
+    }
+
+    /// Create with custom endpoint (for local testing)
+    pub async fn with_endpoint(endpoint: &str, config: TableConfig) -> Self {
+        let aws_config = aws_config::defaults(aws_config::BehaviorVersion::latest())
+            .endpoint_url(endpoint)
+            .load()
+            .await;
+        let client = aws_sdk_dynamodbstreams::Client::new(&aws_config);
+        DynamoDbChangeFeed::new(client, config)
+    }
+}
+
+impl<S: StreamSource> DynamoDbChangeFeed<S> {
+    /// Create a feed reading the configured tables' streams from `source`
+    pub fn new(source: S, config: TableConfig) -> Self {
+        DynamoDbChangeFeed {
+            source: Arc::new(source),
+            config,
+            poll_interval: DEFAULT_POLL_INTERVAL,
+            tenant: None,
+        }
+    }
+
+    /// Set the wait between polls while no shard has new records
+    pub fn with_poll_interval(mut self, poll_interval: Duration) -> Self {
+        self.poll_interval = poll_interval;
+        self
+    }
+}
+
+impl<S: StreamSource> TenantStore for DynamoDbChangeFeed<S> {
+    fn tenant(&self) -> Option<&str> {
+        self.tenant.as_deref()
+    }
+
+    fn for_tenant(&self, tenant: &str) -> DbResult<Self> {
+        validate_tenant(tenant)?;
+        Ok(DynamoDbChangeFeed {
+            source: Arc::clone(&self.source),
+            config: self.config.clone(),
+            poll_interval: self.poll_interval,
+            tenant: Some(tenant.to_string()),
+        })
+    }
+}
+
+#[async_trait]
+impl<S: StreamSource> ChangeFeed for DynamoDbChangeFeed<S> {
+    async fn subscribe(&self, prefix_filter: Option<&str>) -> DbResult<ChangeStream> {
+        let tables = [
+            (Table::Kel, self.config.kel_table.as_str()),
+            (Table::Receipts, self.config.receipts_table.as_str()),
+            (Table::Escrows, self.config.escrows_table.as_str()),
+        ];
+        let mut consumer = Consumer {
+            source: Arc::clone(&self.source),
+            streams: Vec::new(),
+            known: HashSet::new(),
+            readers: Vec::new(),
+            tenant: self.tenant.clone(),
+            prefix_filter: prefix_filter.map(str::to_string),
+            poll_interval: self.poll_interval,
+            polls: 0,
+        };
+        // Position every shard before returning, so no change committed
+        // after subscribing is missed
+        for (table, name) in tables {
+            let stream_arn = self.source.stream_arn(name).await?;
+            for shard in self.source.shards(&stream_arn).await? {
+                let iterator = self
+                    .source
+                    .shard_iterator(&stream_arn, &shard.id, false)
+                    .await?;
+                consumer.known.insert(shard.id.clone());
+                consumer.readers.push(ShardReader {
+                    table,
+                    shard_id: shard.id,
+                    iterator,
+                });
+            }
+            consumer.streams.push((table, stream_arn));
+        }
+
+        let (sender, receiver) = mpsc::channel(CHANGE_BUFFER);
+        tokio::spawn(consumer.run(sender));
+        let changes = futures::stream::unfold(receiver, |mut receiver| async {
+            let next = receiver.recv().await?;
+            Some((next, receiver))
+        });
+        Ok(changes.boxed())
+    }
+}
+
+/// A shard being followed
+struct ShardReader {
+    table: Table,
+    shard_id: String,
+    iterator: String,
+}
+
+/// Background task following the streams for one subscriber
+struct Consumer<S> {
+    source: Arc<S>,
+    /// Stream ARN of each table
+    streams: Vec<(Table, String)>,
+    /// Every shard ID already followed or finished
+    known: HashSet<String>,
+    readers: Vec<ShardReader>,
+    /// Namespace whose changes are forwarded
+    tenant: Option<String>,
+    prefix_filter: Option<String>,
+    poll_interval: Duration,
+    polls: u64,
+}
+
+impl<S: StreamSource> Consumer<S> {
+    /// Poll until the subscriber goes away or reading fails
+    async fn run(mut self, sender: mpsc::Sender<DbResult<Change>>) {
+        loop {
+            let changes = match self.poll().await {
+                Ok(changes) => changes,
+                Err(e) => {
+                    let _ = sender.send(Err(e)).await;
+                    return;
+                }
+            };
+            let idle = changes.is_empty();
+            for change in changes {
+                if sender.send(Ok(change)).await.is_err() {
+                    return;
+                }
+            }
+            if idle {
+                tokio::select! {
+                    _ = tokio::time::sleep(self.poll_interval) => {}
+                    _ = sender.closed() => return,
+                }
+            }
+        }
+    }
+
+    /// Read once from every followed shard
+    async fn poll(&mut self) -> DbResult<Vec<Change>> {
+        let mut changes = Vec::new();
+        let mut closed = false;
+        for mut reader in std::mem::take(&mut self.readers) {
+            let (records, next) = self.source.records(&reader.iterator).await?;
+            for record in &records {
+                let Some((tenant, change)) = change_from_record(reader.table, record)? else {
+                    continue;
+                };
+                if tenant == self.tenant
+                    && self
+                        .prefix_filter
+                        .as_deref()
+                        .is_none_or(|prefix| change.prefix() == prefix)
+                {
+                    changes.push(change);
+                }
+            }
+            match next {
+                Some(iterator) => {
+                    reader.iterator = iterator;
+                    self.readers.push(reader);
+                }
+                None => closed = true,
+            }
+        }
+
+        self.polls += 1;
+        if closed || self.polls.is_multiple_of(SHARD_REFRESH_POLLS) {
+            self.discover_shards().await?;
+        }
+        Ok(changes)
+    }
+
+    /// Follow shards created since the last look from their first record
+    ///
+    /// A shard split from one still being read waits for its parent to
+    /// drain, keeping each item's changes in order.
+    async fn discover_shards(&mut self) -> DbResult<()> {
+        for (table, stream_arn) in &self.streams {
+            for shard in self.source.shards(stream_arn).await? {
+                let parent_open = shard.parent.as_ref().is_some_and(|parent| {
+                    self.readers.iter().any(|reader| &reader.shard_id == parent)
+                });
+                if self.known.contains(&shard.id) || parent_open {
+                    continue;
+                }
+                let iterator = self
+                    .source
+                    .shard_iterator(stream_arn, &shard.id, true)
+                    .await?;
+                self.known.insert(shard.id.clone());
+                self.readers.push(ShardReader {
+                    table: *table,
+                    shard_id: shard.id,
+                    iterator,
+                });
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures::TryStreamExt;
+    use std::sync::Mutex;
+
+    /// In-process stand-in for DynamoDB Streams
+    ///
+    /// Each table has one stream; shards hold records in order and an
+    /// iterator is `shard#position`.
+    #[derive(Default)]
+    struct FakeStreams {
+        shards: Mutex<Vec<FakeShard>>,
+    }
+
+    struct FakeShard {
+        table: String,
+        info: ShardInfo,
+        records: Vec<StreamRecord>,
+        closed: bool,
+    }
+
+    impl FakeStreams {
+        fn add_shard(&self, table: &str, id: &str, parent: Option<&str>) {
+            self.shards.lock().unwrap().push(FakeShard {
+                table: table.to_string(),
+                info: ShardInfo {
+                    id: id.to_string(),
+                    parent: parent.map(str::to_string),
+                },
+                records: Vec::new(),
+                closed: false,
+            });
+        }
+
+        fn push(&self, shard_id: &str, record: StreamRecord) {
+            let mut shards = self.shards.lock().unwrap();
+            let shard = shards.iter_mut().find(|s| s.info.id == shard_id).unwrap();
+            shard.records.push(record);
+        }
+
+        fn close(&self, shard_id: &str) {
+            let mut shards = self.shards.lock().unwrap();
+            shards
+                .iter_mut()
+                .find(|s| s.info.id == shard_id)
+                .unwrap()
+                .closed = true;
+        }
+    }
+
+    #[async_trait]
+    impl StreamSource for FakeStreams {
+        async fn stream_arn(&self, table: &str) -> DbResult<String> {
+            Ok(format!("arn:{}", table))
+        }
+
+        async fn shards(&self, stream_arn: &str) -> DbResult<Vec<ShardInfo>> {
+            let shards = self.shards.lock().unwrap();
+            Ok(shards
+                .iter()
+                .filter(|s| format!("arn:{}", s.table) == stream_arn)
+                .map(|s| s.info.clone())
+                .collect())
+        }
+
+        async fn shard_iterator(
+            &self,
+            _stream_arn: &str,
+            shard_id: &str,
+            from_start: bool,
+        ) -> DbResult<String> {
+            let shards = self.shards.lock().unwrap();
+            let shard = shards.iter().find(|s| s.info.id == shard_id).unwrap();
+            let position = if from_start { 0 } else { shard.records.len() };
+            Ok(format!("{}#{}", shard_id, position))
+        }
+
+        async fn records(&self, iterator: &str) -> DbResult<(Vec<StreamRecord>, Option<String>)> {
+            let (shard_id, position) = iterator.split_once('#').unwrap();
+            let position: usize = position.parse().unwrap();
+            let shards = self.shards.lock().unwrap();
+            let shard = shards.iter().find(|s| s.info.id == shard_id).unwrap();
+            let records = shard.records[position..].to_vec();
+            let next = (!shard.closed).then(|| format!("{}#{}", shard_id, shard.records.len()));
+            Ok((records, next))
+        }
+    }
+
+    fn item(fields: &[(&str, &str)]) -> Item {
+        fields
+            .iter()
+            .map(|(name, value)| {
+                let value = if *name == "event_sn" {
+                    AttributeValue::N(value.to_string())
+                } else {
+                    AttributeValue::S(value.to_string())
+                };
+                (name.to_string(), value)
+            })
+            .collect()
+    }
+
+    fn kel_insert(prefix: &str, sn: u64, digest: &str) -> StreamRecord {
+        let image = item(&[
+            ("aid", prefix),
+            ("sn", &format!("{:016x}", sn)),
+            ("digest", digest),
+        ]);
+        StreamRecord {
+            operation: Operation::Insert,
+            keys: image.clone(),
+            new_image: Some(image),
+            old_image: None,
+        }
+    }
+
+    fn escrow_record(operation: Operation, reason: &str, old_reason: Option<&str>) -> StreamRecord {
+        let image = |reason: &str| {
+            item(&[
+                ("aid", "DTest123"),
+                ("sn_digest", "0000000000000002#EEscrowed"),
+                ("reason", reason),
+            ])
+        };
+        StreamRecord {
+            operation,
+            keys: item(&[
+                ("aid", "DTest123"),
+                ("sn_digest", "0000000000000002#EEscrowed"),
+            ]),
+            new_image: (operation != Operation::Remove).then(|| image(reason)),
+            old_image: old_reason.map(image),
+        }
+    }
+
+    fn feed(fake: FakeStreams) -> DynamoDbChangeFeed<FakeStreams> {
+        let config = TableConfig::new("kel", "states", "receipts", "escrows");
+        DynamoDbChangeFeed::new(fake, config).with_poll_interval(Duration::from_millis(5))
+    }
+
+    /// Build the change for a record in the default namespace
+    fn unscoped_change(table: Table, record: &StreamRecord) -> Option<Change> {
+        change_from_record(table, record)
+            .unwrap()
+            .map(|(tenant, change)| {
+                assert_eq!(tenant, None);
+                change
+            })
+    }
+
+    #[test]
+    fn test_change_from_records() {
+        let change = unscoped_change(Table::Kel, &kel_insert("DTest123", 10, "EDigest"));
+        assert_eq!(
+            change,
+            Some(Change::EventAccepted {
+                prefix: "DTest123".to_string(),
+                sn: 10,
+                digest: "EDigest".to_string(),
+            })
+        );
+
+        let image = item(&[
+            ("event_digest", "EDigest"),
+            ("witness_aid", "BWitness1"),
+            ("signature", "0BSig"),
+            ("event_sn", "3"),
+            ("event_aid", "DTest123"),
+        ]);
+        let receipt = StreamRecord {
+            operation: Operation::Insert,
+            keys: image.clone(),
+            new_image: Some(image.clone()),
+            old_image: None,
+        };
+        assert_eq!(
+            unscoped_change(Table::Receipts, &receipt),
+            Some(Change::ReceiptAdded {
+                prefix: "DTest123".to_string(),
+                sn: 3,
+                event_digest: "EDigest".to_string(),
+                witness_prefix: "BWitness1".to_string(),
+            })
+        );
+        // A rewrite of the same receipt changes nothing
+        let rewrite = StreamRecord {
+            operation: Operation::Modify,
+            old_image: Some(image),
+            ..receipt
+        };
+        assert_eq!(unscoped_change(Table::Receipts, &rewrite), None);
+
+        let escrowed = escrow_record(Operation::Insert, "out_of_order", None);
+        assert_eq!(
+            unscoped_change(Table::Escrows, &escrowed),
+            Some(Change::Escrowed {
+                prefix: "DTest123".to_string(),
+                sn: 2,
+                digest: "EEscrowed".to_string(),
+                reason: EscrowReason::OutOfOrder,
+            })
+        );
+        let same = escrow_record(Operation::Modify, "out_of_order", Some("out_of_order"));
+        assert_eq!(unscoped_change(Table::Escrows, &same), None);
+        let moved = escrow_record(Operation::Modify, "missing_receipts", Some("out_of_order"));
+        assert!(matches!(
+            unscoped_change(Table::Escrows, &moved),
+            Some(Change::Escrowed {
+                reason: EscrowReason::MissingReceipts,
+                ..
+            })
+        ));
+        let removed = escrow_record(Operation::Remove, "", Some("out_of_order"));
+        assert_eq!(
+            unscoped_change(Table::Escrows, &removed),
+            Some(Change::EscrowRemoved {
+                prefix: "DTest123".to_string(),
+                sn: 2,
+                digest: "EEscrowed".to_string(),
+            })
+        );
+
+        // Deleting a KEL item is not a change subscribers see
+        let kel_remove = StreamRecord {
+            operation: Operation::Remove,
+            ..kel_insert("DTest123", 0, "EDigest")
+        };
+        assert_eq!(unscoped_change(Table::Kel, &kel_remove), None);
+    }
+
+    #[test]
+    fn test_change_from_scoped_records() {
+        let (tenant, change) =
+            change_from_record(Table::Kel, &kel_insert("alpha#DTest123", 1, "E1"))
+                .unwrap()
+                .unwrap();
+        assert_eq!(tenant.as_deref(), Some("alpha"));
+        assert_eq!(change.prefix(), "DTest123");
+
+        let mut escrowed = escrow_record(Operation::Insert, "alpha#out_of_order", None);
+        let scoped_aid = AttributeValue::S("alpha#DTest123".to_string());
+        escrowed
+            .new_image
+            .as_mut()
+            .unwrap()
+            .insert("aid".to_string(), scoped_aid);
+        assert_eq!(
+            change_from_record(Table::Escrows, &escrowed).unwrap(),
+            Some((
+                Some("alpha".to_string()),
+                Change::Escrowed {
+                    prefix: "DTest123".to_string(),
+                    sn: 2,
+                    digest: "EEscrowed".to_string(),
+                    reason: EscrowReason::OutOfOrder,
+                }
+            ))
+        );
+    }
+
+    #[test]
+    fn test_change_from_stream_by_table_name() {
+        let config = TableConfig::new("kel", "states", "receipts", "escrows");
+        let record = kel_insert("alpha#DTest123", 1, "E1");
+        let (tenant, change) = config.change_from_stream("kel", &record).unwrap().unwrap();
+        assert_eq!(tenant.as_deref(), Some("alpha"));
+        assert_eq!(change.prefix(), "DTest123");
+
+        // Records from tables the feed does not follow produce no change
+        assert_eq!(config.change_from_stream("states", &record).unwrap(), None);
+    }
+
+    #[tokio::test]
+    async fn test_subscribe_follows_streams() {
+        let fake = FakeStreams::default();
+        fake.add_shard("kel", "kel-1", None);
+        fake.add_shard("receipts", "receipts-1", None);
+        fake.add_shard("escrows", "escrows-1", None);
+        // Written before subscribing, so not delivered
+        fake.push("kel-1", kel_insert("DTest123", 0, "EOld"));
+        let feed = feed(fake);
+
+        let mut all = feed.subscribe(None).await.unwrap();
+        let mut filtered = feed.subscribe(Some("DTest123")).await.unwrap();
+        feed.source.push("kel-1", kel_insert("DOther", 0, "EOther"));
+        feed.source.push("kel-1", kel_insert("DTest123", 1, "ENew"));
+        feed.source.push(
+            "escrows-1",
+            escrow_record(Operation::Insert, "out_of_order", None),
+        );
+
+        let received: Vec<Change> = all.by_ref().take(3).try_collect().await.unwrap();
+        assert_eq!(received.len(), 3);
+        assert!(received.contains(&Change::EventAccepted {
+            prefix: "DOther".to_string(),
+            sn: 0,
+            digest: "EOther".to_string(),
+        }));
+
+        let received: Vec<Change> = filtered.by_ref().take(2).try_collect().await.unwrap();
+        assert_eq!(
+            received[0],
+            Change::EventAccepted {
+                prefix: "DTest123".to_string(),
+                sn: 1,
+                digest: "ENew".to_string(),
+            }
+        );
+        assert!(matches!(received[1], Change::Escrowed { .. }));
+    }
+
+    #[tokio::test]
+    async fn test_subscribe_follows_split_shards_in_order() {
+        let fake = FakeStreams::default();
+        fake.add_shard("kel", "kel-1", None);
+        fake.add_shard("receipts", "receipts-1", None);
+        fake.add_shard("escrows", "escrows-1", None);
+        let feed = feed(fake);
+        let mut changes = feed.subscribe(None).await.unwrap();
+
+        // The child appears while its parent still has records to read
+        feed.source.add_shard("kel", "kel-2", Some("kel-1"));
+        feed.source
+            .push("kel-2", kel_insert("DTest123", 1, "ESecond"));
+        feed.source
+            .push("kel-1", kel_insert("DTest123", 0, "EFirst"));
+        feed.source.close("kel-1");
+
+        let received: Vec<Change> = changes.by_ref().take(2).try_collect().await.unwrap();
+        let digests: Vec<&str> = received
+            .iter()
+            .map(|change| match change {
+                Change::EventAccepted { digest, .. } => digest.as_str(),
+                _ => unreachable!(),
+            })
+            .collect();
+        assert_eq!(digests, vec!["EFirst", "ESecond"]);
+    }
+
+    #[tokio::test]
+    async fn test_subscribe_keeps_to_tenant() {
+        let fake = FakeStreams::default();
+        fake.add_shard("kel", "kel-1", None);
+        fake.add_shard("receipts", "receipts-1", None);
+        fake.add_shard("escrows", "escrows-1", None);
+        let feed = feed(fake);
+        let alpha = feed.for_tenant("alpha").unwrap();
+        assert!(feed.for_tenant("not a tenant").is_err());
+
+        let mut default_changes = feed.subscribe(None).await.unwrap();
+        let mut alpha_changes = alpha.subscribe(Some("DTest123")).await.unwrap();
+        feed.source
+            .push("kel-1", kel_insert("beta#DTest123", 0, "EBeta"));
+        feed.source
+            .push("kel-1", kel_insert("alpha#DTest123", 0, "EAlpha"));
+        feed.source
+            .push("kel-1", kel_insert("DTest123", 0, "EDefault"));
+
+        let digest = |change: Change| match change {
+            Change::EventAccepted { digest, .. } => digest,
+            _ => unreachable!(),
+        };
+        let next = alpha_changes.next().await.unwrap().unwrap();
+        assert_eq!(next.prefix(), "DTest123");
+        assert_eq!(digest(next), "EAlpha");
+        let next = default_changes.next().await.unwrap().unwrap();
+        assert_eq!(digest(next), "EDefault");
+    }
+}
@@ -1,19 +1,62 @@
 //! DynamoDB client wrapper
 
+use super::capacity::CapacityInterceptor;
 use super::TableConfig;
+use crate::error::DbResult;
+use crate::traits::{validate_tenant, TenantStore};
+use aws_sdk_dynamodb::types::AttributeValue;
 use aws_sdk_dynamodb::Client;
 
+/// Separator between a tenant and the partition key it scopes
+///
+/// Identifiers, digests and SAIDs are Base64 and never contain it, nor do
+/// valid tenant names.
+const TENANT_SEPARATOR: char = '#';
+
 /// DynamoDB database implementation
+///
+/// A handle scoped to a tenant stores every partition key as
+/// `{tenant}#{key}`. The default namespace keeps bare keys, so tables
+/// written before tenants existed read the same.
 #[derive(Clone)]
 pub struct DynamoDbDatabase {
     pub(crate) client: Client,
     pub(crate) config: TableConfig,
+    pub(crate) tenant: Option<String>,
+}
+
+/// Split a stored partition key into its tenant and unscoped key
+pub(super) fn split_scoped(stored: &str) -> (Option<&str>, &str) {
+    match stored.split_once(TENANT_SEPARATOR) {
+        Some((tenant, key)) => (Some(tenant), key),
+        None => (None, stored),
+    }
+}
+
+/// Partition key for `key` in a tenant's namespace
+pub(super) fn scoped(tenant: Option<&str>, key: &str) -> String {
+    match tenant {
+        Some(tenant) => format!("{}{}{}", tenant, TENANT_SEPARATOR, key),
+        None => key.to_string(),
+    }
 }
 
 impl DynamoDbDatabase {
     /// Create new DynamoDB database with config
+    ///
+    /// Requests through the database report the capacity they consume to
+    /// `InstrumentedDatabase`.
     pub fn new(client: Client, config: TableConfig) -> Self {
-        DynamoDbDatabase { client, config }
+        let client_config = client
+            .config()
+            .to_builder()
+            .interceptor(CapacityInterceptor)
+            .build();
+        DynamoDbDatabase {
+            client: Client::from_conf(client_config),
+            config,
+            tenant: None,
+        }
     }
 
     /// Create from environment
@@ -22,8 +65,7 @@ impl DynamoDbDatabase {
             .load()
             .await;
         let client = Client::new(&aws_config);
-        let config = TableConfig::from_env();
-        DynamoDbDatabase { client, config }
+        DynamoDbDatabase::new(client, TableConfig::from_env())
     }
 
     /// Create with custom endpoint (for local testing)
@@ -32,8 +74,42 @@ impl DynamoDbDatabase {
             .endpoint_url(endpoint)
             .load()
             .await;
-        let client = Client::new(&aws_config);
-        DynamoDbDatabase { client, config }
+        DynamoDbDatabase::new(Client::new(&aws_config), config)
+    }
+
+    /// Partition key for `key` in this handle's namespace
+    pub(super) fn key(&self, key: &str) -> String {
+        scoped(self.tenant.as_deref(), key)
+    }
+
+    /// Scan filter keeping the items of this handle's namespace, by the
+    /// partition key `attribute`, and the value it binds to `:namespace`
+    pub(super) fn namespace_filter(&self, attribute: &str) -> (String, AttributeValue) {
+        match &self.tenant {
+            Some(tenant) => (
+                format!("begins_with({}, :namespace)", attribute),
+                AttributeValue::S(scoped(Some(tenant), "")),
+            ),
+            None => (
+                format!("NOT contains({}, :namespace)", attribute),
+                AttributeValue::S(TENANT_SEPARATOR.to_string()),
+            ),
+        }
+    }
+}
+
+impl TenantStore for DynamoDbDatabase {
+    fn tenant(&self) -> Option<&str> {
+        self.tenant.as_deref()
+    }
+
+    fn for_tenant(&self, tenant: &str) -> DbResult<Self> {
+        validate_tenant(tenant)?;
+        Ok(DynamoDbDatabase {
+            client: self.client.clone(),
+            config: self.config.clone(),
+            tenant: Some(tenant.to_string()),
+        })
     }
 }
 
@@ -54,4 +130,12 @@ mod tests {
         assert_eq!(config.kel_table, "my-kel");
         assert_eq!(config.states_table, "my-states");
     }
+
+    #[test]
+    fn test_scoped_keys() {
+        assert_eq!(scoped(None, "DTest123"), "DTest123");
+        assert_eq!(scoped(Some("alpha"), "DTest123"), "alpha#DTest123");
+        assert_eq!(split_scoped("alpha#DTest123"), (Some("alpha"), "DTest123"));
+        assert_eq!(split_scoped("DTest123"), (None, "DTest123"));
+    }
 }
@@ -1,49 +1,125 @@
 //! Escrow storage implementation for DynamoDB
 
+use super::kel::{cesr_attr, parse_event};
 use super::DynamoDbDatabase;
 use crate::error::{DbError, DbResult};
-use crate::traits::{EscrowReason, EscrowStore, EscrowedEvent};
+use crate::record;
+use crate::traits::{EscrowReason, EscrowStore, EscrowedEvent, Page};
 use async_trait::async_trait;
-use aws_sdk_dynamodb::types::AttributeValue;
+use aws_sdk_dynamodb::types::{AttributeValue, ReturnValue};
 use kerihost_core::SignedEvent;
+use serde_json::json;
 use std::collections::HashMap;
 
-/// Default escrow TTL in seconds (1 hour)
-const DEFAULT_ESCROW_TTL: u64 = 3600;
+type Item = HashMap<String, AttributeValue>;
+
+/// Parse an escrow item, rebuilding the event from its stored CESR
+fn parse_escrowed(item: &Item) -> DbResult<EscrowedEvent> {
+    let escrowed_json = item
+        .get("escrowed")
+        .and_then(|v| v.as_s().ok())
+        .ok_or_else(|| DbError::Corruption("Missing escrowed field".to_string()))?;
+    let mut escrowed: EscrowedEvent = record::decode(escrowed_json)?;
+
+    if item.contains_key("cesr") {
+        escrowed.event = parse_event(item, "escrowed")?;
+    }
+    Ok(escrowed)
+}
+
+/// GSI for escrows by event digest (keys only)
+pub(super) const BY_DIGEST_INDEX: &str = "escrow-events-by-digest";
+
+/// GSI for escrows by reason, ordered by expiry
+pub(super) const BY_REASON_INDEX: &str = "escrow-events-by-reason";
+
+/// Sort key for an escrow item: sequence number then digest
+///
+/// An event's digest fixes its prefix and sn, so re-escrowing the same
+/// event overwrites its item whatever the reason.
+fn escrow_sk(event: &SignedEvent) -> String {
+    format!("{:016x}#{}", event.event.sn, event.event.digest)
+}
+
+/// Encode a `LastEvaluatedKey` as a continuation token
+pub(super) fn encode_cursor(key: &Item) -> DbResult<String> {
+    let mut fields = serde_json::Map::new();
+    for (name, value) in key {
+        let value = match value {
+            AttributeValue::S(s) => json!({ "S": s }),
+            AttributeValue::N(n) => json!({ "N": n }),
+            _ => {
+                return Err(DbError::Other(format!(
+                    "Unsupported key attribute {}",
+                    name
+                )))
+            }
+        };
+        fields.insert(name.clone(), value);
+    }
+    Ok(serde_json::Value::Object(fields).to_string())
+}
+
+/// Decode a continuation token back into an `ExclusiveStartKey`
+pub(super) fn decode_cursor(cursor: &str) -> DbResult<Item> {
+    let invalid = || DbError::InvalidCursor(cursor.to_string());
+    let fields: HashMap<String, HashMap<String, String>> =
+        serde_json::from_str(cursor).map_err(|_| invalid())?;
+    fields
+        .into_iter()
+        .map(|(name, value)| match value.into_iter().next() {
+            Some((kind, value)) if kind == "S" => Ok((name, AttributeValue::S(value))),
+            Some((kind, value)) if kind == "N" => Ok((name, AttributeValue::N(value))),
+            _ => Err(invalid()),
+        })
+        .collect()
+}
+
+/// Get the table key of an escrow item
+fn escrow_key(item: &Item) -> DbResult<(String, String)> {
+    let field = |name: &str| {
+        item.get(name)
+            .and_then(|v| v.as_s().ok())
+            .cloned()
+            .ok_or_else(|| DbError::Corruption(format!("Missing {} field", name)))
+    };
+    Ok((field("aid")?, field("sn_digest")?))
+}
 
 #[async_trait]
 impl EscrowStore for DynamoDbDatabase {
-    async fn escrow_event(&self, event: &SignedEvent, reason: EscrowReason) -> DbResult<()> {
-        let escrowed = EscrowedEvent::new(event.clone(), reason, DEFAULT_ESCROW_TTL);
-        let escrowed_json =
-            serde_json::to_string(&escrowed).map_err(|e| DbError::Serialization(e.to_string()))?;
-
-        // Sort key format: reason#digest for efficient queries by reason
-        let sk = format!("{}#{}", reason, event.event.digest);
+    async fn escrow_event_with_ttl(
+        &self,
+        event: &SignedEvent,
+        reason: EscrowReason,
+        ttl_seconds: u64,
+    ) -> DbResult<()> {
+        let escrowed = EscrowedEvent::new(event.clone(), reason, ttl_seconds);
+        let escrowed_json = record::encode(&escrowed)?;
 
         let mut item = HashMap::new();
         item.insert(
             "aid".to_string(),
-            AttributeValue::S(event.event.prefix.clone()),
+            AttributeValue::S(self.key(&event.event.prefix)),
         );
-        item.insert("reason_digest".to_string(), AttributeValue::S(sk));
+        item.insert("sn_digest".to_string(), AttributeValue::S(escrow_sk(event)));
         item.insert("escrowed".to_string(), AttributeValue::S(escrowed_json));
+        item.insert("cesr".to_string(), cesr_attr(event)?);
+        // Key attributes of the digest and reason indexes, scoped like the
+        // table key since every tenant shares the indexes
         item.insert(
             "digest".to_string(),
-            AttributeValue::S(event.event.digest.clone()),
+            AttributeValue::S(self.key(&event.event.digest)),
         );
         item.insert(
             "reason".to_string(),
-            AttributeValue::S(reason.to_string()),
+            AttributeValue::S(self.key(&reason.to_string())),
         );
         item.insert("ttl".to_string(), AttributeValue::N(escrowed.ttl.to_string()));
-        // GSI requires created as a number (epoch millis)
-        let created_millis = chrono::DateTime::parse_from_rfc3339(&escrowed.created)
-            .map(|dt| dt.timestamp_millis())
-            .unwrap_or_else(|_| chrono::Utc::now().timestamp_millis());
+        // Tells a re-escrow apart from the escrow an attempt was read from
         item.insert(
             "created".to_string(),
-            AttributeValue::N(created_millis.to_string()),
+            AttributeValue::S(escrowed.created.clone()),
         );
 
         self.client
@@ -51,136 +127,226 @@ impl EscrowStore for DynamoDbDatabase {
             .table_name(&self.config.escrows_table)
             .set_item(Some(item))
             .send()
-            .await
-            .map_err(|e| DbError::DynamoDb(e.to_string()))?;
+            .await?;
 
         Ok(())
     }
 
-    async fn get_escrowed(&self, prefix: &str) -> DbResult<Vec<EscrowedEvent>> {
+    async fn record_escrow_attempt(&self, escrowed: &EscrowedEvent) -> DbResult<()> {
+        let Some(key) = self.find_by_digest(&escrowed.event.event.digest).await? else {
+            return Ok(());
+        };
+
+        // The escrow may be promoted or re-escrowed since it was read, even
+        // for the same reason. Items escrowed before `created` was stored
+        // are only checked by reason.
         let result = self
             .client
-            .query()
+            .update_item()
             .table_name(&self.config.escrows_table)
-            .key_condition_expression("aid = :aid")
-            .expression_attribute_values(":aid", AttributeValue::S(prefix.to_string()))
+            .key("aid", AttributeValue::S(key.0))
+            .key("sn_digest", AttributeValue::S(key.1))
+            .update_expression("SET escrowed = :escrowed")
+            .condition_expression(
+                "attribute_exists(aid) AND reason = :reason \
+                 AND (attribute_not_exists(created) OR created = :created)",
+            )
+            .expression_attribute_values(":escrowed", AttributeValue::S(record::encode(escrowed)?))
+            .expression_attribute_values(
+                ":reason",
+                AttributeValue::S(self.key(&escrowed.reason.to_string())),
+            )
+            .expression_attribute_values(":created", AttributeValue::S(escrowed.created.clone()))
             .send()
-            .await
-            .map_err(|e| DbError::DynamoDb(e.to_string()))?;
-
-        let mut escrowed = Vec::new();
-        if let Some(items) = result.items {
-            for item in items {
-                if let Some(escrowed_json) = item.get("escrowed").and_then(|v| v.as_s().ok()) {
-                    let e: EscrowedEvent = serde_json::from_str(escrowed_json)?;
-                    escrowed.push(e);
-                }
-            }
-        }
+            .await;
 
-        Ok(escrowed)
+        match result.map_err(DbError::from) {
+            Ok(_) | Err(DbError::ConditionFailed(_)) => Ok(()),
+            Err(e) => Err(e),
+        }
     }
 
-    async fn get_all_escrowed(&self) -> DbResult<Vec<EscrowedEvent>> {
-        // Scan all escrows - in production, consider pagination
+    async fn get_escrowed_page(
+        &self,
+        limit: usize,
+        cursor: Option<&str>,
+    ) -> DbResult<Page<EscrowedEvent>> {
+        let limit = limit.clamp(1, i32::MAX as usize);
+        // Other tenants' items count toward the limit, so a page may be short
+        let (namespace_filter, namespace) = self.namespace_filter("aid");
         let result = self
             .client
             .scan()
             .table_name(&self.config.escrows_table)
+            .filter_expression(namespace_filter)
+            .expression_attribute_values(":namespace", namespace)
+            .limit(limit as i32)
+            .set_exclusive_start_key(cursor.map(decode_cursor).transpose()?)
             .send()
-            .await
-            .map_err(|e| DbError::DynamoDb(e.to_string()))?;
-
-        let mut escrowed = Vec::new();
-        if let Some(items) = result.items {
-            for item in items {
-                if let Some(escrowed_json) = item.get("escrowed").and_then(|v| v.as_s().ok()) {
-                    let e: EscrowedEvent = serde_json::from_str(escrowed_json)?;
-                    escrowed.push(e);
-                }
-            }
-        }
+            .await?;
 
-        Ok(escrowed)
+        let items = result
+            .items
+            .unwrap_or_default()
+            .iter()
+            .map(parse_escrowed)
+            .collect::<DbResult<Vec<_>>>()?;
+        let next = result
+            .last_evaluated_key
+            .as_ref()
+            .map(encode_cursor)
+            .transpose()?;
+        Ok(Page { items, next })
     }
 
-    async fn promote_escrowed(&self, event_digest: &str) -> DbResult<Option<SignedEvent>> {
-        // First, find the escrowed event by digest
-        // We need to scan since digest is not a key
+    async fn get_escrowed_by_prefix(
+        &self,
+        prefix: &str,
+        limit: usize,
+        cursor: Option<&str>,
+    ) -> DbResult<Page<EscrowedEvent>> {
+        let limit = limit.clamp(1, i32::MAX as usize);
         let result = self
             .client
-            .scan()
+            .query()
             .table_name(&self.config.escrows_table)
-            .filter_expression("digest = :digest")
-            .expression_attribute_values(":digest", AttributeValue::S(event_digest.to_string()))
+            .key_condition_expression("aid = :aid")
+            .expression_attribute_values(":aid", AttributeValue::S(self.key(prefix)))
+            .limit(limit as i32)
+            .set_exclusive_start_key(cursor.map(decode_cursor).transpose()?)
             .send()
-            .await
-            .map_err(|e| DbError::DynamoDb(e.to_string()))?;
+            .await?;
+
+        let items = result
+            .items
+            .unwrap_or_default()
+            .iter()
+            .map(parse_escrowed)
+            .collect::<DbResult<Vec<_>>>()?;
+        let next = result
+            .last_evaluated_key
+            .as_ref()
+            .map(encode_cursor)
+            .transpose()?;
+        Ok(Page { items, next })
+    }
 
-        let item = match result.items.and_then(|items| items.into_iter().next()) {
-            Some(item) => item,
-            None => return Ok(None),
+    async fn get_escrowed_by_reason(
+        &self,
+        reason: EscrowReason,
+        limit: usize,
+        cursor: Option<&str>,
+    ) -> DbResult<Page<EscrowedEvent>> {
+        let limit = limit.clamp(1, i32::MAX as usize);
+        let result = self
+            .client
+            .query()
+            .table_name(&self.config.escrows_table)
+            .index_name(BY_REASON_INDEX)
+            .key_condition_expression("reason = :reason")
+            .expression_attribute_values(
+                ":reason",
+                AttributeValue::S(self.key(&reason.to_string())),
+            )
+            .limit(limit as i32)
+            .set_exclusive_start_key(cursor.map(decode_cursor).transpose()?)
+            .send()
+            .await?;
+
+        let items = result
+            .items
+            .unwrap_or_default()
+            .iter()
+            .map(parse_escrowed)
+            .collect::<DbResult<Vec<_>>>()?;
+        let next = result
+            .last_evaluated_key
+            .as_ref()
+            .map(encode_cursor)
+            .transpose()?;
+        Ok(Page { items, next })
+    }
+
+    async fn promote_escrowed(&self, event_digest: &str) -> DbResult<Option<SignedEvent>> {
+        let Some(key) = self.find_by_digest(event_digest).await? else {
+            return Ok(None);
         };
 
-        // Get the event
-        let escrowed_json = item
-            .get("escrowed")
-            .and_then(|v| v.as_s().ok())
-            .ok_or_else(|| DbError::Other("Missing escrowed field".to_string()))?;
-        let escrowed: EscrowedEvent = serde_json::from_str(escrowed_json)?;
+        // Only the caller whose delete returns the item promotes it
+        let result = self
+            .client
+            .delete_item()
+            .table_name(&self.config.escrows_table)
+            .key("aid", AttributeValue::S(key.0))
+            .key("sn_digest", AttributeValue::S(key.1))
+            .return_values(ReturnValue::AllOld)
+            .send()
+            .await?;
 
-        // Delete the escrow
-        let aid = item
-            .get("aid")
-            .and_then(|v| v.as_s().ok())
-            .ok_or_else(|| DbError::Other("Missing aid field".to_string()))?;
-        let reason_digest = item
-            .get("reason_digest")
-            .and_then(|v| v.as_s().ok())
-            .ok_or_else(|| DbError::Other("Missing reason_digest field".to_string()))?;
+        result
+            .attributes
+            .map(|item| parse_escrowed(&item).map(|e| e.event))
+            .transpose()
+    }
+
+    async fn remove_escrowed(&self, event_digest: &str) -> DbResult<()> {
+        let Some(key) = self.find_by_digest(event_digest).await? else {
+            return Ok(());
+        };
 
         self.client
             .delete_item()
             .table_name(&self.config.escrows_table)
-            .key("aid", AttributeValue::S(aid.to_string()))
-            .key("reason_digest", AttributeValue::S(reason_digest.to_string()))
+            .key("aid", AttributeValue::S(key.0))
+            .key("sn_digest", AttributeValue::S(key.1))
             .send()
-            .await
-            .map_err(|e| DbError::DynamoDb(e.to_string()))?;
+            .await?;
 
-        Ok(Some(escrowed.event))
+        Ok(())
     }
+}
 
-    async fn remove_escrowed(&self, event_digest: &str) -> DbResult<()> {
-        // Find and delete the escrowed event
+impl DynamoDbDatabase {
+    /// Find the table key of the escrow holding a digest
+    async fn find_by_digest(&self, event_digest: &str) -> DbResult<Option<(String, String)>> {
         let result = self
             .client
-            .scan()
+            .query()
             .table_name(&self.config.escrows_table)
-            .filter_expression("digest = :digest")
-            .expression_attribute_values(":digest", AttributeValue::S(event_digest.to_string()))
+            .index_name(BY_DIGEST_INDEX)
+            .key_condition_expression("digest = :digest")
+            .expression_attribute_values(":digest", AttributeValue::S(self.key(event_digest)))
+            .limit(1)
             .send()
-            .await
-            .map_err(|e| DbError::DynamoDb(e.to_string()))?;
-
-        if let Some(items) = result.items {
-            for item in items {
-                let aid = item.get("aid").and_then(|v| v.as_s().ok());
-                let reason_digest = item.get("reason_digest").and_then(|v| v.as_s().ok());
-
-                if let (Some(aid), Some(rd)) = (aid, reason_digest) {
-                    self.client
-                        .delete_item()
-                        .table_name(&self.config.escrows_table)
-                        .key("aid", AttributeValue::S(aid.to_string()))
-                        .key("reason_digest", AttributeValue::S(rd.to_string()))
-                        .send()
-                        .await
-                        .map_err(|e| DbError::DynamoDb(e.to_string()))?;
-                }
-            }
-        }
+            .await?;
 
-        Ok(())
+        result
+            .items
+            .and_then(|items| items.into_iter().next())
+            .map(|item| escrow_key(&item))
+            .transpose()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_cursor_roundtrip() {
+        let mut key = Item::new();
+        key.insert("aid".to_string(), AttributeValue::S("DTest123".to_string()));
+        key.insert(
+            "sn_digest".to_string(),
+            AttributeValue::S("0000000000000005#EDigest".to_string()),
+        );
+        key.insert("ttl".to_string(), AttributeValue::N("1700000000".to_string()));
+
+        let cursor = encode_cursor(&key).unwrap();
+        assert_eq!(decode_cursor(&cursor).unwrap(), key);
+        assert!(matches!(
+            decode_cursor("not a cursor"),
+            Err(DbError::InvalidCursor(_))
+        ));
     }
 }
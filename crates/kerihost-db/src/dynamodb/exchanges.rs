@@ -0,0 +1,263 @@
+//! IPEX exchange storage implementation for DynamoDB
+//!
+//! One table holds three kinds of item, keyed by PK `said`, SK `kind`:
+//! - `exchange`: exchange state, keyed by the SAID of its first message,
+//!   with the SAID of its latest message in `last_said`
+//! - `message`: accepted message, with the id of its exchange
+//! - `escrow`: message waiting for its prior, with `prior` projected into
+//!   the sparse `by-prior` GSI
+
+use super::escrows::{decode_cursor, encode_cursor};
+use super::DynamoDbDatabase;
+use crate::error::{DbError, DbResult};
+use crate::record::{self, Record};
+use crate::traits::{EscrowedMessage, ExchangeStore, Page};
+use async_trait::async_trait;
+use aws_sdk_dynamodb::types::{AttributeValue, Put, TransactWriteItem};
+use kerihost_core::{ExchangeMessage, IpexExchange};
+use std::collections::HashMap;
+
+/// Item kinds (sort key values)
+const KIND_EXCHANGE: &str = "exchange";
+const KIND_MESSAGE: &str = "message";
+const KIND_ESCROW: &str = "escrow";
+
+/// GSI for escrowed messages by prior SAID
+pub(super) const BY_PRIOR_INDEX: &str = "exchanges-by-prior";
+
+type Item = HashMap<String, AttributeValue>;
+
+impl DynamoDbDatabase {
+    /// Get an item of the given kind
+    async fn get_exchange_item(&self, said: &str, kind: &str) -> DbResult<Option<Item>> {
+        let result = self
+            .client
+            .get_item()
+            .table_name(&self.config.exchanges_table)
+            .key("said", AttributeValue::S(self.key(said)))
+            .key("kind", AttributeValue::S(kind.to_string()))
+            .send()
+            .await?;
+
+        Ok(result.item)
+    }
+
+    /// Delete an item of the given kind
+    async fn delete_exchange_item(&self, said: &str, kind: &str) -> DbResult<()> {
+        self.client
+            .delete_item()
+            .table_name(&self.config.exchanges_table)
+            .key("said", AttributeValue::S(self.key(said)))
+            .key("kind", AttributeValue::S(kind.to_string()))
+            .send()
+            .await?;
+
+        Ok(())
+    }
+}
+
+/// Read a JSON string attribute from an item
+fn field<'a>(item: &'a Item, name: &str) -> DbResult<&'a str> {
+    item.get(name)
+        .and_then(|v| v.as_s().ok())
+        .map(|s| s.as_str())
+        .ok_or_else(|| DbError::Corruption(format!("Missing {} field", name)))
+}
+
+/// Read a versioned record attribute from an item
+fn parse_record<T: Record>(item: &Item, name: &str) -> DbResult<T> {
+    record::decode(field(item, name)?)
+}
+
+fn parse_escrowed(items: Vec<Item>) -> DbResult<Vec<EscrowedMessage>> {
+    let mut escrowed = Vec::new();
+    for item in items {
+        escrowed.push(parse_record(&item, "escrowed")?);
+    }
+    Ok(escrowed)
+}
+
+#[async_trait]
+impl ExchangeStore for DynamoDbDatabase {
+    async fn put_exchange(
+        &self,
+        exchange: &IpexExchange,
+        message: &ExchangeMessage,
+    ) -> DbResult<()> {
+        let exchange_json = record::encode(exchange)?;
+        let message_json = message
+            .to_json()
+            .map_err(|e| DbError::Serialization(e.to_string()))?;
+
+        let mut exchange_item = HashMap::new();
+        exchange_item.insert("said".to_string(), AttributeValue::S(self.key(&exchange.id)));
+        exchange_item.insert("kind".to_string(), AttributeValue::S(KIND_EXCHANGE.to_string()));
+        exchange_item.insert("exchange".to_string(), AttributeValue::S(exchange_json));
+        exchange_item.insert(
+            "state".to_string(),
+            AttributeValue::S(exchange.state.to_string()),
+        );
+        exchange_item.insert(
+            "last_said".to_string(),
+            AttributeValue::S(exchange.last_said().to_string()),
+        );
+
+        let mut message_item = HashMap::new();
+        message_item.insert("said".to_string(), AttributeValue::S(self.key(&message.said)));
+        message_item.insert("kind".to_string(), AttributeValue::S(KIND_MESSAGE.to_string()));
+        message_item.insert("message".to_string(), AttributeValue::S(message_json));
+        message_item.insert(
+            "exchange_id".to_string(),
+            AttributeValue::S(exchange.id.clone()),
+        );
+
+        let message_put = Put::builder()
+            .table_name(&self.config.exchanges_table)
+            .set_item(Some(message_item));
+
+        // The exchange must still end where this message picks it up
+        let exchange_put = Put::builder()
+            .table_name(&self.config.exchanges_table)
+            .set_item(Some(exchange_item));
+        let exchange_put = match message.prior {
+            None => exchange_put.condition_expression("attribute_not_exists(said)"),
+            Some(ref prior) => exchange_put
+                .condition_expression("last_said = :prior")
+                .expression_attribute_values(":prior", AttributeValue::S(prior.clone())),
+        };
+
+        // Message and exchange state are written together
+        let mut transaction = self.client.transact_write_items();
+        for put in [message_put, exchange_put] {
+            let put = put.build().map_err(|e| DbError::Other(e.to_string()))?;
+            transaction = transaction.transact_items(TransactWriteItem::builder().put(put).build());
+        }
+
+        match transaction.send().await.map_err(DbError::from) {
+            Ok(_) => Ok(()),
+            Err(DbError::ConditionFailed(_)) => Err(DbError::StateConflict(format!(
+                "Exchange {} changed before message {}",
+                exchange.id, message.said
+            ))),
+            Err(e) => Err(e),
+        }
+    }
+
+    async fn get_exchange(&self, id: &str) -> DbResult<Option<IpexExchange>> {
+        match self.get_exchange_item(id, KIND_EXCHANGE).await? {
+            Some(item) => Ok(Some(parse_record(&item, "exchange")?)),
+            None => Ok(None),
+        }
+    }
+
+    async fn get_message(&self, said: &str) -> DbResult<Option<ExchangeMessage>> {
+        match self.get_exchange_item(said, KIND_MESSAGE).await? {
+            // Messages are stored as their own serialization, not as records
+            Some(item) => Ok(Some(serde_json::from_str(field(&item, "message")?)?)),
+            None => Ok(None),
+        }
+    }
+
+    async fn get_exchange_by_message(&self, said: &str) -> DbResult<Option<IpexExchange>> {
+        let Some(item) = self.get_exchange_item(said, KIND_MESSAGE).await? else {
+            return Ok(None);
+        };
+
+        let exchange_id = item
+            .get("exchange_id")
+            .and_then(|v| v.as_s().ok())
+            .ok_or_else(|| DbError::Corruption("Missing exchange_id field".to_string()))?;
+
+        self.get_exchange(exchange_id).await
+    }
+
+    async fn escrow_message_with_ttl(
+        &self,
+        message: &ExchangeMessage,
+        ttl_seconds: u64,
+    ) -> DbResult<()> {
+        let escrowed = EscrowedMessage::new(message.clone(), ttl_seconds);
+        let escrowed_json = record::encode(&escrowed)?;
+
+        let mut item = HashMap::new();
+        item.insert("said".to_string(), AttributeValue::S(self.key(&message.said)));
+        item.insert("kind".to_string(), AttributeValue::S(KIND_ESCROW.to_string()));
+        item.insert("escrowed".to_string(), AttributeValue::S(escrowed_json));
+        item.insert("ttl".to_string(), AttributeValue::N(escrowed.ttl.to_string()));
+        if let Some(ref prior) = message.prior {
+            item.insert("prior".to_string(), AttributeValue::S(self.key(prior)));
+        }
+
+        self.client
+            .put_item()
+            .table_name(&self.config.exchanges_table)
+            .set_item(Some(item))
+            .send()
+            .await?;
+
+        Ok(())
+    }
+
+    async fn get_escrowed_messages(&self, prior: &str) -> DbResult<Vec<EscrowedMessage>> {
+        let items = self
+            .client
+            .query()
+            .table_name(&self.config.exchanges_table)
+            .index_name(BY_PRIOR_INDEX)
+            .key_condition_expression("prior = :prior")
+            .expression_attribute_values(":prior", AttributeValue::S(self.key(prior)))
+            .into_paginator()
+            .items()
+            .send()
+            .try_collect()
+            .await?;
+
+        parse_escrowed(items)
+    }
+
+    async fn get_escrowed_messages_page(
+        &self,
+        limit: usize,
+        cursor: Option<&str>,
+    ) -> DbResult<Page<EscrowedMessage>> {
+        let limit = limit.clamp(1, i32::MAX as usize);
+        // Exchanges and other tenants' items count toward the limit, so a
+        // page may be short
+        let (namespace_filter, namespace) = self.namespace_filter("said");
+        let result = self
+            .client
+            .scan()
+            .table_name(&self.config.exchanges_table)
+            .filter_expression(format!("#kind = :kind AND {}", namespace_filter))
+            .expression_attribute_names("#kind", "kind")
+            .expression_attribute_values(":kind", AttributeValue::S(KIND_ESCROW.to_string()))
+            .expression_attribute_values(":namespace", namespace)
+            .limit(limit as i32)
+            .set_exclusive_start_key(cursor.map(decode_cursor).transpose()?)
+            .send()
+            .await?;
+
+        let items = parse_escrowed(result.items.unwrap_or_default())?;
+        let next = result
+            .last_evaluated_key
+            .as_ref()
+            .map(encode_cursor)
+            .transpose()?;
+        Ok(Page { items, next })
+    }
+
+    async fn promote_escrowed_message(&self, said: &str) -> DbResult<Option<ExchangeMessage>> {
+        let Some(item) = self.get_exchange_item(said, KIND_ESCROW).await? else {
+            return Ok(None);
+        };
+
+        let escrowed: EscrowedMessage = parse_record(&item, "escrowed")?;
+        self.delete_exchange_item(said, KIND_ESCROW).await?;
+
+        Ok(Some(escrowed.message))
+    }
+
+    async fn remove_escrowed_message(&self, said: &str) -> DbResult<()> {
+        self.delete_exchange_item(said, KIND_ESCROW).await
+    }
+}
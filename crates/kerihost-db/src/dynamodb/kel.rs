@@ -1,69 +1,602 @@
 //! KEL storage implementation for DynamoDB
 
+use super::client::{scoped, split_scoped};
+use super::escrows::{decode_cursor, encode_cursor};
+use super::states::state_item;
 use super::DynamoDbDatabase;
 use crate::error::{DbError, DbResult};
-use crate::traits::KelStore;
+use crate::traits::{AnchorLocation, FirstSeen, KelStore, Page};
 use async_trait::async_trait;
-use aws_sdk_dynamodb::types::AttributeValue;
-use kerihost_core::SignedEvent;
+use aws_sdk_dynamodb::error::SdkError;
+use aws_sdk_dynamodb::operation::transact_write_items::TransactWriteItemsError;
+use aws_sdk_dynamodb::primitives::Blob;
+use aws_sdk_dynamodb::types::{
+    AttributeValue, ConditionCheck, Put, PutRequest, TransactWriteItem, WriteRequest,
+};
+use kerihost_core::{KeyState, SignedEvent};
 use std::collections::HashMap;
 
+type Item = HashMap<String, AttributeValue>;
+
 /// Zero-pad sequence number for sort key
 fn sn_to_sk(sn: u64) -> String {
     format!("{:016x}", sn)
 }
 
 /// Parse sequence number from sort key
-#[allow(dead_code)]
-fn sk_to_sn(sk: &str) -> Option<u64> {
+pub(super) fn sk_to_sn(sk: &str) -> Option<u64> {
     u64::from_str_radix(sk, 16).ok()
 }
 
-#[async_trait]
-impl KelStore for DynamoDbDatabase {
-    async fn append_event(&self, event: &SignedEvent) -> DbResult<()> {
-        let prefix = &event.event.prefix;
-        let sn = event.event.sn;
-        let sk = sn_to_sk(sn);
+/// Encode a signed event as its canonical CESR attribute
+pub(super) fn cesr_attr(event: &SignedEvent) -> DbResult<AttributeValue> {
+    let cesr = event
+        .to_cesr()
+        .map_err(|e| DbError::Serialization(e.to_string()))?;
+    Ok(AttributeValue::B(Blob::new(cesr)))
+}
+
+/// Rebuild a signed event from an item's `cesr` attribute
+///
+/// Items written before CESR was stored carry only the JSON form in
+/// `json_field`; those are still readable but have no raw bytes.
+pub(super) fn parse_event(item: &Item, json_field: &str) -> DbResult<SignedEvent> {
+    if let Some(cesr) = item.get("cesr").and_then(|v| v.as_b().ok()) {
+        return SignedEvent::from_cesr(cesr.as_ref())
+            .map_err(|e| DbError::Serialization(e.to_string()));
+    }
+
+    let event_json = item
+        .get(json_field)
+        .and_then(|v| v.as_s().ok())
+        .ok_or_else(|| DbError::Corruption(format!("Missing {} field", json_field)))?;
+    Ok(serde_json::from_str(event_json)?)
+}
+
+/// Sort key for an anchor item: the anchoring event's location
+fn anchor_sk(prefix: &str, sn: u64) -> String {
+    format!("{}#{}", prefix, sn_to_sk(sn))
+}
 
-        // Serialize event
-        let event_json =
-            serde_json::to_string(event).map_err(|e| DbError::Serialization(e.to_string()))?;
+/// Build the anchor index items for an event in a tenant's namespace
+///
+/// Anchors table: PK `digest` (seal digest), SK `location`.
+fn anchor_items(tenant: Option<&str>, event: &SignedEvent) -> DbResult<Vec<Item>> {
+    let anchored = chrono::Utc::now().to_rfc3339();
+    let mut items = Vec::new();
+
+    for location in AnchorLocation::from_event(event) {
+        let seal_json = serde_json::to_string(&location.seal)
+            .map_err(|e| DbError::Serialization(e.to_string()))?;
 
         let mut item = HashMap::new();
-        item.insert("aid".to_string(), AttributeValue::S(prefix.clone()));
-        item.insert("sn".to_string(), AttributeValue::S(sk));
+        item.insert(
+            "digest".to_string(),
+            AttributeValue::S(scoped(tenant, &location.seal.d)),
+        );
+        item.insert(
+            "location".to_string(),
+            AttributeValue::S(anchor_sk(&location.prefix, location.sn)),
+        );
+        item.insert("prefix".to_string(), AttributeValue::S(location.prefix));
+        item.insert("sn".to_string(), AttributeValue::N(location.sn.to_string()));
+        item.insert(
+            "event_digest".to_string(),
+            AttributeValue::S(location.event_digest),
+        );
+        item.insert("seal".to_string(), AttributeValue::S(seal_json));
+        item.insert("anchored".to_string(), AttributeValue::S(anchored.clone()));
+        items.push(item);
+    }
+
+    Ok(items)
+}
+
+/// Parse an anchor index item
+fn parse_anchor(item: &Item) -> DbResult<AnchorLocation> {
+    let field = |name: &str| {
+        item.get(name)
+            .and_then(|v| v.as_s().ok())
+            .ok_or_else(|| DbError::Corruption(format!("Missing {} field", name)))
+    };
+    let sn = item
+        .get("sn")
+        .and_then(|v| v.as_n().ok())
+        .and_then(|n| n.parse().ok())
+        .ok_or_else(|| DbError::Corruption("Missing sn field".to_string()))?;
+
+    Ok(AnchorLocation {
+        prefix: field("prefix")?.clone(),
+        sn,
+        event_digest: field("event_digest")?.clone(),
+        seal: serde_json::from_str(field("seal")?)?,
+    })
+}
+
+/// Build the first-seen log item for an event in a tenant's namespace
+///
+/// First-seen table: PK `aid`, SK `fn` (numeric ordinal).
+fn first_seen_item(tenant: Option<&str>, entry: &FirstSeen) -> Item {
+    let mut item = HashMap::new();
+    item.insert(
+        "aid".to_string(),
+        AttributeValue::S(scoped(tenant, &entry.prefix)),
+    );
+    item.insert("fn".to_string(), AttributeValue::N(entry.ordinal.to_string()));
+    item.insert("sn".to_string(), AttributeValue::N(entry.sn.to_string()));
+    item.insert("digest".to_string(), AttributeValue::S(entry.digest.clone()));
+    item.insert("datetime".to_string(), AttributeValue::S(entry.datetime.clone()));
+    item
+}
+
+/// Partition of the item that finds a first-seen entry by its event digest
+///
+/// Digest items share the first-seen table with `fn` fixed at 0, so each is
+/// read with a single key lookup. Prefixes and digests are Base64 URL-safe,
+/// so the separator cannot occur in either.
+fn first_seen_digest_key(prefix: &str, digest: &str) -> String {
+    format!("{}/{}", prefix, digest)
+}
+
+/// Build the digest item for a first-seen entry in a tenant's namespace
+///
+/// The entry's ordinal is kept in `ordinal`.
+fn first_seen_digest_item(tenant: Option<&str>, entry: &FirstSeen) -> Item {
+    let mut item = first_seen_item(tenant, entry);
+    item.insert(
+        "aid".to_string(),
+        AttributeValue::S(scoped(
+            tenant,
+            &first_seen_digest_key(&entry.prefix, &entry.digest),
+        )),
+    );
+    item.insert("fn".to_string(), AttributeValue::N("0".to_string()));
+    item.insert(
+        "ordinal".to_string(),
+        AttributeValue::N(entry.ordinal.to_string()),
+    );
+    item
+}
+
+/// Parse a first-seen log item
+fn parse_first_seen(item: &Item) -> DbResult<FirstSeen> {
+    let aid = item
+        .get("aid")
+        .and_then(|v| v.as_s().ok())
+        .ok_or_else(|| DbError::Corruption("Missing aid field".to_string()))?;
+    first_seen_fields(item, split_scoped(aid).1, "fn")
+}
+
+/// Parse the fields of a first-seen entry of `prefix`, its ordinal held
+/// in `ordinal_field`
+fn first_seen_fields(item: &Item, prefix: &str, ordinal_field: &str) -> DbResult<FirstSeen> {
+    let field = |name: &str| {
+        item.get(name)
+            .and_then(|v| v.as_s().ok())
+            .cloned()
+            .ok_or_else(|| DbError::Corruption(format!("Missing {} field", name)))
+    };
+    let number = |name: &str| {
+        item.get(name)
+            .and_then(|v| v.as_n().ok())
+            .and_then(|n| n.parse().ok())
+            .ok_or_else(|| DbError::Corruption(format!("Missing {} field", name)))
+    };
+
+    Ok(FirstSeen {
+        prefix: prefix.to_string(),
+        ordinal: number(ordinal_field)?,
+        sn: number("sn")?,
+        digest: field("digest")?,
+        datetime: field("datetime")?,
+    })
+}
+
+/// Most items DynamoDB accepts in one transaction
+const MAX_TRANSACTION_ITEMS: usize = 100;
+
+/// Most items DynamoDB accepts in one batch write
+const MAX_BATCH_ITEMS: usize = 25;
+
+/// Position of the KEL put in an append transaction
+const KEL_WRITE: usize = 0;
+
+/// Position of the first-seen put in an append transaction
+const FEL_WRITE: usize = 1;
+
+/// Position of the prior-event check in a non-inception append transaction,
+/// after the first-seen digest item put
+const PRIOR_CHECK: usize = 3;
+
+/// KEL item attribute counting the seal index items its append transaction
+/// wrote, present until the rest have been written
+const PENDING_ANCHORS: &str = "pending_anchors";
+
+/// Error for the failed conditions of an append transaction, if any
+fn append_conflict(event: &SignedEvent, failed: &[usize]) -> Option<DbError> {
+    if failed.contains(&KEL_WRITE) {
+        Some(duplicate_event(event))
+    } else if failed.contains(&FEL_WRITE) {
+        // Another append for the prefix took the ordinal first
+        Some(DbError::StateConflict(format!(
+            "First-seen ordinal already taken for {}",
+            event.event.prefix
+        )))
+    } else {
+        None
+    }
+}
+
+/// Error for an event whose (aid, sn) is already in the KEL
+fn duplicate_event(event: &SignedEvent) -> DbError {
+    DbError::Duplicate(format!(
+        "Event already exists for {} at sn {}",
+        event.event.prefix, event.event.sn
+    ))
+}
+
+/// Indexes of the transaction items whose condition failed
+fn failed_conditions(err: &SdkError<TransactWriteItemsError>) -> Vec<usize> {
+    match err.as_service_error() {
+        Some(TransactWriteItemsError::TransactionCanceledException(e)) => e
+            .cancellation_reasons()
+            .iter()
+            .enumerate()
+            .filter(|(_, r)| r.code() == Some("ConditionalCheckFailed"))
+            .map(|(i, _)| i)
+            .collect(),
+        _ => vec![],
+    }
+}
+
+impl DynamoDbDatabase {
+    /// Build the writes that append an event: the KEL put, its first-seen
+    /// entry and digest item, a check of the prior event and its seal index
+    ///
+    /// The KEL put comes first and is conditional on (aid, sn) not existing.
+    /// The first-seen put follows, conditional on its ordinal being unused,
+    /// then the item that finds the entry by digest. Non-inception events
+    /// then check that the KEL holds their prior event.
+    /// Seal index puts fill the transaction up to `reserved` items short of
+    /// DynamoDB's limit; those that do not fit are returned, to be written
+    /// once the transaction commits. The KEL item then records how many
+    /// went in the transaction, so a write cut short can be resumed.
+    async fn event_writes(
+        &self,
+        event: &SignedEvent,
+        first_seen_at: &str,
+        reserved: usize,
+    ) -> DbResult<(Vec<TransactWriteItem>, Vec<Item>)> {
+        let mut item = HashMap::new();
+        item.insert("aid".to_string(), AttributeValue::S(self.key(&event.event.prefix)));
+        item.insert("sn".to_string(), AttributeValue::S(sn_to_sk(event.event.sn)));
         item.insert("digest".to_string(), AttributeValue::S(event.event.digest.clone()));
-        item.insert("event".to_string(), AttributeValue::S(event_json));
+        // The original CESR is the canonical record
+        item.insert("cesr".to_string(), cesr_attr(event)?);
 
         if let Some(ref prior) = event.event.prior_digest {
             item.insert("prior_digest".to_string(), AttributeValue::S(prior.clone()));
         }
 
-        // Build conditional expression
-        // For non-inception events, we simply check that this exact (aid, sn) doesn't exist yet
-        // The prior digest validation is handled by the processor before calling append_event
-        let condition_expr = "attribute_not_exists(aid) AND attribute_not_exists(sn)";
+        // KEL, first-seen and digest puts, then the prior check
+        let fixed = 3 + usize::from(event.event.sn > 0);
+        let mut anchors = anchor_items(self.tenant.as_deref(), event)?;
+        let room = MAX_TRANSACTION_ITEMS.saturating_sub(fixed + reserved);
+        let overflow = anchors.split_off(room.min(anchors.len()));
+        if !overflow.is_empty() {
+            item.insert(
+                PENDING_ANCHORS.to_string(),
+                AttributeValue::N(anchors.len().to_string()),
+            );
+        }
 
-        self.client
-            .put_item()
+        let event_put = Put::builder()
             .table_name(&self.config.kel_table)
             .set_item(Some(item))
-            .condition_expression(condition_expr)
-            .send()
-            .await
-            .map_err(|e| {
-                let err_str = e.to_string();
-                if err_str.contains("ConditionalCheckFailed") {
-                    DbError::Duplicate(format!("Event already exists for {} at sn {}", prefix, sn))
-                } else {
-                    DbError::DynamoDb(err_str)
-                }
+            .condition_expression("attribute_not_exists(aid) AND attribute_not_exists(sn)")
+            .build()
+            .map_err(|e| DbError::Other(e.to_string()))?;
+
+        let ordinal = self.next_ordinal(&event.event.prefix).await?;
+        let first_seen = FirstSeen::at(event, ordinal, first_seen_at);
+        let fel_put = Put::builder()
+            .table_name(&self.config.fel_table)
+            .set_item(Some(first_seen_item(self.tenant.as_deref(), &first_seen)))
+            .condition_expression("attribute_not_exists(aid)")
+            .build()
+            .map_err(|e| DbError::Other(e.to_string()))?;
+        let fel_digest_put = Put::builder()
+            .table_name(&self.config.fel_table)
+            .set_item(Some(first_seen_digest_item(
+                self.tenant.as_deref(),
+                &first_seen,
+            )))
+            .build()
+            .map_err(|e| DbError::Other(e.to_string()))?;
+
+        let mut writes = vec![
+            TransactWriteItem::builder().put(event_put).build(),
+            TransactWriteItem::builder().put(fel_put).build(),
+            TransactWriteItem::builder().put(fel_digest_put).build(),
+        ];
+        if let Some(prior_sn) = event.event.sn.checked_sub(1) {
+            let prior = event.event.prior_digest.clone().ok_or_else(|| {
+                DbError::Other("Non-inception event missing prior digest".to_string())
             })?;
+            let check = ConditionCheck::builder()
+                .table_name(&self.config.kel_table)
+                .key("aid", AttributeValue::S(self.key(&event.event.prefix)))
+                .key("sn", AttributeValue::S(sn_to_sk(prior_sn)))
+                .condition_expression("digest = :prior")
+                .expression_attribute_values(":prior", AttributeValue::S(prior))
+                .build()
+                .map_err(|e| DbError::Other(e.to_string()))?;
+            writes.push(TransactWriteItem::builder().condition_check(check).build());
+        }
+        for anchor in anchors {
+            let put = Put::builder()
+                .table_name(&self.config.anchors_table)
+                .set_item(Some(anchor))
+                .build()
+                .map_err(|e| DbError::Other(e.to_string()))?;
+            writes.push(TransactWriteItem::builder().put(put).build());
+        }
+
+        Ok((writes, overflow))
+    }
+
+    /// Write seal index items outside the append transaction
+    ///
+    /// Puts are idempotent, so unprocessed items are simply sent again.
+    async fn put_anchors(&self, anchors: Vec<Item>) -> DbResult<()> {
+        let mut requests = Vec::with_capacity(anchors.len());
+        for anchor in anchors {
+            let put = PutRequest::builder()
+                .set_item(Some(anchor))
+                .build()
+                .map_err(|e| DbError::Other(e.to_string()))?;
+            requests.push(WriteRequest::builder().put_request(put).build());
+        }
 
+        while !requests.is_empty() {
+            let batch: Vec<WriteRequest> = requests
+                .drain(..requests.len().min(MAX_BATCH_ITEMS))
+                .collect();
+            let result = self
+                .client
+                .batch_write_item()
+                .request_items(&self.config.anchors_table, batch)
+                .send()
+                .await?;
+            if let Some(mut unprocessed) = result.unprocessed_items {
+                requests.extend(
+                    unprocessed
+                        .remove(&self.config.anchors_table)
+                        .unwrap_or_default(),
+                );
+            }
+        }
         Ok(())
     }
 
+    /// Write the seal index items an append left out of its transaction,
+    /// then clear the KEL item's pending marker
+    async fn complete_anchors(&self, event: &SignedEvent, overflow: Vec<Item>) -> DbResult<()> {
+        self.put_anchors(overflow).await?;
+        self.client
+            .update_item()
+            .table_name(&self.config.kel_table)
+            .key("aid", AttributeValue::S(self.key(&event.event.prefix)))
+            .key("sn", AttributeValue::S(sn_to_sk(event.event.sn)))
+            .update_expression("REMOVE #pending")
+            .condition_expression("digest = :digest")
+            .expression_attribute_names("#pending", PENDING_ANCHORS)
+            .expression_attribute_values(":digest", AttributeValue::S(event.event.digest.clone()))
+            .send()
+            .await?;
+        Ok(())
+    }
+
+    /// Parse a KEL item, first finishing its seal index if the append that
+    /// stored it stopped before writing the overflow
+    async fn read_event(&self, item: &Item) -> DbResult<SignedEvent> {
+        let event = parse_event(item, "event")?;
+        let written = item.get(PENDING_ANCHORS).and_then(|v| v.as_n().ok());
+        if let Some(written) = written {
+            let written: usize = written
+                .parse()
+                .map_err(|_| DbError::Corruption(format!("Invalid {} field", PENDING_ANCHORS)))?;
+            let mut anchors = anchor_items(self.tenant.as_deref(), &event)?;
+            let overflow = anchors.split_off(written.min(anchors.len()));
+            self.complete_anchors(&event, overflow).await?;
+        }
+        Ok(event)
+    }
+
+    /// Finish an append: write the seal index items that did not fit in its
+    /// transaction, or report the transaction's error
+    ///
+    /// An already stored event may have been committed without the rest of
+    /// its seal index, so those items are rewritten before the duplicate is
+    /// reported. Reading it back also resumes a write its marker records.
+    async fn finish_append(
+        &self,
+        event: &SignedEvent,
+        overflow: Vec<Item>,
+        error: Option<DbError>,
+    ) -> DbResult<()> {
+        let Some(error) = error else {
+            if overflow.is_empty() {
+                return Ok(());
+            }
+            return self.complete_anchors(event, overflow).await;
+        };
+        if matches!(error, DbError::Duplicate(_)) && !overflow.is_empty() {
+            let stored = self.get_event(&event.event.prefix, event.event.sn).await?;
+            if stored.is_some_and(|stored| stored.event.digest == event.event.digest) {
+                self.put_anchors(overflow).await?;
+            }
+        }
+        Err(error)
+    }
+
+    /// Map a cancelled append transaction to the error for its failed
+    /// conditions
+    async fn append_error(
+        &self,
+        event: &SignedEvent,
+        err: SdkError<TransactWriteItemsError>,
+    ) -> DbError {
+        let failed = failed_conditions(&err);
+        if failed.contains(&PRIOR_CHECK) && !failed.contains(&KEL_WRITE) {
+            return self.prior_mismatch(event).await;
+        }
+        append_conflict(event, &failed).unwrap_or_else(|| err.into())
+    }
+
+    /// Error for an event that does not chain onto the KEL's prior event
+    async fn prior_mismatch(&self, event: &SignedEvent) -> DbError {
+        let prior_sn = event.event.sn.saturating_sub(1);
+        match self.get_event(&event.event.prefix, prior_sn).await {
+            Ok(Some(prior)) => DbError::PriorDigestMismatch {
+                expected: prior.event.digest,
+                actual: event.event.prior_digest.clone().unwrap_or_default(),
+            },
+            Ok(None) => DbError::NotFound(format!(
+                "Prior event at sn {} not found for {}",
+                prior_sn, event.event.prefix
+            )),
+            Err(e) => e,
+        }
+    }
+
+    /// First-seen ordinal for the next event appended to a prefix
+    ///
+    /// Read consistently: a stale maximum right after another append would
+    /// fail the first-seen put and cost the caller a retry.
+    async fn next_ordinal(&self, prefix: &str) -> DbResult<u64> {
+        let result = self
+            .client
+            .query()
+            .table_name(&self.config.fel_table)
+            .key_condition_expression("aid = :aid")
+            .expression_attribute_values(":aid", AttributeValue::S(self.key(prefix)))
+            .scan_index_forward(false)
+            .limit(1)
+            .consistent_read(true)
+            .send()
+            .await?;
+
+        match result.items.and_then(|items| items.into_iter().next()) {
+            Some(item) => Ok(parse_first_seen(&item)?.ordinal + 1),
+            None => Ok(0),
+        }
+    }
+
+    /// Get all indexed locations of a seal digest, earliest first
+    async fn query_anchors(&self, digest: &str) -> DbResult<Vec<AnchorLocation>> {
+        let mut items: Vec<Item> = self
+            .client
+            .query()
+            .table_name(&self.config.anchors_table)
+            .key_condition_expression("digest = :digest")
+            .expression_attribute_values(":digest", AttributeValue::S(self.key(digest)))
+            .into_paginator()
+            .items()
+            .send()
+            .try_collect()
+            .await?;
+
+        items.sort_by(|a, b| {
+            let anchored = |item: &Item| {
+                item.get("anchored")
+                    .and_then(|v| v.as_s().ok())
+                    .cloned()
+                    .unwrap_or_default()
+            };
+            anchored(a).cmp(&anchored(b))
+        });
+
+        items.iter().map(parse_anchor).collect()
+    }
+}
+
+#[async_trait]
+impl KelStore for DynamoDbDatabase {
+    async fn append_event(&self, event: &SignedEvent) -> DbResult<()> {
+        let now = chrono::Utc::now().to_rfc3339();
+        let (items, overflow) = self.event_writes(event, &now, 0).await?;
+
+        // Event, first-seen entry and as much of the seal index as fits are
+        // written together
+        let result = self
+            .client
+            .transact_write_items()
+            .set_transact_items(Some(items))
+            .send()
+            .await;
+        let error = match result {
+            Ok(_) => None,
+            Err(e) => Some(self.append_error(event, e).await),
+        };
+
+        self.finish_append(event, overflow, error).await
+    }
+
+    async fn commit_event_seen_at(
+        &self,
+        event: &SignedEvent,
+        new_state: &KeyState,
+        datetime: &str,
+    ) -> DbResult<()> {
+        let (mut items, overflow) = self.event_writes(event, datetime, 1).await?;
+        let state_index = items.len();
+
+        // State rows are versioned by (sn, digest): the stored state must
+        // still be at the prior event, or absent for inception
+        let mut state_put = Put::builder()
+            .table_name(&self.config.states_table)
+            .set_item(Some(state_item(self.tenant.as_deref(), new_state)?));
+        state_put = match event.event.sn.checked_sub(1) {
+            None => state_put.condition_expression("attribute_not_exists(aid)"),
+            Some(expected_sn) => state_put
+                .condition_expression("sn = :expected_sn AND digest = :expected_digest")
+                .expression_attribute_values(
+                    ":expected_sn",
+                    AttributeValue::N(expected_sn.to_string()),
+                )
+                .expression_attribute_values(
+                    ":expected_digest",
+                    AttributeValue::S(event.event.prior_digest.clone().unwrap_or_default()),
+                ),
+        };
+        let state_put = state_put
+            .build()
+            .map_err(|e| DbError::Other(e.to_string()))?;
+        items.push(TransactWriteItem::builder().put(state_put).build());
+
+        let result = self
+            .client
+            .transact_write_items()
+            .set_transact_items(Some(items))
+            .send()
+            .await;
+        let error = match result {
+            Ok(_) => None,
+            // A moved state row means another writer won the race
+            Err(e) if failed_conditions(&e).contains(&state_index) => {
+                return Err(DbError::StateConflict(format!(
+                    "State for {} is no longer at sn {}",
+                    event.event.prefix,
+                    event.event.sn.saturating_sub(1)
+                )));
+            }
+            Err(e) => Some(self.append_error(event, e).await),
+        };
+
+        self.finish_append(event, overflow, error).await
+    }
+
     async fn get_event(&self, prefix: &str, sn: u64) -> DbResult<Option<SignedEvent>> {
         let sk = sn_to_sk(sn);
 
@@ -71,22 +604,13 @@ impl KelStore for DynamoDbDatabase {
             .client
             .get_item()
             .table_name(&self.config.kel_table)
-            .key("aid", AttributeValue::S(prefix.to_string()))
+            .key("aid", AttributeValue::S(self.key(prefix)))
             .key("sn", AttributeValue::S(sk))
             .send()
-            .await
-            .map_err(|e| DbError::DynamoDb(e.to_string()))?;
+            .await?;
 
         match result.item {
-            Some(item) => {
-                let event_json = item
-                    .get("event")
-                    .and_then(|v| v.as_s().ok())
-                    .ok_or_else(|| DbError::Other("Missing event field".to_string()))?;
-
-                let event: SignedEvent = serde_json::from_str(event_json)?;
-                Ok(Some(event))
-            }
+            Some(item) => Ok(Some(self.read_event(&item).await?)),
             None => Ok(None),
         }
     }
@@ -104,7 +628,7 @@ impl KelStore for DynamoDbDatabase {
             .query()
             .table_name(&self.config.kel_table)
             .key_condition_expression("aid = :aid AND sn >= :start_sn")
-            .expression_attribute_values(":aid", AttributeValue::S(prefix.to_string()))
+            .expression_attribute_values(":aid", AttributeValue::S(self.key(prefix)))
             .expression_attribute_values(":start_sn", AttributeValue::S(start_sk));
 
         if let Some(end) = end_sn {
@@ -114,19 +638,12 @@ impl KelStore for DynamoDbDatabase {
                 .expression_attribute_values(":end_sn", AttributeValue::S(end_sk));
         }
 
-        let result = query
-            .send()
-            .await
-            .map_err(|e| DbError::DynamoDb(e.to_string()))?;
+        // Long KELs span several 1 MB query pages
+        let items: Vec<Item> = query.into_paginator().items().send().try_collect().await?;
 
-        let mut events = Vec::new();
-        if let Some(items) = result.items {
-            for item in items {
-                if let Some(event_json) = item.get("event").and_then(|v| v.as_s().ok()) {
-                    let event: SignedEvent = serde_json::from_str(event_json)?;
-                    events.push(event);
-                }
-            }
+        let mut events = Vec::with_capacity(items.len());
+        for item in &items {
+            events.push(self.read_event(item).await?);
         }
 
         // Sort by sn
@@ -135,25 +652,64 @@ impl KelStore for DynamoDbDatabase {
         Ok(events)
     }
 
+    async fn get_prefixes_page(
+        &self,
+        limit: usize,
+        cursor: Option<&str>,
+    ) -> DbResult<Page<String>> {
+        // Every KEL has an inception item at sn 0. The scan limit counts
+        // items read before filtering, so keep scanning until the page
+        // fills or the table ends.
+        let limit = limit.clamp(1, i32::MAX as usize);
+        let (namespace_filter, namespace) = self.namespace_filter("aid");
+        let mut start_key = cursor.map(decode_cursor).transpose()?;
+        let mut items = Vec::new();
+        loop {
+            let result = self
+                .client
+                .scan()
+                .table_name(&self.config.kel_table)
+                .filter_expression(format!("sn = :zero AND {}", namespace_filter))
+                .expression_attribute_values(":zero", AttributeValue::S(sn_to_sk(0)))
+                .expression_attribute_values(":namespace", namespace.clone())
+                .projection_expression("aid")
+                .limit((limit - items.len()) as i32)
+                .set_exclusive_start_key(start_key)
+                .send()
+                .await?;
+
+            for item in result.items.unwrap_or_default() {
+                let aid = item
+                    .get("aid")
+                    .and_then(|v| v.as_s().ok())
+                    .ok_or_else(|| DbError::Corruption("Missing aid field".to_string()))?;
+                items.push(split_scoped(aid).1.to_string());
+            }
+            start_key = result.last_evaluated_key;
+            if items.len() >= limit || start_key.is_none() {
+                break;
+            }
+        }
+
+        let next = start_key.as_ref().map(encode_cursor).transpose()?;
+        Ok(Page { items, next })
+    }
+
     async fn get_latest(&self, prefix: &str) -> DbResult<Option<SignedEvent>> {
         let result = self
             .client
             .query()
             .table_name(&self.config.kel_table)
             .key_condition_expression("aid = :aid")
-            .expression_attribute_values(":aid", AttributeValue::S(prefix.to_string()))
+            .expression_attribute_values(":aid", AttributeValue::S(self.key(prefix)))
             .scan_index_forward(false) // Descending order
             .limit(1)
             .send()
-            .await
-            .map_err(|e| DbError::DynamoDb(e.to_string()))?;
+            .await?;
 
         if let Some(items) = result.items {
             if let Some(item) = items.into_iter().next() {
-                if let Some(event_json) = item.get("event").and_then(|v| v.as_s().ok()) {
-                    let event: SignedEvent = serde_json::from_str(event_json)?;
-                    return Ok(Some(event));
-                }
+                return Ok(Some(self.read_event(&item).await?));
             }
         }
 
@@ -165,36 +721,93 @@ impl KelStore for DynamoDbDatabase {
         prefix: &str,
         digest: &str,
     ) -> DbResult<Option<SignedEvent>> {
-        // Query by prefix and filter by digest
-        // In production, consider a GSI on digest
+        // Reads the prefix's partition until the digest turns up
         let result = self
             .client
             .query()
             .table_name(&self.config.kel_table)
             .key_condition_expression("aid = :aid")
             .filter_expression("digest = :digest")
-            .expression_attribute_values(":aid", AttributeValue::S(prefix.to_string()))
+            .expression_attribute_values(":aid", AttributeValue::S(self.key(prefix)))
             .expression_attribute_values(":digest", AttributeValue::S(digest.to_string()))
+            .into_paginator()
+            .items()
             .send()
-            .await
-            .map_err(|e| DbError::DynamoDb(e.to_string()))?;
+            .try_next()
+            .await?;
 
-        if let Some(items) = result.items {
-            if let Some(item) = items.into_iter().next() {
-                if let Some(event_json) = item.get("event").and_then(|v| v.as_s().ok()) {
-                    let event: SignedEvent = serde_json::from_str(event_json)?;
-                    return Ok(Some(event));
-                }
-            }
+        match result {
+            Some(item) => Ok(Some(self.read_event(&item).await?)),
+            None => Ok(None),
         }
+    }
 
-        Ok(None)
+    async fn get_first_seen(&self, prefix: &str, digest: &str) -> DbResult<Option<FirstSeen>> {
+        let result = self
+            .client
+            .get_item()
+            .table_name(&self.config.fel_table)
+            .key(
+                "aid",
+                AttributeValue::S(self.key(&first_seen_digest_key(prefix, digest))),
+            )
+            .key("fn", AttributeValue::N("0".to_string()))
+            .send()
+            .await?;
+
+        result
+            .item
+            .map(|item| first_seen_fields(&item, prefix, "ordinal"))
+            .transpose()
+    }
+
+    async fn get_first_seen_range(
+        &self,
+        prefix: &str,
+        start_fn: u64,
+        end_fn: Option<u64>,
+    ) -> DbResult<Vec<FirstSeen>> {
+        let end_fn = end_fn.unwrap_or(u64::MAX);
+        let items: Vec<Item> = self
+            .client
+            .query()
+            .table_name(&self.config.fel_table)
+            .key_condition_expression("aid = :aid AND #fn BETWEEN :start_fn AND :end_fn")
+            .expression_attribute_names("#fn", "fn")
+            .expression_attribute_values(":aid", AttributeValue::S(self.key(prefix)))
+            .expression_attribute_values(":start_fn", AttributeValue::N(start_fn.to_string()))
+            .expression_attribute_values(":end_fn", AttributeValue::N(end_fn.to_string()))
+            .into_paginator()
+            .items()
+            .send()
+            .try_collect()
+            .await?;
+
+        items.iter().map(parse_first_seen).collect()
+    }
+
+    async fn find_anchor(&self, digest: &str) -> DbResult<Option<AnchorLocation>> {
+        Ok(self.query_anchors(digest).await?.into_iter().next())
+    }
+
+    async fn find_event_seal(
+        &self,
+        prefix: &str,
+        sn: u64,
+        digest: &str,
+    ) -> DbResult<Option<AnchorLocation>> {
+        Ok(self
+            .query_anchors(digest)
+            .await?
+            .into_iter()
+            .find(|location| location.is_event_seal(prefix, sn)))
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use kerihost_core::{Anchor, EventType, KeyEvent, Threshold};
 
     #[test]
     fn test_sn_to_sk() {
@@ -212,4 +825,117 @@ mod tests {
         assert_eq!(sk_to_sn("ffffffffffffffff"), Some(u64::MAX));
         assert_eq!(sk_to_sn("invalid"), None);
     }
+
+    #[test]
+    fn test_first_seen_item_roundtrip() {
+        let entry = FirstSeen {
+            prefix: "DTest123".to_string(),
+            ordinal: 4,
+            sn: 4,
+            digest: "EDigest".to_string(),
+            datetime: "2024-01-01T00:00:00+00:00".to_string(),
+        };
+
+        let item = first_seen_item(None, &entry);
+        assert_eq!(item.get("fn").unwrap().as_n().unwrap(), "4");
+        assert_eq!(parse_first_seen(&item).unwrap(), entry);
+
+        // The tenant scopes the key but not the entry read back
+        let item = first_seen_item(Some("alpha"), &entry);
+        assert_eq!(item.get("aid").unwrap().as_s().unwrap(), "alpha#DTest123");
+        assert_eq!(parse_first_seen(&item).unwrap(), entry);
+    }
+
+    #[test]
+    fn test_append_conflict() {
+        let event = SignedEvent {
+            event: KeyEvent {
+                prefix: "DTest123".to_string(),
+                sn: 1,
+                event_type: EventType::Ixn,
+                prior_digest: Some("EPrior".to_string()),
+                signing_keys: vec![],
+                signing_threshold: Threshold::simple(1),
+                next_key_digest: None,
+                witness_threshold: Threshold::simple(0),
+                witnesses: vec![],
+                anchors: vec![],
+                witnesses_remove: vec![],
+                witnesses_add: vec![],
+                delegator: None,
+                raw: vec![],
+                digest: "EDigest".to_string(),
+            },
+            signatures: vec![],
+            attachments: Vec::new(),
+        };
+
+        assert!(matches!(
+            append_conflict(&event, &[KEL_WRITE, FEL_WRITE]),
+            Some(DbError::Duplicate(_))
+        ));
+        assert!(matches!(
+            append_conflict(&event, &[FEL_WRITE]),
+            Some(DbError::StateConflict(_))
+        ));
+        assert!(append_conflict(&event, &[]).is_none());
+    }
+
+    #[test]
+    fn test_anchor_item_roundtrip() {
+        let event = SignedEvent {
+            event: KeyEvent {
+                prefix: "EDelegator".to_string(),
+                sn: 2,
+                event_type: EventType::Ixn,
+                prior_digest: Some("EPrior".to_string()),
+                signing_keys: vec![],
+                signing_threshold: Threshold::simple(1),
+                next_key_digest: None,
+                witness_threshold: Threshold::simple(0),
+                witnesses: vec![],
+                anchors: vec![Anchor::event("EDelegate", "0", "EDelegated")],
+                witnesses_remove: vec![],
+                witnesses_add: vec![],
+                delegator: None,
+                raw: vec![],
+                digest: "EAnchoring".to_string(),
+            },
+            signatures: vec![],
+            attachments: Vec::new(),
+        };
+
+        let items = anchor_items(None, &event).unwrap();
+        assert_eq!(items.len(), 1);
+        assert_eq!(
+            items[0].get("location").and_then(|v| v.as_s().ok()).unwrap(),
+            &anchor_sk("EDelegator", 2)
+        );
+
+        let location = parse_anchor(&items[0]).unwrap();
+        assert_eq!(location.event_digest, "EAnchoring");
+        assert!(location.is_event_seal("EDelegate", 0));
+    }
+
+    #[test]
+    fn test_first_seen_digest_item_roundtrip() {
+        let entry = FirstSeen {
+            prefix: "DTest123".to_string(),
+            ordinal: 7,
+            sn: 6,
+            digest: "EDigest".to_string(),
+            datetime: "2024-01-01T00:00:00+00:00".to_string(),
+        };
+
+        // Keyed apart from the log's own partition, in the tenant's namespace
+        let item = first_seen_digest_item(Some("alpha"), &entry);
+        assert_eq!(
+            item.get("aid").and_then(|v| v.as_s().ok()).unwrap(),
+            "alpha#DTest123/EDigest"
+        );
+        assert_eq!(item.get("fn").and_then(|v| v.as_n().ok()).unwrap(), "0");
+
+        let parsed = first_seen_fields(&item, "DTest123", "ordinal").unwrap();
+        assert_eq!(parsed, entry);
+    }
 }
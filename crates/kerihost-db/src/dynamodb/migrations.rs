@@ -0,0 +1,120 @@
+//! Record migrations for DynamoDB
+//!
+//! Each table is scanned page by page. An outdated record is rewritten with
+//! an update conditional on the attribute still holding what was read, so a
+//! concurrent writer always wins and the row is left for the next run.
+
+use super::DynamoDbDatabase;
+use crate::error::DbResult;
+use crate::record::{self, Record};
+use crate::traits::{EscrowedEvent, EscrowedMessage, MigrationReport, MigrationStore};
+use async_trait::async_trait;
+use aws_sdk_dynamodb::types::AttributeValue;
+use kerihost_core::{IpexExchange, KeyState, NontransferableReceipt};
+use std::collections::HashMap;
+
+type Item = HashMap<String, AttributeValue>;
+
+impl DynamoDbDatabase {
+    /// Rewrite the outdated records held in `field` across a table
+    async fn migrate_table<T: Record>(
+        &self,
+        table: &str,
+        key_names: &[&str],
+        field: &str,
+        report: &mut MigrationReport,
+    ) -> DbResult<()> {
+        let mut start_key: Option<Item> = None;
+        loop {
+            let page = self
+                .client
+                .scan()
+                .table_name(table)
+                .set_exclusive_start_key(start_key)
+                .send()
+                .await?;
+
+            for item in page.items.unwrap_or_default() {
+                report.scanned += 1;
+                let Some(old) = item.get(field).and_then(|v| v.as_s().ok()) else {
+                    continue;
+                };
+                if !record::is_outdated::<T>(old)? {
+                    continue;
+                }
+                let new = record::encode(&record::decode::<T>(old)?)?;
+
+                let key = key_names
+                    .iter()
+                    .filter_map(|name| Some((name.to_string(), item.get(*name)?.clone())))
+                    .collect();
+                let result = self
+                    .client
+                    .update_item()
+                    .table_name(table)
+                    .set_key(Some(key))
+                    .update_expression("SET #record = :new")
+                    .condition_expression("#record = :old")
+                    .expression_attribute_names("#record", field)
+                    .expression_attribute_values(":new", AttributeValue::S(new))
+                    .expression_attribute_values(":old", AttributeValue::S(old.clone()))
+                    .send()
+                    .await;
+                match result {
+                    Ok(_) => report.migrated += 1,
+                    Err(e)
+                        if e.as_service_error()
+                            .is_some_and(|e| e.is_conditional_check_failed_exception()) =>
+                    {
+                        report.skipped += 1
+                    }
+                    Err(e) => return Err(e.into()),
+                }
+            }
+
+            start_key = page.last_evaluated_key;
+            if start_key.is_none() {
+                return Ok(());
+            }
+        }
+    }
+}
+
+#[async_trait]
+impl MigrationStore for DynamoDbDatabase {
+    async fn migrate_records(&self) -> DbResult<MigrationReport> {
+        let mut report = MigrationReport::default();
+        self.migrate_table::<KeyState>(&self.config.states_table, &["aid"], "state", &mut report)
+            .await?;
+        self.migrate_table::<NontransferableReceipt>(
+            &self.config.receipts_table,
+            &["event_digest", "witness_aid"],
+            "receipt",
+            &mut report,
+        )
+        .await?;
+        self.migrate_table::<EscrowedEvent>(
+            &self.config.escrows_table,
+            &["aid", "sn_digest"],
+            "escrowed",
+            &mut report,
+        )
+        .await?;
+        // Exchanges and escrowed messages share a table, in separate fields
+        self.migrate_table::<IpexExchange>(
+            &self.config.exchanges_table,
+            &["said", "kind"],
+            "exchange",
+            &mut report,
+        )
+        .await?;
+        self.migrate_table::<EscrowedMessage>(
+            &self.config.exchanges_table,
+            &["said", "kind"],
+            "escrowed",
+            &mut report,
+        )
+        .await?;
+        Ok(report)
+    }
+}
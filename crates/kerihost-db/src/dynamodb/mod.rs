@@ -2,14 +2,21 @@
 //!
 //! This implementation uses AWS DynamoDB for persistent storage.
 //! It follows the KERI-honest design principles with conditional writes
-//! to enforce event ordering.
+//! to enforce event ordering. `DynamoDbChangeFeed` reads committed changes
+//! back from the tables' streams.
 
+mod capacity;
+mod changes;
 mod client;
 mod escrows;
+mod exchanges;
 mod kel;
+mod migrations;
 mod receipts;
+mod schemas;
 mod states;
 
+pub use changes::{DynamoDbChangeFeed, Operation, ShardInfo, StreamRecord, StreamSource};
 pub use client::DynamoDbDatabase;
 
 /// Table names configuration
@@ -17,12 +24,22 @@ pub use client::DynamoDbDatabase;
 pub struct TableConfig {
     /// KEL table name
     pub kel_table: String,
+    /// Anchored seal index table name
+    pub anchors_table: String,
+    /// First-seen log table name
+    pub fel_table: String,
     /// States table name
     pub states_table: String,
     /// Receipts table name
     pub receipts_table: String,
+    /// Receipt conflicts table name
+    pub receipt_conflicts_table: String,
     /// Escrows table name
     pub escrows_table: String,
+    /// Credential schemas table name
+    pub schemas_table: String,
+    /// IPEX exchanges table name
+    pub exchanges_table: String,
 }
 
 impl TableConfig {
@@ -30,12 +47,22 @@ impl TableConfig {
     pub fn from_env() -> Self {
         TableConfig {
             kel_table: std::env::var("KEL_TABLE").unwrap_or_else(|_| "kerihost-kel".to_string()),
+            anchors_table: std::env::var("ANCHORS_TABLE")
+                .unwrap_or_else(|_| "kerihost-anchors".to_string()),
+            fel_table: std::env::var("FEL_TABLE")
+                .unwrap_or_else(|_| "kerihost-first-seen".to_string()),
             states_table: std::env::var("STATES_TABLE")
                 .unwrap_or_else(|_| "kerihost-states".to_string()),
             receipts_table: std::env::var("RECEIPTS_TABLE")
                 .unwrap_or_else(|_| "kerihost-receipts".to_string()),
+            receipt_conflicts_table: std::env::var("RECEIPT_CONFLICTS_TABLE")
+                .unwrap_or_else(|_| "kerihost-receipt-conflicts".to_string()),
             escrows_table: std::env::var("ESCROWS_TABLE")
-                .unwrap_or_else(|_| "kerihost-escrows".to_string()),
+                .unwrap_or_else(|_| "kerihost-escrow-events".to_string()),
+            schemas_table: std::env::var("SCHEMAS_TABLE")
+                .unwrap_or_else(|_| "kerihost-schemas".to_string()),
+            exchanges_table: std::env::var("EXCHANGES_TABLE")
+                .unwrap_or_else(|_| "kerihost-exchanges".to_string()),
         }
     }
 
@@ -43,11 +70,46 @@ impl TableConfig {
     pub fn new(kel: &str, states: &str, receipts: &str, escrows: &str) -> Self {
         TableConfig {
             kel_table: kel.to_string(),
+            anchors_table: "kerihost-anchors".to_string(),
+            fel_table: "kerihost-first-seen".to_string(),
             states_table: states.to_string(),
             receipts_table: receipts.to_string(),
+            receipt_conflicts_table: "kerihost-receipt-conflicts".to_string(),
             escrows_table: escrows.to_string(),
+            schemas_table: "kerihost-schemas".to_string(),
+            exchanges_table: "kerihost-exchanges".to_string(),
         }
     }
+
+    /// Set custom anchors table name
+    pub fn with_anchors_table(mut self, anchors: &str) -> Self {
+        self.anchors_table = anchors.to_string();
+        self
+    }
+
+    /// Set custom first-seen log table name
+    pub fn with_fel_table(mut self, fel: &str) -> Self {
+        self.fel_table = fel.to_string();
+        self
+    }
+
+    /// Set custom receipt conflicts table name
+    pub fn with_receipt_conflicts_table(mut self, receipt_conflicts: &str) -> Self {
+        self.receipt_conflicts_table = receipt_conflicts.to_string();
+        self
+    }
+
+    /// Set custom schemas table name
+    pub fn with_schemas_table(mut self, schemas: &str) -> Self {
+        self.schemas_table = schemas.to_string();
+        self
+    }
+
+    /// Set custom exchanges table name
+    pub fn with_exchanges_table(mut self, exchanges: &str) -> Self {
+        self.exchanges_table = exchanges.to_string();
+        self
+    }
 }
 
 impl Default for TableConfig {
@@ -55,3 +117,460 @@ impl Default for TableConfig {
         Self::from_env()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use aws_sdk_dynamodb::config::{BehaviorVersion, Credentials, Region};
+    use aws_sdk_dynamodb::types::{
+        AttributeDefinition, BillingMode, GlobalSecondaryIndex, KeySchemaElement, KeyType,
+        Projection, ProjectionType, ScalarAttributeType, StreamSpecification, StreamViewType,
+    };
+    use crate::test_support::*;
+    use crate::traits::{
+        Change, ChangeFeed, EscrowReason, EscrowStore, KelStore, MigrationReport,
+        MigrationStore, ReceiptStore, StateStore,
+    };
+    use futures::{StreamExt, TryStreamExt};
+    use aws_sdk_dynamodb::types::AttributeValue;
+    use aws_sdk_dynamodb::Client;
+    use kerihost_core::KeyState;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    /// Endpoint of a DynamoDB-compatible emulator, e.g. DynamoDB Local
+    const TEST_ENDPOINT_VAR: &str = "KERIHOST_TEST_DYNAMODB_ENDPOINT";
+
+    static TABLE_COUNTER: AtomicUsize = AtomicUsize::new(0);
+
+    fn attribute(name: &str, kind: ScalarAttributeType) -> AttributeDefinition {
+        AttributeDefinition::builder()
+            .attribute_name(name)
+            .attribute_type(kind)
+            .build()
+            .unwrap()
+    }
+
+    fn key_schema(hash: &str, range: Option<&str>) -> Vec<KeySchemaElement> {
+        let element = |name: &str, kind| {
+            KeySchemaElement::builder()
+                .attribute_name(name)
+                .key_type(kind)
+                .build()
+                .unwrap()
+        };
+        let mut schema = vec![element(hash, KeyType::Hash)];
+        schema.extend(range.map(|range| element(range, KeyType::Range)));
+        schema
+    }
+
+    fn index(
+        name: &str,
+        hash: &str,
+        range: Option<&str>,
+        projection: ProjectionType,
+    ) -> GlobalSecondaryIndex {
+        GlobalSecondaryIndex::builder()
+            .index_name(name)
+            .set_key_schema(Some(key_schema(hash, range)))
+            .projection(Projection::builder().projection_type(projection).build())
+            .build()
+            .unwrap()
+    }
+
+    async fn create_table(
+        client: &Client,
+        name: &str,
+        attributes: Vec<AttributeDefinition>,
+        keys: Vec<KeySchemaElement>,
+        indexes: Vec<GlobalSecondaryIndex>,
+    ) {
+        client
+            .create_table()
+            .table_name(name)
+            .set_attribute_definitions(Some(attributes))
+            .set_key_schema(Some(keys))
+            .set_global_secondary_indexes((!indexes.is_empty()).then_some(indexes))
+            .billing_mode(BillingMode::PayPerRequest)
+            .stream_specification(
+                StreamSpecification::builder()
+                    .stream_enabled(true)
+                    .stream_view_type(StreamViewType::NewAndOldImages)
+                    .build()
+                    .unwrap(),
+            )
+            .send()
+            .await
+            .unwrap();
+    }
+
+    /// Create fresh tables laid out like the data stack's, or `None` if no
+    /// emulator is configured
+    async fn test_db() -> Option<DynamoDbDatabase> {
+        let endpoint = test_server(TEST_ENDPOINT_VAR)?;
+        let sdk_config = aws_sdk_dynamodb::Config::builder()
+            .behavior_version(BehaviorVersion::latest())
+            .endpoint_url(endpoint)
+            .region(Region::new("us-east-1"))
+            .credentials_provider(Credentials::new("test", "test", None, None, "test"))
+            .build();
+        let client = Client::from_conf(sdk_config);
+
+        let stack = format!(
+            "kerihost-test-{}-{}-{}",
+            std::process::id(),
+            chrono::Utc::now().timestamp_micros(),
+            TABLE_COUNTER.fetch_add(1, Ordering::SeqCst)
+        );
+        let name = |slug: &str| format!("{}-{}", stack, slug);
+        let config = TableConfig::new(
+            &name("kel"),
+            &name("states"),
+            &name("receipts"),
+            &name("escrow-events"),
+        )
+        .with_anchors_table(&name("anchors"))
+        .with_fel_table(&name("first-seen"))
+        .with_receipt_conflicts_table(&name("receipt-conflicts"))
+        .with_exchanges_table(&name("exchanges"));
+
+        use ScalarAttributeType::{N, S};
+        create_table(
+            &client,
+            &config.kel_table,
+            vec![attribute("aid", S), attribute("sn", S)],
+            key_schema("aid", Some("sn")),
+            vec![],
+        )
+        .await;
+        create_table(
+            &client,
+            &config.anchors_table,
+            vec![attribute("digest", S), attribute("location", S)],
+            key_schema("digest", Some("location")),
+            vec![],
+        )
+        .await;
+        create_table(
+            &client,
+            &config.fel_table,
+            vec![attribute("aid", S), attribute("fn", N)],
+            key_schema("aid", Some("fn")),
+            vec![],
+        )
+        .await;
+        create_table(
+            &client,
+            &config.states_table,
+            vec![attribute("aid", S)],
+            key_schema("aid", None),
+            vec![],
+        )
+        .await;
+        create_table(
+            &client,
+            &config.receipts_table,
+            vec![attribute("event_digest", S), attribute("witness_aid", S)],
+            key_schema("event_digest", Some("witness_aid")),
+            vec![],
+        )
+        .await;
+        create_table(
+            &client,
+            &config.receipt_conflicts_table,
+            vec![attribute("witness_aid", S), attribute("conflict", S)],
+            key_schema("witness_aid", Some("conflict")),
+            vec![],
+        )
+        .await;
+        create_table(
+            &client,
+            &config.escrows_table,
+            vec![
+                attribute("aid", S),
+                attribute("sn_digest", S),
+                attribute("digest", S),
+                attribute("reason", S),
+                attribute("ttl", N),
+            ],
+            key_schema("aid", Some("sn_digest")),
+            vec![
+                index(escrows::BY_DIGEST_INDEX, "digest", None, ProjectionType::KeysOnly),
+                index(escrows::BY_REASON_INDEX, "reason", Some("ttl"), ProjectionType::All),
+            ],
+        )
+        .await;
+        create_table(
+            &client,
+            &config.exchanges_table,
+            vec![
+                attribute("said", S),
+                attribute("kind", S),
+                attribute("prior", S),
+            ],
+            key_schema("said", Some("kind")),
+            vec![index(
+                exchanges::BY_PRIOR_INDEX,
+                "prior",
+                Some("said"),
+                ProjectionType::All,
+            )],
+        )
+        .await;
+
+        Some(DynamoDbDatabase::new(client, config))
+    }
+
+    crate::conformance::conformance_tests!(test_db().await);
+
+    #[tokio::test]
+    async fn test_tenant_isolation() {
+        let Some(db) = test_db().await else { return };
+        crate::conformance::tenant_isolation(&db).await;
+    }
+
+    #[tokio::test]
+    async fn test_exchange_store() {
+        let Some(db) = test_db().await else { return };
+        crate::conformance::exchange_store(&db).await;
+    }
+
+    #[tokio::test]
+    async fn test_escrow_attempt_ignores_reescrowed_event() {
+        let Some(db) = test_db().await else { return };
+        let event = create_test_event("DTest1", 5, Some("EP1".to_string()));
+        db.escrow_event(&event, EscrowReason::PartiallySigned)
+            .await
+            .unwrap();
+        let mut stale = db.get_escrowed("DTest1").await.unwrap().remove(0);
+
+        // Re-escrowed for the same reason after the attempt's read
+        tokio::time::sleep(std::time::Duration::from_millis(5)).await;
+        db.escrow_event(&event, EscrowReason::PartiallySigned)
+            .await
+            .unwrap();
+
+        stale.record_attempt(&crate::traits::EscrowPolicy::default());
+        db.record_escrow_attempt(&stale).await.unwrap();
+        let stored = db.get_escrowed("DTest1").await.unwrap().remove(0);
+        assert_eq!(stored.attempts, 0);
+        assert!(stored.is_due());
+    }
+
+    #[tokio::test]
+    async fn test_read_resumes_pending_anchors() {
+        let Some(db) = test_db().await else { return };
+        let icp = create_test_event("DTest1", 0, None);
+        db.append_event(&icp).await.unwrap();
+        let seals = (0..150)
+            .map(|i| kerihost_core::Anchor::digest(&format!("ESeal{:03}", i)))
+            .collect();
+        let ixn = create_anchoring_event("DTest1", 1, Some(icp.event.digest.clone()), seals);
+        db.append_event(&ixn).await.unwrap();
+        let pending = || async {
+            db.client
+                .get_item()
+                .table_name(&db.config.kel_table)
+                .key("aid", AttributeValue::S("DTest1".to_string()))
+                .key("sn", AttributeValue::S("0000000000000001".to_string()))
+                .send()
+                .await
+                .unwrap()
+                .item
+                .unwrap()
+                .contains_key("pending_anchors")
+        };
+        assert!(!pending().await);
+
+        // Stopped after the transaction, before the overflow was written
+        db.client
+            .delete_item()
+            .table_name(&db.config.anchors_table)
+            .key("digest", AttributeValue::S("ESeal149".to_string()))
+            .key(
+                "location",
+                AttributeValue::S("DTest1#0000000000000001".to_string()),
+            )
+            .send()
+            .await
+            .unwrap();
+        db.client
+            .update_item()
+            .table_name(&db.config.kel_table)
+            .key("aid", AttributeValue::S("DTest1".to_string()))
+            .key("sn", AttributeValue::S("0000000000000001".to_string()))
+            .update_expression("SET pending_anchors = :written")
+            .expression_attribute_values(":written", AttributeValue::N("96".to_string()))
+            .send()
+            .await
+            .unwrap();
+        assert!(db.find_anchor("ESeal149").await.unwrap().is_none());
+
+        db.get_event("DTest1", 1).await.unwrap().unwrap();
+        let location = db.find_anchor("ESeal149").await.unwrap().unwrap();
+        assert_eq!(location.event_digest, ixn.event.digest);
+        assert!(!pending().await);
+    }
+
+    #[tokio::test]
+    async fn test_consumed_capacity_is_reported() {
+        let Some(db) = test_db().await else { return };
+        let metrics = std::sync::Arc::new(crate::metrics::DbMetrics::new());
+        let db = crate::instrument::InstrumentedDatabase::new(db)
+            .with_metrics(std::sync::Arc::clone(&metrics));
+
+        let icp = create_test_event("DCapacity", 0, None);
+        let state = create_test_state("DCapacity", 0);
+        db.commit_event(&icp, &state).await.unwrap();
+        db.get_state("DCapacity").await.unwrap();
+
+        let commit = metrics.operation("commit_event_seen_at").unwrap();
+        assert!(commit.consumed_capacity > 0.0);
+        assert!(metrics.operation("get_state").unwrap().consumed_capacity > 0.0);
+    }
+
+    /// Overwrite one attribute of an item, bypassing the store
+    async fn set_attribute(
+        db: &DynamoDbDatabase,
+        table: &str,
+        key: &[(&str, &str)],
+        field: &str,
+        value: String,
+    ) {
+        db.client
+            .update_item()
+            .table_name(table)
+            .set_key(Some(
+                key.iter()
+                    .map(|(name, v)| (name.to_string(), AttributeValue::S(v.to_string())))
+                    .collect(),
+            ))
+            .update_expression("SET #field = :value")
+            .expression_attribute_names("#field", field)
+            .expression_attribute_values(":value", AttributeValue::S(value))
+            .send()
+            .await
+            .unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_migrate_records() {
+        let Some(db) = test_db().await else { return };
+        let state = create_test_state("DTest123", 2);
+        let receipt = create_test_receipt("EDigest123", "BWitness1");
+        let event = create_test_event("DTest123", 5, Some("EPrior".to_string()));
+        db.put_state(&state).await.unwrap();
+        db.add_receipt(&receipt).await.unwrap();
+        db.escrow_event(&event, EscrowReason::OutOfOrder)
+            .await
+            .unwrap();
+
+        // Rewind every row to the bare JSON written before records were versioned
+        let escrowed = db.get_all_escrowed().await.unwrap().remove(0);
+        let config = db.config.clone();
+        set_attribute(
+            &db,
+            &config.states_table,
+            &[("aid", "DTest123")],
+            "state",
+            serde_json::to_string(&state).unwrap(),
+        )
+        .await;
+        set_attribute(
+            &db,
+            &config.receipts_table,
+            &[("event_digest", "EDigest123"), ("witness_aid", "BWitness1")],
+            "receipt",
+            serde_json::to_string(&receipt).unwrap(),
+        )
+        .await;
+        let sn_digest = format!("{:016x}#{}", 5, event.event.digest);
+        set_attribute(
+            &db,
+            &config.escrows_table,
+            &[("aid", "DTest123"), ("sn_digest", &sn_digest)],
+            "escrowed",
+            serde_json::to_string(&escrowed).unwrap(),
+        )
+        .await;
+        assert_eq!(db.get_state("DTest123").await.unwrap().unwrap().sn, 2);
+
+        let report = db.migrate_records().await.unwrap();
+        assert_eq!(
+            report,
+            MigrationReport {
+                scanned: 3,
+                migrated: 3,
+                skipped: 0,
+            }
+        );
+
+        let item = db
+            .client
+            .get_item()
+            .table_name(&config.states_table)
+            .key("aid", AttributeValue::S("DTest123".to_string()))
+            .send()
+            .await
+            .unwrap()
+            .item
+            .unwrap();
+        let stored = item["state"].as_s().unwrap();
+        assert!(!crate::record::is_outdated::<KeyState>(stored).unwrap());
+
+        assert_eq!(db.get_state("DTest123").await.unwrap().unwrap().sn, 2);
+        let stored = db.get_receipt("EDigest123", "BWitness1").await.unwrap().unwrap();
+        assert_eq!(stored.signature, receipt.signature);
+        let escrows = db.get_all_escrowed().await.unwrap();
+        assert_eq!(escrows[0].event.event.digest, event.event.digest);
+
+        assert_eq!(db.migrate_records().await.unwrap().migrated, 0);
+    }
+
+    #[tokio::test]
+    async fn test_change_feed_from_streams() {
+        let Some(db) = test_db().await else { return };
+        let streams_config = aws_sdk_dynamodbstreams::Config::builder()
+            .behavior_version(BehaviorVersion::latest())
+            .endpoint_url(std::env::var(TEST_ENDPOINT_VAR).unwrap())
+            .region(Region::new("us-east-1"))
+            .credentials_provider(Credentials::new("test", "test", None, None, "test"))
+            .build();
+        let feed = DynamoDbChangeFeed::new(
+            aws_sdk_dynamodbstreams::Client::from_conf(streams_config),
+            db.config.clone(),
+        )
+        .with_poll_interval(std::time::Duration::from_millis(20));
+        let changes = feed.subscribe(Some("DTest123")).await.unwrap();
+
+        let icp = create_test_event("DTest123", 0, None);
+        let ixn = create_test_event("DTest123", 1, Some(icp.event.digest.clone()));
+        let receipt = create_test_receipt(&icp.event.digest, "BWitness1");
+        db.append_event(&icp).await.unwrap();
+        db.append_event(&create_test_event("DOther", 0, None))
+            .await
+            .unwrap();
+        db.add_receipt(&receipt).await.unwrap();
+        db.escrow_event(&ixn, EscrowReason::MissingReceipts)
+            .await
+            .unwrap();
+        db.remove_escrowed(&ixn.event.digest).await.unwrap();
+
+        // Each table's changes are in order, but tables interleave freely
+        let received: Vec<Change> = tokio::time::timeout(
+            std::time::Duration::from_secs(10),
+            changes.take(4).try_collect(),
+        )
+        .await
+        .unwrap()
+        .unwrap();
+        let expected = [
+            Change::accepted(&icp),
+            Change::from(&receipt),
+            Change::escrowed(&ixn, EscrowReason::MissingReceipts),
+            Change::escrow_removed(&ixn),
+        ];
+        assert!(expected.iter().all(|change| received.contains(change)));
+        let position = |change: &Change| received.iter().position(|c| c == change);
+        assert!(position(&expected[2]) < position(&expected[3]));
+    }
+}
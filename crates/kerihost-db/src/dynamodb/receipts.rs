@@ -2,22 +2,23 @@
 
 use super::DynamoDbDatabase;
 use crate::error::{DbError, DbResult};
-use crate::traits::ReceiptStore;
+use crate::record;
+use crate::traits::{ReceiptConflict, ReceiptStore};
 use async_trait::async_trait;
-use aws_sdk_dynamodb::types::AttributeValue;
+use aws_sdk_dynamodb::operation::put_item::PutItemError;
+use aws_sdk_dynamodb::types::{AttributeValue, ReturnValuesOnConditionCheckFailure};
 use kerihost_core::NontransferableReceipt;
 use std::collections::HashMap;
 
 #[async_trait]
 impl ReceiptStore for DynamoDbDatabase {
     async fn add_receipt(&self, receipt: &NontransferableReceipt) -> DbResult<()> {
-        let receipt_json =
-            serde_json::to_string(receipt).map_err(|e| DbError::Serialization(e.to_string()))?;
+        let receipt_json = record::encode(receipt)?;
 
         let mut item = HashMap::new();
         item.insert(
             "event_digest".to_string(),
-            AttributeValue::S(receipt.event_digest.clone()),
+            AttributeValue::S(self.key(&receipt.event_digest)),
         );
         item.insert(
             "witness_aid".to_string(),
@@ -37,38 +38,63 @@ impl ReceiptStore for DynamoDbDatabase {
             AttributeValue::S(receipt.event_prefix.clone()),
         );
 
-        self.client
+        // Only the witness's first receipt, or the same one again, is written
+        let result = self
+            .client
             .put_item()
             .table_name(&self.config.receipts_table)
             .set_item(Some(item))
+            .condition_expression("attribute_not_exists(event_digest) OR signature = :signature")
+            .expression_attribute_values(":signature", AttributeValue::S(receipt.signature.clone()))
+            .return_values_on_condition_check_failure(ReturnValuesOnConditionCheckFailure::AllOld)
             .send()
-            .await
-            .map_err(|e| DbError::DynamoDb(e.to_string()))?;
+            .await;
+
+        let error = match result {
+            Ok(_) => return Ok(()),
+            Err(e) => e,
+        };
+        let Some(PutItemError::ConditionalCheckFailedException(failed)) = error.as_service_error()
+        else {
+            return Err(error.into());
+        };
+        let stored_json = failed
+            .item()
+            .and_then(|item| item.get("receipt"))
+            .and_then(|v| v.as_s().ok())
+            .ok_or_else(|| DbError::Corruption("Missing receipt field".to_string()))?;
+        let stored: NontransferableReceipt = record::decode(stored_json)?;
 
-        Ok(())
+        match ReceiptConflict::between(&stored, receipt) {
+            Some(conflict) => {
+                self.put_receipt_conflict(&conflict).await?;
+                Err(DbError::from(&conflict))
+            }
+            None => Ok(()),
+        }
     }
 
     async fn get_receipts(&self, event_digest: &str) -> DbResult<Vec<NontransferableReceipt>> {
-        let result = self
+        let items: Vec<_> = self
             .client
             .query()
             .table_name(&self.config.receipts_table)
             .key_condition_expression("event_digest = :digest")
             .expression_attribute_values(
                 ":digest",
-                AttributeValue::S(event_digest.to_string()),
+                AttributeValue::S(self.key(event_digest)),
             )
+            .into_paginator()
+            .items()
             .send()
-            .await
-            .map_err(|e| DbError::DynamoDb(e.to_string()))?;
+            .try_collect()
+            .await?;
 
         let mut receipts = Vec::new();
-        if let Some(items) = result.items {
-            for item in items {
-                if let Some(receipt_json) = item.get("receipt").and_then(|v| v.as_s().ok()) {
-                    let receipt: NontransferableReceipt = serde_json::from_str(receipt_json)?;
-                    receipts.push(receipt);
-                }
+        for item in items {
+            if let Some(receipt_json) = item.get("receipt").and_then(|v| v.as_s().ok()) {
+                let receipt: NontransferableReceipt = record::decode(receipt_json)?;
+                receipts.push(receipt);
             }
         }
 
@@ -84,20 +110,19 @@ impl ReceiptStore for DynamoDbDatabase {
             .client
             .get_item()
             .table_name(&self.config.receipts_table)
-            .key("event_digest", AttributeValue::S(event_digest.to_string()))
+            .key("event_digest", AttributeValue::S(self.key(event_digest)))
             .key("witness_aid", AttributeValue::S(witness_prefix.to_string()))
             .send()
-            .await
-            .map_err(|e| DbError::DynamoDb(e.to_string()))?;
+            .await?;
 
         match result.item {
             Some(item) => {
                 let receipt_json = item
                     .get("receipt")
                     .and_then(|v| v.as_s().ok())
-                    .ok_or_else(|| DbError::Other("Missing receipt field".to_string()))?;
+                    .ok_or_else(|| DbError::Corruption("Missing receipt field".to_string()))?;
 
-                let receipt: NontransferableReceipt = serde_json::from_str(receipt_json)?;
+                let receipt: NontransferableReceipt = record::decode(receipt_json)?;
                 Ok(Some(receipt))
             }
             None => Ok(None),
@@ -105,20 +130,82 @@ impl ReceiptStore for DynamoDbDatabase {
     }
 
     async fn count_receipts(&self, event_digest: &str) -> DbResult<usize> {
-        let result = self
+        let pages: Vec<_> = self
             .client
             .query()
             .table_name(&self.config.receipts_table)
             .key_condition_expression("event_digest = :digest")
             .expression_attribute_values(
                 ":digest",
-                AttributeValue::S(event_digest.to_string()),
+                AttributeValue::S(self.key(event_digest)),
             )
             .select(aws_sdk_dynamodb::types::Select::Count)
+            .into_paginator()
+            .send()
+            .try_collect()
+            .await?;
+
+        // Each page counts only the items it evaluated
+        Ok(pages.iter().map(|page| page.count as usize).sum())
+    }
+
+    async fn get_receipt_conflicts(&self, witness_prefix: &str) -> DbResult<Vec<ReceiptConflict>> {
+        let items: Vec<_> = self
+            .client
+            .query()
+            .table_name(&self.config.receipt_conflicts_table)
+            .key_condition_expression("witness_aid = :witness")
+            .expression_attribute_values(":witness", AttributeValue::S(self.key(witness_prefix)))
+            .into_paginator()
+            .items()
+            .send()
+            .try_collect()
+            .await?;
+
+        items
+            .iter()
+            .filter_map(|item| item.get("record").and_then(|v| v.as_s().ok()))
+            .map(|json| record::decode(json))
+            .collect()
+    }
+}
+
+impl DynamoDbDatabase {
+    /// Record a receipt conflict, keeping an earlier record of the same one
+    async fn put_receipt_conflict(&self, conflict: &ReceiptConflict) -> DbResult<()> {
+        let mut item = HashMap::new();
+        item.insert(
+            "witness_aid".to_string(),
+            AttributeValue::S(self.key(&conflict.witness_prefix)),
+        );
+        item.insert("conflict".to_string(), AttributeValue::S(conflict.key()));
+        item.insert(
+            "record".to_string(),
+            AttributeValue::S(record::encode(conflict)?),
+        );
+        item.insert(
+            "event_digest".to_string(),
+            AttributeValue::S(conflict.event_digest.clone()),
+        );
+
+        let result = self
+            .client
+            .put_item()
+            .table_name(&self.config.receipt_conflicts_table)
+            .set_item(Some(item))
+            .condition_expression("attribute_not_exists(witness_aid)")
             .send()
-            .await
-            .map_err(|e| DbError::DynamoDb(e.to_string()))?;
+            .await;
 
-        Ok(result.count as usize)
+        match result {
+            Ok(_) => Ok(()),
+            Err(e)
+                if e.as_service_error()
+                    .is_some_and(|e| e.is_conditional_check_failed_exception()) =>
+            {
+                Ok(())
+            }
+            Err(e) => Err(e.into()),
+        }
     }
 }
@@ -0,0 +1,68 @@
+//! Credential schema storage implementation for DynamoDB
+
+use super::DynamoDbDatabase;
+use crate::error::{DbError, DbResult};
+use crate::traits::SchemaStore;
+use async_trait::async_trait;
+use aws_sdk_dynamodb::types::AttributeValue;
+use kerihost_core::CredentialSchema;
+use std::collections::HashMap;
+
+#[async_trait]
+impl SchemaStore for DynamoDbDatabase {
+    async fn put_schema(&self, schema: &CredentialSchema) -> DbResult<()> {
+        let schema_json = schema
+            .to_json()
+            .map_err(|e| DbError::Serialization(e.to_string()))?;
+
+        let mut item = HashMap::new();
+        item.insert(
+            "said".to_string(),
+            AttributeValue::S(self.key(&schema.said)),
+        );
+        item.insert("schema".to_string(), AttributeValue::S(schema_json));
+        if let Some(ref title) = schema.title {
+            item.insert("title".to_string(), AttributeValue::S(title.clone()));
+        }
+
+        // Content-addressed: an existing item is byte-for-byte the same schema
+        let result = self
+            .client
+            .put_item()
+            .table_name(&self.config.schemas_table)
+            .set_item(Some(item))
+            .condition_expression("attribute_not_exists(said)")
+            .send()
+            .await;
+
+        match result.map_err(DbError::from) {
+            Ok(_) | Err(DbError::ConditionFailed(_)) => Ok(()),
+            Err(e) => Err(e),
+        }
+    }
+
+    async fn get_schema(&self, said: &str) -> DbResult<Option<CredentialSchema>> {
+        let result = self
+            .client
+            .get_item()
+            .table_name(&self.config.schemas_table)
+            .key("said", AttributeValue::S(self.key(said)))
+            .send()
+            .await?;
+
+        match result.item {
+            Some(item) => {
+                let schema_json = item
+                    .get("schema")
+                    .and_then(|v| v.as_s().ok())
+                    .ok_or_else(|| DbError::Corruption("Missing schema field".to_string()))?;
+
+                // Re-verify on read so a tampered item is never served
+                let schema = CredentialSchema::from_json(schema_json.as_bytes())
+                    .map_err(|e| DbError::Serialization(e.to_string()))?;
+                Ok(Some(schema))
+            }
+            None => Ok(None),
+        }
+    }
+}
@@ -1,13 +1,35 @@
 //! State storage implementation for DynamoDB
 
+use super::client::scoped;
 use super::DynamoDbDatabase;
 use crate::error::{DbError, DbResult};
+use crate::record;
 use crate::traits::StateStore;
 use async_trait::async_trait;
 use aws_sdk_dynamodb::types::AttributeValue;
 use kerihost_core::KeyState;
 use std::collections::HashMap;
 
+type Item = HashMap<String, AttributeValue>;
+
+/// Build the state item for a key state in a tenant's namespace
+pub(super) fn state_item(tenant: Option<&str>, state: &KeyState) -> DbResult<Item> {
+    let state_json = record::encode(state)?;
+
+    let mut item = HashMap::new();
+    item.insert(
+        "aid".to_string(),
+        AttributeValue::S(scoped(tenant, &state.prefix)),
+    );
+    item.insert("state".to_string(), AttributeValue::S(state_json));
+    item.insert("sn".to_string(), AttributeValue::N(state.sn.to_string()));
+    item.insert(
+        "digest".to_string(),
+        AttributeValue::S(state.latest_digest.clone()),
+    );
+    Ok(item)
+}
+
 #[async_trait]
 impl StateStore for DynamoDbDatabase {
     async fn get_state(&self, prefix: &str) -> DbResult<Option<KeyState>> {
@@ -15,19 +37,18 @@ impl StateStore for DynamoDbDatabase {
             .client
             .get_item()
             .table_name(&self.config.states_table)
-            .key("aid", AttributeValue::S(prefix.to_string()))
+            .key("aid", AttributeValue::S(self.key(prefix)))
             .send()
-            .await
-            .map_err(|e| DbError::DynamoDb(e.to_string()))?;
+            .await?;
 
         match result.item {
             Some(item) => {
                 let state_json = item
                     .get("state")
                     .and_then(|v| v.as_s().ok())
-                    .ok_or_else(|| DbError::Other("Missing state field".to_string()))?;
+                    .ok_or_else(|| DbError::Corruption("Missing state field".to_string()))?;
 
-                let state: KeyState = serde_json::from_str(state_json)?;
+                let state: KeyState = record::decode(state_json)?;
                 Ok(Some(state))
             }
             None => Ok(None),
@@ -35,25 +56,12 @@ impl StateStore for DynamoDbDatabase {
     }
 
     async fn put_state(&self, state: &KeyState) -> DbResult<()> {
-        let state_json =
-            serde_json::to_string(state).map_err(|e| DbError::Serialization(e.to_string()))?;
-
-        let mut item = HashMap::new();
-        item.insert("aid".to_string(), AttributeValue::S(state.prefix.clone()));
-        item.insert("state".to_string(), AttributeValue::S(state_json));
-        item.insert("sn".to_string(), AttributeValue::N(state.sn.to_string()));
-        item.insert(
-            "digest".to_string(),
-            AttributeValue::S(state.latest_digest.clone()),
-        );
-
         self.client
             .put_item()
             .table_name(&self.config.states_table)
-            .set_item(Some(item))
+            .set_item(Some(state_item(self.tenant.as_deref(), state)?))
             .send()
-            .await
-            .map_err(|e| DbError::DynamoDb(e.to_string()))?;
+            .await?;
 
         Ok(())
     }
@@ -62,10 +70,9 @@ impl StateStore for DynamoDbDatabase {
         self.client
             .delete_item()
             .table_name(&self.config.states_table)
-            .key("aid", AttributeValue::S(prefix.to_string()))
+            .key("aid", AttributeValue::S(self.key(prefix)))
             .send()
-            .await
-            .map_err(|e| DbError::DynamoDb(e.to_string()))?;
+            .await?;
 
         Ok(())
     }
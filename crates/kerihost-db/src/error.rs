@@ -1,5 +1,6 @@
 //! Database error types
 
+use aws_sdk_dynamodb::error::{DisplayErrorContext, ProvideErrorMetadata, SdkError};
 use thiserror::Error;
 
 /// Database errors
@@ -21,6 +22,42 @@ pub enum DbError {
     #[error("Duplicate: {0}")]
     Duplicate(String),
 
+    /// Witness receipt differs from the one already stored for the event
+    #[error("Conflicting receipt from {witness} for {digest}")]
+    ConflictingReceipt { witness: String, digest: String },
+
+    /// Key state changed since it was read (conditional state write failed)
+    #[error("State conflict: {0}")]
+    StateConflict(String),
+
+    /// Continuation token was not issued by this backend
+    #[error("Invalid cursor: {0}")]
+    InvalidCursor(String),
+
+    /// Tenant name is not a valid namespace
+    #[error("Invalid tenant: {0}")]
+    InvalidTenant(String),
+
+    /// Conditional write failed for a reason no other variant describes
+    #[error("Condition failed: {0}")]
+    ConditionFailed(String),
+
+    /// Backend is rejecting requests over its capacity
+    #[error("Throttled: {0}")]
+    Throttled(String),
+
+    /// Network or service failure that may pass on its own
+    #[error("Transient error: {0}")]
+    Transient(String),
+
+    /// Stored data is missing fields or cannot be decoded
+    #[error("Corrupt record: {0}")]
+    Corruption(String),
+
+    /// Change subscriber fell behind and missed changes
+    #[error("Change feed lagged: {0} changes missed")]
+    Lagged(u64),
+
     /// Serialization error
     #[error("Serialization error: {0}")]
     Serialization(String),
@@ -29,16 +66,221 @@ pub enum DbError {
     #[error("DynamoDB error: {0}")]
     DynamoDb(String),
 
+    /// SQL backend error
+    #[error("SQL error: {0}")]
+    Sql(String),
+
+    /// Embedded storage error
+    #[error("Storage error: {0}")]
+    Storage(String),
+
     /// Generic error
     #[error("Database error: {0}")]
     Other(String),
 }
 
+impl DbError {
+    /// Check whether the operation may succeed if retried
+    ///
+    /// A `StateConflict` can be retried at once after re-reading; transient
+    /// failures only after backing off (see [`DbError::is_transient`]).
+    pub fn is_retryable(&self) -> bool {
+        matches!(self, DbError::StateConflict(_)) || self.is_transient()
+    }
+
+    /// Check whether the backend failed for a reason that may pass, so a
+    /// retry should wait first
+    pub fn is_transient(&self) -> bool {
+        matches!(self, DbError::Throttled(_) | DbError::Transient(_))
+    }
+
+    /// Short, stable name of the kind of failure, for metrics and logs
+    pub fn category(&self) -> &'static str {
+        match self {
+            DbError::Connection(_) => "connection",
+            DbError::NotFound(_) => "not_found",
+            DbError::PriorDigestMismatch { .. }
+            | DbError::Duplicate(_)
+            | DbError::ConflictingReceipt { .. }
+            | DbError::StateConflict(_)
+            | DbError::ConditionFailed(_) => "conflict",
+            DbError::Throttled(_) => "throttled",
+            DbError::Transient(_) => "transient",
+            DbError::Corruption(_) => "corruption",
+            DbError::InvalidCursor(_) | DbError::InvalidTenant(_) => "invalid_request",
+            DbError::Lagged(_) => "lagged",
+            DbError::Serialization(_) => "serialization",
+            DbError::DynamoDb(_) | DbError::Sql(_) | DbError::Storage(_) => "backend",
+            DbError::Other(_) => "other",
+        }
+    }
+}
+
 impl From<serde_json::Error> for DbError {
     fn from(err: serde_json::Error) -> Self {
         DbError::Serialization(err.to_string())
     }
 }
 
+impl From<aws_sdk_dynamodb::Error> for DbError {
+    fn from(err: aws_sdk_dynamodb::Error) -> Self {
+        use aws_sdk_dynamodb::Error as E;
+        let message = DisplayErrorContext(&err).to_string();
+        match &err {
+            E::ConditionalCheckFailedException(_) => DbError::ConditionFailed(message),
+            E::ProvisionedThroughputExceededException(_)
+            | E::RequestLimitExceeded(_)
+            | E::ThrottlingException(_) => DbError::Throttled(message),
+            E::ResourceNotFoundException(_) | E::TableNotFoundException(_) => {
+                DbError::NotFound(message)
+            }
+            E::InternalServerError(_)
+            | E::TransactionConflictException(_)
+            | E::TransactionInProgressException(_)
+            | E::ReplicatedWriteConflictException(_) => DbError::Transient(message),
+            // Classified by why its items were cancelled
+            E::TransactionCanceledException(e) => {
+                let reasons = e.cancellation_reasons();
+                let any = |codes: &[&str]| {
+                    reasons
+                        .iter()
+                        .any(|r| r.code().is_some_and(|code| codes.contains(&code)))
+                };
+                if any(&["ConditionalCheckFailed"]) {
+                    DbError::ConditionFailed(message)
+                } else if any(&["ThrottlingError", "ProvisionedThroughputExceeded"]) {
+                    DbError::Throttled(message)
+                } else if any(&["TransactionConflict"]) {
+                    DbError::Transient(message)
+                } else {
+                    DbError::DynamoDb(message)
+                }
+            }
+            // Unmodelled server-side failures
+            _ if matches!(err.code(), Some("ServiceUnavailable" | "InternalFailure")) => {
+                DbError::Transient(message)
+            }
+            _ => DbError::DynamoDb(message),
+        }
+    }
+}
+
+impl<E, R> From<SdkError<E, R>> for DbError
+where
+    E: std::error::Error + Send + Sync + 'static,
+    R: std::fmt::Debug,
+    aws_sdk_dynamodb::Error: From<E>,
+{
+    fn from(err: SdkError<E, R>) -> Self {
+        match err {
+            SdkError::ServiceError(context) => {
+                aws_sdk_dynamodb::Error::from(context.into_err()).into()
+            }
+            // The request never got a response
+            SdkError::TimeoutError(_)
+            | SdkError::DispatchFailure(_)
+            | SdkError::ResponseError(_) => {
+                DbError::Transient(DisplayErrorContext(&err).to_string())
+            }
+            _ => DbError::DynamoDb(DisplayErrorContext(&err).to_string()),
+        }
+    }
+}
+
+#[cfg(any(feature = "sqlite", feature = "postgres"))]
+impl From<sqlx::Error> for DbError {
+    fn from(err: sqlx::Error) -> Self {
+        match err {
+            sqlx::Error::Database(e) if e.is_unique_violation() => {
+                DbError::Duplicate(e.message().to_string())
+            }
+            // Serialization failure or deadlock: the transaction lost a race
+            sqlx::Error::Database(e)
+                if matches!(e.code().as_deref(), Some("40001") | Some("40P01")) =>
+            {
+                DbError::StateConflict(e.message().to_string())
+            }
+            // Too many connections
+            sqlx::Error::Database(e) if e.code().as_deref() == Some("53300") => {
+                DbError::Throttled(e.message().to_string())
+            }
+            // SQLite busy or locked, or a PostgreSQL connection failure or
+            // server starting up or shutting down
+            sqlx::Error::Database(e)
+                if e.code().is_some_and(|code| {
+                    matches!(code.as_ref(), "5" | "6")
+                        || code.starts_with("08")
+                        || code.starts_with("57P")
+                }) =>
+            {
+                DbError::Transient(e.message().to_string())
+            }
+            sqlx::Error::PoolTimedOut | sqlx::Error::Io(_) => DbError::Transient(err.to_string()),
+            sqlx::Error::PoolClosed => DbError::Connection(err.to_string()),
+            err => DbError::Sql(err.to_string()),
+        }
+    }
+}
+
 /// Result type for database operations
 pub type DbResult<T> = Result<T, DbError>;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use aws_sdk_dynamodb::types::error::{
+        ConditionalCheckFailedException, ProvisionedThroughputExceededException,
+        TransactionCanceledException,
+    };
+    use aws_sdk_dynamodb::types::CancellationReason;
+
+    fn cancelled(codes: &[&str]) -> DbError {
+        let reasons = codes
+            .iter()
+            .map(|code| CancellationReason::builder().code(*code).build())
+            .collect();
+        let err = TransactionCanceledException::builder()
+            .set_cancellation_reasons(Some(reasons))
+            .build();
+        aws_sdk_dynamodb::Error::TransactionCanceledException(err).into()
+    }
+
+    #[test]
+    fn test_dynamodb_errors_are_classified() {
+        let throttled: DbError = aws_sdk_dynamodb::Error::ProvisionedThroughputExceededException(
+            ProvisionedThroughputExceededException::builder().build(),
+        )
+        .into();
+        assert!(matches!(throttled, DbError::Throttled(_)));
+        assert!(throttled.is_retryable() && throttled.is_transient());
+
+        let failed: DbError = aws_sdk_dynamodb::Error::ConditionalCheckFailedException(
+            ConditionalCheckFailedException::builder().build(),
+        )
+        .into();
+        assert!(matches!(failed, DbError::ConditionFailed(_)));
+        assert!(!failed.is_retryable());
+
+        assert!(matches!(
+            cancelled(&["None", "ConditionalCheckFailed", "ThrottlingError"]),
+            DbError::ConditionFailed(_)
+        ));
+        assert!(matches!(
+            cancelled(&["None", "ThrottlingError"]),
+            DbError::Throttled(_)
+        ));
+        assert!(matches!(
+            cancelled(&["TransactionConflict", "None"]),
+            DbError::Transient(_)
+        ));
+    }
+
+    #[test]
+    fn test_retryable_errors() {
+        assert!(DbError::StateConflict("race".to_string()).is_retryable());
+        assert!(!DbError::StateConflict("race".to_string()).is_transient());
+        assert!(DbError::Transient("timeout".to_string()).is_retryable());
+        assert!(!DbError::Corruption("missing field".to_string()).is_retryable());
+        assert!(!DbError::NotFound("table".to_string()).is_retryable());
+    }
+}
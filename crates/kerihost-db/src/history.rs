@@ -0,0 +1,318 @@
+//! Historical key state
+//!
+//! Only the latest key state is stored, so the state at an earlier event is
+//! rebuilt by replaying the KEL through `KeyState::apply`. `StateHistory`
+//! keeps the states it replays through at establishment events and at
+//! regular checkpoints, so later lookups only replay the events since the
+//! nearest one. Accepted events are never rewritten, so kept states never go
+//! stale. States are kept for a bounded number of prefixes, the least
+//! recently used being dropped first.
+
+use crate::error::{DbError, DbResult};
+use crate::traits::{FirstSeen, KelStore};
+use chrono::{DateTime, Utc};
+use kerihost_core::{KeyState, SignedEvent};
+use lru::LruCache;
+use std::collections::BTreeMap;
+use std::num::NonZeroUsize;
+use std::sync::{Arc, Mutex, MutexGuard};
+
+/// Events fetched per page while replaying
+const REPLAY_PAGE_SIZE: usize = 100;
+
+/// Keep a state at least every this many events, bounding each replay
+const CHECKPOINT_INTERVAL: u64 = 100;
+
+/// Default number of prefixes states are kept for
+const DEFAULT_CAPACITY: usize = 1_000;
+
+/// Key state at any sequence number or point in time
+pub struct StateHistory<D: KelStore + ?Sized> {
+    db: Arc<D>,
+    /// Kept states by prefix, then sn
+    snapshots: Mutex<LruCache<String, BTreeMap<u64, KeyState>>>,
+}
+
+impl<D: KelStore + ?Sized> StateHistory<D> {
+    /// Create a history over a KEL store
+    pub fn new(db: Arc<D>) -> Self {
+        Self::with_capacity(db, DEFAULT_CAPACITY)
+    }
+
+    /// Create a history keeping states for at most `capacity` prefixes
+    pub fn with_capacity(db: Arc<D>, capacity: usize) -> Self {
+        let capacity = NonZeroUsize::new(capacity).unwrap_or(NonZeroUsize::MIN);
+        StateHistory {
+            db,
+            snapshots: Mutex::new(LruCache::new(capacity)),
+        }
+    }
+
+    fn snapshots(&self) -> MutexGuard<'_, LruCache<String, BTreeMap<u64, KeyState>>> {
+        self.snapshots.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Get the key state as of the event at `sn`
+    ///
+    /// Returns `None` if the KEL has no event at `sn`.
+    pub async fn get_state_at(&self, prefix: &str, sn: u64) -> DbResult<Option<KeyState>> {
+        let nearest = self
+            .snapshots()
+            .get(prefix)
+            .and_then(|states| states.range(..=sn).next_back())
+            .map(|(_, state)| state.clone());
+        if let Some(state) = nearest.as_ref().filter(|state| state.sn == sn) {
+            return Ok(Some(state.clone()));
+        }
+
+        let mut state = nearest;
+        let mut cursor: Option<String> = None;
+        let start = state.as_ref().map_or(0, |state| state.sn + 1);
+        loop {
+            let page = self
+                .db
+                .get_events_page(prefix, start, Some(sn), REPLAY_PAGE_SIZE, cursor.as_deref())
+                .await?;
+            for event in &page.items {
+                let next = replay(state.as_ref(), event)?;
+                let establishment = event.event.event_type.is_establishment();
+                if establishment || next.sn % CHECKPOINT_INTERVAL == 0 {
+                    self.snapshots()
+                        .get_or_insert_mut(prefix.to_string(), BTreeMap::new)
+                        .insert(next.sn, next.clone());
+                }
+                state = Some(next);
+            }
+            match page.next {
+                Some(next) => cursor = Some(next),
+                None => break,
+            }
+        }
+
+        Ok(state.filter(|state| state.sn == sn))
+    }
+
+    /// Get the key state as of `datetime`
+    ///
+    /// Uses the latest event this node had first seen by then, so the answer
+    /// is what this node would have reported at that moment. Returns `None`
+    /// if no event of the prefix had been seen yet.
+    pub async fn get_state_as_of(
+        &self,
+        prefix: &str,
+        datetime: DateTime<Utc>,
+    ) -> DbResult<Option<KeyState>> {
+        match self.last_seen_by(prefix, datetime).await? {
+            Some(entry) => self.get_state_at(prefix, entry.sn).await,
+            None => Ok(None),
+        }
+    }
+
+    /// Find the last first-seen entry of a prefix seen by `datetime`
+    ///
+    /// First-seen times grow with the ordinal, so the entry is found by
+    /// reading single entries: at ordinals 0, 1, 3, 7, ... until one is
+    /// missing or too late, then by binary search below it.
+    async fn last_seen_by(
+        &self,
+        prefix: &str,
+        datetime: DateTime<Utc>,
+    ) -> DbResult<Option<FirstSeen>> {
+        let mut found = None;
+        let mut bound = 0u64;
+        while let Some(entry) = self.entry_seen_by(prefix, bound, datetime).await? {
+            found = Some(entry);
+            bound = bound.saturating_mul(2).saturating_add(1);
+        }
+
+        let mut low = found.as_ref().map_or(0, |entry| entry.ordinal + 1);
+        let mut high = bound;
+        while low < high {
+            let mid = low + (high - low) / 2;
+            match self.entry_seen_by(prefix, mid, datetime).await? {
+                Some(entry) => {
+                    found = Some(entry);
+                    low = mid + 1;
+                }
+                None => high = mid,
+            }
+        }
+        Ok(found)
+    }
+
+    /// Get the first-seen entry at `ordinal`, if it was seen by `datetime`
+    async fn entry_seen_by(
+        &self,
+        prefix: &str,
+        ordinal: u64,
+        datetime: DateTime<Utc>,
+    ) -> DbResult<Option<FirstSeen>> {
+        let entry = self
+            .db
+            .get_first_seen_range(prefix, ordinal, Some(ordinal))
+            .await?
+            .into_iter()
+            .next();
+        let Some(entry) = entry else {
+            return Ok(None);
+        };
+
+        let first_seen = DateTime::parse_from_rfc3339(&entry.datetime)
+            .map_err(|e| DbError::Serialization(e.to_string()))?;
+        Ok((first_seen <= datetime).then_some(entry))
+    }
+}
+
+/// Apply the next KEL event to the state before it
+fn replay(state: Option<&KeyState>, event: &SignedEvent) -> DbResult<KeyState> {
+    let replayed = match state {
+        Some(state) => state.apply(&event.event),
+        None => KeyState::from_inception(&event.event),
+    };
+    replayed.map_err(|e| {
+        DbError::Other(format!(
+            "Cannot replay {} at sn {}: {}",
+            event.event.prefix, event.event.sn, e
+        ))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::memory::InMemoryDatabase;
+    use crate::test_support::*;
+
+    fn snapshot_sns(history: &StateHistory<InMemoryDatabase>, prefix: &str) -> Vec<u64> {
+        history
+            .snapshots()
+            .peek(prefix)
+            .unwrap()
+            .keys()
+            .copied()
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn test_get_state_at() {
+        let db = Arc::new(InMemoryDatabase::new());
+        let events = append_test_kel(db.as_ref(), "DTest123", 5).await;
+        let history = StateHistory::new(Arc::clone(&db));
+
+        let state = history.get_state_at("DTest123", 3).await.unwrap().unwrap();
+        assert_eq!(state.sn, 3);
+        assert_eq!(state.latest_digest, events[3].event.digest);
+        assert_eq!(state.signing_keys, events[0].event.signing_keys);
+        assert_eq!(snapshot_sns(&history, "DTest123"), vec![0]);
+
+        let state = history.get_state_at("DTest123", 0).await.unwrap().unwrap();
+        assert_eq!(state.latest_digest, events[0].event.digest);
+
+        assert!(history.get_state_at("DTest123", 5).await.unwrap().is_none());
+        assert!(history.get_state_at("DUnknown", 0).await.unwrap().is_none());
+    }
+
+    #[tokio::test]
+    async fn test_get_state_at_checkpoints_long_kels() {
+        let db = Arc::new(InMemoryDatabase::new());
+        let events = append_test_kel(db.as_ref(), "DTest123", 205).await;
+        let history = StateHistory::new(Arc::clone(&db));
+
+        let state = history
+            .get_state_at("DTest123", 204)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(state.latest_digest, events[204].event.digest);
+        assert_eq!(snapshot_sns(&history, "DTest123"), vec![0, 100, 200]);
+
+        // Replays from the nearest kept state
+        let state = history
+            .get_state_at("DTest123", 150)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(state.latest_digest, events[150].event.digest);
+    }
+
+    #[tokio::test]
+    async fn test_get_state_as_of() {
+        let db = Arc::new(InMemoryDatabase::new());
+        let events = append_test_kel(db.as_ref(), "DTest123", 2).await;
+        let history = StateHistory::new(Arc::clone(&db));
+
+        let state = history
+            .get_state_as_of("DTest123", Utc::now())
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(state.latest_digest, events[1].event.digest);
+
+        let before = Utc::now() - chrono::Duration::days(1);
+        assert!(history
+            .get_state_as_of("DTest123", before)
+            .await
+            .unwrap()
+            .is_none());
+    }
+
+    #[tokio::test]
+    async fn test_get_state_as_of_searches_first_seen_log() {
+        let db = Arc::new(InMemoryDatabase::new());
+        let start = DateTime::parse_from_rfc3339("2024-01-01T00:00:00+00:00")
+            .unwrap()
+            .with_timezone(&Utc);
+        let minute = |n: i64| start + chrono::Duration::minutes(n);
+
+        // Events first seen a minute apart
+        let mut state: Option<KeyState> = None;
+        let mut events: Vec<SignedEvent> = Vec::new();
+        for sn in 0..20 {
+            let prior = events.last().map(|e| e.event.digest.clone());
+            let event = create_test_event("DTest123", sn, prior);
+            let next = replay(state.as_ref(), &event).unwrap();
+            db.commit_event_seen_at(&event, &next, &minute(sn as i64).to_rfc3339())
+                .await
+                .unwrap();
+            state = Some(next);
+            events.push(event);
+        }
+        let history = StateHistory::new(Arc::clone(&db));
+
+        for sn in 0..20 {
+            let state = history
+                .get_state_as_of("DTest123", minute(sn) + chrono::Duration::seconds(30))
+                .await
+                .unwrap()
+                .unwrap();
+            assert_eq!(state.latest_digest, events[sn as usize].event.digest);
+        }
+        let at = history
+            .get_state_as_of("DTest123", minute(7))
+            .await
+            .unwrap();
+        assert_eq!(at.unwrap().sn, 7);
+        assert!(history
+            .get_state_as_of("DTest123", minute(-1))
+            .await
+            .unwrap()
+            .is_none());
+    }
+
+    #[tokio::test]
+    async fn test_kept_states_are_bounded() {
+        let db = Arc::new(InMemoryDatabase::new());
+        append_test_kel(db.as_ref(), "DFirst", 2).await;
+        append_test_kel(db.as_ref(), "DSecond", 2).await;
+        let history = StateHistory::with_capacity(Arc::clone(&db), 1);
+
+        history.get_state_at("DFirst", 1).await.unwrap().unwrap();
+        history.get_state_at("DSecond", 1).await.unwrap().unwrap();
+
+        assert!(history.snapshots().peek("DFirst").is_none());
+        assert_eq!(snapshot_sns(&history, "DSecond"), vec![0]);
+        // Dropped states are replayed again
+        let state = history.get_state_at("DFirst", 1).await.unwrap().unwrap();
+        assert_eq!(state.sn, 1);
+    }
+}
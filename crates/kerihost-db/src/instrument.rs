@@ -0,0 +1,564 @@
+//! Tracing and metrics for every storage call
+//!
+//! [`InstrumentedDatabase`] wraps any backend and runs each store call in a
+//! `db` span naming the operation. When the call returns, the span records
+//! its latency, the items it returned, the DynamoDB capacity it consumed and,
+//! on failure, the error category, and a `DEBUG` event reports them. Set
+//! against a request's total time, these spans show how much of it went to
+//! storage.
+//!
+//! With [`InstrumentedDatabase::with_metrics`] the same measurements are
+//! also collected in a [`DbMetrics`] registry, which renders them in the
+//! Prometheus text format.
+
+use crate::error::{DbError, DbResult};
+use crate::metrics::DbMetrics;
+use crate::traits::{
+    AnchorLocation, ChangeFeed, ChangeStream, EscrowReason, EscrowStore, EscrowedEvent,
+    EscrowedMessage, ExchangeStore, FirstSeen, KelStore, MigrationReport, MigrationStore, Page,
+    ReceiptConflict, ReceiptStore, SchemaStore, StateStore, TenantStore,
+};
+use async_trait::async_trait;
+use kerihost_core::{
+    CredentialSchema, ExchangeMessage, IpexExchange, KeyState, NontransferableReceipt, SignedEvent,
+};
+use std::cell::Cell;
+use std::future::Future;
+use std::sync::Arc;
+use std::time::Instant;
+use tracing::field::Empty;
+use tracing::Instrument;
+
+tokio::task_local! {
+    /// Capacity units consumed so far by the instrumented call on this task
+    static CONSUMED_CAPACITY: Cell<f64>;
+}
+
+/// Add capacity units to the instrumented call in progress
+///
+/// Does nothing outside an instrumented call.
+pub(crate) fn add_consumed_capacity(units: f64) {
+    let _ = CONSUMED_CAPACITY.try_with(|total| total.set(total.get() + units));
+}
+
+/// Number of items a call returned, where that means something
+trait Items {
+    fn items(&self) -> Option<usize> {
+        None
+    }
+}
+
+impl Items for () {}
+
+impl Items for usize {}
+
+impl Items for ChangeStream {}
+
+impl<T> Items for Option<T> {
+    fn items(&self) -> Option<usize> {
+        Some(self.is_some() as usize)
+    }
+}
+
+impl<T> Items for Vec<T> {
+    fn items(&self) -> Option<usize> {
+        Some(self.len())
+    }
+}
+
+impl<T> Items for Page<T> {
+    fn items(&self) -> Option<usize> {
+        Some(self.items.len())
+    }
+}
+
+impl Items for MigrationReport {
+    fn items(&self) -> Option<usize> {
+        Some(self.scanned)
+    }
+}
+
+/// Instrumenting decorator for a database
+///
+/// Implements every store trait its backend does, so it can stand in for
+/// the backend anywhere, including under `CachedDatabase` or in `Witness`.
+#[derive(Clone)]
+pub struct InstrumentedDatabase<D> {
+    inner: D,
+    metrics: Option<Arc<DbMetrics>>,
+}
+
+impl<D> InstrumentedDatabase<D> {
+    /// Wrap a backend, tracing its calls
+    pub fn new(inner: D) -> Self {
+        InstrumentedDatabase {
+            inner,
+            metrics: None,
+        }
+    }
+
+    /// Also collect measurements in `metrics`
+    pub fn with_metrics(mut self, metrics: Arc<DbMetrics>) -> Self {
+        self.metrics = Some(metrics);
+        self
+    }
+
+    /// The wrapped backend
+    pub fn inner(&self) -> &D {
+        &self.inner
+    }
+
+    /// The registry measurements are collected in, if any
+    pub fn metrics(&self) -> Option<&Arc<DbMetrics>> {
+        self.metrics.as_ref()
+    }
+
+    /// Run one backend call inside its span and record how it went
+    async fn call<T: Items>(
+        &self,
+        operation: &'static str,
+        call: impl Future<Output = DbResult<T>>,
+    ) -> DbResult<T> {
+        let span = tracing::info_span!(
+            "db",
+            operation,
+            latency_ms = Empty,
+            items = Empty,
+            consumed_capacity = Empty,
+            error = Empty,
+        );
+        let started = Instant::now();
+        let (result, capacity) = CONSUMED_CAPACITY
+            .scope(Cell::new(0.0), async {
+                let result = call.await;
+                (result, CONSUMED_CAPACITY.with(Cell::get))
+            })
+            .instrument(span.clone())
+            .await;
+        let latency = started.elapsed();
+
+        span.record("latency_ms", latency.as_secs_f64() * 1000.0);
+        let items = result.as_ref().ok().and_then(Items::items);
+        if let Some(items) = items {
+            span.record("items", items);
+        }
+        if capacity > 0.0 {
+            span.record("consumed_capacity", capacity);
+        }
+        let category = result.as_ref().err().map(DbError::category);
+        if let Some(category) = category {
+            span.record("error", category);
+        }
+        tracing::debug!(parent: &span, "db call complete");
+
+        if let Some(metrics) = &self.metrics {
+            metrics.observe(operation, latency, items, capacity, category);
+        }
+        result
+    }
+}
+
+impl<D: TenantStore> TenantStore for InstrumentedDatabase<D> {
+    fn tenant(&self) -> Option<&str> {
+        self.inner.tenant()
+    }
+
+    fn for_tenant(&self, tenant: &str) -> DbResult<Self> {
+        Ok(InstrumentedDatabase {
+            inner: self.inner.for_tenant(tenant)?,
+            metrics: self.metrics.clone(),
+        })
+    }
+}
+
+#[async_trait]
+impl<D: KelStore> KelStore for InstrumentedDatabase<D> {
+    async fn append_event(&self, event: &SignedEvent) -> DbResult<()> {
+        self.call("append_event", self.inner.append_event(event))
+            .await
+    }
+
+    async fn commit_event_seen_at(
+        &self,
+        event: &SignedEvent,
+        new_state: &KeyState,
+        datetime: &str,
+    ) -> DbResult<()> {
+        let call = self.inner.commit_event_seen_at(event, new_state, datetime);
+        self.call("commit_event_seen_at", call).await
+    }
+
+    async fn get_event(&self, prefix: &str, sn: u64) -> DbResult<Option<SignedEvent>> {
+        self.call("get_event", self.inner.get_event(prefix, sn))
+            .await
+    }
+
+    async fn get_events(
+        &self,
+        prefix: &str,
+        start_sn: u64,
+        end_sn: Option<u64>,
+    ) -> DbResult<Vec<SignedEvent>> {
+        let call = self.inner.get_events(prefix, start_sn, end_sn);
+        self.call("get_events", call).await
+    }
+
+    async fn get_events_page(
+        &self,
+        prefix: &str,
+        start_sn: u64,
+        end_sn: Option<u64>,
+        limit: usize,
+        cursor: Option<&str>,
+    ) -> DbResult<Page<SignedEvent>> {
+        let call = self
+            .inner
+            .get_events_page(prefix, start_sn, end_sn, limit, cursor);
+        self.call("get_events_page", call).await
+    }
+
+    async fn get_prefixes_page(
+        &self,
+        limit: usize,
+        cursor: Option<&str>,
+    ) -> DbResult<Page<String>> {
+        let call = self.inner.get_prefixes_page(limit, cursor);
+        self.call("get_prefixes_page", call).await
+    }
+
+    async fn get_latest(&self, prefix: &str) -> DbResult<Option<SignedEvent>> {
+        self.call("get_latest", self.inner.get_latest(prefix)).await
+    }
+
+    async fn get_event_by_digest(
+        &self,
+        prefix: &str,
+        digest: &str,
+    ) -> DbResult<Option<SignedEvent>> {
+        let call = self.inner.get_event_by_digest(prefix, digest);
+        self.call("get_event_by_digest", call).await
+    }
+
+    async fn get_first_seen(&self, prefix: &str, digest: &str) -> DbResult<Option<FirstSeen>> {
+        let call = self.inner.get_first_seen(prefix, digest);
+        self.call("get_first_seen", call).await
+    }
+
+    async fn get_first_seen_range(
+        &self,
+        prefix: &str,
+        start_fn: u64,
+        end_fn: Option<u64>,
+    ) -> DbResult<Vec<FirstSeen>> {
+        let call = self.inner.get_first_seen_range(prefix, start_fn, end_fn);
+        self.call("get_first_seen_range", call).await
+    }
+
+    async fn find_anchor(&self, digest: &str) -> DbResult<Option<AnchorLocation>> {
+        self.call("find_anchor", self.inner.find_anchor(digest))
+            .await
+    }
+
+    async fn find_event_seal(
+        &self,
+        prefix: &str,
+        sn: u64,
+        digest: &str,
+    ) -> DbResult<Option<AnchorLocation>> {
+        let call = self.inner.find_event_seal(prefix, sn, digest);
+        self.call("find_event_seal", call).await
+    }
+}
+
+#[async_trait]
+impl<D: StateStore> StateStore for InstrumentedDatabase<D> {
+    async fn get_state(&self, prefix: &str) -> DbResult<Option<KeyState>> {
+        self.call("get_state", self.inner.get_state(prefix)).await
+    }
+
+    async fn put_state(&self, state: &KeyState) -> DbResult<()> {
+        self.call("put_state", self.inner.put_state(state)).await
+    }
+
+    async fn delete_state(&self, prefix: &str) -> DbResult<()> {
+        self.call("delete_state", self.inner.delete_state(prefix))
+            .await
+    }
+}
+
+#[async_trait]
+impl<D: ReceiptStore> ReceiptStore for InstrumentedDatabase<D> {
+    async fn add_receipt(&self, receipt: &NontransferableReceipt) -> DbResult<()> {
+        self.call("add_receipt", self.inner.add_receipt(receipt))
+            .await
+    }
+
+    async fn get_receipts(&self, event_digest: &str) -> DbResult<Vec<NontransferableReceipt>> {
+        self.call("get_receipts", self.inner.get_receipts(event_digest))
+            .await
+    }
+
+    async fn get_receipt(
+        &self,
+        event_digest: &str,
+        witness_prefix: &str,
+    ) -> DbResult<Option<NontransferableReceipt>> {
+        let call = self.inner.get_receipt(event_digest, witness_prefix);
+        self.call("get_receipt", call).await
+    }
+
+    async fn count_receipts(&self, event_digest: &str) -> DbResult<usize> {
+        let call = self.inner.count_receipts(event_digest);
+        self.call("count_receipts", call).await
+    }
+
+    async fn get_receipt_conflicts(&self, witness_prefix: &str) -> DbResult<Vec<ReceiptConflict>> {
+        let call = self.inner.get_receipt_conflicts(witness_prefix);
+        self.call("get_receipt_conflicts", call).await
+    }
+}
+
+#[async_trait]
+impl<D: EscrowStore> EscrowStore for InstrumentedDatabase<D> {
+    async fn escrow_event_with_ttl(
+        &self,
+        event: &SignedEvent,
+        reason: EscrowReason,
+        ttl_seconds: u64,
+    ) -> DbResult<()> {
+        self.call(
+            "escrow_event_with_ttl",
+            self.inner.escrow_event_with_ttl(event, reason, ttl_seconds),
+        )
+        .await
+    }
+
+    async fn record_escrow_attempt(&self, escrowed: &EscrowedEvent) -> DbResult<()> {
+        self.call(
+            "record_escrow_attempt",
+            self.inner.record_escrow_attempt(escrowed),
+        )
+        .await
+    }
+
+    async fn get_escrowed_page(
+        &self,
+        limit: usize,
+        cursor: Option<&str>,
+    ) -> DbResult<Page<EscrowedEvent>> {
+        let call = self.inner.get_escrowed_page(limit, cursor);
+        self.call("get_escrowed_page", call).await
+    }
+
+    async fn get_escrowed_by_prefix(
+        &self,
+        prefix: &str,
+        limit: usize,
+        cursor: Option<&str>,
+    ) -> DbResult<Page<EscrowedEvent>> {
+        let call = self.inner.get_escrowed_by_prefix(prefix, limit, cursor);
+        self.call("get_escrowed_by_prefix", call).await
+    }
+
+    async fn get_escrowed_by_reason(
+        &self,
+        reason: EscrowReason,
+        limit: usize,
+        cursor: Option<&str>,
+    ) -> DbResult<Page<EscrowedEvent>> {
+        let call = self.inner.get_escrowed_by_reason(reason, limit, cursor);
+        self.call("get_escrowed_by_reason", call).await
+    }
+
+    async fn promote_escrowed(&self, event_digest: &str) -> DbResult<Option<SignedEvent>> {
+        let call = self.inner.promote_escrowed(event_digest);
+        self.call("promote_escrowed", call).await
+    }
+
+    async fn remove_escrowed(&self, event_digest: &str) -> DbResult<()> {
+        let call = self.inner.remove_escrowed(event_digest);
+        self.call("remove_escrowed", call).await
+    }
+}
+
+#[async_trait]
+impl<D: SchemaStore> SchemaStore for InstrumentedDatabase<D> {
+    async fn put_schema(&self, schema: &CredentialSchema) -> DbResult<()> {
+        self.call("put_schema", self.inner.put_schema(schema)).await
+    }
+
+    async fn get_schema(&self, said: &str) -> DbResult<Option<CredentialSchema>> {
+        self.call("get_schema", self.inner.get_schema(said)).await
+    }
+}
+
+#[async_trait]
+impl<D: ExchangeStore> ExchangeStore for InstrumentedDatabase<D> {
+    async fn put_exchange(
+        &self,
+        exchange: &IpexExchange,
+        message: &ExchangeMessage,
+    ) -> DbResult<()> {
+        let call = self.inner.put_exchange(exchange, message);
+        self.call("put_exchange", call).await
+    }
+
+    async fn get_exchange(&self, id: &str) -> DbResult<Option<IpexExchange>> {
+        self.call("get_exchange", self.inner.get_exchange(id)).await
+    }
+
+    async fn get_message(&self, said: &str) -> DbResult<Option<ExchangeMessage>> {
+        self.call("get_message", self.inner.get_message(said)).await
+    }
+
+    async fn get_exchange_by_message(&self, said: &str) -> DbResult<Option<IpexExchange>> {
+        let call = self.inner.get_exchange_by_message(said);
+        self.call("get_exchange_by_message", call).await
+    }
+
+    async fn escrow_message_with_ttl(
+        &self,
+        message: &ExchangeMessage,
+        ttl_seconds: u64,
+    ) -> DbResult<()> {
+        self.call(
+            "escrow_message_with_ttl",
+            self.inner.escrow_message_with_ttl(message, ttl_seconds),
+        )
+        .await
+    }
+
+    async fn get_escrowed_messages(&self, prior: &str) -> DbResult<Vec<EscrowedMessage>> {
+        let call = self.inner.get_escrowed_messages(prior);
+        self.call("get_escrowed_messages", call).await
+    }
+
+    async fn get_escrowed_messages_page(
+        &self,
+        limit: usize,
+        cursor: Option<&str>,
+    ) -> DbResult<Page<EscrowedMessage>> {
+        let call = self.inner.get_escrowed_messages_page(limit, cursor);
+        self.call("get_escrowed_messages_page", call).await
+    }
+
+    async fn promote_escrowed_message(&self, said: &str) -> DbResult<Option<ExchangeMessage>> {
+        let call = self.inner.promote_escrowed_message(said);
+        self.call("promote_escrowed_message", call).await
+    }
+
+    async fn remove_escrowed_message(&self, said: &str) -> DbResult<()> {
+        let call = self.inner.remove_escrowed_message(said);
+        self.call("remove_escrowed_message", call).await
+    }
+}
+
+#[async_trait]
+impl<D: MigrationStore> MigrationStore for InstrumentedDatabase<D> {
+    async fn migrate_records(&self) -> DbResult<MigrationReport> {
+        self.call("migrate_records", self.inner.migrate_records())
+            .await
+    }
+}
+
+#[async_trait]
+impl<D: ChangeFeed> ChangeFeed for InstrumentedDatabase<D> {
+    async fn subscribe(&self, prefix_filter: Option<&str>) -> DbResult<ChangeStream> {
+        self.call("subscribe", self.inner.subscribe(prefix_filter))
+            .await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::memory::InMemoryDatabase;
+    use crate::test_support::*;
+    use std::time::Duration;
+
+    crate::conformance::conformance_tests!(Some(
+        InstrumentedDatabase::new(InMemoryDatabase::new())
+    ));
+
+    /// Backend that charges capacity for every call, as DynamoDB reports it
+    struct Metered(InMemoryDatabase);
+
+    #[async_trait]
+    impl StateStore for Metered {
+        async fn get_state(&self, prefix: &str) -> DbResult<Option<KeyState>> {
+            add_consumed_capacity(0.5);
+            self.0.get_state(prefix).await
+        }
+
+        async fn put_state(&self, state: &KeyState) -> DbResult<()> {
+            add_consumed_capacity(1.0);
+            self.0.put_state(state).await
+        }
+
+        async fn delete_state(&self, prefix: &str) -> DbResult<()> {
+            self.0.delete_state(prefix).await
+        }
+    }
+
+    #[tokio::test]
+    async fn test_calls_are_measured() {
+        let metrics = Arc::new(DbMetrics::new());
+        let db = InstrumentedDatabase::new(Metered(InMemoryDatabase::new()))
+            .with_metrics(Arc::clone(&metrics));
+
+        db.put_state(&create_test_state("DTest123", 0))
+            .await
+            .unwrap();
+        db.get_state("DTest123").await.unwrap();
+        db.get_state("DOther").await.unwrap();
+
+        let put = metrics.operation("put_state").unwrap();
+        assert_eq!(put.calls, 1);
+        assert_eq!(put.consumed_capacity, 1.0);
+        let get = metrics.operation("get_state").unwrap();
+        assert_eq!(get.calls, 2);
+        assert_eq!(get.items, 1);
+        assert_eq!(get.consumed_capacity, 1.0);
+        assert!(get.errors.is_empty());
+        assert!(get.latency < Duration::from_secs(1));
+
+        // Capacity charged outside an instrumented call goes nowhere
+        add_consumed_capacity(5.0);
+        db.inner().get_state("DTest123").await.unwrap();
+        assert_eq!(metrics.operation("get_state").unwrap().calls, 2);
+    }
+
+    #[tokio::test]
+    async fn test_errors_are_counted_by_category() {
+        let metrics = Arc::new(DbMetrics::new());
+        let db =
+            InstrumentedDatabase::new(InMemoryDatabase::new()).with_metrics(Arc::clone(&metrics));
+
+        let icp = create_test_event("DTest123", 0, None);
+        db.append_event(&icp).await.unwrap();
+        assert!(db.append_event(&icp).await.is_err());
+        assert!(db
+            .get_events_page("DTest123", 0, None, 10, Some("not a cursor"))
+            .await
+            .is_err());
+
+        let append = metrics.operation("append_event").unwrap();
+        assert_eq!(append.calls, 2);
+        assert_eq!(append.errors.get("conflict"), Some(&1));
+        let page = metrics.operation("get_events_page").unwrap();
+        assert_eq!(page.errors.get("invalid_request"), Some(&1));
+    }
+
+    #[tokio::test]
+    async fn test_tenant_handles_share_metrics() {
+        let metrics = Arc::new(DbMetrics::new());
+        let db =
+            InstrumentedDatabase::new(InMemoryDatabase::new()).with_metrics(Arc::clone(&metrics));
+        let alpha = db.for_tenant("alpha").unwrap();
+        assert_eq!(alpha.tenant(), Some("alpha"));
+
+        alpha.get_state("DTest123").await.unwrap();
+        db.get_state("DTest123").await.unwrap();
+        assert_eq!(metrics.operation("get_state").unwrap().calls, 2);
+    }
+}
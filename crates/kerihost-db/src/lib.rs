@@ -3,23 +3,60 @@
 //! This crate provides the database traits and implementations for storing
 //! KERI data including:
 //! - Key Event Log (KEL) storage
+//! - Anchored seal index
 //! - Key state storage
 //! - Receipt storage
 //! - Escrow storage
+//! - Credential schema storage
+//! - IPEX exchange storage
 //!
 //! # Implementations
 //!
 //! - `DynamoDbDatabase`: Production implementation using AWS DynamoDB
 //! - `InMemoryDatabase`: Testing implementation using in-memory storage
+//! - `SqliteDatabase`: Single-node implementation using SQLite (`sqlite` feature)
+//! - `PostgresDatabase`: Shared implementation using PostgreSQL (`postgres` feature)
+//! - `RedbDatabase`: Embedded implementation with a keripy-style layout (`redb` feature)
+//!
+//! `CachedDatabase` wraps any of them with a read-through cache, and
+//! `InstrumentedDatabase` with tracing spans and metrics for every call.
 
+pub mod cache;
 pub mod dynamodb;
 pub mod error;
+pub mod history;
+pub mod instrument;
 pub mod memory;
+pub mod metrics;
+#[cfg(feature = "postgres")]
+pub mod postgres;
+pub mod record;
+#[cfg(feature = "redb")]
+pub mod redb;
+#[cfg(feature = "sqlite")]
+pub mod sqlite;
+pub mod stream;
 pub mod traits;
 
+#[cfg(test)]
+mod conformance;
+#[cfg(test)]
+mod test_support;
+
 pub use error::*;
 pub use traits::*;
 
 // Re-export implementations
-pub use dynamodb::DynamoDbDatabase;
+pub use cache::CachedDatabase;
+pub use dynamodb::{DynamoDbChangeFeed, DynamoDbDatabase};
+pub use instrument::InstrumentedDatabase;
 pub use memory::InMemoryDatabase;
+pub use metrics::DbMetrics;
+#[cfg(feature = "prometheus")]
+pub use metrics::serve_metrics;
+#[cfg(feature = "postgres")]
+pub use postgres::{EscrowListener, PostgresDatabase};
+#[cfg(feature = "redb")]
+pub use self::redb::RedbDatabase;
+#[cfg(feature = "sqlite")]
+pub use sqlite::SqliteDatabase;
@@ -2,27 +2,77 @@
 //!
 //! This implementation stores all data in memory using HashMaps.
 //! It's useful for unit tests and local development.
+//!
+//! Each tenant namespace has its own set of maps, created the first time a
+//! handle is scoped to it.
 
 use crate::error::{DbError, DbResult};
+use crate::record::{self, Record};
 use crate::traits::{
-    EscrowReason, EscrowStore, EscrowedEvent, KelStore, ReceiptStore, StateStore,
+    expiry_cursor, parse_expiry_cursor, validate_tenant, AnchorLocation, Change, ChangeFeed,
+    ChangeStream, EscrowReason, EscrowStore, EscrowedEvent, EscrowedMessage, ExchangeStore,
+    FirstSeen, KelStore, MigrationReport, MigrationStore, Page, ReceiptConflict, ReceiptStore,
+    SchemaStore, StateStore, TenantStore,
 };
 use async_trait::async_trait;
-use kerihost_core::{KeyState, NontransferableReceipt, SignedEvent};
+use futures::{future, StreamExt, TryStreamExt};
+use kerihost_core::{
+    CredentialSchema, ExchangeMessage, IpexExchange, KeyState, NontransferableReceipt,
+    SignedEvent,
+};
 use std::collections::{BTreeMap, HashMap};
-use std::sync::Arc;
+use std::ops::Bound;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use tokio::sync::broadcast::{self, error::RecvError};
 use tokio::sync::RwLock;
 
+/// Stored KEL entry: event digest and the event's original CESR
+type StoredEvent = (String, Vec<u8>);
+
+/// Stored escrow entry: the escrow record and the event's raw bytes and
+/// attachments, which its JSON does not carry
+type StoredEscrow = (String, Vec<u8>, Vec<u8>);
+
+/// Changes buffered for each subscriber before it lags
+const CHANGE_BUFFER: usize = 1024;
+
 /// In-memory database for testing
 pub struct InMemoryDatabase {
-    /// KEL storage: prefix -> (sn -> event)
-    kel: Arc<RwLock<HashMap<String, BTreeMap<u64, SignedEvent>>>>,
-    /// State storage: prefix -> state
-    states: Arc<RwLock<HashMap<String, KeyState>>>,
-    /// Receipt storage: event_digest -> (witness_prefix -> receipt)
-    receipts: Arc<RwLock<HashMap<String, HashMap<String, NontransferableReceipt>>>>,
-    /// Escrow storage: digest -> escrowed_event
-    escrows: Arc<RwLock<HashMap<String, EscrowedEvent>>>,
+    /// KEL storage: prefix -> (sn -> stored event)
+    kel: Arc<RwLock<HashMap<String, BTreeMap<u64, StoredEvent>>>>,
+    /// Anchor index: seal digest -> locations in append order
+    anchors: Arc<RwLock<HashMap<String, Vec<AnchorLocation>>>>,
+    /// First-seen log: prefix -> entries indexed by ordinal
+    fel: Arc<RwLock<HashMap<String, Vec<FirstSeen>>>>,
+    /// State storage: prefix -> state record
+    states: Arc<RwLock<HashMap<String, String>>>,
+    /// Receipt storage: event_digest -> (witness_prefix -> receipt record), in witness order
+    receipts: Arc<RwLock<HashMap<String, BTreeMap<String, String>>>>,
+    /// Receipt conflicts: witness_prefix -> (conflict key -> conflict record)
+    receipt_conflicts: Arc<RwLock<HashMap<String, BTreeMap<String, String>>>>,
+    /// Escrow storage: digest -> stored escrow, ordered for paging
+    escrows: Arc<RwLock<BTreeMap<String, StoredEscrow>>>,
+    /// Schema storage: said -> schema
+    schemas: Arc<RwLock<HashMap<String, CredentialSchema>>>,
+    /// Exchange storage: exchange_id -> exchange
+    exchanges: Arc<RwLock<HashMap<String, IpexExchange>>>,
+    /// Exchange message storage: said -> (message, exchange_id)
+    messages: Arc<RwLock<HashMap<String, (ExchangeMessage, String)>>>,
+    /// Exchange message escrow: said -> escrowed_message, ordered for paging
+    message_escrows: Arc<RwLock<BTreeMap<String, EscrowedMessage>>>,
+    /// Change feed, sent to while the written map is still locked so
+    /// subscribers see changes in commit order
+    changes: broadcast::Sender<Change>,
+    /// Commits left to fail with a transient error
+    failing_commits: Arc<AtomicUsize>,
+    /// Tenant this handle is scoped to
+    tenant: Option<String>,
+    /// Every tenant's storage, shared by all handles on this database
+    ///
+    /// Entries hold an empty registry of their own, so the registry is not
+    /// kept alive through itself.
+    tenants: Arc<Mutex<HashMap<String, InMemoryDatabase>>>,
 }
 
 impl InMemoryDatabase {
@@ -30,18 +80,36 @@ impl InMemoryDatabase {
     pub fn new() -> Self {
         InMemoryDatabase {
             kel: Arc::new(RwLock::new(HashMap::new())),
+            anchors: Arc::new(RwLock::new(HashMap::new())),
+            fel: Arc::new(RwLock::new(HashMap::new())),
             states: Arc::new(RwLock::new(HashMap::new())),
             receipts: Arc::new(RwLock::new(HashMap::new())),
-            escrows: Arc::new(RwLock::new(HashMap::new())),
+            receipt_conflicts: Arc::new(RwLock::new(HashMap::new())),
+            escrows: Arc::new(RwLock::new(BTreeMap::new())),
+            schemas: Arc::new(RwLock::new(HashMap::new())),
+            exchanges: Arc::new(RwLock::new(HashMap::new())),
+            messages: Arc::new(RwLock::new(HashMap::new())),
+            message_escrows: Arc::new(RwLock::new(BTreeMap::new())),
+            changes: broadcast::channel(CHANGE_BUFFER).0,
+            failing_commits: Arc::new(AtomicUsize::new(0)),
+            tenant: None,
+            tenants: Arc::new(Mutex::new(HashMap::new())),
         }
     }
 
-    /// Clear all data (for testing)
+    /// Clear all data in this handle's namespace (for testing)
     pub async fn clear(&self) {
         self.kel.write().await.clear();
+        self.anchors.write().await.clear();
+        self.fel.write().await.clear();
         self.states.write().await.clear();
         self.receipts.write().await.clear();
+        self.receipt_conflicts.write().await.clear();
         self.escrows.write().await.clear();
+        self.schemas.write().await.clear();
+        self.exchanges.write().await.clear();
+        self.messages.write().await.clear();
+        self.message_escrows.write().await.clear();
     }
 
     /// Get count of events for a prefix (for testing)
@@ -49,6 +117,55 @@ impl InMemoryDatabase {
         let kel = self.kel.read().await;
         kel.get(prefix).map(|m| m.len()).unwrap_or(0)
     }
+
+    /// Make the next `count` commits fail with a transient error, as on a
+    /// throttled or unreachable backend (for testing)
+    pub fn fail_commits(&self, count: usize) {
+        self.failing_commits.store(count, Ordering::SeqCst);
+    }
+
+    /// Publish a committed change
+    fn emit(&self, change: Change) {
+        // Sending only fails when nobody is subscribed
+        let _ = self.changes.send(change);
+    }
+}
+
+/// Rebuild a signed event from its stored CESR
+fn decode_event(cesr: &[u8]) -> DbResult<SignedEvent> {
+    SignedEvent::from_cesr(cesr).map_err(|e| DbError::Corruption(e.to_string()))
+}
+
+/// Encode an escrow entry for storage
+fn store_escrow(escrowed: &EscrowedEvent) -> DbResult<StoredEscrow> {
+    Ok((
+        record::encode(escrowed)?,
+        escrowed.event.event.raw.clone(),
+        escrowed.event.attachments.clone(),
+    ))
+}
+
+/// Rebuild an escrow entry from storage
+fn load_escrow((json, raw, attachments): &StoredEscrow) -> DbResult<EscrowedEvent> {
+    let mut escrowed: EscrowedEvent = record::decode(json)?;
+    escrowed.event.event.raw = raw.clone();
+    escrowed.event.attachments = attachments.clone();
+    Ok(escrowed)
+}
+
+/// Rewrite outdated records of one kind in place
+fn migrate_rows<'a, T: Record>(
+    rows: impl Iterator<Item = &'a mut String>,
+    report: &mut MigrationReport,
+) -> DbResult<()> {
+    for row in rows {
+        report.scanned += 1;
+        if record::is_outdated::<T>(row)? {
+            *row = record::encode(&record::decode::<T>(row)?)?;
+            report.migrated += 1;
+        }
+    }
+    Ok(())
 }
 
 impl Default for InMemoryDatabase {
@@ -61,69 +178,175 @@ impl Clone for InMemoryDatabase {
     fn clone(&self) -> Self {
         InMemoryDatabase {
             kel: Arc::clone(&self.kel),
+            anchors: Arc::clone(&self.anchors),
+            fel: Arc::clone(&self.fel),
             states: Arc::clone(&self.states),
             receipts: Arc::clone(&self.receipts),
+            receipt_conflicts: Arc::clone(&self.receipt_conflicts),
             escrows: Arc::clone(&self.escrows),
+            schemas: Arc::clone(&self.schemas),
+            exchanges: Arc::clone(&self.exchanges),
+            messages: Arc::clone(&self.messages),
+            message_escrows: Arc::clone(&self.message_escrows),
+            changes: self.changes.clone(),
+            failing_commits: Arc::clone(&self.failing_commits),
+            tenant: self.tenant.clone(),
+            tenants: Arc::clone(&self.tenants),
+        }
+    }
+}
+
+impl TenantStore for InMemoryDatabase {
+    fn tenant(&self) -> Option<&str> {
+        self.tenant.as_deref()
+    }
+
+    fn for_tenant(&self, tenant: &str) -> DbResult<Self> {
+        validate_tenant(tenant)?;
+        let mut tenants = self.tenants.lock().unwrap_or_else(|e| e.into_inner());
+        let storage = tenants.entry(tenant.to_string()).or_default().clone();
+        Ok(InMemoryDatabase {
+            tenant: Some(tenant.to_string()),
+            tenants: Arc::clone(&self.tenants),
+            ..storage
+        })
+    }
+}
+
+/// Append an event first seen at `first_seen_at` to locked KEL, first-seen
+/// and anchor maps
+fn append_locked(
+    kel: &mut HashMap<String, BTreeMap<u64, StoredEvent>>,
+    fel: &mut HashMap<String, Vec<FirstSeen>>,
+    anchors: &mut HashMap<String, Vec<AnchorLocation>>,
+    event: &SignedEvent,
+    first_seen_at: &str,
+) -> DbResult<()> {
+    let prefix = &event.event.prefix;
+    let sn = event.event.sn;
+
+    let prefix_kel = kel.entry(prefix.clone()).or_default();
+
+    // For inception (sn=0), check that no events exist
+    if sn == 0 {
+        if !prefix_kel.is_empty() {
+            return Err(DbError::Duplicate(format!(
+                "Inception already exists for {}",
+                prefix
+            )));
         }
+    } else {
+        // For non-inception, verify prior digest
+        let prior_sn = sn - 1;
+        if let Some((prior_digest, _)) = prefix_kel.get(&prior_sn) {
+            if let Some(ref event_prior) = event.event.prior_digest {
+                if event_prior != prior_digest {
+                    return Err(DbError::PriorDigestMismatch {
+                        expected: prior_digest.clone(),
+                        actual: event_prior.clone(),
+                    });
+                }
+            } else {
+                return Err(DbError::Other(
+                    "Non-inception event missing prior digest".to_string(),
+                ));
+            }
+        } else {
+            return Err(DbError::NotFound(format!(
+                "Prior event at sn {} not found for {}",
+                prior_sn, prefix
+            )));
+        }
+    }
+
+    // Check for duplicate
+    if prefix_kel.contains_key(&sn) {
+        return Err(DbError::Duplicate(format!(
+            "Event at sn {} already exists for {}",
+            sn, prefix
+        )));
+    }
+
+    // The original CESR is the canonical record
+    let cesr = event
+        .to_cesr()
+        .map_err(|e| DbError::Serialization(e.to_string()))?;
+    prefix_kel.insert(sn, (event.event.digest.clone(), cesr));
+
+    let prefix_fel = fel.entry(prefix.clone()).or_default();
+    prefix_fel.push(FirstSeen::at(event, prefix_fel.len() as u64, first_seen_at));
+
+    for location in AnchorLocation::from_event(event) {
+        anchors
+            .entry(location.seal.d.clone())
+            .or_default()
+            .push(location);
     }
+    Ok(())
 }
 
 #[async_trait]
 impl KelStore for InMemoryDatabase {
     async fn append_event(&self, event: &SignedEvent) -> DbResult<()> {
         let mut kel = self.kel.write().await;
-        let prefix = &event.event.prefix;
-        let sn = event.event.sn;
-
-        let prefix_kel = kel.entry(prefix.clone()).or_insert_with(BTreeMap::new);
-
-        // For inception (sn=0), check that no events exist
-        if sn == 0 {
-            if !prefix_kel.is_empty() {
-                return Err(DbError::Duplicate(format!(
-                    "Inception already exists for {}",
-                    prefix
-                )));
-            }
-        } else {
-            // For non-inception, verify prior digest
-            let prior_sn = sn - 1;
-            if let Some(prior_event) = prefix_kel.get(&prior_sn) {
-                if let Some(ref event_prior) = event.event.prior_digest {
-                    if event_prior != &prior_event.event.digest {
-                        return Err(DbError::PriorDigestMismatch {
-                            expected: prior_event.event.digest.clone(),
-                            actual: event_prior.clone(),
-                        });
-                    }
-                } else {
-                    return Err(DbError::Other(
-                        "Non-inception event missing prior digest".to_string(),
-                    ));
-                }
-            } else {
-                return Err(DbError::NotFound(format!(
-                    "Prior event at sn {} not found for {}",
-                    prior_sn, prefix
-                )));
-            }
+        let mut fel = self.fel.write().await;
+        let mut anchors = self.anchors.write().await;
+        let now = chrono::Utc::now().to_rfc3339();
+        append_locked(&mut kel, &mut fel, &mut anchors, event, &now)?;
+        self.emit(Change::accepted(event));
+        Ok(())
+    }
+
+    async fn commit_event_seen_at(
+        &self,
+        event: &SignedEvent,
+        new_state: &KeyState,
+        datetime: &str,
+    ) -> DbResult<()> {
+        let failing = self
+            .failing_commits
+            .fetch_update(Ordering::SeqCst, Ordering::SeqCst, |n| n.checked_sub(1));
+        if failing.is_ok() {
+            return Err(DbError::Transient("Injected commit failure".to_string()));
         }
 
-        // Check for duplicate
-        if prefix_kel.contains_key(&sn) {
-            return Err(DbError::Duplicate(format!(
-                "Event at sn {} already exists for {}",
-                sn, prefix
+        // Hold every lock for the whole commit so it is atomic
+        let mut kel = self.kel.write().await;
+        let mut fel = self.fel.write().await;
+        let mut anchors = self.anchors.write().await;
+        let mut states = self.states.write().await;
+
+        // State is versioned by (sn, digest) of its latest event
+        let current = states
+            .get(&event.event.prefix)
+            .map(|s| record::decode::<KeyState>(s))
+            .transpose()?;
+        let current = current.as_ref().map(|s| (s.sn, s.latest_digest.as_str()));
+        let expected = event
+            .event
+            .sn
+            .checked_sub(1)
+            .map(|sn| (sn, event.event.prior_digest.as_deref().unwrap_or_default()));
+        if current != expected {
+            return Err(DbError::StateConflict(format!(
+                "State for {} is at {:?}, expected {:?}",
+                event.event.prefix, current, expected
             )));
         }
 
-        prefix_kel.insert(sn, event.clone());
+        let state_record = record::encode(new_state)?;
+        append_locked(&mut kel, &mut fel, &mut anchors, event, datetime)?;
+        states.insert(new_state.prefix.clone(), state_record);
+        self.emit(Change::accepted(event));
         Ok(())
     }
 
     async fn get_event(&self, prefix: &str, sn: u64) -> DbResult<Option<SignedEvent>> {
         let kel = self.kel.read().await;
-        Ok(kel.get(prefix).and_then(|m| m.get(&sn).cloned()))
+        kel.get(prefix)
+            .and_then(|m| m.get(&sn))
+            .map(|(_, cesr)| decode_event(cesr))
+            .transpose()
     }
 
     async fn get_events(
@@ -138,27 +361,94 @@ impl KelStore for InMemoryDatabase {
             return Ok(vec![]);
         };
 
-        let events: Vec<SignedEvent> = prefix_kel
+        prefix_kel
             .range(start_sn..)
             .filter(|(sn, _)| end_sn.map(|e| **sn <= e).unwrap_or(true))
-            .map(|(_, e)| e.clone())
-            .collect();
+            .map(|(_, (_, cesr))| decode_event(cesr))
+            .collect()
+    }
 
-        Ok(events)
+    async fn get_prefixes_page(
+        &self,
+        limit: usize,
+        cursor: Option<&str>,
+    ) -> DbResult<Page<String>> {
+        let kel = self.kel.read().await;
+        let mut prefixes: Vec<String> = kel
+            .iter()
+            .filter(|(prefix, events)| {
+                !events.is_empty() && cursor.is_none_or(|after| prefix.as_str() > after)
+            })
+            .map(|(prefix, _)| prefix.clone())
+            .collect();
+        prefixes.sort();
+
+        let limit = limit.max(1);
+        let next = (prefixes.len() > limit).then(|| prefixes[limit - 1].clone());
+        prefixes.truncate(limit);
+        Ok(Page {
+            items: prefixes,
+            next,
+        })
     }
 
     async fn get_latest(&self, prefix: &str) -> DbResult<Option<SignedEvent>> {
         let kel = self.kel.read().await;
-        Ok(kel
-            .get(prefix)
-            .and_then(|m| m.iter().last().map(|(_, e)| e.clone())))
+        kel.get(prefix)
+            .and_then(|m| m.values().last())
+            .map(|(_, cesr)| decode_event(cesr))
+            .transpose()
     }
 
     async fn get_event_by_digest(&self, prefix: &str, digest: &str) -> DbResult<Option<SignedEvent>> {
         let kel = self.kel.read().await;
-        Ok(kel.get(prefix).and_then(|m| {
-            m.values()
-                .find(|e| e.event.digest == digest)
+        kel.get(prefix)
+            .and_then(|m| m.values().find(|(d, _)| d == digest))
+            .map(|(_, cesr)| decode_event(cesr))
+            .transpose()
+    }
+
+    async fn get_first_seen(&self, prefix: &str, digest: &str) -> DbResult<Option<FirstSeen>> {
+        let fel = self.fel.read().await;
+        Ok(fel
+            .get(prefix)
+            .and_then(|entries| entries.iter().find(|e| e.digest == digest).cloned()))
+    }
+
+    async fn get_first_seen_range(
+        &self,
+        prefix: &str,
+        start_fn: u64,
+        end_fn: Option<u64>,
+    ) -> DbResult<Vec<FirstSeen>> {
+        let fel = self.fel.read().await;
+        Ok(fel
+            .get(prefix)
+            .map(|entries| {
+                entries
+                    .iter()
+                    .filter(|e| e.ordinal >= start_fn && end_fn.is_none_or(|end| e.ordinal <= end))
+                    .cloned()
+                    .collect()
+            })
+            .unwrap_or_default())
+    }
+
+    async fn find_anchor(&self, digest: &str) -> DbResult<Option<AnchorLocation>> {
+        let anchors = self.anchors.read().await;
+        Ok(anchors.get(digest).and_then(|l| l.first().cloned()))
+    }
+
+    async fn find_event_seal(
+        &self,
+        prefix: &str,
+        sn: u64,
+        digest: &str,
+    ) -> DbResult<Option<AnchorLocation>> {
+        let anchors = self.anchors.read().await;
+        Ok(anchors.get(digest).and_then(|l| {
+            l.iter()
+                .find(|location| location.is_event_seal(prefix, sn))
                 .cloned()
         }))
     }
@@ -168,12 +458,13 @@ impl KelStore for InMemoryDatabase {
 impl StateStore for InMemoryDatabase {
     async fn get_state(&self, prefix: &str) -> DbResult<Option<KeyState>> {
         let states = self.states.read().await;
-        Ok(states.get(prefix).cloned())
+        states.get(prefix).map(|s| record::decode(s)).transpose()
     }
 
     async fn put_state(&self, state: &KeyState) -> DbResult<()> {
+        let state_record = record::encode(state)?;
         let mut states = self.states.write().await;
-        states.insert(state.prefix.clone(), state.clone());
+        states.insert(state.prefix.clone(), state_record);
         Ok(())
     }
 
@@ -187,21 +478,39 @@ impl StateStore for InMemoryDatabase {
 #[async_trait]
 impl ReceiptStore for InMemoryDatabase {
     async fn add_receipt(&self, receipt: &NontransferableReceipt) -> DbResult<()> {
+        let receipt_record = record::encode(receipt)?;
         let mut receipts = self.receipts.write().await;
         let event_receipts = receipts
             .entry(receipt.event_digest.clone())
-            .or_insert_with(HashMap::new);
+            .or_default();
+
+        if let Some(stored) = event_receipts.get(&receipt.witness_prefix) {
+            let stored: NontransferableReceipt = record::decode(stored)?;
+            let Some(conflict) = ReceiptConflict::between(&stored, receipt) else {
+                return Ok(());
+            };
+            let conflict_record = record::encode(&conflict)?;
+            self.receipt_conflicts
+                .write()
+                .await
+                .entry(conflict.witness_prefix.clone())
+                .or_default()
+                .entry(conflict.key())
+                .or_insert(conflict_record);
+            return Err(DbError::from(&conflict));
+        }
 
-        event_receipts.insert(receipt.witness_prefix.clone(), receipt.clone());
+        event_receipts.insert(receipt.witness_prefix.clone(), receipt_record);
+        self.emit(Change::from(receipt));
         Ok(())
     }
 
     async fn get_receipts(&self, event_digest: &str) -> DbResult<Vec<NontransferableReceipt>> {
         let receipts = self.receipts.read().await;
-        Ok(receipts
+        receipts
             .get(event_digest)
-            .map(|m| m.values().cloned().collect())
-            .unwrap_or_default())
+            .map(|m| m.values().map(|r| record::decode(r)).collect())
+            .unwrap_or_else(|| Ok(Vec::new()))
     }
 
     async fn get_receipt(
@@ -210,118 +519,383 @@ impl ReceiptStore for InMemoryDatabase {
         witness_prefix: &str,
     ) -> DbResult<Option<NontransferableReceipt>> {
         let receipts = self.receipts.read().await;
-        Ok(receipts
+        receipts
             .get(event_digest)
-            .and_then(|m| m.get(witness_prefix).cloned()))
+            .and_then(|m| m.get(witness_prefix))
+            .map(|r| record::decode(r))
+            .transpose()
     }
 
     async fn count_receipts(&self, event_digest: &str) -> DbResult<usize> {
         let receipts = self.receipts.read().await;
         Ok(receipts.get(event_digest).map(|m| m.len()).unwrap_or(0))
     }
+
+    async fn get_receipt_conflicts(&self, witness_prefix: &str) -> DbResult<Vec<ReceiptConflict>> {
+        let conflicts = self.receipt_conflicts.read().await;
+        conflicts
+            .get(witness_prefix)
+            .map(|m| m.values().map(|r| record::decode(r)).collect())
+            .unwrap_or_else(|| Ok(Vec::new()))
+    }
 }
 
 #[async_trait]
 impl EscrowStore for InMemoryDatabase {
-    async fn escrow_event(&self, event: &SignedEvent, reason: EscrowReason) -> DbResult<()> {
+    async fn escrow_event_with_ttl(
+        &self,
+        event: &SignedEvent,
+        reason: EscrowReason,
+        ttl_seconds: u64,
+    ) -> DbResult<()> {
+        let escrowed = EscrowedEvent::new(event.clone(), reason, ttl_seconds);
+        let stored = store_escrow(&escrowed)?;
         let mut escrows = self.escrows.write().await;
-        let escrowed = EscrowedEvent::new(event.clone(), reason, 3600); // 1 hour TTL
-        escrows.insert(event.event.digest.clone(), escrowed);
+        let previous = escrows
+            .insert(event.event.digest.clone(), stored)
+            .map(|e| load_escrow(&e))
+            .transpose()?;
+        if previous.is_none_or(|e| e.reason != reason) {
+            self.emit(Change::escrowed(event, reason));
+        }
         Ok(())
     }
 
-    async fn get_escrowed(&self, prefix: &str) -> DbResult<Vec<EscrowedEvent>> {
+    async fn record_escrow_attempt(&self, escrowed: &EscrowedEvent) -> DbResult<()> {
+        let mut escrows = self.escrows.write().await;
+        let Some(stored) = escrows.get_mut(&escrowed.event.event.digest) else {
+            return Ok(());
+        };
+        if load_escrow(stored)?.reason == escrowed.reason {
+            *stored = store_escrow(escrowed)?;
+        }
+        Ok(())
+    }
+
+    async fn get_escrowed_page(
+        &self,
+        limit: usize,
+        cursor: Option<&str>,
+    ) -> DbResult<Page<EscrowedEvent>> {
+        // The cursor is the digest of the last escrow returned
+        let start = match cursor {
+            Some(digest) => Bound::Excluded(digest.to_string()),
+            None => Bound::Unbounded,
+        };
+        let limit = limit.max(1);
+
         let escrows = self.escrows.read().await;
-        Ok(escrows
-            .values()
-            .filter(|e| e.event.event.prefix == prefix)
-            .cloned()
-            .collect())
+        let mut entries = escrows.range((start, Bound::Unbounded));
+        let items = entries
+            .by_ref()
+            .take(limit)
+            .map(|(_, e)| load_escrow(e))
+            .collect::<DbResult<Vec<EscrowedEvent>>>()?;
+        let next = match entries.next() {
+            Some(_) => items.last().map(|e| e.event.event.digest.clone()),
+            None => None,
+        };
+        Ok(Page { items, next })
     }
 
-    async fn get_all_escrowed(&self) -> DbResult<Vec<EscrowedEvent>> {
+    async fn get_escrowed_by_prefix(
+        &self,
+        prefix: &str,
+        limit: usize,
+        cursor: Option<&str>,
+    ) -> DbResult<Page<EscrowedEvent>> {
+        // The cursor is the digest of the last escrow returned
+        let start = match cursor {
+            Some(digest) => Bound::Excluded(digest.to_string()),
+            None => Bound::Unbounded,
+        };
+        let limit = limit.max(1);
+
         let escrows = self.escrows.read().await;
-        Ok(escrows.values().cloned().collect())
+        let mut matching = escrows
+            .range((start, Bound::Unbounded))
+            .map(|(_, e)| load_escrow(e))
+            .filter(|e| e.as_ref().map_or(true, |e| e.event.event.prefix == prefix));
+        let items = matching
+            .by_ref()
+            .take(limit)
+            .collect::<DbResult<Vec<EscrowedEvent>>>()?;
+        let next = match matching.next() {
+            Some(_) => items.last().map(|e| e.event.event.digest.clone()),
+            None => None,
+        };
+        Ok(Page { items, next })
+    }
+
+    async fn get_escrowed_by_reason(
+        &self,
+        reason: EscrowReason,
+        limit: usize,
+        cursor: Option<&str>,
+    ) -> DbResult<Page<EscrowedEvent>> {
+        let after = cursor.map(parse_expiry_cursor).transpose()?;
+        let limit = limit.max(1);
+
+        let mut matching: Vec<EscrowedEvent> = self
+            .escrows
+            .read()
+            .await
+            .values()
+            .map(load_escrow)
+            .collect::<DbResult<Vec<_>>>()?
+            .into_iter()
+            .filter(|e| e.reason == reason)
+            .filter(|e| after.is_none_or(|after| (e.ttl, e.event.event.digest.as_str()) > after))
+            .collect();
+        matching.sort_by(|a, b| {
+            (a.ttl, &a.event.event.digest).cmp(&(b.ttl, &b.event.event.digest))
+        });
+
+        let more = matching.len() > limit;
+        matching.truncate(limit);
+        let next = more.then(|| matching.last().map(expiry_cursor)).flatten();
+        Ok(Page {
+            items: matching,
+            next,
+        })
     }
 
     async fn promote_escrowed(&self, event_digest: &str) -> DbResult<Option<SignedEvent>> {
         let mut escrows = self.escrows.write().await;
-        Ok(escrows.remove(event_digest).map(|e| e.event))
+        let promoted = escrows
+            .remove(event_digest)
+            .map(|e| load_escrow(&e).map(|e| e.event))
+            .transpose()?;
+        if let Some(event) = &promoted {
+            self.emit(Change::escrow_removed(event));
+        }
+        Ok(promoted)
     }
 
     async fn remove_escrowed(&self, event_digest: &str) -> DbResult<()> {
         let mut escrows = self.escrows.write().await;
-        escrows.remove(event_digest);
+        if let Some(removed) = escrows.remove(event_digest) {
+            self.emit(Change::escrow_removed(&load_escrow(&removed)?.event));
+        }
         Ok(())
     }
 }
 
-#[cfg(test)]
-mod tests {
-    use super::*;
-    use kerihost_core::{EventType, IndexedSignature, KeyEvent, Threshold};
-
-    fn create_test_event(prefix: &str, sn: u64, prior_digest: Option<String>) -> SignedEvent {
-        let event = KeyEvent {
-            prefix: prefix.to_string(),
-            sn,
-            event_type: if sn == 0 {
-                EventType::Icp
-            } else {
-                EventType::Ixn
-            },
-            prior_digest,
-            signing_keys: vec!["DKey1234567890123456789012345678901234567890123".to_string()],
-            signing_threshold: Threshold::simple(1),
-            next_key_digest: Some("ENext123456789012345678901234567890123456789012".to_string()),
-            witness_threshold: Threshold::simple(1),
-            witnesses: vec![],
-            anchors: vec![],
-            witnesses_remove: vec![],
-            witnesses_add: vec![],
-            delegator: None,
-            raw: b"test".to_vec(),
-            digest: format!("EDigest{}_{}", prefix, sn),
-        };
+#[async_trait]
+impl ChangeFeed for InMemoryDatabase {
+    async fn subscribe(&self, prefix_filter: Option<&str>) -> DbResult<ChangeStream> {
+        let prefix_filter = prefix_filter.map(str::to_string);
+        let changes = futures::stream::unfold(self.changes.subscribe(), |mut receiver| async {
+            let next = match receiver.recv().await {
+                Ok(change) => Ok(change),
+                Err(RecvError::Lagged(missed)) => Err(DbError::Lagged(missed)),
+                Err(RecvError::Closed) => return None,
+            };
+            Some((next, receiver))
+        });
+        Ok(changes
+            .try_filter(move |change| {
+                future::ready(prefix_filter.as_deref().is_none_or(|p| change.prefix() == p))
+            })
+            .boxed())
+    }
+}
 
-        SignedEvent {
-            event,
-            signatures: vec![IndexedSignature {
-                index: 0,
-                signature: "AASig".to_string(),
-            }],
-        }
+#[async_trait]
+impl MigrationStore for InMemoryDatabase {
+    async fn migrate_records(&self) -> DbResult<MigrationReport> {
+        // Writers are locked out for the run, so nothing is skipped
+        let mut report = MigrationReport::default();
+        let mut states = self.states.write().await;
+        migrate_rows::<KeyState>(states.values_mut(), &mut report)?;
+        let mut receipts = self.receipts.write().await;
+        migrate_rows::<NontransferableReceipt>(
+            receipts.values_mut().flat_map(|m| m.values_mut()),
+            &mut report,
+        )?;
+        let mut escrows = self.escrows.write().await;
+        migrate_rows::<EscrowedEvent>(escrows.values_mut().map(|(json, _, _)| json), &mut report)?;
+        Ok(report)
     }
+}
 
-    fn create_test_state(prefix: &str, sn: u64) -> KeyState {
-        KeyState {
-            prefix: prefix.to_string(),
-            sn,
-            latest_digest: format!("EDigest{}_{}", prefix, sn),
-            signing_keys: vec!["DKey1".to_string()],
-            signing_threshold: Threshold::simple(1),
-            next_key_digest: Some("ENext123".to_string()),
-            witnesses: vec![],
-            witness_threshold: Threshold::simple(0),
-            delegator: None,
-            config: vec![],
-            transferable: true,
-            metadata: kerihost_core::HonestMetadata::local_only(0),
-        }
+#[async_trait]
+impl SchemaStore for InMemoryDatabase {
+    async fn put_schema(&self, schema: &CredentialSchema) -> DbResult<()> {
+        let mut schemas = self.schemas.write().await;
+        schemas
+            .entry(schema.said.clone())
+            .or_insert_with(|| schema.clone());
+        Ok(())
+    }
+
+    async fn get_schema(&self, said: &str) -> DbResult<Option<CredentialSchema>> {
+        let schemas = self.schemas.read().await;
+        Ok(schemas.get(said).cloned())
     }
+}
 
-    fn create_test_receipt(event_digest: &str, witness: &str) -> NontransferableReceipt {
-        NontransferableReceipt {
-            event_digest: event_digest.to_string(),
-            event_sn: 0,
-            event_prefix: "DTest123".to_string(),
-            witness_prefix: witness.to_string(),
-            signature: "0BSig123".to_string(),
+#[async_trait]
+impl ExchangeStore for InMemoryDatabase {
+    async fn put_exchange(
+        &self,
+        exchange: &IpexExchange,
+        message: &ExchangeMessage,
+    ) -> DbResult<()> {
+        let mut messages = self.messages.write().await;
+        let mut exchanges = self.exchanges.write().await;
+        let stored = exchanges.get(&exchange.id).map(|e| e.last_said());
+        if stored != message.prior.as_deref() {
+            return Err(DbError::StateConflict(format!(
+                "Exchange {} changed before message {}",
+                exchange.id, message.said
+            )));
         }
+        messages.insert(
+            message.said.clone(),
+            (message.clone(), exchange.id.clone()),
+        );
+        exchanges.insert(exchange.id.clone(), exchange.clone());
+        Ok(())
     }
 
+    async fn get_exchange(&self, id: &str) -> DbResult<Option<IpexExchange>> {
+        let exchanges = self.exchanges.read().await;
+        Ok(exchanges.get(id).cloned())
+    }
+
+    async fn get_message(&self, said: &str) -> DbResult<Option<ExchangeMessage>> {
+        let messages = self.messages.read().await;
+        Ok(messages.get(said).map(|(m, _)| m.clone()))
+    }
+
+    async fn get_exchange_by_message(&self, said: &str) -> DbResult<Option<IpexExchange>> {
+        let messages = self.messages.read().await;
+        let exchanges = self.exchanges.read().await;
+        Ok(messages
+            .get(said)
+            .and_then(|(_, id)| exchanges.get(id).cloned()))
+    }
+
+    async fn escrow_message_with_ttl(
+        &self,
+        message: &ExchangeMessage,
+        ttl_seconds: u64,
+    ) -> DbResult<()> {
+        let mut escrows = self.message_escrows.write().await;
+        let escrowed = EscrowedMessage::new(message.clone(), ttl_seconds);
+        escrows.insert(message.said.clone(), escrowed);
+        Ok(())
+    }
+
+    async fn get_escrowed_messages(&self, prior: &str) -> DbResult<Vec<EscrowedMessage>> {
+        let escrows = self.message_escrows.read().await;
+        Ok(escrows
+            .values()
+            .filter(|e| e.message.prior.as_deref() == Some(prior))
+            .cloned()
+            .collect())
+    }
+
+    async fn get_escrowed_messages_page(
+        &self,
+        limit: usize,
+        cursor: Option<&str>,
+    ) -> DbResult<Page<EscrowedMessage>> {
+        // The cursor is the SAID of the last message returned
+        let start = match cursor {
+            Some(said) => Bound::Excluded(said.to_string()),
+            None => Bound::Unbounded,
+        };
+        let limit = limit.max(1);
+
+        let escrows = self.message_escrows.read().await;
+        let mut entries = escrows.range((start, Bound::Unbounded));
+        let items: Vec<EscrowedMessage> = entries
+            .by_ref()
+            .take(limit)
+            .map(|(_, e)| e.clone())
+            .collect();
+        let next = match entries.next() {
+            Some(_) => items.last().map(|e| e.message.said.clone()),
+            None => None,
+        };
+        Ok(Page { items, next })
+    }
+
+    async fn promote_escrowed_message(&self, said: &str) -> DbResult<Option<ExchangeMessage>> {
+        let mut escrows = self.message_escrows.write().await;
+        Ok(escrows.remove(said).map(|e| e.message))
+    }
+
+    async fn remove_escrowed_message(&self, said: &str) -> DbResult<()> {
+        let mut escrows = self.message_escrows.write().await;
+        escrows.remove(said);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::*;
+    use cesride::Matter;
+    use kerihost_core::{Anchor, EventValidator, ValidationResult};
+
     // KEL Store Tests
 
+    crate::conformance::conformance_tests!(Some(InMemoryDatabase::new()));
+
+    #[tokio::test]
+    async fn test_tenant_isolation() {
+        crate::conformance::tenant_isolation(&InMemoryDatabase::new()).await;
+    }
+
+    #[tokio::test]
+    async fn test_exchange_store() {
+        crate::conformance::exchange_store(&InMemoryDatabase::new()).await;
+    }
+
+    #[tokio::test]
+    async fn test_tenant_schemas_exchanges_and_changes() {
+        let db = InMemoryDatabase::new();
+        let alpha = db.for_tenant("alpha").unwrap();
+        let mut changes = db.subscribe(None).await.unwrap();
+        let mut alpha_changes = alpha.subscribe(None).await.unwrap();
+
+        let raw = kerihost_core::saidify(
+            &serde_json::json!({"$id": "", "title": "Test", "type": "object"}),
+            "$id",
+        )
+        .unwrap();
+        let schema = CredentialSchema::from_value(raw).unwrap();
+        alpha.put_schema(&schema).await.unwrap();
+        assert!(db.get_schema(&schema.said).await.unwrap().is_none());
+
+        let apply = create_test_message("EHolder", "/ipex/apply", None);
+        let exchange = IpexExchange::start(&apply).unwrap();
+        alpha.put_exchange(&exchange, &apply).await.unwrap();
+        let offer = create_test_message("EIssuer", "/ipex/offer", Some("EApply"));
+        alpha.escrow_message(&offer).await.unwrap();
+        assert!(db.get_exchange(&exchange.id).await.unwrap().is_none());
+        assert!(db.get_escrowed_messages("EApply").await.unwrap().is_empty());
+        assert!(db.get_all_escrowed_messages().await.unwrap().is_empty());
+
+        // Changes go only to the namespace's subscribers
+        let event = create_test_event("DTest123", 0, None);
+        alpha.append_event(&event).await.unwrap();
+        db.append_event(&create_test_event("DOther", 0, None))
+            .await
+            .unwrap();
+        assert_eq!(alpha_changes.next().await.unwrap().unwrap(), Change::accepted(&event));
+        assert_eq!(changes.next().await.unwrap().unwrap().prefix(), "DOther");
+
+        // Clearing a namespace leaves the others
+        alpha.clear().await;
+        assert!(alpha.get_event("DTest123", 0).await.unwrap().is_none());
+        assert!(db.get_event("DOther", 0).await.unwrap().is_some());
+    }
+
     #[tokio::test]
     async fn test_kel_append_inception() {
         let db = InMemoryDatabase::new();
@@ -401,6 +975,73 @@ mod tests {
         assert_eq!(range.len(), 2);
     }
 
+    #[tokio::test]
+    async fn test_kel_get_events_page() {
+        let db = InMemoryDatabase::new();
+        let events = append_test_kel(&db, "DTest123", 5).await;
+
+        let first = db
+            .get_events_page("DTest123", 0, None, 2, None)
+            .await
+            .unwrap();
+        assert_eq!(first.items.len(), 2);
+        assert_eq!(first.next.as_deref(), Some("2"));
+
+        let second = db
+            .get_events_page("DTest123", 0, None, 2, first.next.as_deref())
+            .await
+            .unwrap();
+        assert_eq!(second.items[0].event.digest, events[2].event.digest);
+
+        let last = db
+            .get_events_page("DTest123", 0, None, 2, second.next.as_deref())
+            .await
+            .unwrap();
+        assert_eq!(last.items.len(), 1);
+        assert!(last.next.is_none());
+
+        // The end of the range bounds the last page
+        let bounded = db
+            .get_events_page("DTest123", 1, Some(2), 2, None)
+            .await
+            .unwrap();
+        assert_eq!(bounded.items.len(), 2);
+        assert!(bounded.next.is_none());
+
+        let result = db.get_events_page("DTest123", 0, None, 2, Some("zz")).await;
+        assert!(matches!(result, Err(DbError::InvalidCursor(_))));
+    }
+
+    #[tokio::test]
+    async fn test_first_seen_log() {
+        let db = InMemoryDatabase::new();
+        let events = append_test_kel(&db, "DTest123", 3).await;
+
+        let entries = db.get_first_seen_range("DTest123", 0, None).await.unwrap();
+        assert_eq!(entries.len(), 3);
+        for (ordinal, (entry, event)) in entries.iter().zip(&events).enumerate() {
+            assert_eq!(entry.ordinal, ordinal as u64);
+            assert_eq!(entry.digest, event.event.digest);
+        }
+        assert!(entries[0].datetime <= entries[2].datetime);
+
+        let range = db.get_first_seen_range("DTest123", 1, Some(1)).await.unwrap();
+        assert_eq!(range.len(), 1);
+        assert_eq!(range[0].sn, 1);
+
+        let entry = db
+            .get_first_seen("DTest123", &events[2].event.digest)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(entry.ordinal, 2);
+
+        // A rejected append records nothing
+        let _ = db.append_event(&events[1]).await;
+        assert_eq!(db.get_first_seen_range("DTest123", 0, None).await.unwrap().len(), 3);
+        assert!(db.get_first_seen("DOther", &events[0].event.digest).await.unwrap().is_none());
+    }
+
     #[tokio::test]
     async fn test_kel_get_latest() {
         let db = InMemoryDatabase::new();
@@ -416,6 +1057,77 @@ mod tests {
         assert_eq!(latest.unwrap().event.sn, 1);
     }
 
+    #[tokio::test]
+    async fn test_kel_roundtrip_preserves_signatures() {
+        let db = InMemoryDatabase::new();
+        let key = test_signer().verfer().qb64().unwrap();
+
+        let icp = create_test_event(&key, 0, None);
+        db.append_event(&icp).await.unwrap();
+
+        let stored = db.get_event(&key, 0).await.unwrap().unwrap();
+        assert_eq!(stored.event.raw, icp.event.raw);
+        assert_eq!(stored.to_cesr().unwrap(), icp.to_cesr().unwrap());
+        assert_eq!(
+            EventValidator::validate(&stored, None).unwrap(),
+            ValidationResult::Valid
+        );
+    }
+
+    #[tokio::test]
+    async fn test_commit_event() {
+        let db = InMemoryDatabase::new();
+
+        let icp = create_test_event("DTest123", 0, None);
+        let mut state = create_test_state("DTest123", 0);
+        state.latest_digest = icp.event.digest.clone();
+        db.commit_event(&icp, &state).await.unwrap();
+        let ixn = create_test_event("DTest123", 1, Some(icp.event.digest.clone()));
+        db.commit_event(&ixn, &create_test_state("DTest123", 1))
+            .await
+            .unwrap();
+
+        assert_eq!(db.event_count("DTest123").await, 2);
+        assert_eq!(db.get_state("DTest123").await.unwrap().unwrap().sn, 1);
+    }
+
+    #[tokio::test]
+    async fn test_commit_event_state_conflict() {
+        let db = InMemoryDatabase::new();
+
+        let icp = create_test_event("DTest123", 0, None);
+        db.append_event(&icp).await.unwrap();
+
+        // State was never written for the inception, so sn 1 cannot commit
+        let ixn = create_test_event("DTest123", 1, Some(icp.event.digest.clone()));
+        let result = db
+            .commit_event(&ixn, &create_test_state("DTest123", 1))
+            .await;
+        assert!(matches!(result, Err(DbError::StateConflict(_))));
+
+        // Neither write happened
+        assert_eq!(db.event_count("DTest123").await, 1);
+        assert!(db.get_state("DTest123").await.unwrap().is_none());
+    }
+
+    #[tokio::test]
+    async fn test_commit_event_stale_digest() {
+        let db = InMemoryDatabase::new();
+
+        let icp = create_test_event("DTest123", 0, None);
+        let mut state = create_test_state("DTest123", 0);
+        state.latest_digest = icp.event.digest.clone();
+        db.commit_event(&icp, &state).await.unwrap();
+
+        // Right sn, but built on a different prior event
+        let ixn = create_test_event("DTest123", 1, Some("EOtherInception".to_string()));
+        let result = db
+            .commit_event(&ixn, &create_test_state("DTest123", 1))
+            .await;
+        assert!(matches!(result, Err(DbError::StateConflict(ref e)) if e.contains("EOtherInception")));
+        assert!(result.unwrap_err().is_retryable());
+    }
+
     #[tokio::test]
     async fn test_kel_get_by_digest() {
         let db = InMemoryDatabase::new();
@@ -436,6 +1148,42 @@ mod tests {
         assert!(not_found.is_none());
     }
 
+    #[tokio::test]
+    async fn test_kel_find_anchor() {
+        let db = InMemoryDatabase::new();
+
+        let icp = create_test_event("DTest123", 0, None);
+        db.append_event(&icp).await.unwrap();
+
+        let ixn = create_anchoring_event(
+            "DTest123",
+            1,
+            Some(icp.event.digest.clone()),
+            vec![
+                Anchor::digest("ECredential"),
+                Anchor::event("EDelegate", "b", "EDelegated"),
+            ],
+        );
+        db.append_event(&ixn).await.unwrap();
+
+        let location = db.find_anchor("ECredential").await.unwrap().unwrap();
+        assert_eq!(location.prefix, "DTest123");
+        assert_eq!(location.sn, 1);
+        assert_eq!(location.event_digest, ixn.event.digest);
+        assert!(db.find_anchor("ENotAnchored").await.unwrap().is_none());
+
+        let seal = db
+            .find_event_seal("EDelegate", 11, "EDelegated")
+            .await
+            .unwrap();
+        assert_eq!(seal.map(|l| l.sn), Some(1));
+        assert!(db
+            .find_event_seal("EDelegate", 12, "EDelegated")
+            .await
+            .unwrap()
+            .is_none());
+    }
+
     // State Store Tests
 
     #[tokio::test]
@@ -575,6 +1323,66 @@ mod tests {
         assert_eq!(all.len(), 2);
     }
 
+    #[tokio::test]
+    async fn test_escrow_get_page() {
+        let db = InMemoryDatabase::new();
+        for i in 0..5 {
+            let event = create_test_event(&format!("DTest{}", i), 5, Some("EP".to_string()));
+            db.escrow_event(&event, EscrowReason::OutOfOrder)
+                .await
+                .unwrap();
+        }
+
+        let first = db.get_escrowed_page(3, None).await.unwrap();
+        assert_eq!(first.items.len(), 3);
+        assert!(first.next.is_some());
+
+        // Removing a returned escrow does not disturb the next page
+        db.remove_escrowed(&first.items[0].event.event.digest)
+            .await
+            .unwrap();
+        let second = db
+            .get_escrowed_page(3, first.next.as_deref())
+            .await
+            .unwrap();
+        assert_eq!(second.items.len(), 2);
+        assert!(second.next.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_escrow_get_by_reason() {
+        let db = InMemoryDatabase::new();
+        for i in 0..3 {
+            let event = create_test_event(&format!("DTest{}", i), 5, Some("EP".to_string()));
+            db.escrow_event(&event, EscrowReason::OutOfOrder)
+                .await
+                .unwrap();
+        }
+        let signed = create_test_event("DSigned", 1, Some("EP".to_string()));
+        db.escrow_event(&signed, EscrowReason::PartiallySigned)
+            .await
+            .unwrap();
+
+        let first = db
+            .get_escrowed_by_reason(EscrowReason::OutOfOrder, 2, None)
+            .await
+            .unwrap();
+        assert_eq!(first.items.len(), 2);
+        let second = db
+            .get_escrowed_by_reason(EscrowReason::OutOfOrder, 2, first.next.as_deref())
+            .await
+            .unwrap();
+        assert_eq!(second.items.len(), 1);
+        assert!(second.next.is_none());
+
+        let partial = db
+            .get_escrowed_by_reason(EscrowReason::PartiallySigned, 10, None)
+            .await
+            .unwrap();
+        assert_eq!(partial.items.len(), 1);
+        assert_eq!(partial.items[0].event.event.digest, signed.event.digest);
+    }
+
     #[tokio::test]
     async fn test_escrow_promote() {
         let db = InMemoryDatabase::new();
@@ -605,6 +1413,63 @@ mod tests {
         assert!(remaining.is_empty());
     }
 
+    // Schema Store Tests
+
+    #[tokio::test]
+    async fn test_schema_put_get() {
+        let db = InMemoryDatabase::new();
+        let raw = kerihost_core::saidify(
+            &serde_json::json!({"$id": "", "title": "Test", "type": "object"}),
+            "$id",
+        )
+        .unwrap();
+        let schema = CredentialSchema::from_value(raw).unwrap();
+
+        db.put_schema(&schema).await.unwrap();
+        db.put_schema(&schema).await.unwrap();
+
+        let retrieved = db.get_schema(&schema.said).await.unwrap();
+        assert_eq!(retrieved, Some(schema));
+        assert!(db.get_schema("ENotExist").await.unwrap().is_none());
+    }
+
+    // Exchange Store Tests
+
+    fn create_test_message(sender: &str, route: &str, prior: Option<&str>) -> ExchangeMessage {
+        ExchangeMessage::new(sender, None, route, prior, serde_json::json!({}), None).unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_exchange_put_get() {
+        let db = InMemoryDatabase::new();
+        let apply = create_test_message("EHolder", "/ipex/apply", None);
+        let exchange = IpexExchange::start(&apply).unwrap();
+
+        db.put_exchange(&exchange, &apply).await.unwrap();
+
+        assert_eq!(db.get_exchange(&exchange.id).await.unwrap(), Some(exchange.clone()));
+        assert_eq!(db.get_message(&apply.said).await.unwrap(), Some(apply.clone()));
+        assert_eq!(
+            db.get_exchange_by_message(&apply.said).await.unwrap(),
+            Some(exchange)
+        );
+    }
+
+    #[tokio::test]
+    async fn test_exchange_escrow_by_prior() {
+        let db = InMemoryDatabase::new();
+        let offer = create_test_message("EIssuer", "/ipex/offer", Some("EApply"));
+
+        db.escrow_message(&offer).await.unwrap();
+
+        assert_eq!(db.get_escrowed_messages("EApply").await.unwrap().len(), 1);
+        assert!(db.get_escrowed_messages("EOther").await.unwrap().is_empty());
+
+        let promoted = db.promote_escrowed_message(&offer.said).await.unwrap();
+        assert_eq!(promoted, Some(offer));
+        assert!(db.get_all_escrowed_messages().await.unwrap().is_empty());
+    }
+
     // Database Clear Test
 
     #[tokio::test]
@@ -637,4 +1502,116 @@ mod tests {
         let retrieved = db2.get_event("DTest123", 0).await.unwrap();
         assert!(retrieved.is_some());
     }
+
+    // Migration Test
+
+    #[tokio::test]
+    async fn test_migrate_records() {
+        let db = InMemoryDatabase::new();
+        let state = create_test_state("DTest123", 2);
+        let receipt = create_test_receipt("EDigest123", "BWitness1");
+        let event = create_test_event("DTest123", 5, Some("EPrior".to_string()));
+        let escrowed = EscrowedEvent::new(event.clone(), EscrowReason::OutOfOrder, 3600);
+
+        // Rows as written before records were versioned
+        db.states.write().await.insert(
+            "DTest123".to_string(),
+            serde_json::to_string(&state).unwrap(),
+        );
+        db.receipts.write().await.entry("EDigest123".to_string()).or_default().insert(
+            "BWitness1".to_string(),
+            serde_json::to_string(&receipt).unwrap(),
+        );
+        db.escrows.write().await.insert(
+            event.event.digest.clone(),
+            (
+                serde_json::to_string(&escrowed).unwrap(),
+                event.event.raw.clone(),
+                Vec::new(),
+            ),
+        );
+        db.put_state(&create_test_state("DOther", 0)).await.unwrap();
+
+        // Old rows read the same before migrating
+        assert_eq!(db.get_state("DTest123").await.unwrap().unwrap().sn, 2);
+
+        let report = db.migrate_records().await.unwrap();
+        assert_eq!(
+            report,
+            MigrationReport {
+                scanned: 4,
+                migrated: 3,
+                skipped: 0,
+            }
+        );
+        assert!(!record::is_outdated::<KeyState>(&db.states.read().await["DTest123"]).unwrap());
+
+        assert_eq!(db.get_state("DTest123").await.unwrap().unwrap().sn, 2);
+        let stored = db.get_receipt("EDigest123", "BWitness1").await.unwrap().unwrap();
+        assert_eq!(stored.signature, receipt.signature);
+        let escrows = db.get_all_escrowed().await.unwrap();
+        assert_eq!(escrows[0].event.event.raw, event.event.raw);
+        assert_eq!(escrows[0].reason, EscrowReason::OutOfOrder);
+
+        let report = db.migrate_records().await.unwrap();
+        assert_eq!(report.migrated, 0);
+    }
+
+    #[tokio::test]
+    async fn test_subscribe_changes() {
+        let db = InMemoryDatabase::new();
+        let mut all = db.subscribe(None).await.unwrap();
+        let mut filtered = db.subscribe(Some("DTest123")).await.unwrap();
+
+        let icp = create_test_event("DTest123", 0, None);
+        let other = create_test_event("DOther", 0, None);
+        let ixn = create_test_event("DTest123", 1, Some(icp.event.digest.clone()));
+        let receipt = create_test_receipt(&icp.event.digest, "BWitness1");
+        db.append_event(&icp).await.unwrap();
+        db.append_event(&other).await.unwrap();
+        db.add_receipt(&receipt).await.unwrap();
+        db.escrow_event(&ixn, EscrowReason::PartiallySigned)
+            .await
+            .unwrap();
+        // Re-escrowing for the same reason is not a transition
+        db.escrow_event(&ixn, EscrowReason::PartiallySigned)
+            .await
+            .unwrap();
+        db.promote_escrowed(&ixn.event.digest).await.unwrap();
+        db.remove_escrowed(&ixn.event.digest).await.unwrap();
+
+        let expected = vec![
+            Change::accepted(&icp),
+            Change::accepted(&other),
+            Change::from(&receipt),
+            Change::escrowed(&ixn, EscrowReason::PartiallySigned),
+            Change::escrow_removed(&ixn),
+        ];
+        let received: Vec<Change> = all.by_ref().take(5).try_collect().await.unwrap();
+        assert_eq!(received, expected);
+
+        let received: Vec<Change> = filtered.by_ref().take(4).try_collect().await.unwrap();
+        let mut expected = expected;
+        expected.remove(1);
+        assert_eq!(received, expected);
+    }
+
+    #[tokio::test]
+    async fn test_subscribe_reports_lag() {
+        let db = InMemoryDatabase::new();
+        let mut changes = db.subscribe(None).await.unwrap();
+        for i in 0..CHANGE_BUFFER + 1 {
+            let receipt = create_test_receipt("EDigest123", &format!("BWitness{}", i));
+            db.add_receipt(&receipt).await.unwrap();
+        }
+
+        assert!(matches!(
+            changes.next().await,
+            Some(Err(DbError::Lagged(1)))
+        ));
+        assert!(matches!(
+            changes.next().await,
+            Some(Ok(Change::ReceiptAdded { .. }))
+        ));
+    }
 }
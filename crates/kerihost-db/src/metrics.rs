@@ -0,0 +1,301 @@
+//! Storage metrics and their Prometheus exposition
+//!
+//! [`DbMetrics`] collects what `InstrumentedDatabase` measures, by
+//! operation: calls, errors by category, latency, items returned and
+//! consumed DynamoDB capacity. [`DbMetrics::render`] writes them in the
+//! Prometheus text format, and with the `prometheus` feature
+//! [`serve_metrics`] serves that at `GET /metrics` for self-hosted servers
+//! to be scraped.
+
+use std::collections::BTreeMap;
+use std::fmt::Write;
+use std::sync::Mutex;
+use std::time::Duration;
+
+/// Content type of [`DbMetrics::render`]'s output
+pub const CONTENT_TYPE: &str = "text/plain; version=0.0.4; charset=utf-8";
+
+/// Upper bounds of the latency histogram buckets, in seconds
+const LATENCY_BUCKETS: [f64; 12] = [
+    0.001, 0.0025, 0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0,
+];
+
+/// What has been measured for one operation
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct OperationMetrics {
+    /// Calls made, failed or not
+    pub calls: u64,
+    /// Failed calls by `DbError::category`
+    pub errors: BTreeMap<&'static str, u64>,
+    /// Time spent in all calls
+    pub latency: Duration,
+    /// Items returned by successful calls
+    pub items: u64,
+    /// DynamoDB capacity units consumed
+    pub consumed_capacity: f64,
+    /// Calls per latency bucket, not cumulative
+    buckets: [u64; LATENCY_BUCKETS.len()],
+}
+
+/// Registry of storage measurements
+#[derive(Debug, Default)]
+pub struct DbMetrics {
+    operations: Mutex<BTreeMap<&'static str, OperationMetrics>>,
+}
+
+impl DbMetrics {
+    /// Create an empty registry
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Record one call
+    pub fn observe(
+        &self,
+        operation: &'static str,
+        latency: Duration,
+        items: Option<usize>,
+        consumed_capacity: f64,
+        error: Option<&'static str>,
+    ) {
+        let mut operations = self.lock();
+        let metrics = operations.entry(operation).or_default();
+        metrics.calls += 1;
+        metrics.latency += latency;
+        metrics.items += items.unwrap_or(0) as u64;
+        metrics.consumed_capacity += consumed_capacity;
+        if let Some(category) = error {
+            *metrics.errors.entry(category).or_default() += 1;
+        }
+        let seconds = latency.as_secs_f64();
+        if let Some(bucket) = LATENCY_BUCKETS.iter().position(|le| seconds <= *le) {
+            metrics.buckets[bucket] += 1;
+        }
+    }
+
+    /// What has been measured for an operation, if it was ever called
+    pub fn operation(&self, operation: &str) -> Option<OperationMetrics> {
+        self.lock().get(operation).cloned()
+    }
+
+    /// Write all measurements in the Prometheus text format
+    pub fn render(&self) -> String {
+        let operations = self.lock();
+        let mut out = String::new();
+
+        family(
+            &mut out,
+            "kerihost_db_calls_total",
+            "counter",
+            "Storage calls made",
+        );
+        for (op, m) in operations.iter() {
+            let _ = writeln!(
+                out,
+                "kerihost_db_calls_total{{operation=\"{op}\"}} {}",
+                m.calls
+            );
+        }
+
+        family(
+            &mut out,
+            "kerihost_db_errors_total",
+            "counter",
+            "Failed storage calls",
+        );
+        for (op, m) in operations.iter() {
+            for (category, count) in &m.errors {
+                let _ = writeln!(
+                    out,
+                    "kerihost_db_errors_total{{operation=\"{op}\",category=\"{category}\"}} {count}"
+                );
+            }
+        }
+
+        family(
+            &mut out,
+            "kerihost_db_call_duration_seconds",
+            "histogram",
+            "Storage call latency",
+        );
+        for (op, m) in operations.iter() {
+            let mut cumulative = 0;
+            for (le, count) in LATENCY_BUCKETS.iter().zip(m.buckets) {
+                cumulative += count;
+                let _ = writeln!(
+                    out,
+                    "kerihost_db_call_duration_seconds_bucket{{operation=\"{op}\",le=\"{le}\"}} {cumulative}"
+                );
+            }
+            let _ = writeln!(
+                out,
+                "kerihost_db_call_duration_seconds_bucket{{operation=\"{op}\",le=\"+Inf\"}} {}",
+                m.calls
+            );
+            let _ = writeln!(
+                out,
+                "kerihost_db_call_duration_seconds_sum{{operation=\"{op}\"}} {}",
+                m.latency.as_secs_f64()
+            );
+            let _ = writeln!(
+                out,
+                "kerihost_db_call_duration_seconds_count{{operation=\"{op}\"}} {}",
+                m.calls
+            );
+        }
+
+        family(
+            &mut out,
+            "kerihost_db_items_total",
+            "counter",
+            "Items returned by storage calls",
+        );
+        for (op, m) in operations.iter() {
+            let _ = writeln!(
+                out,
+                "kerihost_db_items_total{{operation=\"{op}\"}} {}",
+                m.items
+            );
+        }
+
+        family(
+            &mut out,
+            "kerihost_db_consumed_capacity_units_total",
+            "counter",
+            "DynamoDB capacity units consumed by storage calls",
+        );
+        for (op, m) in operations.iter() {
+            let _ = writeln!(
+                out,
+                "kerihost_db_consumed_capacity_units_total{{operation=\"{op}\"}} {}",
+                m.consumed_capacity
+            );
+        }
+
+        out
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, BTreeMap<&'static str, OperationMetrics>> {
+        self.operations.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+/// Write a metric family's `HELP` and `TYPE` lines
+fn family(out: &mut String, name: &str, kind: &str, help: &str) {
+    let _ = writeln!(out, "# HELP {name} {help}");
+    let _ = writeln!(out, "# TYPE {name} {kind}");
+}
+
+/// Serve `metrics` at `GET /metrics` on `listener` until it fails
+///
+/// A minimal HTTP/1.1 responder for a scraper, not a general server: each
+/// connection gets one response and is closed.
+#[cfg(feature = "prometheus")]
+pub async fn serve_metrics(
+    metrics: std::sync::Arc<DbMetrics>,
+    listener: tokio::net::TcpListener,
+) -> std::io::Result<()> {
+    use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+
+    loop {
+        let (stream, _) = listener.accept().await?;
+        let metrics = std::sync::Arc::clone(&metrics);
+        tokio::spawn(async move {
+            let mut stream = BufReader::new(stream);
+            let mut request_line = String::new();
+            stream.read_line(&mut request_line).await?;
+            // Drain the headers; there is no body to read
+            let mut header = String::new();
+            while stream.read_line(&mut header).await? > 2 {
+                header.clear();
+            }
+
+            let mut parts = request_line.split_whitespace();
+            let response = match (parts.next(), parts.next()) {
+                (Some("GET"), Some("/metrics")) => {
+                    let body = metrics.render();
+                    format!(
+                        "HTTP/1.1 200 OK\r\nContent-Type: {CONTENT_TYPE}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{body}",
+                        body.len()
+                    )
+                }
+                _ => "HTTP/1.1 404 Not Found\r\nContent-Length: 0\r\nConnection: close\r\n\r\n"
+                    .to_string(),
+            };
+            stream.get_mut().write_all(response.as_bytes()).await?;
+            stream.get_mut().shutdown().await?;
+            Ok::<_, std::io::Error>(())
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_render_exposition() {
+        let metrics = DbMetrics::new();
+        metrics.observe("get_state", Duration::from_millis(3), Some(1), 0.5, None);
+        metrics.observe("get_state", Duration::from_millis(30), Some(0), 0.5, None);
+        metrics.observe(
+            "put_state",
+            Duration::from_secs(10),
+            None,
+            1.0,
+            Some("conflict"),
+        );
+
+        let text = metrics.render();
+        assert!(text.contains("# TYPE kerihost_db_calls_total counter\n"));
+        assert!(text.contains("kerihost_db_calls_total{operation=\"get_state\"} 2\n"));
+        assert!(text.contains(
+            "kerihost_db_errors_total{operation=\"put_state\",category=\"conflict\"} 1\n"
+        ));
+        assert!(text.contains(
+            "kerihost_db_call_duration_seconds_bucket{operation=\"get_state\",le=\"0.005\"} 1\n"
+        ));
+        assert!(text.contains(
+            "kerihost_db_call_duration_seconds_bucket{operation=\"get_state\",le=\"0.05\"} 2\n"
+        ));
+        // Slower than the last bucket: counted only under +Inf
+        assert!(text.contains(
+            "kerihost_db_call_duration_seconds_bucket{operation=\"put_state\",le=\"5\"} 0\n"
+        ));
+        assert!(text.contains(
+            "kerihost_db_call_duration_seconds_bucket{operation=\"put_state\",le=\"+Inf\"} 1\n"
+        ));
+        assert!(text.contains("kerihost_db_items_total{operation=\"get_state\"} 1\n"));
+        assert!(
+            text.contains("kerihost_db_consumed_capacity_units_total{operation=\"get_state\"} 1\n")
+        );
+    }
+
+    #[cfg(feature = "prometheus")]
+    #[tokio::test]
+    async fn test_serve_metrics() {
+        use tokio::io::{AsyncReadExt, AsyncWriteExt};
+        use tokio::net::{TcpListener, TcpStream};
+
+        let metrics = std::sync::Arc::new(DbMetrics::new());
+        metrics.observe("get_state", Duration::from_millis(1), Some(1), 0.0, None);
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(serve_metrics(metrics, listener));
+
+        async fn get(addr: std::net::SocketAddr, path: &str) -> String {
+            let mut stream = TcpStream::connect(addr).await.unwrap();
+            let request = format!("GET {path} HTTP/1.1\r\nHost: localhost\r\n\r\n");
+            stream.write_all(request.as_bytes()).await.unwrap();
+            let mut response = String::new();
+            stream.read_to_string(&mut response).await.unwrap();
+            response
+        }
+
+        let response = get(addr, "/metrics").await;
+        assert!(response.starts_with("HTTP/1.1 200 OK\r\n"));
+        assert!(response.contains(CONTENT_TYPE));
+        assert!(response.contains("kerihost_db_calls_total{operation=\"get_state\"} 1\n"));
+        assert!(get(addr, "/").await.starts_with("HTTP/1.1 404"));
+    }
+}
@@ -0,0 +1,373 @@
+//! KEL storage implementation for PostgreSQL
+
+use super::states::upsert_state;
+use super::{notify_escrow, PostgresDatabase};
+use crate::error::{DbError, DbResult};
+use crate::traits::{AnchorLocation, FirstSeen, KelStore, Page};
+use async_trait::async_trait;
+use kerihost_core::{KeyState, SignedEvent};
+use sqlx::PgConnection;
+
+/// Rebuild a signed event from its stored CESR
+pub(super) fn decode_event(cesr: &[u8]) -> DbResult<SignedEvent> {
+    SignedEvent::from_cesr(cesr).map_err(|e| DbError::Corruption(e.to_string()))
+}
+
+/// Parse an anchor index row
+fn parse_anchor(
+    (prefix, sn, event_digest, seal): (String, i64, String, String),
+) -> DbResult<AnchorLocation> {
+    Ok(AnchorLocation {
+        prefix,
+        sn: sn as u64,
+        event_digest,
+        seal: serde_json::from_str(&seal)?,
+    })
+}
+
+/// Parse a first-seen log row
+fn parse_first_seen(
+    (prefix, ordinal, sn, digest, datetime): (String, i64, i64, String, String),
+) -> FirstSeen {
+    FirstSeen {
+        prefix,
+        ordinal: ordinal as u64,
+        sn: sn as u64,
+        digest,
+        datetime,
+    }
+}
+
+/// Append an event, its first-seen entry and its anchors inside an open
+/// write transaction
+async fn append_locked(
+    conn: &mut PgConnection,
+    tenant: &str,
+    event: &SignedEvent,
+    first_seen_at: &str,
+) -> DbResult<()> {
+    let prefix = &event.event.prefix;
+    let sn = event.event.sn;
+
+    if sn == 0 {
+        // For inception, check that no events exist
+        let existing = sqlx::query("SELECT 1 FROM kel WHERE tenant = $1 AND aid = $2 LIMIT 1")
+            .bind(tenant)
+            .bind(prefix)
+            .fetch_optional(&mut *conn)
+            .await?;
+        if existing.is_some() {
+            return Err(DbError::Duplicate(format!(
+                "Inception already exists for {}",
+                prefix
+            )));
+        }
+    } else {
+        // For non-inception, verify prior digest
+        let prior_sn = sn - 1;
+        let prior_digest: Option<String> =
+            sqlx::query_scalar("SELECT digest FROM kel WHERE tenant = $1 AND aid = $2 AND sn = $3")
+                .bind(tenant)
+                .bind(prefix)
+                .bind(prior_sn as i64)
+                .fetch_optional(&mut *conn)
+                .await?;
+
+        let Some(prior_digest) = prior_digest else {
+            return Err(DbError::NotFound(format!(
+                "Prior event at sn {} not found for {}",
+                prior_sn, prefix
+            )));
+        };
+        match &event.event.prior_digest {
+            Some(event_prior) if *event_prior != prior_digest => {
+                return Err(DbError::PriorDigestMismatch {
+                    expected: prior_digest,
+                    actual: event_prior.clone(),
+                });
+            }
+            Some(_) => {}
+            None => {
+                return Err(DbError::Other(
+                    "Non-inception event missing prior digest".to_string(),
+                ));
+            }
+        }
+    }
+
+    // The original CESR is the canonical record
+    let cesr = event
+        .to_cesr()
+        .map_err(|e| DbError::Serialization(e.to_string()))?;
+
+    sqlx::query(
+        "INSERT INTO kel (tenant, aid, sn, digest, prior_digest, cesr) \
+         VALUES ($1, $2, $3, $4, $5, $6)",
+    )
+    .bind(tenant)
+    .bind(prefix)
+    .bind(sn as i64)
+    .bind(&event.event.digest)
+    .bind(&event.event.prior_digest)
+    .bind(cesr)
+    .execute(&mut *conn)
+    .await
+    .map_err(|e| match DbError::from(e) {
+        DbError::Duplicate(_) => {
+            DbError::Duplicate(format!("Event at sn {} already exists for {}", sn, prefix))
+        }
+        e => e,
+    })?;
+
+    // First-seen ordinals count up from the prefix's last entry
+    let ordinal: i64 = sqlx::query_scalar(
+        "SELECT COALESCE(MAX(fn) + 1, 0) FROM first_seen WHERE tenant = $1 AND aid = $2",
+    )
+    .bind(tenant)
+    .bind(prefix)
+    .fetch_one(&mut *conn)
+    .await?;
+    let first_seen = FirstSeen::at(event, ordinal as u64, first_seen_at);
+    sqlx::query(
+        "INSERT INTO first_seen (tenant, aid, fn, sn, digest, datetime) \
+         VALUES ($1, $2, $3, $4, $5, $6)",
+    )
+    .bind(tenant)
+    .bind(prefix)
+    .bind(ordinal)
+    .bind(sn as i64)
+    .bind(&first_seen.digest)
+    .bind(&first_seen.datetime)
+    .execute(&mut *conn)
+    .await
+    .map_err(|e| match DbError::from(e) {
+        // Another append for the prefix took the ordinal first
+        DbError::Duplicate(_) => DbError::StateConflict(format!(
+            "First-seen ordinal {} already taken for {}",
+            ordinal, prefix
+        )),
+        e => e,
+    })?;
+
+    for location in AnchorLocation::from_event(event) {
+        let seal_json = serde_json::to_string(&location.seal)?;
+        sqlx::query(
+            "INSERT INTO anchors (tenant, digest, aid, sn, event_digest, seal) \
+             VALUES ($1, $2, $3, $4, $5, $6)",
+        )
+        .bind(tenant)
+        .bind(&location.seal.d)
+        .bind(&location.prefix)
+        .bind(location.sn as i64)
+        .bind(&location.event_digest)
+        .bind(seal_json)
+        .execute(&mut *conn)
+        .await?;
+    }
+
+    // Delivered on commit, so listeners only hear about durable appends
+    notify_escrow(&mut *conn, tenant, prefix).await
+}
+
+#[async_trait]
+impl KelStore for PostgresDatabase {
+    async fn append_event(&self, event: &SignedEvent) -> DbResult<()> {
+        let mut tx = self.begin_write().await?;
+        let now = chrono::Utc::now().to_rfc3339();
+        append_locked(&mut tx, self.namespace(), event, &now).await?;
+        tx.commit().await?;
+        Ok(())
+    }
+
+    async fn commit_event_seen_at(
+        &self,
+        event: &SignedEvent,
+        new_state: &KeyState,
+        datetime: &str,
+    ) -> DbResult<()> {
+        let mut tx = self.begin_write().await?;
+
+        // State is versioned by (sn, digest) of its latest event. Locking the
+        // row makes competing commits for the prefix wait for this one, then
+        // see the new version and fail the check below.
+        let current: Option<(i64, String)> =
+            sqlx::query_as(
+                "SELECT sn, digest FROM states WHERE tenant = $1 AND aid = $2 FOR UPDATE",
+            )
+            .bind(self.namespace())
+            .bind(&event.event.prefix)
+                .fetch_optional(&mut *tx)
+                .await?;
+        let current = current.map(|(sn, digest)| (sn as u64, digest));
+        let expected = event.event.sn.checked_sub(1).map(|sn| {
+            (
+                sn,
+                event.event.prior_digest.clone().unwrap_or_default(),
+            )
+        });
+        if current != expected {
+            // Dropping the transaction rolls it back
+            return Err(DbError::StateConflict(format!(
+                "State for {} is at {:?}, expected {:?}",
+                event.event.prefix, current, expected
+            )));
+        }
+
+        append_locked(&mut tx, self.namespace(), event, datetime).await?;
+        upsert_state(&mut *tx, self.namespace(), new_state).await?;
+        tx.commit().await?;
+        Ok(())
+    }
+
+    async fn get_event(&self, prefix: &str, sn: u64) -> DbResult<Option<SignedEvent>> {
+        let cesr: Option<Vec<u8>> =
+            sqlx::query_scalar("SELECT cesr FROM kel WHERE tenant = $1 AND aid = $2 AND sn = $3")
+                .bind(self.namespace())
+                .bind(prefix)
+                .bind(sn as i64)
+                .fetch_optional(&self.pool)
+                .await?;
+
+        cesr.as_deref().map(decode_event).transpose()
+    }
+
+    async fn get_events(
+        &self,
+        prefix: &str,
+        start_sn: u64,
+        end_sn: Option<u64>,
+    ) -> DbResult<Vec<SignedEvent>> {
+        let end_sn = end_sn.map(|sn| sn as i64).unwrap_or(i64::MAX);
+        let rows: Vec<Vec<u8>> = sqlx::query_scalar(
+            "SELECT cesr FROM kel WHERE tenant = $1 AND aid = $2 AND sn >= $3 AND sn <= $4 \
+             ORDER BY sn",
+        )
+        .bind(self.namespace())
+        .bind(prefix)
+        .bind(start_sn as i64)
+        .bind(end_sn)
+        .fetch_all(&self.pool)
+        .await?;
+
+        rows.iter().map(|cesr| decode_event(cesr)).collect()
+    }
+
+    async fn get_prefixes_page(
+        &self,
+        limit: usize,
+        cursor: Option<&str>,
+    ) -> DbResult<Page<String>> {
+        // Every KEL starts with its inception at sn 0
+        let limit = limit.max(1);
+        let mut items: Vec<String> = sqlx::query_scalar(
+            "SELECT aid FROM kel WHERE tenant = $1 AND sn = 0 AND aid > $2 ORDER BY aid LIMIT $3",
+        )
+        .bind(self.namespace())
+        .bind(cursor.unwrap_or_default())
+        .bind(limit as i64 + 1)
+        .fetch_all(&self.pool)
+        .await?;
+
+        let more = items.len() > limit;
+        items.truncate(limit);
+        let next = more.then(|| items.last().cloned()).flatten();
+        Ok(Page { items, next })
+    }
+
+    async fn get_latest(&self, prefix: &str) -> DbResult<Option<SignedEvent>> {
+        let cesr: Option<Vec<u8>> = sqlx::query_scalar(
+            "SELECT cesr FROM kel WHERE tenant = $1 AND aid = $2 ORDER BY sn DESC LIMIT 1",
+        )
+        .bind(self.namespace())
+        .bind(prefix)
+        .fetch_optional(&self.pool)
+        .await?;
+
+        cesr.as_deref().map(decode_event).transpose()
+    }
+
+    async fn get_event_by_digest(&self, prefix: &str, digest: &str) -> DbResult<Option<SignedEvent>> {
+        let cesr: Option<Vec<u8>> = sqlx::query_scalar(
+            "SELECT cesr FROM kel WHERE digest = $1 AND tenant = $2 AND aid = $3 LIMIT 1",
+        )
+        .bind(digest)
+        .bind(self.namespace())
+        .bind(prefix)
+        .fetch_optional(&self.pool)
+        .await?;
+
+        cesr.as_deref().map(decode_event).transpose()
+    }
+
+    async fn get_first_seen(&self, prefix: &str, digest: &str) -> DbResult<Option<FirstSeen>> {
+        let row: Option<(String, i64, i64, String, String)> = sqlx::query_as(
+            "SELECT aid, fn, sn, digest, datetime FROM first_seen \
+             WHERE digest = $1 AND tenant = $2 AND aid = $3 LIMIT 1",
+        )
+        .bind(digest)
+        .bind(self.namespace())
+        .bind(prefix)
+        .fetch_optional(&self.pool)
+        .await?;
+
+        Ok(row.map(parse_first_seen))
+    }
+
+    async fn get_first_seen_range(
+        &self,
+        prefix: &str,
+        start_fn: u64,
+        end_fn: Option<u64>,
+    ) -> DbResult<Vec<FirstSeen>> {
+        let end_fn = end_fn.map(|fn_| fn_ as i64).unwrap_or(i64::MAX);
+        let rows: Vec<(String, i64, i64, String, String)> = sqlx::query_as(
+            "SELECT aid, fn, sn, digest, datetime FROM first_seen \
+             WHERE tenant = $1 AND aid = $2 AND fn >= $3 AND fn <= $4 ORDER BY fn",
+        )
+        .bind(self.namespace())
+        .bind(prefix)
+        .bind(start_fn as i64)
+        .bind(end_fn)
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(rows.into_iter().map(parse_first_seen).collect())
+    }
+
+    async fn find_anchor(&self, digest: &str) -> DbResult<Option<AnchorLocation>> {
+        let row: Option<(String, i64, String, String)> = sqlx::query_as(
+            "SELECT aid, sn, event_digest, seal FROM anchors \
+             WHERE digest = $1 AND tenant = $2 ORDER BY id LIMIT 1",
+        )
+        .bind(digest)
+        .bind(self.namespace())
+        .fetch_optional(&self.pool)
+        .await?;
+
+        row.map(parse_anchor).transpose()
+    }
+
+    async fn find_event_seal(
+        &self,
+        prefix: &str,
+        sn: u64,
+        digest: &str,
+    ) -> DbResult<Option<AnchorLocation>> {
+        let rows: Vec<(String, i64, String, String)> = sqlx::query_as(
+            "SELECT aid, sn, event_digest, seal FROM anchors \
+             WHERE digest = $1 AND tenant = $2 ORDER BY id",
+        )
+        .bind(digest)
+        .bind(self.namespace())
+        .fetch_all(&self.pool)
+        .await?;
+
+        for row in rows {
+            let location = parse_anchor(row)?;
+            if location.is_event_seal(prefix, sn) {
+                return Ok(Some(location));
+            }
+        }
+        Ok(None)
+    }
+}
@@ -0,0 +1,443 @@
+//! PostgreSQL database implementation
+//!
+//! This implementation lets several witness processes share one database,
+//! for example behind a load balancer without AWS. Appends and commits run
+//! in transactions; `commit_event` locks the key state row with
+//! `SELECT ... FOR UPDATE`, so competing commits for a prefix serialize and
+//! the loser sees a `StateConflict` it can retry.
+//!
+//! Every KEL append and escrow write sends a `NOTIFY` on
+//! [`ESCROW_CHANNEL`] with the affected prefix. [`EscrowListener`] receives
+//! these so escrow processing can wake as soon as an escrowed event may
+//! have become processable instead of polling.
+//!
+//! Rows carry the tenant they were written under in a `tenant` column,
+//! empty for the default namespace, and handles scoped with
+//! `TenantStore::for_tenant` only read and write their tenant's rows.
+//!
+//! The schema is created and upgraded by the migrations under
+//! `migrations/postgres`, which run whenever a database is opened.
+
+mod client;
+mod escrows;
+mod kel;
+mod receipts;
+mod states;
+
+pub use client::{EscrowListener, PostgresDatabase};
+
+use crate::error::DbResult;
+use sqlx::{Executor, Postgres};
+
+/// Notification channel for escrow wakeups
+pub const ESCROW_CHANNEL: &str = "kerihost_escrow";
+
+/// Separates a tenant from the prefix in escrow notifications
+const TENANT_SEPARATOR: char = '#';
+
+/// Escrow notification payload for `prefix` in the `tenant` namespace
+fn escrow_payload(tenant: &str, prefix: &str) -> String {
+    if tenant.is_empty() {
+        prefix.to_string()
+    } else {
+        format!("{}{}{}", tenant, TENANT_SEPARATOR, prefix)
+    }
+}
+
+/// Notify escrow listeners that `prefix` has new events or escrows in the
+/// `tenant` namespace
+///
+/// Inside a transaction the notification is only delivered on commit.
+async fn notify_escrow<'e, E>(executor: E, tenant: &str, prefix: &str) -> DbResult<()>
+where
+    E: Executor<'e, Database = Postgres>,
+{
+    sqlx::query("SELECT pg_notify($1, $2)")
+        .bind(ESCROW_CHANNEL)
+        .bind(escrow_payload(tenant, prefix))
+        .execute(executor)
+        .await?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    //! These tests need a running PostgreSQL server. Set
+    //! `KERIHOST_TEST_POSTGRES_URL` to a database the tests may create
+    //! schemas in; without it they are skipped. Each test runs in its own
+    //! schema so tests and repeated runs do not see each other's data.
+
+    use super::*;
+    use crate::error::DbError;
+    use crate::test_support::*;
+    use crate::traits::{
+        EscrowReason, EscrowStore, KelStore, ReceiptStore, StateStore, TenantStore,
+    };
+    use kerihost_core::Anchor;
+    use sqlx::postgres::{PgConnectOptions, PgPoolOptions};
+    use std::str::FromStr;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::time::Duration;
+
+    const TEST_URL_VAR: &str = "KERIHOST_TEST_POSTGRES_URL";
+
+    static SCHEMA_COUNTER: AtomicUsize = AtomicUsize::new(0);
+
+    /// Open a database in a fresh schema, or `None` if no server is configured
+    async fn test_db() -> Option<PostgresDatabase> {
+        let url = test_server(TEST_URL_VAR)?;
+        let schema = format!(
+            "kerihost_test_{}_{}_{}",
+            std::process::id(),
+            chrono::Utc::now().timestamp_micros(),
+            SCHEMA_COUNTER.fetch_add(1, Ordering::SeqCst)
+        );
+
+        let options = PgConnectOptions::from_str(&url).unwrap();
+        let admin = PgPoolOptions::new()
+            .max_connections(1)
+            .connect_with(options.clone())
+            .await
+            .unwrap();
+        sqlx::query(&format!("CREATE SCHEMA {}", schema))
+            .execute(&admin)
+            .await
+            .unwrap();
+        admin.close().await;
+
+        let pool = PgPoolOptions::new()
+            .connect_with(options.options([("search_path", schema.as_str())]))
+            .await
+            .unwrap();
+        Some(PostgresDatabase::from_pool(pool).await.unwrap())
+    }
+
+    crate::conformance::conformance_tests!(test_db().await);
+
+    #[tokio::test]
+    async fn test_kel_append_and_read() {
+        let Some(db) = test_db().await else { return };
+
+        let icp = create_test_event("DTest123", 0, None);
+        db.append_event(&icp).await.unwrap();
+        let ixn1 = create_test_event("DTest123", 1, Some(icp.event.digest.clone()));
+        db.append_event(&ixn1).await.unwrap();
+        let ixn2 = create_test_event("DTest123", 2, Some(ixn1.event.digest.clone()));
+        db.append_event(&ixn2).await.unwrap();
+
+        let stored = db.get_event("DTest123", 0).await.unwrap().unwrap();
+        assert_eq!(stored.to_cesr().unwrap(), icp.to_cesr().unwrap());
+        assert_eq!(db.get_events("DTest123", 0, None).await.unwrap().len(), 3);
+        assert_eq!(db.get_events("DTest123", 1, Some(1)).await.unwrap().len(), 1);
+        assert_eq!(db.get_latest("DTest123").await.unwrap().unwrap().event.sn, 2);
+        assert_eq!(
+            db.get_event_by_digest("DTest123", &ixn1.event.digest)
+                .await
+                .unwrap()
+                .map(|e| e.event.sn),
+            Some(1)
+        );
+    }
+
+    #[tokio::test]
+    async fn test_kel_append_rejections() {
+        let Some(db) = test_db().await else { return };
+        let icp = create_test_event("DTest123", 0, None);
+        db.append_event(&icp).await.unwrap();
+
+        let result = db.append_event(&icp).await;
+        assert!(matches!(result, Err(DbError::Duplicate(_))));
+
+        let wrong = create_test_event("DTest123", 1, Some("EWrongDigest".to_string()));
+        let result = db.append_event(&wrong).await;
+        assert!(matches!(result, Err(DbError::PriorDigestMismatch { .. })));
+
+        let gap = create_test_event("DTest123", 5, Some("EPrior".to_string()));
+        let result = db.append_event(&gap).await;
+        assert!(matches!(result, Err(DbError::NotFound(_))));
+    }
+
+    #[tokio::test]
+    async fn test_first_seen_log() {
+        let Some(db) = test_db().await else { return };
+        let events = append_test_kel(&db, "DTest123", 3).await;
+
+        let entries = db.get_first_seen_range("DTest123", 0, None).await.unwrap();
+        assert_eq!(entries.len(), 3);
+        assert_eq!(entries[2].ordinal, 2);
+        assert_eq!(entries[2].digest, events[2].event.digest);
+        assert_eq!(
+            db.get_first_seen_range("DTest123", 1, Some(1)).await.unwrap()[0].sn,
+            1
+        );
+
+        let entry = db
+            .get_first_seen("DTest123", &events[1].event.digest)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(entry.ordinal, 1);
+        assert!(!entry.datetime.is_empty());
+
+        // A rejected append records nothing
+        assert!(db.append_event(&events[2]).await.is_err());
+        assert_eq!(db.get_first_seen_range("DTest123", 0, None).await.unwrap().len(), 3);
+    }
+
+    #[tokio::test]
+    async fn test_kel_find_anchor() {
+        let Some(db) = test_db().await else { return };
+        let icp = create_test_event("DTest123", 0, None);
+        db.append_event(&icp).await.unwrap();
+        let ixn = create_anchoring_event(
+            "DTest123",
+            1,
+            Some(icp.event.digest.clone()),
+            vec![
+                Anchor::digest("ECredential"),
+                Anchor::event("EDelegate", "b", "EDelegated"),
+            ],
+        );
+        db.append_event(&ixn).await.unwrap();
+
+        let location = db.find_anchor("ECredential").await.unwrap().unwrap();
+        assert_eq!(location.event_digest, ixn.event.digest);
+        let seal = db.find_event_seal("EDelegate", 11, "EDelegated").await.unwrap();
+        assert_eq!(seal.map(|l| l.sn), Some(1));
+    }
+
+    #[tokio::test]
+    async fn test_commit_event_rolls_back_on_conflict() {
+        let Some(db) = test_db().await else { return };
+
+        let icp = create_test_event("DTest123", 0, None);
+        db.append_event(&icp).await.unwrap();
+
+        // State was never written for the inception, so sn 1 cannot commit
+        let ixn = create_test_event("DTest123", 1, Some(icp.event.digest.clone()));
+        let result = db
+            .commit_event(&ixn, &create_test_state("DTest123", 1))
+            .await;
+        assert!(matches!(result, Err(DbError::StateConflict(_))));
+
+        assert_eq!(db.get_events("DTest123", 0, None).await.unwrap().len(), 1);
+        assert!(db.get_state("DTest123").await.unwrap().is_none());
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+    async fn test_concurrent_commits_single_winner() {
+        let Some(db) = test_db().await else { return };
+
+        let icp = create_test_event("DTest123", 0, None);
+        let mut state = create_test_state("DTest123", 0);
+        state.latest_digest = icp.event.digest.clone();
+        db.commit_event(&icp, &state).await.unwrap();
+
+        // Competing sn 1 events, as from several witness processes at once
+        let handles: Vec<_> = (0..8)
+            .map(|i| {
+                let db = db.clone();
+                let ixn = create_anchoring_event(
+                    "DTest123",
+                    1,
+                    Some(icp.event.digest.clone()),
+                    vec![Anchor::digest(&format!("ESeal{}", i))],
+                );
+                let mut state = create_test_state("DTest123", 1);
+                state.latest_digest = ixn.event.digest.clone();
+                tokio::spawn(async move { db.commit_event(&ixn, &state).await })
+            })
+            .collect();
+
+        let mut winners = 0;
+        for handle in handles {
+            match handle.await.unwrap() {
+                Ok(()) => winners += 1,
+                Err(DbError::StateConflict(_)) | Err(DbError::Duplicate(_)) => {}
+                Err(e) => panic!("unexpected error: {}", e),
+            }
+        }
+
+        assert_eq!(winners, 1);
+        let state = db.get_state("DTest123").await.unwrap().unwrap();
+        let latest = db.get_latest("DTest123").await.unwrap().unwrap();
+        assert_eq!(state.latest_digest, latest.event.digest);
+    }
+
+    #[tokio::test]
+    async fn test_state_and_receipt_stores() {
+        let Some(db) = test_db().await else { return };
+
+        db.put_state(&create_test_state("DTest123", 0)).await.unwrap();
+        db.put_state(&create_test_state("DTest123", 1)).await.unwrap();
+        assert_eq!(db.get_state("DTest123").await.unwrap().unwrap().sn, 1);
+        db.delete_state("DTest123").await.unwrap();
+        assert!(db.get_state("DTest123").await.unwrap().is_none());
+
+        db.add_receipt(&create_test_receipt("EDigest123", "BWitness1"))
+            .await
+            .unwrap();
+        db.add_receipt(&create_test_receipt("EDigest123", "BWitness2"))
+            .await
+            .unwrap();
+        db.add_receipt(&create_test_receipt("EDigest123", "BWitness1"))
+            .await
+            .unwrap();
+        assert_eq!(db.count_receipts("EDigest123").await.unwrap(), 2);
+        assert_eq!(db.get_receipts("EDigest123").await.unwrap().len(), 2);
+        assert!(db
+            .get_receipt("EDigest123", "BWitness2")
+            .await
+            .unwrap()
+            .is_some());
+    }
+
+    #[tokio::test]
+    async fn test_escrow_store() {
+        let Some(db) = test_db().await else { return };
+        let event1 = create_test_event("DTest1", 5, Some("EP1".to_string()));
+        let event2 = create_test_event("DTest2", 3, Some("EP2".to_string()));
+
+        db.escrow_event(&event1, EscrowReason::OutOfOrder)
+            .await
+            .unwrap();
+        db.escrow_event(&event2, EscrowReason::PartiallySigned)
+            .await
+            .unwrap();
+
+        let escrowed = db.get_escrowed("DTest1").await.unwrap();
+        assert_eq!(escrowed.len(), 1);
+        assert_eq!(escrowed[0].reason, EscrowReason::OutOfOrder);
+        assert_eq!(db.get_all_escrowed().await.unwrap().len(), 2);
+
+        let signed = db
+            .get_escrowed_by_reason(EscrowReason::PartiallySigned, 10, None)
+            .await
+            .unwrap();
+        assert_eq!(signed.items.len(), 1);
+        assert_eq!(signed.items[0].event.event.digest, event2.event.digest);
+        assert!(signed.next.is_none());
+
+        let promoted = db.promote_escrowed(&event1.event.digest).await.unwrap();
+        assert!(promoted.is_some());
+        assert!(db
+            .promote_escrowed(&event1.event.digest)
+            .await
+            .unwrap()
+            .is_none());
+
+        db.remove_escrowed(&event2.event.digest).await.unwrap();
+        assert!(db.get_all_escrowed().await.unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_pagination() {
+        let Some(db) = test_db().await else { return };
+
+        let events = append_test_kel(&db, "DTest123", 5).await;
+        let mut kel = Vec::new();
+        let mut cursor = None;
+        loop {
+            let page = db
+                .get_events_page("DTest123", 0, None, 2, cursor.as_deref())
+                .await
+                .unwrap();
+            kel.extend(page.items);
+            cursor = page.next;
+            if cursor.is_none() {
+                break;
+            }
+        }
+        assert_eq!(kel.len(), 5);
+        assert_eq!(kel[4].event.digest, events[4].event.digest);
+
+        for i in 0..5 {
+            let event = create_test_event(&format!("DTest{}", i), 5, Some("EP".to_string()));
+            db.escrow_event(&event, EscrowReason::OutOfOrder)
+                .await
+                .unwrap();
+        }
+        let first = db.get_escrowed_page(3, None).await.unwrap();
+        assert_eq!(first.items.len(), 3);
+        db.remove_escrowed(&first.items[0].event.event.digest)
+            .await
+            .unwrap();
+        let second = db
+            .get_escrowed_page(3, first.next.as_deref())
+            .await
+            .unwrap();
+        assert_eq!(second.items.len(), 2);
+        assert!(second.next.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_tenant_isolation() {
+        let Some(db) = test_db().await else { return };
+        crate::conformance::tenant_isolation(&db).await;
+    }
+
+    #[tokio::test]
+    async fn test_escrow_listener_hears_own_namespace() {
+        let Some(db) = test_db().await else { return };
+        let prefix = format!("DListen{}", chrono::Utc::now().timestamp_micros());
+        let alpha = db.for_tenant("alpha").unwrap();
+        let mut listener = alpha.escrow_listener().await.unwrap();
+
+        let other = create_test_event(&format!("{}X", prefix), 0, None);
+        db.append_event(&other).await.unwrap();
+        let icp = create_test_event(&prefix, 0, None);
+        alpha.append_event(&icp).await.unwrap();
+
+        let woken = tokio::time::timeout(Duration::from_secs(5), async {
+            loop {
+                let woken = listener.recv().await.unwrap();
+                if woken.starts_with(&prefix) {
+                    break woken;
+                }
+            }
+        })
+        .await
+        .expect("no escrow wakeup");
+        assert_eq!(woken, prefix);
+    }
+
+    #[tokio::test]
+    async fn test_escrow_listener_wakes_on_append_and_escrow() {
+        let Some(db) = test_db().await else { return };
+        // The channel is shared by every schema, so use prefixes unique to this run
+        let prefix = format!("DListen{}", chrono::Utc::now().timestamp_micros());
+        let mut listener = db.escrow_listener().await.unwrap();
+
+        let icp = create_test_event(&prefix, 0, None);
+        db.append_event(&icp).await.unwrap();
+        let escrowed = create_test_event(&prefix, 2, Some("EMissing".to_string()));
+        db.escrow_event(&escrowed, EscrowReason::OutOfOrder)
+            .await
+            .unwrap();
+
+        let mut wakeups = 0;
+        while wakeups < 2 {
+            let woken = tokio::time::timeout(Duration::from_secs(5), listener.recv())
+                .await
+                .expect("no escrow wakeup")
+                .unwrap();
+            if woken == prefix {
+                wakeups += 1;
+            }
+        }
+
+        // A rolled-back append sends nothing
+        let wrong = create_test_event(&prefix, 1, Some("EWrongDigest".to_string()));
+        assert!(db.append_event(&wrong).await.is_err());
+        let result = tokio::time::timeout(Duration::from_millis(200), async {
+            loop {
+                if listener.recv().await.unwrap() == prefix {
+                    break;
+                }
+            }
+        })
+        .await;
+        assert!(result.is_err());
+    }
+}
@@ -0,0 +1,121 @@
+//! Receipt storage implementation for PostgreSQL
+
+use super::PostgresDatabase;
+use crate::error::{DbError, DbResult};
+use crate::record;
+use crate::traits::{ReceiptConflict, ReceiptStore};
+use async_trait::async_trait;
+use kerihost_core::NontransferableReceipt;
+
+#[async_trait]
+impl ReceiptStore for PostgresDatabase {
+    async fn add_receipt(&self, receipt: &NontransferableReceipt) -> DbResult<()> {
+        let receipt_json = record::encode(receipt)?;
+
+        // One receipt per witness: only the first is written
+        let inserted = sqlx::query(
+            "INSERT INTO receipts \
+             (tenant, event_digest, witness_aid, event_aid, event_sn, signature, receipt) \
+             VALUES ($1, $2, $3, $4, $5, $6, $7) \
+             ON CONFLICT (tenant, event_digest, witness_aid) DO NOTHING",
+        )
+        .bind(self.namespace())
+        .bind(&receipt.event_digest)
+        .bind(&receipt.witness_prefix)
+        .bind(&receipt.event_prefix)
+        .bind(receipt.event_sn as i64)
+        .bind(&receipt.signature)
+        .bind(receipt_json)
+        .execute(&self.pool)
+        .await?
+        .rows_affected();
+        if inserted > 0 {
+            return Ok(());
+        }
+
+        let stored = self
+            .get_receipt(&receipt.event_digest, &receipt.witness_prefix)
+            .await?
+            .ok_or_else(|| DbError::NotFound(receipt.event_digest.clone()))?;
+        let Some(conflict) = ReceiptConflict::between(&stored, receipt) else {
+            return Ok(());
+        };
+
+        sqlx::query(
+            "INSERT INTO receipt_conflicts \
+             (tenant, witness_aid, event_digest, conflicting_signature, conflict) \
+             VALUES ($1, $2, $3, $4, $5) \
+             ON CONFLICT DO NOTHING",
+        )
+        .bind(self.namespace())
+        .bind(&conflict.witness_prefix)
+        .bind(&conflict.event_digest)
+        .bind(&conflict.conflicting_signature)
+        .bind(record::encode(&conflict)?)
+        .execute(&self.pool)
+        .await?;
+
+        Err(DbError::from(&conflict))
+    }
+
+    async fn get_receipts(&self, event_digest: &str) -> DbResult<Vec<NontransferableReceipt>> {
+        let rows: Vec<String> = sqlx::query_scalar(
+            "SELECT receipt FROM receipts WHERE tenant = $1 AND event_digest = $2 \
+             ORDER BY witness_aid",
+        )
+        .bind(self.namespace())
+        .bind(event_digest)
+        .fetch_all(&self.pool)
+        .await?;
+
+        rows.iter()
+            .map(|json| record::decode(json))
+            .collect()
+    }
+
+    async fn get_receipt(
+        &self,
+        event_digest: &str,
+        witness_prefix: &str,
+    ) -> DbResult<Option<NontransferableReceipt>> {
+        let receipt_json: Option<String> = sqlx::query_scalar(
+            "SELECT receipt FROM receipts \
+             WHERE tenant = $1 AND event_digest = $2 AND witness_aid = $3",
+        )
+        .bind(self.namespace())
+        .bind(event_digest)
+        .bind(witness_prefix)
+        .fetch_optional(&self.pool)
+        .await?;
+
+        match receipt_json {
+            Some(json) => Ok(Some(record::decode(&json)?)),
+            None => Ok(None),
+        }
+    }
+
+    async fn count_receipts(&self, event_digest: &str) -> DbResult<usize> {
+        let count: i64 = sqlx::query_scalar(
+            "SELECT COUNT(*) FROM receipts WHERE tenant = $1 AND event_digest = $2",
+        )
+        .bind(self.namespace())
+        .bind(event_digest)
+        .fetch_one(&self.pool)
+        .await?;
+
+        Ok(count as usize)
+    }
+
+    async fn get_receipt_conflicts(&self, witness_prefix: &str) -> DbResult<Vec<ReceiptConflict>> {
+        let rows: Vec<String> = sqlx::query_scalar(
+            "SELECT conflict FROM receipt_conflicts WHERE tenant = $1 AND witness_aid = $2 \
+             ORDER BY event_digest, conflicting_signature",
+        )
+        .bind(self.namespace())
+        .bind(witness_prefix)
+        .fetch_all(&self.pool)
+        .await?;
+
+        rows.iter().map(|json| record::decode(json)).collect()
+    }
+}
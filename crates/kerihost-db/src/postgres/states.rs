@@ -0,0 +1,63 @@
+//! State storage implementation for PostgreSQL
+
+use super::PostgresDatabase;
+use crate::error::DbResult;
+use crate::record;
+use crate::traits::StateStore;
+use async_trait::async_trait;
+use kerihost_core::KeyState;
+use sqlx::{Executor, Postgres};
+
+/// Insert or replace the state row for a key state
+pub(super) async fn upsert_state<'e, E>(executor: E, tenant: &str, state: &KeyState) -> DbResult<()>
+where
+    E: Executor<'e, Database = Postgres>,
+{
+    let state_json = record::encode(state)?;
+
+    sqlx::query(
+        "INSERT INTO states (tenant, aid, sn, digest, state) VALUES ($1, $2, $3, $4, $5) \
+         ON CONFLICT (tenant, aid) DO UPDATE SET \
+         sn = excluded.sn, digest = excluded.digest, state = excluded.state",
+    )
+    .bind(tenant)
+    .bind(&state.prefix)
+    .bind(state.sn as i64)
+    .bind(&state.latest_digest)
+    .bind(state_json)
+    .execute(executor)
+    .await?;
+
+    Ok(())
+}
+
+#[async_trait]
+impl StateStore for PostgresDatabase {
+    async fn get_state(&self, prefix: &str) -> DbResult<Option<KeyState>> {
+        let state_json: Option<String> =
+            sqlx::query_scalar("SELECT state FROM states WHERE tenant = $1 AND aid = $2")
+                .bind(self.namespace())
+                .bind(prefix)
+                .fetch_optional(&self.pool)
+                .await?;
+
+        match state_json {
+            Some(json) => Ok(Some(record::decode(&json)?)),
+            None => Ok(None),
+        }
+    }
+
+    async fn put_state(&self, state: &KeyState) -> DbResult<()> {
+        upsert_state(&self.pool, self.namespace(), state).await
+    }
+
+    async fn delete_state(&self, prefix: &str) -> DbResult<()> {
+        sqlx::query("DELETE FROM states WHERE tenant = $1 AND aid = $2")
+            .bind(self.namespace())
+            .bind(prefix)
+            .execute(&self.pool)
+            .await?;
+
+        Ok(())
+    }
+}
@@ -0,0 +1,276 @@
+//! Versioned storage records
+//!
+//! Key states, escrow entries, receipts, receipt conflicts, IPEX exchanges
+//! and escrowed exchange messages are stored as JSON inside an envelope
+//! naming the record kind and the version of its layout:
+//!
+//! ```json
+//! {"kind":"key_state","v":1,"data":{...}}
+//! ```
+//!
+//! Rows written before envelopes existed hold the bare JSON of the struct
+//! and decode as version 0. Decoding runs older data through
+//! `Record::upgrade` one version at a time before deserializing, so a field
+//! change to a stored struct needs a version bump and an upgrade step
+//! rather than new tables. `MigrationStore` rewrites old rows at the current
+//! version so the upgrade steps can eventually be retired.
+//!
+//! KEL events are stored as CESR and accepted exchange messages as their
+//! own serialization; both carry their own version string and are not
+//! wrapped.
+
+use crate::error::{DbError, DbResult};
+use crate::traits::{EscrowedEvent, EscrowedMessage, ReceiptConflict};
+use kerihost_core::{IpexExchange, KeyState, NontransferableReceipt};
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use serde_json::{json, Value};
+
+/// A struct stored as a versioned record
+pub trait Record: Serialize + DeserializeOwned {
+    /// Kind named in the envelope
+    const KIND: &'static str;
+
+    /// Version written by this build
+    const VERSION: u32;
+
+    /// Upgrade record data from `version` to `version + 1`
+    ///
+    /// Version 0 is a bare row from before envelopes, whose data is the
+    /// version 1 layout.
+    fn upgrade(version: u32, data: Value) -> DbResult<Value> {
+        match version {
+            0 => Ok(data),
+            _ => Err(DbError::Serialization(format!(
+                "No upgrade for {} record from version {}",
+                Self::KIND,
+                version
+            ))),
+        }
+    }
+}
+
+impl Record for KeyState {
+    const KIND: &'static str = "key_state";
+    const VERSION: u32 = 1;
+}
+
+impl Record for EscrowedEvent {
+    const KIND: &'static str = "escrowed_event";
+    const VERSION: u32 = 2;
+
+    /// Version 2 added the attempt count and schedule; older escrows are
+    /// due immediately
+    fn upgrade(version: u32, mut data: Value) -> DbResult<Value> {
+        match version {
+            0 => Ok(data),
+            1 => {
+                data["attempts"] = json!(0);
+                data["last_attempt"] = Value::Null;
+                data["next_attempt"] = json!(0);
+                Ok(data)
+            }
+            _ => Err(DbError::Serialization(format!(
+                "No upgrade for {} record from version {}",
+                Self::KIND,
+                version
+            ))),
+        }
+    }
+}
+
+impl Record for NontransferableReceipt {
+    const KIND: &'static str = "receipt";
+    const VERSION: u32 = 1;
+}
+
+impl Record for ReceiptConflict {
+    const KIND: &'static str = "receipt_conflict";
+    const VERSION: u32 = 1;
+}
+
+impl Record for IpexExchange {
+    const KIND: &'static str = "ipex_exchange";
+    const VERSION: u32 = 1;
+}
+
+impl Record for EscrowedMessage {
+    const KIND: &'static str = "escrowed_message";
+    const VERSION: u32 = 1;
+}
+
+/// Encode a record at the current version
+pub fn encode<T: Record>(record: &T) -> DbResult<String> {
+    let envelope = json!({
+        "kind": T::KIND,
+        "v": T::VERSION,
+        "data": serde_json::to_value(record)?,
+    });
+    Ok(envelope.to_string())
+}
+
+/// Decode a record written at any version up to the current one
+pub fn decode<T: Record>(json: &str) -> DbResult<T> {
+    let (mut version, mut data) = open::<T>(json)?;
+    while version < T::VERSION {
+        data = T::upgrade(version, data)?;
+        version += 1;
+    }
+    Ok(serde_json::from_value(data)?)
+}
+
+/// Check whether a stored record is older than the current version
+pub fn is_outdated<T: Record>(json: &str) -> DbResult<bool> {
+    Ok(open::<T>(json)?.0 < T::VERSION)
+}
+
+/// Split a stored record into its version and data
+fn open<T: Record>(json: &str) -> DbResult<(u32, Value)> {
+    let value: Value = serde_json::from_str(json)?;
+    let Some(fields) = value.as_object().filter(|fields| is_envelope(fields)) else {
+        return Ok((0, value));
+    };
+
+    let kind = fields["kind"].as_str().unwrap_or_default();
+    if kind != T::KIND {
+        return Err(DbError::Serialization(format!(
+            "Expected {} record, found {}",
+            T::KIND,
+            kind
+        )));
+    }
+    let version = fields["v"]
+        .as_u64()
+        .and_then(|v| u32::try_from(v).ok())
+        .ok_or_else(|| DbError::Serialization(format!("Invalid {} record version", kind)))?;
+    if version > T::VERSION {
+        return Err(DbError::Serialization(format!(
+            "{} record version {} is newer than supported version {}",
+            kind,
+            version,
+            T::VERSION
+        )));
+    }
+    Ok((version, fields["data"].clone()))
+}
+
+/// Whether an object has exactly the envelope's fields
+///
+/// None of the stored structs has a field named `kind`, `v` or `data`.
+fn is_envelope(fields: &serde_json::Map<String, Value>) -> bool {
+    fields.len() == 3
+        && fields.get("kind").is_some_and(Value::is_string)
+        && fields.contains_key("v")
+        && fields.contains_key("data")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::*;
+    use crate::traits::EscrowReason;
+    use serde::Deserialize;
+
+    /// A record that renamed a field and then added one
+    #[derive(Debug, Serialize, Deserialize, PartialEq)]
+    struct Evolved {
+        name: String,
+        tags: Vec<String>,
+    }
+
+    impl Record for Evolved {
+        const KIND: &'static str = "evolved";
+        const VERSION: u32 = 3;
+
+        fn upgrade(version: u32, mut data: Value) -> DbResult<Value> {
+            match version {
+                0 => Ok(data),
+                1 => {
+                    let label = data["label"].take();
+                    Ok(json!({ "name": label }))
+                }
+                2 => {
+                    data["tags"] = json!([]);
+                    Ok(data)
+                }
+                _ => unreachable!(),
+            }
+        }
+    }
+
+    #[test]
+    fn test_record_roundtrip() {
+        let state = create_test_state("DTest123", 4);
+        let json = encode(&state).unwrap();
+        assert!(json.contains("\"kind\":\"key_state\""));
+        assert!(!is_outdated::<KeyState>(&json).unwrap());
+
+        let decoded: KeyState = decode(&json).unwrap();
+        assert_eq!(decoded.sn, 4);
+        assert_eq!(decoded.latest_digest, state.latest_digest);
+    }
+
+    #[test]
+    fn test_record_decodes_bare_rows() {
+        let receipt = create_test_receipt("EDigest123", "BWitness1");
+        let bare = serde_json::to_string(&receipt).unwrap();
+        assert!(is_outdated::<NontransferableReceipt>(&bare).unwrap());
+
+        let decoded: NontransferableReceipt = decode(&bare).unwrap();
+        assert_eq!(decoded.witness_prefix, "BWitness1");
+        assert_eq!(decoded.signature, receipt.signature);
+    }
+
+    #[test]
+    fn test_record_upgrades_each_version() {
+        let bare = r#"{"label":"first"}"#;
+        let expected = Evolved {
+            name: "first".to_string(),
+            tags: vec![],
+        };
+        assert_eq!(decode::<Evolved>(bare).unwrap(), expected);
+
+        let v2 = r#"{"kind":"evolved","v":2,"data":{"name":"first"}}"#;
+        assert_eq!(decode::<Evolved>(v2).unwrap(), expected);
+        assert!(is_outdated::<Evolved>(v2).unwrap());
+
+        let current = encode(&expected).unwrap();
+        assert_eq!(decode::<Evolved>(&current).unwrap(), expected);
+    }
+
+    #[test]
+    fn test_record_upgrades_escrowed_event() {
+        let event = create_test_event("DTest123", 1, Some("EPrior".to_string()));
+        let escrowed = EscrowedEvent::new(event, EscrowReason::OutOfOrder, 600);
+        let mut data = serde_json::to_value(&escrowed).unwrap();
+        for field in ["attempts", "last_attempt", "next_attempt"] {
+            data.as_object_mut().unwrap().remove(field);
+        }
+
+        let v1 = json!({ "kind": "escrowed_event", "v": 1, "data": data }).to_string();
+        assert!(is_outdated::<EscrowedEvent>(&v1).unwrap());
+        let decoded: EscrowedEvent = decode(&v1).unwrap();
+        assert_eq!(decoded.ttl, escrowed.ttl);
+        assert_eq!(decoded.attempts, 0);
+        assert_eq!(decoded.last_attempt, None);
+        assert!(decoded.is_due());
+
+        let bare: EscrowedEvent = decode(&data.to_string()).unwrap();
+        assert_eq!(bare.attempts, 0);
+    }
+
+    #[test]
+    fn test_record_rejects_newer_or_foreign_records() {
+        let newer = r#"{"kind":"key_state","v":99,"data":{}}"#;
+        assert!(matches!(
+            decode::<KeyState>(newer),
+            Err(DbError::Serialization(_))
+        ));
+
+        let receipt = encode(&create_test_receipt("EDigest123", "BWitness1")).unwrap();
+        assert!(matches!(
+            decode::<KeyState>(&receipt),
+            Err(DbError::Serialization(_))
+        ));
+    }
+}
@@ -0,0 +1,368 @@
+//! Escrow storage implementation for redb
+//!
+//! Escrowed events are written to `evts`, `sigs` and `dtss` like accepted
+//! ones, and indexed by `snKey` in the keripy escrow table for their reason.
+//! The escrow time is the event's `dtss` entry, and its expiry and retry
+//! schedule are kept in `escs`.
+
+use super::{
+    delete_event, dg_key, kv_err, load_event, now_iso8601, parse_sn_key, prefix_bounds, put_event,
+    sn_key, ReadTxn, RedbDatabase, WriteTxn, ATTS, DIGS, DTSS, ESCS, EVTS, KELS, OOES, PDES, PSES,
+    PWES, SIGS,
+};
+use crate::error::{DbError, DbResult};
+use crate::traits::{EscrowReason, EscrowStore, EscrowedEvent, Page, DEFAULT_ESCROW_TTL};
+use ::redb::{MultimapTableDefinition, ReadableMultimapTable, ReadableTable};
+use async_trait::async_trait;
+use kerihost_core::SignedEvent;
+use serde::{Deserialize, Serialize};
+
+/// Expiry and retry schedule of an escrowed event, stored in `escs`
+#[derive(Serialize, Deserialize)]
+struct Schedule {
+    ttl: u64,
+    attempts: u32,
+    last_attempt: Option<String>,
+    next_attempt: u64,
+}
+
+impl Schedule {
+    fn of(escrowed: &EscrowedEvent) -> Self {
+        Schedule {
+            ttl: escrowed.ttl,
+            attempts: escrowed.attempts,
+            last_attempt: escrowed.last_attempt.clone(),
+            next_attempt: escrowed.next_attempt,
+        }
+    }
+}
+
+/// Store the schedule of an escrowed event
+fn put_schedule(txn: &WriteTxn, escrowed: &EscrowedEvent) -> DbResult<()> {
+    let event = &escrowed.event.event;
+    let key = dg_key(&event.prefix, &event.digest);
+    let schedule = serde_json::to_string(&Schedule::of(escrowed))?;
+    txn.open_table(ESCS)
+        .map_err(kv_err)?
+        .insert(key.as_str(), schedule.as_str())
+        .map_err(kv_err)?;
+    Ok(())
+}
+
+/// Escrow table for a reason
+fn escrow_table(
+    reason: EscrowReason,
+) -> DbResult<MultimapTableDefinition<'static, &'static str, &'static str>> {
+    ESCROW_TABLES
+        .iter()
+        .find(|(r, _)| *r == reason)
+        .map(|(_, d)| *d)
+        .ok_or_else(|| DbError::Other(format!("No escrow table for {}", reason)))
+}
+
+/// keripy escrow table for each escrow reason
+const ESCROW_TABLES: [(EscrowReason, MultimapTableDefinition<&str, &str>); 4] = [
+    (EscrowReason::OutOfOrder, OOES),
+    (EscrowReason::PartiallySigned, PSES),
+    (EscrowReason::MissingDelegator, PDES),
+    (EscrowReason::MissingReceipts, PWES),
+];
+
+/// Rebuild an escrowed event from its `snKey` and digest
+fn read_escrowed(
+    txn: &ReadTxn,
+    reason: EscrowReason,
+    key: &str,
+    digest: &str,
+) -> DbResult<Option<EscrowedEvent>> {
+    let (prefix, _) = parse_sn_key(key)?;
+    let evts = txn.open_table(EVTS).map_err(kv_err)?;
+    let sigs = txn.open_multimap_table(SIGS).map_err(kv_err)?;
+    let atts = txn.open_table(ATTS).map_err(kv_err)?;
+    let Some(event) = load_event(&evts, &sigs, &atts, prefix, digest)? else {
+        return Ok(None);
+    };
+
+    let dtss = txn.open_table(DTSS).map_err(kv_err)?;
+    let created = dtss
+        .get(dg_key(prefix, digest).as_str())
+        .map_err(kv_err)?
+        .map(|v| v.value().to_string())
+        .ok_or_else(|| DbError::Other(format!("Missing escrow datetime for {}", digest)))?;
+    let schedule = txn
+        .open_table(ESCS)
+        .map_err(kv_err)?
+        .get(dg_key(prefix, digest).as_str())
+        .map_err(kv_err)?
+        .map(|v| serde_json::from_str::<Schedule>(v.value()))
+        .transpose()?;
+
+    // Escrows from before `escs` expire after the default TTL
+    let schedule = match schedule {
+        Some(schedule) => schedule,
+        None => {
+            let escrowed_at = chrono::DateTime::parse_from_rfc3339(&created)
+                .map_err(|e| DbError::Serialization(e.to_string()))?;
+            Schedule {
+                ttl: escrowed_at.timestamp() as u64 + DEFAULT_ESCROW_TTL,
+                attempts: 0,
+                last_attempt: None,
+                next_attempt: 0,
+            }
+        }
+    };
+
+    Ok(Some(EscrowedEvent {
+        event,
+        reason,
+        created,
+        ttl: schedule.ttl,
+        attempts: schedule.attempts,
+        last_attempt: schedule.last_attempt,
+        next_attempt: schedule.next_attempt,
+    }))
+}
+
+/// Read up to `limit` escrowed events after `cursor`, a `snKey#digest` pair,
+/// of one prefix or of all
+///
+/// Pages are ordered by `snKey` then digest across the given escrow tables.
+fn read_escrow_page(
+    txn: &ReadTxn,
+    tables: &[(EscrowReason, MultimapTableDefinition<&str, &str>)],
+    prefix: Option<&str>,
+    limit: usize,
+    cursor: Option<&str>,
+) -> DbResult<Page<EscrowedEvent>> {
+    let after = cursor
+        .map(|cursor| {
+            cursor
+                .split_once('#')
+                .ok_or_else(|| DbError::InvalidCursor(cursor.to_string()))
+        })
+        .transpose()?;
+
+    let bounds = prefix.map(prefix_bounds);
+    let start = match (after, &bounds) {
+        (Some((key, _)), Some((first, _))) => key.max(first.as_str()),
+        (Some((key, _)), None) => key,
+        (None, Some((first, _))) => first.as_str(),
+        (None, None) => "",
+    };
+
+    // Each table contributes at most one more entry than the page needs
+    let mut entries = Vec::new();
+    for &(reason, definition) in tables {
+        let table = txn.open_multimap_table(definition).map_err(kv_err)?;
+        let range = match &bounds {
+            Some((_, end)) => table.range(start..end.as_str()),
+            None => table.range(start..),
+        }
+        .map_err(kv_err)?;
+
+        let mut taken = 0;
+        'table: for entry in range {
+            let (key, digests) = entry.map_err(kv_err)?;
+            for digest in digests {
+                let digest = digest.map_err(kv_err)?;
+                let position = (key.value().to_string(), digest.value().to_string());
+                if after.is_some_and(|(k, d)| (k, d) >= (position.0.as_str(), position.1.as_str())) {
+                    continue;
+                }
+                entries.push((position, reason));
+                taken += 1;
+                if taken > limit {
+                    break 'table;
+                }
+            }
+        }
+    }
+    entries.sort_by(|a, b| a.0.cmp(&b.0));
+
+    let more = entries.len() > limit;
+    entries.truncate(limit);
+    let next = more
+        .then(|| entries.last().map(|((key, digest), _)| format!("{}#{}", key, digest)))
+        .flatten();
+
+    let mut items = Vec::new();
+    for ((key, digest), reason) in entries {
+        if let Some(event) = read_escrowed(txn, reason, &key, &digest)? {
+            items.push(event);
+        }
+    }
+    Ok(Page { items, next })
+}
+
+/// Whether the KEL holds the event with this digest at `key`
+fn is_accepted(txn: &WriteTxn, key: &str, digest: &str) -> DbResult<bool> {
+    let kels = txn.open_table(KELS).map_err(kv_err)?;
+    let accepted = kels
+        .get(key)
+        .map_err(kv_err)?
+        .is_some_and(|d| d.value() == digest);
+    Ok(accepted)
+}
+
+/// Remove an event from every escrow table, returning it if it was escrowed
+fn take_escrowed(txn: &WriteTxn, digest: &str) -> DbResult<Option<SignedEvent>> {
+    let Some(key) = txn
+        .open_table(DIGS)
+        .map_err(kv_err)?
+        .get(digest)
+        .map_err(kv_err)?
+        .map(|v| v.value().to_string())
+    else {
+        return Ok(None);
+    };
+
+    let mut removed = false;
+    for (_, definition) in ESCROW_TABLES {
+        let mut table = txn.open_multimap_table(definition).map_err(kv_err)?;
+        removed |= table.remove(key.as_str(), digest).map_err(kv_err)?;
+    }
+    if !removed {
+        return Ok(None);
+    }
+
+    let (prefix, _) = parse_sn_key(&key)?;
+    txn.open_table(ESCS)
+        .map_err(kv_err)?
+        .remove(dg_key(prefix, digest).as_str())
+        .map_err(kv_err)?;
+    let event = {
+        let evts = txn.open_table(EVTS).map_err(kv_err)?;
+        let sigs = txn.open_multimap_table(SIGS).map_err(kv_err)?;
+        let atts = txn.open_table(ATTS).map_err(kv_err)?;
+        load_event(&evts, &sigs, &atts, prefix, digest)?
+    };
+
+    // Keep the event's records if it has since been accepted
+    if !is_accepted(txn, &key, digest)? {
+        delete_event(txn, prefix, digest)?;
+        txn.open_table(DIGS)
+            .map_err(kv_err)?
+            .remove(digest)
+            .map_err(kv_err)?;
+    }
+    Ok(event)
+}
+
+#[async_trait]
+impl EscrowStore for RedbDatabase {
+    async fn escrow_event_with_ttl(
+        &self,
+        event: &SignedEvent,
+        reason: EscrowReason,
+        ttl_seconds: u64,
+    ) -> DbResult<()> {
+        let escrowed = EscrowedEvent::new(event.clone(), reason, ttl_seconds);
+        self.write(move |txn| {
+            let event = &escrowed.event;
+            let digest = event.event.digest.as_str();
+            let key = sn_key(&event.event.prefix, event.event.sn);
+            if is_accepted(txn, &key, digest)? {
+                return Err(DbError::Duplicate(format!(
+                    "Event {} is already in the KEL",
+                    digest
+                )));
+            }
+
+            // Re-escrowing replaces any earlier escrow of the same event
+            take_escrowed(txn, digest)?;
+
+            put_event(txn, event)?;
+            let now = now_iso8601();
+            txn.open_table(DTSS)
+                .map_err(kv_err)?
+                .insert(dg_key(&event.event.prefix, digest).as_str(), now.as_str())
+                .map_err(kv_err)?;
+            put_schedule(txn, &escrowed)?;
+            txn.open_table(DIGS)
+                .map_err(kv_err)?
+                .insert(digest, key.as_str())
+                .map_err(kv_err)?;
+
+            txn.open_multimap_table(escrow_table(reason)?)
+                .map_err(kv_err)?
+                .insert(key.as_str(), digest)
+                .map_err(kv_err)?;
+            Ok(())
+        })
+        .await
+    }
+
+    async fn record_escrow_attempt(&self, escrowed: &EscrowedEvent) -> DbResult<()> {
+        let escrowed = escrowed.clone();
+        self.write(move |txn| {
+            let event = &escrowed.event.event;
+            let key = sn_key(&event.prefix, event.sn);
+            let escrowed_for_reason = txn
+                .open_multimap_table(escrow_table(escrowed.reason)?)
+                .map_err(kv_err)?
+                .get(key.as_str())
+                .map_err(kv_err)?
+                .any(|d| d.is_ok_and(|d| d.value() == event.digest));
+            if escrowed_for_reason {
+                put_schedule(txn, &escrowed)?;
+            }
+            Ok(())
+        })
+        .await
+    }
+
+    async fn get_escrowed_page(
+        &self,
+        limit: usize,
+        cursor: Option<&str>,
+    ) -> DbResult<Page<EscrowedEvent>> {
+        let limit = limit.max(1);
+        let cursor = cursor.map(str::to_string);
+        self.read(move |txn| read_escrow_page(txn, &ESCROW_TABLES, None, limit, cursor.as_deref()))
+            .await
+    }
+
+    async fn get_escrowed_by_prefix(
+        &self,
+        prefix: &str,
+        limit: usize,
+        cursor: Option<&str>,
+    ) -> DbResult<Page<EscrowedEvent>> {
+        let limit = limit.max(1);
+        let prefix = prefix.to_string();
+        let cursor = cursor.map(str::to_string);
+        self.read(move |txn| {
+            read_escrow_page(txn, &ESCROW_TABLES, Some(&prefix), limit, cursor.as_deref())
+        })
+        .await
+    }
+
+    async fn get_escrowed_by_reason(
+        &self,
+        reason: EscrowReason,
+        limit: usize,
+        cursor: Option<&str>,
+    ) -> DbResult<Page<EscrowedEvent>> {
+        // keripy keeps one escrow table per reason, ordered by snKey
+        let tables: Vec<_> = ESCROW_TABLES
+            .into_iter()
+            .filter(|(r, _)| *r == reason)
+            .collect();
+        let limit = limit.max(1);
+        let cursor = cursor.map(str::to_string);
+        self.read(move |txn| read_escrow_page(txn, &tables, None, limit, cursor.as_deref()))
+            .await
+    }
+
+    async fn promote_escrowed(&self, event_digest: &str) -> DbResult<Option<SignedEvent>> {
+        let event_digest = event_digest.to_string();
+        self.write(move |txn| take_escrowed(txn, &event_digest))
+            .await
+    }
+
+    async fn remove_escrowed(&self, event_digest: &str) -> DbResult<()> {
+        let event_digest = event_digest.to_string();
+        self.write(move |txn| take_escrowed(txn, &event_digest).map(|_| ()))
+            .await
+    }
+}
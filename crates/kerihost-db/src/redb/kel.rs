@@ -0,0 +1,411 @@
+//! KEL storage implementation for redb
+
+use super::{
+    dg_key, kv_err, load_event, now_iso8601, parse_sn_key, prefix_bounds, put_event, sn_key,
+    ReadTxn, RedbDatabase, WriteTxn, ANCS, ATTS, DIGS, DTSS, EVTS, FELS, FONS, KELS, SIGS, STTS,
+};
+use crate::error::{DbError, DbResult};
+use crate::record;
+use crate::traits::{AnchorLocation, FirstSeen, KelStore, Page};
+use ::redb::ReadableTable;
+use async_trait::async_trait;
+use kerihost_core::{KeyState, SignedEvent};
+
+/// Append an event and its indexes inside an open write transaction
+fn append_locked(txn: &WriteTxn, event: &SignedEvent, first_seen_at: &str) -> DbResult<()> {
+    let prefix = &event.event.prefix;
+    let sn = event.event.sn;
+    let (start, end) = prefix_bounds(prefix);
+
+    let mut kels = txn.open_table(KELS).map_err(kv_err)?;
+    if sn == 0 {
+        // For inception, check that no events exist
+        if kels
+            .range(start.as_str()..end.as_str())
+            .map_err(kv_err)?
+            .next()
+            .is_some()
+        {
+            return Err(DbError::Duplicate(format!(
+                "Inception already exists for {}",
+                prefix
+            )));
+        }
+    } else {
+        // For non-inception, verify prior digest
+        let prior_sn = sn - 1;
+        let Some(prior_digest) = kels
+            .get(sn_key(prefix, prior_sn).as_str())
+            .map_err(kv_err)?
+            .map(|v| v.value().to_string())
+        else {
+            return Err(DbError::NotFound(format!(
+                "Prior event at sn {} not found for {}",
+                prior_sn, prefix
+            )));
+        };
+        match &event.event.prior_digest {
+            Some(event_prior) if *event_prior != prior_digest => {
+                return Err(DbError::PriorDigestMismatch {
+                    expected: prior_digest,
+                    actual: event_prior.clone(),
+                });
+            }
+            Some(_) => {}
+            None => {
+                return Err(DbError::Other(
+                    "Non-inception event missing prior digest".to_string(),
+                ));
+            }
+        }
+    }
+
+    let key = sn_key(prefix, sn);
+    if kels.get(key.as_str()).map_err(kv_err)?.is_some() {
+        return Err(DbError::Duplicate(format!(
+            "Event at sn {} already exists for {}",
+            sn, prefix
+        )));
+    }
+    kels.insert(key.as_str(), event.event.digest.as_str())
+        .map_err(kv_err)?;
+
+    put_event(txn, event)?;
+    let dg = dg_key(prefix, &event.event.digest);
+    txn.open_table(DTSS)
+        .map_err(kv_err)?
+        .insert(dg.as_str(), first_seen_at)
+        .map_err(kv_err)?;
+    txn.open_table(DIGS)
+        .map_err(kv_err)?
+        .insert(event.event.digest.as_str(), key.as_str())
+        .map_err(kv_err)?;
+
+    // First-seen ordinal is one past the prefix's last entry
+    let mut fels = txn.open_table(FELS).map_err(kv_err)?;
+    let next_fn = match fels
+        .range(start.as_str()..end.as_str())
+        .map_err(kv_err)?
+        .next_back()
+    {
+        Some(entry) => parse_sn_key(entry.map_err(kv_err)?.0.value())?.1 + 1,
+        None => 0,
+    };
+    fels.insert(
+        sn_key(prefix, next_fn).as_str(),
+        event.event.digest.as_str(),
+    )
+    .map_err(kv_err)?;
+    txn.open_table(FONS)
+        .map_err(kv_err)?
+        .insert(dg.as_str(), format!("{:032x}", next_fn).as_str())
+        .map_err(kv_err)?;
+
+    // Anchors are keyed by seal digest then a per-digest counter, so a
+    // range scan returns them in append order
+    let mut ancs = txn.open_table(ANCS).map_err(kv_err)?;
+    for location in AnchorLocation::from_event(event) {
+        let (seal_start, seal_end) = prefix_bounds(&location.seal.d);
+        let next = match ancs
+            .range(seal_start.as_str()..seal_end.as_str())
+            .map_err(kv_err)?
+            .next_back()
+        {
+            Some(entry) => parse_sn_key(entry.map_err(kv_err)?.0.value())?.1 + 1,
+            None => 0,
+        };
+        let location_json = serde_json::to_string(&location)?;
+        ancs.insert(
+            sn_key(&location.seal.d, next).as_str(),
+            location_json.as_str(),
+        )
+        .map_err(kv_err)?;
+    }
+
+    Ok(())
+}
+
+/// Rebuild a first-seen entry from `fels`, `dtss` and `digs`
+fn read_first_seen(txn: &ReadTxn, prefix: &str, ordinal: u64, digest: &str) -> DbResult<FirstSeen> {
+    let missing = |table: &str| DbError::Other(format!("Missing {} entry for {}", table, digest));
+    let datetime = txn
+        .open_table(DTSS)
+        .map_err(kv_err)?
+        .get(dg_key(prefix, digest).as_str())
+        .map_err(kv_err)?
+        .map(|v| v.value().to_string())
+        .ok_or_else(|| missing("dtss"))?;
+    let key = txn
+        .open_table(DIGS)
+        .map_err(kv_err)?
+        .get(digest)
+        .map_err(kv_err)?
+        .map(|v| v.value().to_string())
+        .ok_or_else(|| missing("digs"))?;
+
+    Ok(FirstSeen {
+        prefix: prefix.to_string(),
+        ordinal,
+        sn: parse_sn_key(&key)?.1,
+        digest: digest.to_string(),
+        datetime,
+    })
+}
+
+#[async_trait]
+impl KelStore for RedbDatabase {
+    async fn append_event(&self, event: &SignedEvent) -> DbResult<()> {
+        let event = event.clone();
+        let now = now_iso8601();
+        self.write(move |txn| append_locked(txn, &event, &now)).await
+    }
+
+    async fn commit_event_seen_at(
+        &self,
+        event: &SignedEvent,
+        new_state: &KeyState,
+        datetime: &str,
+    ) -> DbResult<()> {
+        let event = event.clone();
+        let datetime = datetime.to_string();
+        let state_json = record::encode(new_state)?;
+        let state_prefix = new_state.prefix.clone();
+
+        self.write(move |txn| {
+            // State is versioned by (sn, digest) of its latest event
+            let current = {
+                let stts = txn.open_table(STTS).map_err(kv_err)?;
+                let state = stts
+                    .get(event.event.prefix.as_str())
+                    .map_err(kv_err)?
+                    .map(|v| record::decode::<KeyState>(v.value()))
+                    .transpose()?;
+                state.map(|s| (s.sn, s.latest_digest))
+            };
+            let expected = event
+                .event
+                .sn
+                .checked_sub(1)
+                .map(|sn| (sn, event.event.prior_digest.clone().unwrap_or_default()));
+            if current != expected {
+                return Err(DbError::StateConflict(format!(
+                    "State for {} is at {:?}, expected {:?}",
+                    event.event.prefix, current, expected
+                )));
+            }
+
+            append_locked(txn, &event, &datetime)?;
+            txn.open_table(STTS)
+                .map_err(kv_err)?
+                .insert(state_prefix.as_str(), state_json.as_str())
+                .map_err(kv_err)?;
+            Ok(())
+        })
+        .await
+    }
+
+    async fn get_event(&self, prefix: &str, sn: u64) -> DbResult<Option<SignedEvent>> {
+        let prefix = prefix.to_string();
+        self.read(move |txn| {
+            let kels = txn.open_table(KELS).map_err(kv_err)?;
+            let Some(digest) = kels.get(sn_key(&prefix, sn).as_str()).map_err(kv_err)? else {
+                return Ok(None);
+            };
+            let evts = txn.open_table(EVTS).map_err(kv_err)?;
+            let sigs = txn.open_multimap_table(SIGS).map_err(kv_err)?;
+            let atts = txn.open_table(ATTS).map_err(kv_err)?;
+            load_event(&evts, &sigs, &atts, &prefix, digest.value())
+        })
+        .await
+    }
+
+    async fn get_events(
+        &self,
+        prefix: &str,
+        start_sn: u64,
+        end_sn: Option<u64>,
+    ) -> DbResult<Vec<SignedEvent>> {
+        let prefix = prefix.to_string();
+        self.read(move |txn| {
+            let kels = txn.open_table(KELS).map_err(kv_err)?;
+            let evts = txn.open_table(EVTS).map_err(kv_err)?;
+            let sigs = txn.open_multimap_table(SIGS).map_err(kv_err)?;
+            let atts = txn.open_table(ATTS).map_err(kv_err)?;
+
+            let start = sn_key(&prefix, start_sn);
+            let end = match end_sn {
+                Some(end_sn) => sn_key(&prefix, end_sn),
+                None => sn_key(&prefix, u64::MAX),
+            };
+
+            let mut events = Vec::new();
+            for entry in kels.range(start.as_str()..=end.as_str()).map_err(kv_err)? {
+                let (_, digest) = entry.map_err(kv_err)?;
+                if let Some(event) = load_event(&evts, &sigs, &atts, &prefix, digest.value())? {
+                    events.push(event);
+                }
+            }
+            Ok(events)
+        })
+        .await
+    }
+
+    async fn get_prefixes_page(
+        &self,
+        limit: usize,
+        cursor: Option<&str>,
+    ) -> DbResult<Page<String>> {
+        let limit = limit.max(1);
+        let mut start = cursor.map(|c| prefix_bounds(c).1).unwrap_or_default();
+        self.read(move |txn| {
+            let kels = txn.open_table(KELS).map_err(kv_err)?;
+
+            // Seek past each prefix's events rather than scanning them
+            let mut items = Vec::new();
+            while items.len() <= limit {
+                let Some(entry) = kels.range(start.as_str()..).map_err(kv_err)?.next() else {
+                    break;
+                };
+                let (key, _) = entry.map_err(kv_err)?;
+                let prefix = parse_sn_key(key.value())?.0.to_string();
+                start = prefix_bounds(&prefix).1;
+                items.push(prefix);
+            }
+
+            let more = items.len() > limit;
+            items.truncate(limit);
+            let next = more.then(|| items.last().cloned()).flatten();
+            Ok(Page { items, next })
+        })
+        .await
+    }
+
+    async fn get_latest(&self, prefix: &str) -> DbResult<Option<SignedEvent>> {
+        let prefix = prefix.to_string();
+        self.read(move |txn| {
+            let kels = txn.open_table(KELS).map_err(kv_err)?;
+            let (start, end) = prefix_bounds(&prefix);
+            let Some(entry) = kels
+                .range(start.as_str()..end.as_str())
+                .map_err(kv_err)?
+                .next_back()
+            else {
+                return Ok(None);
+            };
+            let (_, digest) = entry.map_err(kv_err)?;
+
+            let evts = txn.open_table(EVTS).map_err(kv_err)?;
+            let sigs = txn.open_multimap_table(SIGS).map_err(kv_err)?;
+            let atts = txn.open_table(ATTS).map_err(kv_err)?;
+            load_event(&evts, &sigs, &atts, &prefix, digest.value())
+        })
+        .await
+    }
+
+    async fn get_event_by_digest(
+        &self,
+        prefix: &str,
+        digest: &str,
+    ) -> DbResult<Option<SignedEvent>> {
+        let prefix = prefix.to_string();
+        let digest = digest.to_string();
+        self.read(move |txn| {
+            // Escrowed events share evts, so only follow digests the KEL holds
+            let digs = txn.open_table(DIGS).map_err(kv_err)?;
+            let Some(key) = digs.get(digest.as_str()).map_err(kv_err)? else {
+                return Ok(None);
+            };
+            let kels = txn.open_table(KELS).map_err(kv_err)?;
+            let accepted = kels
+                .get(key.value())
+                .map_err(kv_err)?
+                .is_some_and(|d| d.value() == digest);
+            if !accepted || parse_sn_key(key.value())?.0 != prefix {
+                return Ok(None);
+            }
+
+            let evts = txn.open_table(EVTS).map_err(kv_err)?;
+            let sigs = txn.open_multimap_table(SIGS).map_err(kv_err)?;
+            let atts = txn.open_table(ATTS).map_err(kv_err)?;
+            load_event(&evts, &sigs, &atts, &prefix, &digest)
+        })
+        .await
+    }
+
+    async fn get_first_seen(&self, prefix: &str, digest: &str) -> DbResult<Option<FirstSeen>> {
+        let prefix = prefix.to_string();
+        let digest = digest.to_string();
+        self.read(move |txn| {
+            let fons = txn.open_table(FONS).map_err(kv_err)?;
+            let Some(ordinal) = fons
+                .get(dg_key(&prefix, &digest).as_str())
+                .map_err(kv_err)?
+            else {
+                return Ok(None);
+            };
+            let ordinal = u64::from_str_radix(ordinal.value(), 16)
+                .map_err(|e| DbError::Serialization(e.to_string()))?;
+            read_first_seen(txn, &prefix, ordinal, &digest).map(Some)
+        })
+        .await
+    }
+
+    async fn get_first_seen_range(
+        &self,
+        prefix: &str,
+        start_fn: u64,
+        end_fn: Option<u64>,
+    ) -> DbResult<Vec<FirstSeen>> {
+        let prefix = prefix.to_string();
+        self.read(move |txn| {
+            let fels = txn.open_table(FELS).map_err(kv_err)?;
+            let start = sn_key(&prefix, start_fn);
+            let end = sn_key(&prefix, end_fn.unwrap_or(u64::MAX));
+
+            let mut entries = Vec::new();
+            for entry in fels.range(start.as_str()..=end.as_str()).map_err(kv_err)? {
+                let (key, digest) = entry.map_err(kv_err)?;
+                let ordinal = parse_sn_key(key.value())?.1;
+                entries.push(read_first_seen(txn, &prefix, ordinal, digest.value())?);
+            }
+            Ok(entries)
+        })
+        .await
+    }
+
+    async fn find_anchor(&self, digest: &str) -> DbResult<Option<AnchorLocation>> {
+        Ok(self.anchors(digest).await?.into_iter().next())
+    }
+
+    async fn find_event_seal(
+        &self,
+        prefix: &str,
+        sn: u64,
+        digest: &str,
+    ) -> DbResult<Option<AnchorLocation>> {
+        Ok(self
+            .anchors(digest)
+            .await?
+            .into_iter()
+            .find(|location| location.is_event_seal(prefix, sn)))
+    }
+}
+
+impl RedbDatabase {
+    /// Every location anchoring a seal digest, in append order
+    async fn anchors(&self, digest: &str) -> DbResult<Vec<AnchorLocation>> {
+        let digest = digest.to_string();
+        self.read(move |txn| {
+            let ancs = txn.open_table(ANCS).map_err(kv_err)?;
+            let (start, end) = prefix_bounds(&digest);
+
+            let mut locations = Vec::new();
+            for entry in ancs.range(start.as_str()..end.as_str()).map_err(kv_err)? {
+                let (_, location) = entry.map_err(kv_err)?;
+                locations.push(serde_json::from_str(location.value())?);
+            }
+            Ok(locations)
+        })
+        .await
+    }
+}
@@ -0,0 +1,777 @@
+//! Embedded key-value database implementation using redb
+//!
+//! A zero-config, crash-safe store for edge deployments. The layout mirrors
+//! keripy's LMDB sub-databases, using the same table names and key formats,
+//! so a dump can be compared entry by entry with a keripy `Baser`:
+//!
+//! | Table  | Key             | Value                                  |
+//! |--------|-----------------|----------------------------------------|
+//! | `evts` | `pre.dig`       | raw event bytes                        |
+//! | `sigs` | `pre.dig`       | indexed signatures, qb64 (multi)       |
+//! | `dtss` | `pre.dig`       | first-seen datetime, ISO 8601          |
+//! | `kels` | `pre.sn`        | accepted event digest                  |
+//! | `fels` | `pre.fn`        | event digest in first-seen order       |
+//! | `fons` | `pre.dig`       | first-seen ordinal, 32 hex digits      |
+//! | `rcts` | `pre.dig`       | `witness.signature` couplets (multi)   |
+//! | `ooes` | `pre.sn`        | out-of-order escrow digests (multi)    |
+//! | `pses` | `pre.sn`        | partially signed escrow digests (multi)|
+//! | `pdes` | `pre.sn`        | delegation escrow digests (multi)      |
+//! | `pwes` | `pre.sn`        | partially witnessed escrow (multi)     |
+//! | `ures` | `pre.sn`        | unverified receipt escrow (multi)      |
+//! | `vres` | `pre.sn`        | unverified validator receipts (multi)  |
+//! | `ldes` | `pre.sn`        | likely duplicitous escrow (multi)      |
+//! | `dels` | `pre.sn`        | duplicitous event log (multi)          |
+//! | `stts` | `pre`           | key state record, JSON                 |
+//! | `rcfs` | `wit.dig.sig`   | receipt conflict record, JSON          |
+//! | `escs` | `pre.dig`       | escrow expiry and attempts, JSON       |
+//! | `atts` | `pre.dig`       | other attachment groups, raw CESR      |
+//!
+//! Sequence and first-seen numbers are 32 hex digits, as in keripy's
+//! `snKey`. `ures`, `vres`, `ldes` and `dels` are created for layout parity
+//! but not yet written. Five tables have no keripy counterpart: `digs` maps an
+//! event digest to its `pre.sn` key, `ancs` is the anchored seal index,
+//! `rcfs` holds receipt conflicts, `escs` schedules escrow retries and `atts`
+//! keeps the attachment groups after an event's signatures, such as witness
+//! signatures and delegation seals, as received.
+//!
+//! Each tenant namespace has its own copy of these tables, named
+//! `tenant/evts` and so on, so a tenant's tables read like a `Baser` of its
+//! own. The default namespace uses the bare names. A tenant's tables are
+//! created the first time a handle scoped to it is used.
+//!
+//! redb allows one write transaction at a time, so every append and commit
+//! is checked and applied atomically. Blocking database work runs on tokio's
+//! blocking pool.
+
+mod escrows;
+mod kel;
+mod receipts;
+mod states;
+
+use crate::error::{DbError, DbResult};
+use crate::traits::{validate_tenant, TenantStore};
+use ::redb::backends::InMemoryBackend;
+use ::redb::{
+    Database, Key, MultimapTable, MultimapTableDefinition, MultimapTableHandle,
+    ReadOnlyMultimapTable, ReadOnlyTable, ReadTransaction, ReadableDatabase, ReadableMultimapTable,
+    ReadableTable, Table, TableDefinition, TableError, TableHandle, Value, WriteTransaction,
+};
+use cesride::Siger;
+use kerihost_core::{IndexedSignature, KeyEvent, SignedEvent};
+use std::collections::HashSet;
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+
+pub(crate) const EVTS: TableDefinition<&str, &[u8]> = TableDefinition::new("evts");
+pub(crate) const SIGS: MultimapTableDefinition<&str, &str> = MultimapTableDefinition::new("sigs");
+pub(crate) const DTSS: TableDefinition<&str, &str> = TableDefinition::new("dtss");
+pub(crate) const KELS: TableDefinition<&str, &str> = TableDefinition::new("kels");
+pub(crate) const FELS: TableDefinition<&str, &str> = TableDefinition::new("fels");
+pub(crate) const FONS: TableDefinition<&str, &str> = TableDefinition::new("fons");
+pub(crate) const RCTS: MultimapTableDefinition<&str, &str> = MultimapTableDefinition::new("rcts");
+pub(crate) const OOES: MultimapTableDefinition<&str, &str> = MultimapTableDefinition::new("ooes");
+pub(crate) const PSES: MultimapTableDefinition<&str, &str> = MultimapTableDefinition::new("pses");
+pub(crate) const PDES: MultimapTableDefinition<&str, &str> = MultimapTableDefinition::new("pdes");
+pub(crate) const PWES: MultimapTableDefinition<&str, &str> = MultimapTableDefinition::new("pwes");
+pub(crate) const URES: MultimapTableDefinition<&str, &str> = MultimapTableDefinition::new("ures");
+pub(crate) const VRES: MultimapTableDefinition<&str, &str> = MultimapTableDefinition::new("vres");
+pub(crate) const LDES: MultimapTableDefinition<&str, &str> = MultimapTableDefinition::new("ldes");
+pub(crate) const DELS: MultimapTableDefinition<&str, &str> = MultimapTableDefinition::new("dels");
+pub(crate) const STTS: TableDefinition<&str, &str> = TableDefinition::new("stts");
+pub(crate) const DIGS: TableDefinition<&str, &str> = TableDefinition::new("digs");
+pub(crate) const ANCS: TableDefinition<&str, &str> = TableDefinition::new("ancs");
+pub(crate) const RCFS: TableDefinition<&str, &str> = TableDefinition::new("rcfs");
+pub(crate) const ESCS: TableDefinition<&str, &str> = TableDefinition::new("escs");
+pub(crate) const ATTS: TableDefinition<&str, &[u8]> = TableDefinition::new("atts");
+
+/// Embedded redb database implementation
+#[derive(Clone)]
+pub struct RedbDatabase {
+    db: Arc<Database>,
+    tenant: Option<String>,
+    /// Tenants whose tables exist
+    tenants: Arc<Mutex<HashSet<String>>>,
+}
+
+/// Name of `table` in the `tenant` namespace
+fn table_name(tenant: Option<&str>, table: &str) -> String {
+    match tenant {
+        Some(tenant) => format!("{}/{}", tenant, table),
+        None => table.to_string(),
+    }
+}
+
+/// A write transaction on one namespace's tables
+pub(crate) struct WriteTxn {
+    txn: WriteTransaction,
+    tenant: Option<String>,
+}
+
+impl WriteTxn {
+    /// Open `table` in this transaction's namespace, creating it if missing
+    pub(crate) fn open_table<K: Key + 'static, V: Value + 'static>(
+        &self,
+        table: TableDefinition<K, V>,
+    ) -> Result<Table<'_, K, V>, TableError> {
+        let name = table_name(self.tenant.as_deref(), table.name());
+        self.txn.open_table(TableDefinition::new(&name))
+    }
+
+    /// Open multimap `table` in this transaction's namespace, creating it if
+    /// missing
+    pub(crate) fn open_multimap_table<K: Key + 'static, V: Key + 'static>(
+        &self,
+        table: MultimapTableDefinition<K, V>,
+    ) -> Result<MultimapTable<'_, K, V>, TableError> {
+        let name = table_name(self.tenant.as_deref(), table.name());
+        self.txn
+            .open_multimap_table(MultimapTableDefinition::new(&name))
+    }
+}
+
+/// A read transaction on one namespace's tables
+pub(crate) struct ReadTxn {
+    txn: ReadTransaction,
+    tenant: Option<String>,
+}
+
+impl ReadTxn {
+    /// Open `table` in this transaction's namespace
+    pub(crate) fn open_table<K: Key + 'static, V: Value + 'static>(
+        &self,
+        table: TableDefinition<K, V>,
+    ) -> Result<ReadOnlyTable<K, V>, TableError> {
+        let name = table_name(self.tenant.as_deref(), table.name());
+        self.txn.open_table(TableDefinition::new(&name))
+    }
+
+    /// Open multimap `table` in this transaction's namespace
+    pub(crate) fn open_multimap_table<K: Key + 'static, V: Key + 'static>(
+        &self,
+        table: MultimapTableDefinition<K, V>,
+    ) -> Result<ReadOnlyMultimapTable<K, V>, TableError> {
+        let name = table_name(self.tenant.as_deref(), table.name());
+        self.txn
+            .open_multimap_table(MultimapTableDefinition::new(&name))
+    }
+}
+
+/// Create every table of a namespace
+fn create_tables(txn: &WriteTxn) -> DbResult<()> {
+    txn.open_table(EVTS).map_err(kv_err)?;
+    txn.open_table(ATTS).map_err(kv_err)?;
+    for table in [DTSS, KELS, FELS, FONS, STTS, DIGS, ANCS, RCFS, ESCS] {
+        txn.open_table(table).map_err(kv_err)?;
+    }
+    for table in [SIGS, RCTS, OOES, PSES, PDES, PWES, URES, VRES, LDES, DELS] {
+        txn.open_multimap_table(table).map_err(kv_err)?;
+    }
+    Ok(())
+}
+
+impl RedbDatabase {
+    /// Open the database file at `path`, creating it if missing
+    pub fn open(path: impl AsRef<Path>) -> DbResult<Self> {
+        Self::init(Database::create(path).map_err(kv_err)?)
+    }
+
+    /// Open a private in-memory database (for testing)
+    pub fn in_memory() -> DbResult<Self> {
+        let db = Database::builder()
+            .create_with_backend(InMemoryBackend::new())
+            .map_err(kv_err)?;
+        Self::init(db)
+    }
+
+    /// Create every table up front so the full layout shows in dumps
+    fn init(db: Database) -> DbResult<Self> {
+        let txn = WriteTxn {
+            txn: db.begin_write().map_err(kv_err)?,
+            tenant: None,
+        };
+        create_tables(&txn)?;
+        txn.txn.commit().map_err(kv_err)?;
+
+        Ok(RedbDatabase {
+            db: Arc::new(db),
+            tenant: None,
+            tenants: Arc::default(),
+        })
+    }
+
+    /// Create this handle's tenant tables unless they exist
+    ///
+    /// Runs on the blocking pool, before the handle's first transaction.
+    fn ensure_tables(&self) -> DbResult<()> {
+        let Some(tenant) = &self.tenant else {
+            return Ok(());
+        };
+        let mut tenants = self.tenants.lock().unwrap_or_else(|e| e.into_inner());
+        if tenants.contains(tenant) {
+            return Ok(());
+        }
+
+        let txn = WriteTxn {
+            txn: self.db.begin_write().map_err(kv_err)?,
+            tenant: Some(tenant.clone()),
+        };
+        create_tables(&txn)?;
+        txn.txn.commit().map_err(kv_err)?;
+        tenants.insert(tenant.clone());
+        Ok(())
+    }
+
+    /// Run `f` in a write transaction on the blocking pool
+    ///
+    /// The transaction commits if `f` succeeds and aborts otherwise.
+    pub(crate) async fn write<T, F>(&self, f: F) -> DbResult<T>
+    where
+        F: FnOnce(&WriteTxn) -> DbResult<T> + Send + 'static,
+        T: Send + 'static,
+    {
+        let db = self.clone();
+        tokio::task::spawn_blocking(move || {
+            db.ensure_tables()?;
+            let txn = WriteTxn {
+                txn: db.db.begin_write().map_err(kv_err)?,
+                tenant: db.tenant,
+            };
+            let value = f(&txn)?;
+            txn.txn.commit().map_err(kv_err)?;
+            Ok(value)
+        })
+        .await
+        .map_err(|e| DbError::Other(e.to_string()))?
+    }
+
+    /// Run `f` in a read transaction on the blocking pool
+    pub(crate) async fn read<T, F>(&self, f: F) -> DbResult<T>
+    where
+        F: FnOnce(&ReadTxn) -> DbResult<T> + Send + 'static,
+        T: Send + 'static,
+    {
+        let db = self.clone();
+        tokio::task::spawn_blocking(move || {
+            db.ensure_tables()?;
+            let txn = ReadTxn {
+                txn: db.db.begin_read().map_err(kv_err)?,
+                tenant: db.tenant,
+            };
+            f(&txn)
+        })
+        .await
+        .map_err(|e| DbError::Other(e.to_string()))?
+    }
+}
+
+impl TenantStore for RedbDatabase {
+    fn tenant(&self) -> Option<&str> {
+        self.tenant.as_deref()
+    }
+
+    fn for_tenant(&self, tenant: &str) -> DbResult<Self> {
+        validate_tenant(tenant)?;
+        Ok(RedbDatabase {
+            db: Arc::clone(&self.db),
+            tenant: Some(tenant.to_string()),
+            tenants: Arc::clone(&self.tenants),
+        })
+    }
+}
+
+/// Map any redb error to a database error
+pub(crate) fn kv_err(err: impl Into<::redb::Error>) -> DbError {
+    match err.into() {
+        ::redb::Error::Corrupted(detail) => DbError::Corruption(detail),
+        err => DbError::Storage(err.to_string()),
+    }
+}
+
+/// keripy `dgKey`: `pre.dig`
+pub(crate) fn dg_key(prefix: &str, digest: &str) -> String {
+    format!("{}.{}", prefix, digest)
+}
+
+/// keripy `snKey`: `pre.sn` with sn as 32 hex digits
+pub(crate) fn sn_key(prefix: &str, sn: u64) -> String {
+    format!("{}.{:032x}", prefix, sn)
+}
+
+/// Parse the sequence number out of an `snKey`
+pub(crate) fn parse_sn_key(key: &str) -> DbResult<(&str, u64)> {
+    key.rsplit_once('.')
+        .and_then(|(prefix, sn)| Some((prefix, u64::from_str_radix(sn, 16).ok()?)))
+        .ok_or_else(|| DbError::Other(format!("Malformed sn key {}", key)))
+}
+
+/// Key bounds covering every `pre.*` entry for a prefix
+///
+/// qb64 never contains `.` or `/`, and `/` sorts right after `.`.
+pub(crate) fn prefix_bounds(prefix: &str) -> (String, String) {
+    (format!("{}.", prefix), format!("{}/", prefix))
+}
+
+/// Current datetime in keripy's ISO 8601 format
+pub(crate) fn now_iso8601() -> String {
+    chrono::Utc::now().to_rfc3339_opts(chrono::SecondsFormat::Micros, false)
+}
+
+/// Write an event's raw bytes, signatures and other attachments under its
+/// `dgKey`
+pub(crate) fn put_event(txn: &WriteTxn, event: &SignedEvent) -> DbResult<()> {
+    let key = dg_key(&event.event.prefix, &event.event.digest);
+    if event.event.raw.is_empty() {
+        return Err(DbError::Serialization(format!(
+            "Event {} has no raw bytes",
+            event.event.digest
+        )));
+    }
+
+    let mut evts = txn.open_table(EVTS).map_err(kv_err)?;
+    evts.insert(key.as_str(), event.event.raw.as_slice())
+        .map_err(kv_err)?;
+
+    let mut sigs = txn.open_multimap_table(SIGS).map_err(kv_err)?;
+    sigs.remove_all(key.as_str()).map_err(kv_err)?;
+    for sig in &event.signatures {
+        sigs.insert(key.as_str(), sig.signature.as_str())
+            .map_err(kv_err)?;
+    }
+
+    let mut atts = txn.open_table(ATTS).map_err(kv_err)?;
+    if event.attachments.is_empty() {
+        atts.remove(key.as_str()).map_err(kv_err)?;
+    } else {
+        atts.insert(key.as_str(), event.attachments.as_slice())
+            .map_err(kv_err)?;
+    }
+    Ok(())
+}
+
+/// Delete an event's raw bytes, signatures, attachments and datetime
+pub(crate) fn delete_event(txn: &WriteTxn, prefix: &str, digest: &str) -> DbResult<()> {
+    let key = dg_key(prefix, digest);
+    txn.open_table(EVTS)
+        .map_err(kv_err)?
+        .remove(key.as_str())
+        .map_err(kv_err)?;
+    txn.open_multimap_table(SIGS)
+        .map_err(kv_err)?
+        .remove_all(key.as_str())
+        .map_err(kv_err)?;
+    txn.open_table(ATTS)
+        .map_err(kv_err)?
+        .remove(key.as_str())
+        .map_err(kv_err)?;
+    txn.open_table(DTSS)
+        .map_err(kv_err)?
+        .remove(key.as_str())
+        .map_err(kv_err)?;
+    Ok(())
+}
+
+/// Rebuild a signed event from `evts`, `sigs` and `atts`
+pub(crate) fn load_event(
+    evts: &impl ReadableTable<&'static str, &'static [u8]>,
+    sigs: &impl ReadableMultimapTable<&'static str, &'static str>,
+    atts: &impl ReadableTable<&'static str, &'static [u8]>,
+    prefix: &str,
+    digest: &str,
+) -> DbResult<Option<SignedEvent>> {
+    let key = dg_key(prefix, digest);
+    let Some(raw) = evts.get(key.as_str()).map_err(kv_err)? else {
+        return Ok(None);
+    };
+    let event = KeyEvent::from_cesr(raw.value()).map_err(|e| DbError::Corruption(e.to_string()))?;
+
+    let mut signatures = Vec::new();
+    for sig in sigs.get(key.as_str()).map_err(kv_err)? {
+        let qb64 = sig.map_err(kv_err)?;
+        let siger = Siger::new_with_qb64(qb64.value(), None)
+            .map_err(|e| DbError::Serialization(e.to_string()))?;
+        signatures.push(
+            IndexedSignature::from_siger(&siger)
+                .map_err(|e| DbError::Serialization(e.to_string()))?,
+        );
+    }
+    signatures.sort_by_key(|sig| sig.index);
+
+    let mut signed = SignedEvent::new(event, signatures);
+    if let Some(attachments) = atts.get(key.as_str()).map_err(kv_err)? {
+        signed.attachments = attachments.value().to_vec();
+    }
+    Ok(Some(signed))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::*;
+    use crate::traits::{EscrowReason, EscrowStore, KelStore, ReceiptStore, StateStore};
+    use cesride::Matter;
+    use kerihost_core::{Anchor, EventValidator, ValidationResult};
+
+    fn test_db() -> RedbDatabase {
+        RedbDatabase::in_memory().unwrap()
+    }
+
+    crate::conformance::conformance_tests!(Some(test_db()));
+
+    #[tokio::test]
+    async fn test_tenant_isolation() {
+        crate::conformance::tenant_isolation(&test_db()).await;
+    }
+
+    #[tokio::test]
+    async fn test_kel_append_and_read() {
+        let db = test_db();
+
+        let icp = create_test_event("DTest123", 0, None);
+        db.append_event(&icp).await.unwrap();
+        let ixn1 = create_test_event("DTest123", 1, Some(icp.event.digest.clone()));
+        db.append_event(&ixn1).await.unwrap();
+        let ixn2 = create_test_event("DTest123", 2, Some(ixn1.event.digest.clone()));
+        db.append_event(&ixn2).await.unwrap();
+
+        let stored = db.get_event("DTest123", 0).await.unwrap().unwrap();
+        assert_eq!(stored.to_cesr().unwrap(), icp.to_cesr().unwrap());
+        assert_eq!(db.get_events("DTest123", 0, None).await.unwrap().len(), 3);
+        assert_eq!(
+            db.get_events("DTest123", 1, Some(1)).await.unwrap().len(),
+            1
+        );
+        assert_eq!(
+            db.get_latest("DTest123").await.unwrap().unwrap().event.sn,
+            2
+        );
+        assert_eq!(
+            db.get_event_by_digest("DTest123", &ixn1.event.digest)
+                .await
+                .unwrap()
+                .map(|e| e.event.sn),
+            Some(1)
+        );
+        assert!(db
+            .get_event_by_digest("DOther", &ixn1.event.digest)
+            .await
+            .unwrap()
+            .is_none());
+    }
+
+    #[tokio::test]
+    async fn test_kel_roundtrip_preserves_signatures() {
+        let db = test_db();
+        let key = test_signer().verfer().qb64().unwrap();
+
+        let icp = create_test_event(&key, 0, None);
+        db.append_event(&icp).await.unwrap();
+
+        let stored = db.get_event(&key, 0).await.unwrap().unwrap();
+        assert_eq!(stored.to_cesr().unwrap(), icp.to_cesr().unwrap());
+        assert_eq!(
+            EventValidator::validate(&stored, None).unwrap(),
+            ValidationResult::Valid
+        );
+    }
+
+    #[tokio::test]
+    async fn test_kel_append_rejections() {
+        let db = test_db();
+        let icp = create_test_event("DTest123", 0, None);
+        db.append_event(&icp).await.unwrap();
+
+        let result = db.append_event(&icp).await;
+        assert!(matches!(result, Err(DbError::Duplicate(_))));
+
+        let wrong = create_test_event("DTest123", 1, Some("EWrongDigest".to_string()));
+        let result = db.append_event(&wrong).await;
+        assert!(matches!(result, Err(DbError::PriorDigestMismatch { .. })));
+
+        let gap = create_test_event("DTest123", 5, Some("EPrior".to_string()));
+        let result = db.append_event(&gap).await;
+        assert!(matches!(result, Err(DbError::NotFound(_))));
+    }
+
+    #[tokio::test]
+    async fn test_keripy_layout() {
+        let db = test_db();
+        let icp = create_test_event("DTest123", 0, None);
+        db.append_event(&icp).await.unwrap();
+        let ixn = create_test_event("DTest123", 1, Some(icp.event.digest.clone()));
+        db.append_event(&ixn).await.unwrap();
+
+        let dig = ixn.event.digest.clone();
+        let (kel_dig, fel_dig, fon, raw, dts) = db
+            .read(move |txn| {
+                let sn = "DTest123.00000000000000000000000000000001";
+                let kel_dig = txn
+                    .open_table(KELS)
+                    .map_err(kv_err)?
+                    .get(sn)
+                    .map_err(kv_err)?;
+                let fel_dig = txn
+                    .open_table(FELS)
+                    .map_err(kv_err)?
+                    .get(sn)
+                    .map_err(kv_err)?;
+                let dg = format!("DTest123.{}", dig);
+                let fon = txn
+                    .open_table(FONS)
+                    .map_err(kv_err)?
+                    .get(dg.as_str())
+                    .map_err(kv_err)?;
+                let raw = txn
+                    .open_table(EVTS)
+                    .map_err(kv_err)?
+                    .get(dg.as_str())
+                    .map_err(kv_err)?;
+                let dts = txn
+                    .open_table(DTSS)
+                    .map_err(kv_err)?
+                    .get(dg.as_str())
+                    .map_err(kv_err)?;
+                Ok((
+                    kel_dig.map(|v| v.value().to_string()),
+                    fel_dig.map(|v| v.value().to_string()),
+                    fon.map(|v| v.value().to_string()),
+                    raw.map(|v| v.value().to_vec()),
+                    dts.map(|v| v.value().to_string()),
+                ))
+            })
+            .await
+            .unwrap();
+
+        assert_eq!(kel_dig.as_deref(), Some(ixn.event.digest.as_str()));
+        assert_eq!(fel_dig.as_deref(), Some(ixn.event.digest.as_str()));
+        assert_eq!(fon.as_deref(), Some("00000000000000000000000000000001"));
+        assert_eq!(raw, Some(ixn.event.raw.clone()));
+        assert!(dts.unwrap().ends_with("+00:00"));
+    }
+
+    #[tokio::test]
+    async fn test_first_seen_log() {
+        let db = test_db();
+        let events = append_test_kel(&db, "DTest123", 3).await;
+
+        let entries = db.get_first_seen_range("DTest123", 0, None).await.unwrap();
+        assert_eq!(entries.len(), 3);
+        assert_eq!(entries[2].ordinal, 2);
+        assert_eq!(entries[2].digest, events[2].event.digest);
+        assert_eq!(
+            db.get_first_seen_range("DTest123", 1, Some(1)).await.unwrap()[0].sn,
+            1
+        );
+
+        let entry = db
+            .get_first_seen("DTest123", &events[1].event.digest)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(entry.ordinal, 1);
+        assert!(!entry.datetime.is_empty());
+
+        // A rejected append records nothing
+        assert!(db.append_event(&events[2]).await.is_err());
+        assert_eq!(db.get_first_seen_range("DTest123", 0, None).await.unwrap().len(), 3);
+    }
+
+    #[tokio::test]
+    async fn test_kel_find_anchor() {
+        let db = test_db();
+        let icp = create_test_event("DTest123", 0, None);
+        db.append_event(&icp).await.unwrap();
+        let ixn = create_anchoring_event(
+            "DTest123",
+            1,
+            Some(icp.event.digest.clone()),
+            vec![
+                Anchor::digest("ECredential"),
+                Anchor::event("EDelegate", "b", "EDelegated"),
+            ],
+        );
+        db.append_event(&ixn).await.unwrap();
+
+        let location = db.find_anchor("ECredential").await.unwrap().unwrap();
+        assert_eq!(location.sn, 1);
+        assert_eq!(location.event_digest, ixn.event.digest);
+        assert!(db.find_anchor("ENotAnchored").await.unwrap().is_none());
+
+        let seal = db
+            .find_event_seal("EDelegate", 11, "EDelegated")
+            .await
+            .unwrap();
+        assert_eq!(seal.map(|l| l.sn), Some(1));
+        assert!(db
+            .find_event_seal("EDelegate", 12, "EDelegated")
+            .await
+            .unwrap()
+            .is_none());
+    }
+
+    #[tokio::test]
+    async fn test_commit_event() {
+        let db = test_db();
+
+        let icp = create_test_event("DTest123", 0, None);
+        let mut state = create_test_state("DTest123", 0);
+        state.latest_digest = icp.event.digest.clone();
+        db.commit_event(&icp, &state).await.unwrap();
+
+        let ixn = create_test_event("DTest123", 1, Some(icp.event.digest.clone()));
+        db.commit_event(&ixn, &create_test_state("DTest123", 1))
+            .await
+            .unwrap();
+        assert_eq!(db.get_state("DTest123").await.unwrap().unwrap().sn, 1);
+
+        // A stale commit changes nothing
+        let other = create_test_event("DTest123", 1, Some("EOtherInception".to_string()));
+        let result = db
+            .commit_event(&other, &create_test_state("DTest123", 1))
+            .await;
+        assert!(matches!(result, Err(DbError::StateConflict(_))));
+        assert_eq!(db.get_events("DTest123", 0, None).await.unwrap().len(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_state_and_receipt_stores() {
+        let db = test_db();
+
+        db.put_state(&create_test_state("DTest123", 0))
+            .await
+            .unwrap();
+        db.put_state(&create_test_state("DTest123", 1))
+            .await
+            .unwrap();
+        assert_eq!(db.get_state("DTest123").await.unwrap().unwrap().sn, 1);
+        db.delete_state("DTest123").await.unwrap();
+        assert!(db.get_state("DTest123").await.unwrap().is_none());
+
+        db.add_receipt(&create_test_receipt("EDigest123", "BWitness1"))
+            .await
+            .unwrap();
+        db.add_receipt(&create_test_receipt("EDigest123", "BWitness2"))
+            .await
+            .unwrap();
+        // Deduped by witness
+        db.add_receipt(&create_test_receipt("EDigest123", "BWitness1"))
+            .await
+            .unwrap();
+
+        assert_eq!(db.count_receipts("EDigest123").await.unwrap(), 2);
+        let receipt = db
+            .get_receipt("EDigest123", "BWitness2")
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(receipt.event_prefix, "DTest123");
+        assert_eq!(receipt.signature, "0BSig123");
+        assert!(db
+            .get_receipt("EDigest123", "BWitness3")
+            .await
+            .unwrap()
+            .is_none());
+        assert!(db.get_receipts("ENotExist").await.unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_escrow_store() {
+        let db = test_db();
+        let event1 = create_test_event("DTest1", 5, Some("EP1".to_string()));
+        let event2 = create_test_event("DTest2", 3, Some("EP2".to_string()));
+
+        db.escrow_event(&event1, EscrowReason::OutOfOrder)
+            .await
+            .unwrap();
+        db.escrow_event(&event2, EscrowReason::PartiallySigned)
+            .await
+            .unwrap();
+
+        let escrowed = db.get_escrowed("DTest1").await.unwrap();
+        assert_eq!(escrowed.len(), 1);
+        assert_eq!(escrowed[0].reason, EscrowReason::OutOfOrder);
+        assert_eq!(escrowed[0].event.event.raw, event1.event.raw);
+        assert!(!escrowed[0].is_expired());
+        assert_eq!(db.get_all_escrowed().await.unwrap().len(), 2);
+
+        let signed = db
+            .get_escrowed_by_reason(EscrowReason::PartiallySigned, 10, None)
+            .await
+            .unwrap();
+        assert_eq!(signed.items.len(), 1);
+        assert_eq!(signed.items[0].event.event.digest, event2.event.digest);
+        assert!(signed.next.is_none());
+
+        let promoted = db.promote_escrowed(&event1.event.digest).await.unwrap();
+        assert_eq!(
+            promoted.map(|e| e.event.digest),
+            Some(event1.event.digest.clone())
+        );
+        assert!(db
+            .promote_escrowed(&event1.event.digest)
+            .await
+            .unwrap()
+            .is_none());
+
+        db.remove_escrowed(&event2.event.digest).await.unwrap();
+        assert!(db.get_all_escrowed().await.unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_pagination() {
+        let db = test_db();
+
+        let events = append_test_kel(&db, "DTest123", 5).await;
+        let mut kel = Vec::new();
+        let mut cursor = None;
+        loop {
+            let page = db
+                .get_events_page("DTest123", 0, None, 2, cursor.as_deref())
+                .await
+                .unwrap();
+            kel.extend(page.items);
+            cursor = page.next;
+            if cursor.is_none() {
+                break;
+            }
+        }
+        assert_eq!(kel.len(), 5);
+        assert_eq!(kel[4].event.digest, events[4].event.digest);
+
+        for i in 0..5 {
+            let event = create_test_event(&format!("DTest{}", i), 5, Some("EP".to_string()));
+            db.escrow_event(&event, EscrowReason::OutOfOrder)
+                .await
+                .unwrap();
+        }
+        let first = db.get_escrowed_page(3, None).await.unwrap();
+        assert_eq!(first.items.len(), 3);
+        db.remove_escrowed(&first.items[0].event.event.digest)
+            .await
+            .unwrap();
+        let second = db
+            .get_escrowed_page(3, first.next.as_deref())
+            .await
+            .unwrap();
+        assert_eq!(second.items.len(), 2);
+        assert!(second.next.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_file_database_persists() {
+        let path =
+            std::env::temp_dir().join(format!("kerihost-redb-test-{}.redb", std::process::id()));
+        let icp = create_test_event("DTest123", 0, None);
+
+        {
+            let db = RedbDatabase::open(&path).unwrap();
+            db.append_event(&icp).await.unwrap();
+        }
+
+        let db = RedbDatabase::open(&path).unwrap();
+        let stored = db.get_event("DTest123", 0).await.unwrap();
+        assert_eq!(stored.map(|e| e.event.digest), Some(icp.event.digest));
+        drop(db);
+
+        let _ = std::fs::remove_file(&path);
+    }
+}
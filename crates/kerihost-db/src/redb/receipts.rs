@@ -0,0 +1,138 @@
+//! Receipt storage implementation for redb
+//!
+//! Receipts live in `rcts` as `witness.signature` couplets under the
+//! event's `dgKey`. The event's prefix and sequence number come from `digs`,
+//! which is written with the first receipt if the event itself is not held.
+//! Conflicting receipts are recorded in `rcfs` under `wit.dig.sig`.
+
+use super::{
+    dg_key, kv_err, parse_sn_key, prefix_bounds, sn_key, ReadTxn, RedbDatabase, DIGS, RCFS, RCTS,
+};
+use crate::error::{DbError, DbResult};
+use crate::record;
+use crate::traits::{ReceiptConflict, ReceiptStore};
+use ::redb::{ReadableMultimapTable, ReadableTable};
+use async_trait::async_trait;
+use kerihost_core::NontransferableReceipt;
+
+/// Read every receipt for an event digest
+fn read_receipts(txn: &ReadTxn, event_digest: &str) -> DbResult<Vec<NontransferableReceipt>> {
+    let digs = txn.open_table(DIGS).map_err(kv_err)?;
+    let Some(key) = digs.get(event_digest).map_err(kv_err)? else {
+        return Ok(vec![]);
+    };
+    let (prefix, sn) = parse_sn_key(key.value())?;
+
+    let rcts = txn.open_multimap_table(RCTS).map_err(kv_err)?;
+    let mut receipts = Vec::new();
+    for couplet in rcts
+        .get(dg_key(prefix, event_digest).as_str())
+        .map_err(kv_err)?
+    {
+        let couplet = couplet.map_err(kv_err)?;
+        let (witness, signature) = couplet.value().split_once('.').ok_or_else(|| {
+            DbError::Other(format!("Malformed receipt couplet {}", couplet.value()))
+        })?;
+        receipts.push(NontransferableReceipt {
+            event_digest: event_digest.to_string(),
+            event_sn: sn,
+            event_prefix: prefix.to_string(),
+            witness_prefix: witness.to_string(),
+            signature: signature.to_string(),
+        });
+    }
+    Ok(receipts)
+}
+
+#[async_trait]
+impl ReceiptStore for RedbDatabase {
+    async fn add_receipt(&self, receipt: &NontransferableReceipt) -> DbResult<()> {
+        let receipt = receipt.clone();
+        // The conflict is committed before the receipt is rejected
+        let conflict = self
+            .write(move |txn| {
+                let mut digs = txn.open_table(DIGS).map_err(kv_err)?;
+                if digs
+                    .get(receipt.event_digest.as_str())
+                    .map_err(kv_err)?
+                    .is_none()
+                {
+                    let key = sn_key(&receipt.event_prefix, receipt.event_sn);
+                    digs.insert(receipt.event_digest.as_str(), key.as_str())
+                        .map_err(kv_err)?;
+                }
+
+                // One receipt per witness: only the first is written
+                let key = dg_key(&receipt.event_prefix, &receipt.event_digest);
+                let witness = format!("{}.", receipt.witness_prefix);
+                let mut rcts = txn.open_multimap_table(RCTS).map_err(kv_err)?;
+                let stored = rcts
+                    .get(key.as_str())
+                    .map_err(kv_err)?
+                    .map(|c| c.map(|c| c.value().to_string()).map_err(kv_err))
+                    .find(|c| c.as_ref().map_or(true, |c| c.starts_with(&witness)))
+                    .transpose()?;
+                let Some(stored) = stored else {
+                    let couplet = format!("{}{}", witness, receipt.signature);
+                    rcts.insert(key.as_str(), couplet.as_str())
+                        .map_err(kv_err)?;
+                    return Ok(None);
+                };
+
+                let stored = NontransferableReceipt {
+                    signature: stored[witness.len()..].to_string(),
+                    ..receipt.clone()
+                };
+                let Some(conflict) = ReceiptConflict::between(&stored, &receipt) else {
+                    return Ok(None);
+                };
+                let key = format!("{}{}", witness, conflict.key());
+                let mut rcfs = txn.open_table(RCFS).map_err(kv_err)?;
+                if rcfs.get(key.as_str()).map_err(kv_err)?.is_none() {
+                    rcfs.insert(key.as_str(), record::encode(&conflict)?.as_str())
+                        .map_err(kv_err)?;
+                }
+                Ok(Some(conflict))
+            })
+            .await?;
+
+        match conflict {
+            Some(conflict) => Err(DbError::from(&conflict)),
+            None => Ok(()),
+        }
+    }
+
+    async fn get_receipts(&self, event_digest: &str) -> DbResult<Vec<NontransferableReceipt>> {
+        let event_digest = event_digest.to_string();
+        self.read(move |txn| read_receipts(txn, &event_digest))
+            .await
+    }
+
+    async fn get_receipt(
+        &self,
+        event_digest: &str,
+        witness_prefix: &str,
+    ) -> DbResult<Option<NontransferableReceipt>> {
+        Ok(self
+            .get_receipts(event_digest)
+            .await?
+            .into_iter()
+            .find(|r| r.witness_prefix == witness_prefix))
+    }
+
+    async fn count_receipts(&self, event_digest: &str) -> DbResult<usize> {
+        Ok(self.get_receipts(event_digest).await?.len())
+    }
+
+    async fn get_receipt_conflicts(&self, witness_prefix: &str) -> DbResult<Vec<ReceiptConflict>> {
+        let (start, end) = prefix_bounds(witness_prefix);
+        self.read(move |txn| {
+            let rcfs = txn.open_table(RCFS).map_err(kv_err)?;
+            rcfs.range(start.as_str()..end.as_str())
+                .map_err(kv_err)?
+                .map(|entry| record::decode(entry.map_err(kv_err)?.1.value()))
+                .collect()
+        })
+        .await
+    }
+}
@@ -0,0 +1,49 @@
+//! State storage implementation for redb
+
+use super::{kv_err, RedbDatabase, STTS};
+use crate::error::DbResult;
+use crate::record;
+use crate::traits::StateStore;
+use async_trait::async_trait;
+use kerihost_core::KeyState;
+
+#[async_trait]
+impl StateStore for RedbDatabase {
+    async fn get_state(&self, prefix: &str) -> DbResult<Option<KeyState>> {
+        let prefix = prefix.to_string();
+        self.read(move |txn| {
+            let stts = txn.open_table(STTS).map_err(kv_err)?;
+            let state_json = stts.get(prefix.as_str()).map_err(kv_err)?;
+            match state_json {
+                Some(json) => Ok(Some(record::decode(json.value())?)),
+                None => Ok(None),
+            }
+        })
+        .await
+    }
+
+    async fn put_state(&self, state: &KeyState) -> DbResult<()> {
+        let prefix = state.prefix.clone();
+        let state_json = record::encode(state)?;
+        self.write(move |txn| {
+            txn.open_table(STTS)
+                .map_err(kv_err)?
+                .insert(prefix.as_str(), state_json.as_str())
+                .map_err(kv_err)?;
+            Ok(())
+        })
+        .await
+    }
+
+    async fn delete_state(&self, prefix: &str) -> DbResult<()> {
+        let prefix = prefix.to_string();
+        self.write(move |txn| {
+            txn.open_table(STTS)
+                .map_err(kv_err)?
+                .remove(prefix.as_str())
+                .map_err(kv_err)?;
+            Ok(())
+        })
+        .await
+    }
+}
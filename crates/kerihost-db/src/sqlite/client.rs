@@ -0,0 +1,78 @@
+//! SQLite connection pool wrapper
+
+use crate::error::DbResult;
+use crate::traits::{validate_tenant, TenantStore};
+use sqlx::migrate::Migrator;
+use sqlx::sqlite::{SqliteConnectOptions, SqliteJournalMode, SqlitePool, SqlitePoolOptions};
+use sqlx::{Sqlite, Transaction};
+use std::path::Path;
+use std::time::Duration;
+
+/// Schema migrations, embedded at compile time
+static MIGRATOR: Migrator = sqlx::migrate!("./migrations/sqlite");
+
+/// How long a writer waits for the database lock before failing
+const BUSY_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// SQLite database implementation
+#[derive(Clone)]
+pub struct SqliteDatabase {
+    pub(crate) pool: SqlitePool,
+    tenant: Option<String>,
+}
+
+impl SqliteDatabase {
+    /// Open the database file at `path`, creating it if missing
+    pub async fn connect(path: impl AsRef<Path>) -> DbResult<Self> {
+        let options = SqliteConnectOptions::new()
+            .filename(path)
+            .create_if_missing(true)
+            .journal_mode(SqliteJournalMode::Wal)
+            .busy_timeout(BUSY_TIMEOUT);
+        let pool = SqlitePoolOptions::new().connect_with(options).await?;
+        Self::from_pool(pool).await
+    }
+
+    /// Open a private in-memory database (for testing)
+    pub async fn in_memory() -> DbResult<Self> {
+        // Every in-memory connection is a separate database, so the pool
+        // must keep exactly one connection alive for the database's lifetime
+        let pool = SqlitePoolOptions::new()
+            .max_connections(1)
+            .idle_timeout(None)
+            .max_lifetime(None)
+            .connect_with(SqliteConnectOptions::new().in_memory(true))
+            .await?;
+        Self::from_pool(pool).await
+    }
+
+    /// Wrap an existing pool, applying any pending migrations
+    pub async fn from_pool(pool: SqlitePool) -> DbResult<Self> {
+        MIGRATOR.run(&pool).await.map_err(sqlx::Error::from)?;
+        Ok(SqliteDatabase { pool, tenant: None })
+    }
+
+    /// Begin a transaction that takes the write lock immediately
+    pub(crate) async fn begin_write(&self) -> DbResult<Transaction<'static, Sqlite>> {
+        Ok(self.pool.begin_with("BEGIN IMMEDIATE").await?)
+    }
+
+    /// Value of the `tenant` column for this handle's namespace
+    pub(super) fn namespace(&self) -> &str {
+        self.tenant.as_deref().unwrap_or_default()
+    }
+}
+
+impl TenantStore for SqliteDatabase {
+    fn tenant(&self) -> Option<&str> {
+        self.tenant.as_deref()
+    }
+
+    fn for_tenant(&self, tenant: &str) -> DbResult<Self> {
+        validate_tenant(tenant)?;
+        Ok(SqliteDatabase {
+            pool: self.pool.clone(),
+            tenant: Some(tenant.to_string()),
+        })
+    }
+}
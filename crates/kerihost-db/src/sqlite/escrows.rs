@@ -0,0 +1,188 @@
+//! Escrow storage implementation for SQLite
+
+use super::kel::decode_event;
+use super::SqliteDatabase;
+use crate::error::{DbError, DbResult};
+use crate::record;
+use crate::traits::{
+    expiry_cursor, parse_expiry_cursor, EscrowReason, EscrowStore, EscrowedEvent, Page,
+};
+use async_trait::async_trait;
+use kerihost_core::SignedEvent;
+
+/// Parse an escrow row, rebuilding the event from its stored CESR
+fn parse_escrowed((escrowed_json, cesr): (String, Vec<u8>)) -> DbResult<EscrowedEvent> {
+    let mut escrowed: EscrowedEvent = record::decode(&escrowed_json)?;
+    escrowed.event = decode_event(&cesr)?;
+    Ok(escrowed)
+}
+
+#[async_trait]
+impl EscrowStore for SqliteDatabase {
+    async fn escrow_event_with_ttl(
+        &self,
+        event: &SignedEvent,
+        reason: EscrowReason,
+        ttl_seconds: u64,
+    ) -> DbResult<()> {
+        let escrowed = EscrowedEvent::new(event.clone(), reason, ttl_seconds);
+        let escrowed_json = record::encode(&escrowed)?;
+        let cesr = event
+            .to_cesr()
+            .map_err(|e| DbError::Serialization(e.to_string()))?;
+
+        sqlx::query(
+            "INSERT INTO escrows (tenant, digest, aid, reason, escrowed, cesr, ttl) \
+             VALUES (?, ?, ?, ?, ?, ?, ?) \
+             ON CONFLICT (tenant, digest) DO UPDATE SET \
+             reason = excluded.reason, escrowed = excluded.escrowed, \
+             cesr = excluded.cesr, ttl = excluded.ttl",
+        )
+        .bind(self.namespace())
+        .bind(&event.event.digest)
+        .bind(&event.event.prefix)
+        .bind(reason.to_string())
+        .bind(escrowed_json)
+        .bind(cesr)
+        .bind(escrowed.ttl as i64)
+        .execute(&self.pool)
+        .await?;
+
+        Ok(())
+    }
+
+    async fn record_escrow_attempt(&self, escrowed: &EscrowedEvent) -> DbResult<()> {
+        sqlx::query(
+            "UPDATE escrows SET escrowed = ? WHERE tenant = ? AND digest = ? AND reason = ?",
+        )
+        .bind(record::encode(escrowed)?)
+        .bind(self.namespace())
+        .bind(&escrowed.event.event.digest)
+        .bind(escrowed.reason.to_string())
+        .execute(&self.pool)
+        .await?;
+
+        Ok(())
+    }
+
+    async fn get_escrowed_page(
+        &self,
+        limit: usize,
+        cursor: Option<&str>,
+    ) -> DbResult<Page<EscrowedEvent>> {
+        // Pages are keyed by digest so re-escrowed rows are not revisited
+        let limit = limit.max(1);
+        let rows: Vec<(String, Vec<u8>)> = sqlx::query_as(
+            "SELECT escrowed, cesr FROM escrows WHERE tenant = ? AND digest > ? \
+             ORDER BY digest LIMIT ?",
+        )
+        .bind(self.namespace())
+        .bind(cursor.unwrap_or_default())
+        .bind(limit as i64 + 1)
+        .fetch_all(&self.pool)
+        .await?;
+
+        let more = rows.len() > limit;
+        let items = rows
+            .into_iter()
+            .take(limit)
+            .map(parse_escrowed)
+            .collect::<DbResult<Vec<_>>>()?;
+        let next = more
+            .then(|| items.last().map(|e| e.event.event.digest.clone()))
+            .flatten();
+        Ok(Page { items, next })
+    }
+
+    async fn get_escrowed_by_prefix(
+        &self,
+        prefix: &str,
+        limit: usize,
+        cursor: Option<&str>,
+    ) -> DbResult<Page<EscrowedEvent>> {
+        let limit = limit.max(1);
+        let rows: Vec<(String, Vec<u8>)> = sqlx::query_as(
+            "SELECT escrowed, cesr FROM escrows WHERE tenant = ? AND aid = ? AND digest > ? \
+             ORDER BY digest LIMIT ?",
+        )
+        .bind(self.namespace())
+        .bind(prefix)
+        .bind(cursor.unwrap_or_default())
+        .bind(limit as i64 + 1)
+        .fetch_all(&self.pool)
+        .await?;
+
+        let more = rows.len() > limit;
+        let items = rows
+            .into_iter()
+            .take(limit)
+            .map(parse_escrowed)
+            .collect::<DbResult<Vec<_>>>()?;
+        let next = more
+            .then(|| items.last().map(|e| e.event.event.digest.clone()))
+            .flatten();
+        Ok(Page { items, next })
+    }
+
+    async fn get_escrowed_by_reason(
+        &self,
+        reason: EscrowReason,
+        limit: usize,
+        cursor: Option<&str>,
+    ) -> DbResult<Page<EscrowedEvent>> {
+        let (ttl, digest) = match cursor {
+            Some(cursor) => {
+                let (ttl, digest) = parse_expiry_cursor(cursor)?;
+                (ttl as i64, digest)
+            }
+            None => (-1, ""),
+        };
+        let limit = limit.max(1);
+        let rows: Vec<(String, Vec<u8>)> = sqlx::query_as(
+            "SELECT escrowed, cesr FROM escrows \
+             WHERE tenant = ? AND reason = ? AND (ttl > ? OR (ttl = ? AND digest > ?)) \
+             ORDER BY ttl, digest LIMIT ?",
+        )
+        .bind(self.namespace())
+        .bind(reason.to_string())
+        .bind(ttl)
+        .bind(ttl)
+        .bind(digest)
+        .bind(limit as i64 + 1)
+        .fetch_all(&self.pool)
+        .await?;
+
+        let more = rows.len() > limit;
+        let items = rows
+            .into_iter()
+            .take(limit)
+            .map(parse_escrowed)
+            .collect::<DbResult<Vec<_>>>()?;
+        let next = more.then(|| items.last().map(expiry_cursor)).flatten();
+        Ok(Page { items, next })
+    }
+
+    async fn promote_escrowed(&self, event_digest: &str) -> DbResult<Option<SignedEvent>> {
+        // Deleting and returning in one statement means only one caller
+        // can promote a given escrow
+        let cesr: Option<Vec<u8>> = sqlx::query_scalar(
+            "DELETE FROM escrows WHERE tenant = ? AND digest = ? RETURNING cesr",
+        )
+        .bind(self.namespace())
+        .bind(event_digest)
+        .fetch_optional(&self.pool)
+        .await?;
+
+        cesr.as_deref().map(decode_event).transpose()
+    }
+
+    async fn remove_escrowed(&self, event_digest: &str) -> DbResult<()> {
+        sqlx::query("DELETE FROM escrows WHERE tenant = ? AND digest = ?")
+            .bind(self.namespace())
+            .bind(event_digest)
+            .execute(&self.pool)
+            .await?;
+
+        Ok(())
+    }
+}
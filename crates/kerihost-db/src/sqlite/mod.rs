@@ -0,0 +1,331 @@
+//! SQLite database implementation
+//!
+//! This implementation stores KERI data in a single SQLite database file,
+//! suitable for self-hosted witnesses that do not run on AWS. Writes that
+//! must be atomic run in `BEGIN IMMEDIATE` transactions, so concurrent
+//! writers serialize on the database lock instead of racing between the
+//! prior-event check and the insert.
+//!
+//! Rows carry the tenant they were written under in a `tenant` column,
+//! empty for the default namespace, and handles scoped with
+//! `TenantStore::for_tenant` only read and write their tenant's rows.
+//!
+//! The schema is created and upgraded by the migrations under
+//! `migrations/sqlite`, which run whenever a database is opened.
+
+mod client;
+mod escrows;
+mod kel;
+mod receipts;
+mod states;
+
+pub use client::SqliteDatabase;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error::DbError;
+    use crate::test_support::*;
+    use crate::traits::{EscrowReason, EscrowStore, KelStore, ReceiptStore, StateStore};
+    use kerihost_core::Anchor;
+
+    async fn test_db() -> SqliteDatabase {
+        SqliteDatabase::in_memory().await.unwrap()
+    }
+
+    crate::conformance::conformance_tests!(Some(test_db().await));
+
+    #[tokio::test]
+    async fn test_tenant_isolation() {
+        crate::conformance::tenant_isolation(&test_db().await).await;
+    }
+
+    #[tokio::test]
+    async fn test_kel_append_and_read() {
+        let db = test_db().await;
+
+        let icp = create_test_event("DTest123", 0, None);
+        db.append_event(&icp).await.unwrap();
+        let ixn1 = create_test_event("DTest123", 1, Some(icp.event.digest.clone()));
+        db.append_event(&ixn1).await.unwrap();
+        let ixn2 = create_test_event("DTest123", 2, Some(ixn1.event.digest.clone()));
+        db.append_event(&ixn2).await.unwrap();
+
+        let stored = db.get_event("DTest123", 0).await.unwrap().unwrap();
+        assert_eq!(stored.to_cesr().unwrap(), icp.to_cesr().unwrap());
+        assert_eq!(db.get_events("DTest123", 0, None).await.unwrap().len(), 3);
+        assert_eq!(db.get_events("DTest123", 1, Some(1)).await.unwrap().len(), 1);
+        assert_eq!(db.get_latest("DTest123").await.unwrap().unwrap().event.sn, 2);
+        assert_eq!(
+            db.get_event_by_digest("DTest123", &ixn1.event.digest)
+                .await
+                .unwrap()
+                .map(|e| e.event.sn),
+            Some(1)
+        );
+        assert!(db.get_event("DOther", 0).await.unwrap().is_none());
+    }
+
+    #[tokio::test]
+    async fn test_kel_append_rejections() {
+        let db = test_db().await;
+        let icp = create_test_event("DTest123", 0, None);
+        db.append_event(&icp).await.unwrap();
+
+        let result = db.append_event(&icp).await;
+        assert!(matches!(result, Err(DbError::Duplicate(_))));
+
+        let wrong = create_test_event("DTest123", 1, Some("EWrongDigest".to_string()));
+        let result = db.append_event(&wrong).await;
+        assert!(matches!(result, Err(DbError::PriorDigestMismatch { .. })));
+
+        let gap = create_test_event("DTest123", 5, Some("EPrior".to_string()));
+        let result = db.append_event(&gap).await;
+        assert!(matches!(result, Err(DbError::NotFound(_))));
+    }
+
+    #[tokio::test]
+    async fn test_first_seen_log() {
+        let db = test_db().await;
+        let events = append_test_kel(&db, "DTest123", 3).await;
+
+        let entries = db.get_first_seen_range("DTest123", 0, None).await.unwrap();
+        assert_eq!(entries.len(), 3);
+        assert_eq!(entries[2].ordinal, 2);
+        assert_eq!(entries[2].digest, events[2].event.digest);
+        assert_eq!(
+            db.get_first_seen_range("DTest123", 1, Some(1)).await.unwrap()[0].sn,
+            1
+        );
+
+        let entry = db
+            .get_first_seen("DTest123", &events[1].event.digest)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(entry.ordinal, 1);
+        assert!(!entry.datetime.is_empty());
+
+        // A rejected append records nothing
+        assert!(db.append_event(&events[2]).await.is_err());
+        assert_eq!(db.get_first_seen_range("DTest123", 0, None).await.unwrap().len(), 3);
+    }
+
+    #[tokio::test]
+    async fn test_kel_find_anchor() {
+        let db = test_db().await;
+        let icp = create_test_event("DTest123", 0, None);
+        db.append_event(&icp).await.unwrap();
+        let ixn = create_anchoring_event(
+            "DTest123",
+            1,
+            Some(icp.event.digest.clone()),
+            vec![
+                Anchor::digest("ECredential"),
+                Anchor::event("EDelegate", "b", "EDelegated"),
+            ],
+        );
+        db.append_event(&ixn).await.unwrap();
+
+        let location = db.find_anchor("ECredential").await.unwrap().unwrap();
+        assert_eq!(location.sn, 1);
+        assert_eq!(location.event_digest, ixn.event.digest);
+        assert!(db.find_anchor("ENotAnchored").await.unwrap().is_none());
+
+        let seal = db.find_event_seal("EDelegate", 11, "EDelegated").await.unwrap();
+        assert_eq!(seal.map(|l| l.sn), Some(1));
+        assert!(db
+            .find_event_seal("EDelegate", 12, "EDelegated")
+            .await
+            .unwrap()
+            .is_none());
+    }
+
+    #[tokio::test]
+    async fn test_commit_event() {
+        let db = test_db().await;
+
+        let icp = create_test_event("DTest123", 0, None);
+        let mut state = create_test_state("DTest123", 0);
+        state.latest_digest = icp.event.digest.clone();
+        db.commit_event(&icp, &state).await.unwrap();
+
+        let ixn = create_test_event("DTest123", 1, Some(icp.event.digest.clone()));
+        db.commit_event(&ixn, &create_test_state("DTest123", 1))
+            .await
+            .unwrap();
+
+        assert_eq!(db.get_events("DTest123", 0, None).await.unwrap().len(), 2);
+        assert_eq!(db.get_state("DTest123").await.unwrap().unwrap().sn, 1);
+    }
+
+    #[tokio::test]
+    async fn test_commit_event_rolls_back_on_conflict() {
+        let db = test_db().await;
+
+        let icp = create_test_event("DTest123", 0, None);
+        db.append_event(&icp).await.unwrap();
+
+        // State was never written for the inception, so sn 1 cannot commit
+        let ixn = create_test_event("DTest123", 1, Some(icp.event.digest.clone()));
+        let result = db
+            .commit_event(&ixn, &create_test_state("DTest123", 1))
+            .await;
+        assert!(matches!(result, Err(DbError::StateConflict(_))));
+        assert!(result.unwrap_err().is_retryable());
+
+        // A failed append inside the commit leaves the state untouched too
+        let mut state = create_test_state("DTest123", 0);
+        state.latest_digest = icp.event.digest.clone();
+        let result = db.commit_event(&icp, &state).await;
+        assert!(matches!(result, Err(DbError::Duplicate(_))));
+
+        assert_eq!(db.get_events("DTest123", 0, None).await.unwrap().len(), 1);
+        assert!(db.get_state("DTest123").await.unwrap().is_none());
+    }
+
+    #[tokio::test]
+    async fn test_state_store() {
+        let db = test_db().await;
+
+        db.put_state(&create_test_state("DTest123", 0)).await.unwrap();
+        db.put_state(&create_test_state("DTest123", 1)).await.unwrap();
+        assert_eq!(db.get_state("DTest123").await.unwrap().unwrap().sn, 1);
+
+        db.delete_state("DTest123").await.unwrap();
+        assert!(db.get_state("DTest123").await.unwrap().is_none());
+    }
+
+    #[tokio::test]
+    async fn test_receipt_store() {
+        let db = test_db().await;
+
+        db.add_receipt(&create_test_receipt("EDigest123", "BWitness1"))
+            .await
+            .unwrap();
+        db.add_receipt(&create_test_receipt("EDigest123", "BWitness2"))
+            .await
+            .unwrap();
+        // Deduped by witness
+        db.add_receipt(&create_test_receipt("EDigest123", "BWitness1"))
+            .await
+            .unwrap();
+
+        assert_eq!(db.count_receipts("EDigest123").await.unwrap(), 2);
+        assert_eq!(db.get_receipts("EDigest123").await.unwrap().len(), 2);
+        assert!(db
+            .get_receipt("EDigest123", "BWitness2")
+            .await
+            .unwrap()
+            .is_some());
+        assert!(db
+            .get_receipt("EDigest123", "BWitness3")
+            .await
+            .unwrap()
+            .is_none());
+    }
+
+    #[tokio::test]
+    async fn test_escrow_store() {
+        let db = test_db().await;
+        let event1 = create_test_event("DTest1", 5, Some("EP1".to_string()));
+        let event2 = create_test_event("DTest2", 3, Some("EP2".to_string()));
+
+        db.escrow_event(&event1, EscrowReason::OutOfOrder)
+            .await
+            .unwrap();
+        db.escrow_event(&event2, EscrowReason::PartiallySigned)
+            .await
+            .unwrap();
+
+        let escrowed = db.get_escrowed("DTest1").await.unwrap();
+        assert_eq!(escrowed.len(), 1);
+        assert_eq!(escrowed[0].reason, EscrowReason::OutOfOrder);
+        assert_eq!(escrowed[0].event.event.raw, event1.event.raw);
+        assert_eq!(db.get_all_escrowed().await.unwrap().len(), 2);
+
+        let signed = db
+            .get_escrowed_by_reason(EscrowReason::PartiallySigned, 10, None)
+            .await
+            .unwrap();
+        assert_eq!(signed.items.len(), 1);
+        assert_eq!(signed.items[0].event.event.digest, event2.event.digest);
+        assert!(signed.next.is_none());
+
+        let promoted = db.promote_escrowed(&event1.event.digest).await.unwrap();
+        assert_eq!(promoted.map(|e| e.event.digest), Some(event1.event.digest.clone()));
+        assert!(db
+            .promote_escrowed(&event1.event.digest)
+            .await
+            .unwrap()
+            .is_none());
+
+        db.remove_escrowed(&event2.event.digest).await.unwrap();
+        assert!(db.get_all_escrowed().await.unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_pagination() {
+        let db = test_db().await;
+
+        let events = append_test_kel(&db, "DTest123", 5).await;
+        let mut kel = Vec::new();
+        let mut cursor = None;
+        loop {
+            let page = db
+                .get_events_page("DTest123", 0, None, 2, cursor.as_deref())
+                .await
+                .unwrap();
+            kel.extend(page.items);
+            cursor = page.next;
+            if cursor.is_none() {
+                break;
+            }
+        }
+        assert_eq!(kel.len(), 5);
+        assert_eq!(kel[4].event.digest, events[4].event.digest);
+
+        for i in 0..5 {
+            let event = create_test_event(&format!("DTest{}", i), 5, Some("EP".to_string()));
+            db.escrow_event(&event, EscrowReason::OutOfOrder)
+                .await
+                .unwrap();
+        }
+        let first = db.get_escrowed_page(3, None).await.unwrap();
+        assert_eq!(first.items.len(), 3);
+        db.remove_escrowed(&first.items[0].event.event.digest)
+            .await
+            .unwrap();
+        let second = db
+            .get_escrowed_page(3, first.next.as_deref())
+            .await
+            .unwrap();
+        assert_eq!(second.items.len(), 2);
+        assert!(second.next.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_file_database_persists() {
+        let path = std::env::temp_dir().join(format!(
+            "kerihost-sqlite-test-{}.db",
+            std::process::id()
+        ));
+        let icp = create_test_event("DTest123", 0, None);
+
+        {
+            let db = SqliteDatabase::connect(&path).await.unwrap();
+            db.append_event(&icp).await.unwrap();
+            db.pool.close().await;
+        }
+
+        // Reopening re-runs migrations as a no-op and sees the data
+        let db = SqliteDatabase::connect(&path).await.unwrap();
+        let stored = db.get_event("DTest123", 0).await.unwrap();
+        assert_eq!(stored.map(|e| e.event.digest), Some(icp.event.digest));
+        db.pool.close().await;
+
+        for suffix in ["", "-wal", "-shm"] {
+            let _ = std::fs::remove_file(format!("{}{}", path.display(), suffix));
+        }
+    }
+}
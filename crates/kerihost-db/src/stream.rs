@@ -0,0 +1,242 @@
+//! Streaming reads built on paginated store methods
+//!
+//! Each stream fetches one page at a time, so memory stays bounded by the
+//! page size however long the KEL or escrow backlog is.
+
+use crate::error::{DbError, DbResult};
+use crate::traits::{
+    EscrowStore, EscrowedEvent, EscrowedMessage, ExchangeStore, FirstSeen, KelStore, Page,
+    ReceiptStore,
+};
+use futures::stream::{self, Stream, TryStreamExt};
+use kerihost_core::{FirstSeenCouple, ReplayEvent, SignedEvent};
+use std::future::Future;
+
+/// Stream the events of a KEL from `start_sn`, `page_size` at a time
+pub fn kel_events<'a, D: KelStore + ?Sized>(
+    db: &'a D,
+    prefix: &'a str,
+    start_sn: u64,
+    page_size: usize,
+) -> impl Stream<Item = DbResult<SignedEvent>> + Send + 'a {
+    paged(move |cursor| async move {
+        db.get_events_page(prefix, start_sn, None, page_size, cursor.as_deref())
+            .await
+    })
+}
+
+/// Stream every escrowed event, `page_size` at a time
+pub fn escrowed_events<D: EscrowStore + ?Sized>(
+    db: &D,
+    page_size: usize,
+) -> impl Stream<Item = DbResult<EscrowedEvent>> + Send + '_ {
+    paged(move |cursor| async move { db.get_escrowed_page(page_size, cursor.as_deref()).await })
+}
+
+/// Stream the escrowed events of one prefix, `page_size` at a time
+pub fn escrowed_events_for<'a, D: EscrowStore + ?Sized>(
+    db: &'a D,
+    prefix: &'a str,
+    page_size: usize,
+) -> impl Stream<Item = DbResult<EscrowedEvent>> + Send + 'a {
+    paged(move |cursor| async move {
+        db.get_escrowed_by_prefix(prefix, page_size, cursor.as_deref())
+            .await
+    })
+}
+
+/// Stream every escrowed exchange message, `page_size` at a time
+pub fn escrowed_messages<D: ExchangeStore + ?Sized>(
+    db: &D,
+    page_size: usize,
+) -> impl Stream<Item = DbResult<EscrowedMessage>> + Send + '_ {
+    paged(move |cursor| async move {
+        db.get_escrowed_messages_page(page_size, cursor.as_deref())
+            .await
+    })
+}
+
+/// Stream the prefix of every KEL, `page_size` at a time
+pub fn prefixes<D: KelStore + ?Sized>(
+    db: &D,
+    page_size: usize,
+) -> impl Stream<Item = DbResult<String>> + Send + '_ {
+    paged(move |cursor| async move { db.get_prefixes_page(page_size, cursor.as_deref()).await })
+}
+
+/// Stream a KEL in first-seen order with its receipts and first-seen data,
+/// ready to encode as a replay stream
+///
+/// The first-seen log is read `page_size` ordinals at a time and each event
+/// is fetched by its sequence number, so the cost is linear in the KEL.
+pub fn kel_replay<D: KelStore + ReceiptStore + ?Sized>(
+    db: &D,
+    prefix: String,
+    page_size: usize,
+) -> impl Stream<Item = DbResult<ReplayEvent>> + Send + '_ {
+    let page_size = page_size.max(1) as u64;
+    // Ordinals run from 0 without gaps, so a short window is the last one
+    stream::try_unfold(Some(0), move |start: Option<u64>| {
+        let prefix = prefix.clone();
+        async move {
+            let Some(start) = start else {
+                return DbResult::Ok(None);
+            };
+            let end = start + page_size - 1;
+            let entries = db.get_first_seen_range(&prefix, start, Some(end)).await?;
+            let next = (entries.len() as u64 == page_size).then_some(end + 1);
+            Ok(Some((stream::iter(entries.into_iter().map(Ok)), next)))
+        }
+    })
+    .try_flatten()
+    .and_then(move |entry| replay_event(db, entry))
+}
+
+/// Stream every KEL as `kel_replay` does, one prefix after another
+pub fn replay_all<D: KelStore + ReceiptStore + ?Sized>(
+    db: &D,
+    page_size: usize,
+) -> impl Stream<Item = DbResult<ReplayEvent>> + Send + '_ {
+    prefixes(db, page_size)
+        .map_ok(move |prefix| kel_replay(db, prefix, page_size))
+        .try_flatten()
+}
+
+/// Load the event and receipts behind a first-seen entry
+async fn replay_event<D: KelStore + ReceiptStore + ?Sized>(
+    db: &D,
+    entry: FirstSeen,
+) -> DbResult<ReplayEvent> {
+    let signed = db
+        .get_event(&entry.prefix, entry.sn)
+        .await?
+        .ok_or_else(|| {
+            DbError::NotFound(format!(
+                "First-seen event {} missing from KEL of {}",
+                entry.digest, entry.prefix
+            ))
+        })?;
+    if signed.event.digest != entry.digest {
+        return Err(DbError::Corruption(format!(
+            "First-seen entry {} of {} names {} but sn {} holds {}",
+            entry.ordinal, entry.prefix, entry.digest, entry.sn, signed.event.digest
+        )));
+    }
+    let receipts = db.get_receipts(&entry.digest).await?;
+    Ok(ReplayEvent {
+        signed,
+        receipts,
+        first_seen: Some(FirstSeenCouple {
+            ordinal: entry.ordinal,
+            datetime: entry.datetime,
+        }),
+    })
+}
+
+/// Flatten successive pages into a stream of items
+fn paged<T, F, Fut>(fetch: F) -> impl Stream<Item = DbResult<T>> + Send
+where
+    T: Send,
+    F: Fn(Option<String>) -> Fut + Send,
+    Fut: Future<Output = DbResult<Page<T>>> + Send,
+{
+    // `None` once the last page has been fetched
+    stream::try_unfold(Some(None), move |cursor: Option<Option<String>>| {
+        let page = cursor.map(&fetch);
+        async move {
+            match page {
+                Some(page) => page.await.map(|page| {
+                    let items = stream::iter(page.items.into_iter().map(Ok));
+                    Some((items, page.next.map(Some)))
+                }),
+                None => Ok(None),
+            }
+        }
+    })
+    .try_flatten()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::memory::InMemoryDatabase;
+    use crate::test_support::*;
+    use crate::traits::EscrowReason;
+
+    #[tokio::test]
+    async fn test_kel_events_spans_pages() {
+        let db = InMemoryDatabase::new();
+        let events = append_test_kel(&db, "DTest123", 7).await;
+
+        let streamed: Vec<SignedEvent> = kel_events(&db, "DTest123", 1, 3)
+            .try_collect()
+            .await
+            .unwrap();
+        assert_eq!(streamed.len(), 6);
+        assert_eq!(streamed[0].event.digest, events[1].event.digest);
+        assert_eq!(streamed[5].event.digest, events[6].event.digest);
+    }
+
+    #[tokio::test]
+    async fn test_escrowed_events_spans_pages() {
+        let db = InMemoryDatabase::new();
+        for i in 0..5 {
+            let event = create_test_event(&format!("DTest{}", i), 5, Some("EP".to_string()));
+            db.escrow_event(&event, EscrowReason::OutOfOrder)
+                .await
+                .unwrap();
+        }
+
+        let streamed: Vec<EscrowedEvent> = escrowed_events(&db, 2).try_collect().await.unwrap();
+        assert_eq!(streamed.len(), 5);
+
+        let empty = InMemoryDatabase::new();
+        let streamed: Vec<EscrowedEvent> = escrowed_events(&empty, 2).try_collect().await.unwrap();
+        assert!(streamed.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_kel_replay_spans_windows() {
+        let db = InMemoryDatabase::new();
+        let events = append_test_kel(&db, "DTest123", 4).await;
+
+        // A KEL that fills its last window exactly ends on an empty one
+        let replayed: Vec<ReplayEvent> = kel_replay(&db, "DTest123".to_string(), 2)
+            .try_collect()
+            .await
+            .unwrap();
+        let digests: Vec<&str> = replayed
+            .iter()
+            .map(|r| r.signed.event.digest.as_str())
+            .collect();
+        let expected: Vec<&str> = events.iter().map(|e| e.event.digest.as_str()).collect();
+        assert_eq!(digests, expected);
+    }
+
+    #[tokio::test]
+    async fn test_replay_all_in_first_seen_order() {
+        let db = InMemoryDatabase::new();
+        let events = append_test_kel(&db, "DTest1", 3).await;
+        append_test_kel(&db, "DTest2", 2).await;
+        append_test_kel(&db, "DTest3", 1).await;
+        db.add_receipt(&create_test_receipt(&events[1].event.digest, "BWitness1"))
+            .await
+            .unwrap();
+
+        let replayed: Vec<ReplayEvent> = replay_all(&db, 2).try_collect().await.unwrap();
+        assert_eq!(replayed.len(), 6);
+
+        let kel: Vec<&ReplayEvent> = replayed
+            .iter()
+            .filter(|r| r.signed.event.prefix == "DTest1")
+            .collect();
+        let ordinals: Vec<u64> = kel
+            .iter()
+            .map(|r| r.first_seen.as_ref().unwrap().ordinal)
+            .collect();
+        assert_eq!(ordinals, vec![0, 1, 2]);
+        assert_eq!(kel[1].signed.event.digest, events[1].event.digest);
+        assert_eq!(kel[1].receipts.len(), 1);
+        assert!(kel[0].receipts.is_empty());
+    }
+}
@@ -0,0 +1,136 @@
+//! Shared fixtures for backend tests
+
+use cesride::{counter, Counter, Diger, Matter, Seqner, Signer};
+use kerihost_core::{
+    Anchor, IndexedSignature, KeyEvent, KeyState, NontransferableReceipt, SignedEvent, Threshold,
+};
+use crate::traits::KelStore;
+use serde_json::json;
+
+/// Set, as in CI, to fail backend tests whose server is not configured
+/// instead of skipping them
+const REQUIRE_BACKENDS_VAR: &str = "KERIHOST_TEST_REQUIRE_BACKENDS";
+
+/// Read the setting naming a backend's test server, or `None` to skip the
+/// backend's tests
+///
+/// Panics if the setting is missing while `KERIHOST_TEST_REQUIRE_BACKENDS`
+/// is set, so a backend suite cannot pass without having run.
+pub(crate) fn test_server(var: &str) -> Option<String> {
+    match std::env::var(var) {
+        Ok(value) => Some(value),
+        Err(_) if std::env::var_os(REQUIRE_BACKENDS_VAR).is_some() => {
+            panic!("{} not set but {} is", var, REQUIRE_BACKENDS_VAR)
+        }
+        Err(_) => {
+            eprintln!("{} not set, skipping test", var);
+            None
+        }
+    }
+}
+
+/// Controller signer for test events
+pub(crate) fn test_signer() -> Signer {
+    Signer::new_with_raw(&[1u8; 32], Some(true), None).unwrap()
+}
+
+/// Append a KEL of `len` chained test events
+pub(crate) async fn append_test_kel<D: KelStore + ?Sized>(
+    db: &D,
+    prefix: &str,
+    len: u64,
+) -> Vec<SignedEvent> {
+    let mut events: Vec<SignedEvent> = Vec::new();
+    for sn in 0..len {
+        let prior = events.last().map(|e| e.event.digest.clone());
+        let event = create_test_event(prefix, sn, prior);
+        db.append_event(&event).await.unwrap();
+        events.push(event);
+    }
+    events
+}
+
+pub(crate) fn create_test_event(prefix: &str, sn: u64, prior_digest: Option<String>) -> SignedEvent {
+    create_anchoring_event(prefix, sn, prior_digest, vec![])
+}
+
+/// Build a real SAIDified event signed by `test_signer`
+pub(crate) fn create_anchoring_event(
+    prefix: &str,
+    sn: u64,
+    prior_digest: Option<String>,
+    anchors: Vec<Anchor>,
+) -> SignedEvent {
+    let signer = test_signer();
+    let key = signer.verfer().qb64().unwrap();
+
+    let mut ked = json!({
+        "v": "KERI10JSON000000_",
+        "t": if sn == 0 { "icp" } else { "ixn" },
+        "d": "",
+        "i": prefix,
+        "s": format!("{:x}", sn),
+    });
+    if sn == 0 {
+        ked["kt"] = json!("1");
+        ked["k"] = json!([key]);
+        ked["nt"] = json!("0");
+        ked["n"] = json!([]);
+        ked["bt"] = json!("0");
+        ked["b"] = json!([]);
+        ked["c"] = json!([]);
+    } else if let Some(prior) = prior_digest {
+        ked["p"] = json!(prior);
+    }
+    ked["a"] = json!(anchors);
+
+    let ked = kerihost_core::saidify(&ked, "d").unwrap();
+    let raw = serde_json::to_vec(&ked).unwrap();
+    let siger = signer.sign_indexed(&raw, false, 0, None).unwrap();
+
+    SignedEvent::new(
+        KeyEvent::from_cesr(&raw).unwrap(),
+        vec![IndexedSignature::from_siger(&siger).unwrap()],
+    )
+}
+
+/// Attach a delegation seal source couple, as on a delegated event, after
+/// the event's signatures
+pub(crate) fn with_seal_source(mut event: SignedEvent, sn: u64, digest: &str) -> SignedEvent {
+    let counter = Counter::new_with_code_and_count(counter::Codex::SealSourceCouples, 1).unwrap();
+    event.attachments.extend(counter.qb64b().unwrap());
+    event
+        .attachments
+        .extend(Seqner::new_with_sn(sn as u128).unwrap().qb64b().unwrap());
+    event
+        .attachments
+        .extend(Diger::new_with_qb64(digest).unwrap().qb64b().unwrap());
+    event
+}
+
+pub(crate) fn create_test_state(prefix: &str, sn: u64) -> KeyState {
+    KeyState {
+        prefix: prefix.to_string(),
+        sn,
+        latest_digest: format!("EDigest{}_{}", prefix, sn),
+        signing_keys: vec!["DKey1".to_string()],
+        signing_threshold: Threshold::simple(1),
+        next_key_digest: Some("ENext123".to_string()),
+        witnesses: vec![],
+        witness_threshold: Threshold::simple(0),
+        delegator: None,
+        config: vec![],
+        transferable: true,
+        metadata: kerihost_core::HonestMetadata::local_only(0),
+    }
+}
+
+pub(crate) fn create_test_receipt(event_digest: &str, witness: &str) -> NontransferableReceipt {
+    NontransferableReceipt {
+        event_digest: event_digest.to_string(),
+        event_sn: 0,
+        event_prefix: "DTest123".to_string(),
+        witness_prefix: witness.to_string(),
+        signature: "0BSig123".to_string(),
+    }
+}
@@ -3,10 +3,21 @@
 //! These traits define the interface for storing and retrieving KERI data.
 //! Implementations can use different backends (DynamoDB, in-memory, etc.)
 
-use crate::error::DbResult;
+use crate::error::{DbError, DbResult};
 use async_trait::async_trait;
-use kerihost_core::{KeyState, NontransferableReceipt, SignedEvent};
+use futures::stream::BoxStream;
+#[cfg(any(test, feature = "test-util"))]
+use futures::TryStreamExt;
+use kerihost_core::{
+    Anchor, CredentialSchema, ExchangeMessage, IpexExchange, KeyState, NontransferableReceipt,
+    SignedEvent,
+};
 use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+/// Page size of the test-only getters that collect every page
+#[cfg(any(test, feature = "test-util"))]
+const COLLECT_PAGE_SIZE: usize = 100;
 
 /// Key Event Log storage
 #[async_trait]
@@ -19,6 +30,30 @@ pub trait KelStore: Send + Sync {
     /// This ensures proper ordering and prevents duplicates.
     async fn append_event(&self, event: &SignedEvent) -> DbResult<()>;
 
+    /// Append event to KEL and store the key state it produces, atomically
+    ///
+    /// Either both writes happen or neither does. State records are
+    /// versioned by the sn and digest of their latest event: the write is
+    /// conditional on the stored state still being at `sn - 1` with the
+    /// event's prior digest (or absent for inception). A lost race returns
+    /// the retryable `DbError::StateConflict`.
+    async fn commit_event(&self, event: &SignedEvent, new_state: &KeyState) -> DbResult<()> {
+        let now = chrono::Utc::now().to_rfc3339();
+        self.commit_event_seen_at(event, new_state, &now).await
+    }
+
+    /// Commit an event as `commit_event` does, recording it as first seen
+    /// at `datetime` (RFC 3339) rather than now
+    ///
+    /// Replaying an exported KEL keeps the times the source first saw its
+    /// events; the first-seen ordinal is still assigned on append.
+    async fn commit_event_seen_at(
+        &self,
+        event: &SignedEvent,
+        new_state: &KeyState,
+        datetime: &str,
+    ) -> DbResult<()>;
+
     /// Get event by prefix and sequence number
     async fn get_event(&self, prefix: &str, sn: u64) -> DbResult<Option<SignedEvent>>;
 
@@ -30,11 +65,81 @@ pub trait KelStore: Send + Sync {
         end_sn: Option<u64>,
     ) -> DbResult<Vec<SignedEvent>>;
 
+    /// Get up to `limit` events in a range, continuing from `cursor`
+    ///
+    /// KELs have no gaps, so a page is the sn range after the cursor and
+    /// the cursor is the hex sn the next page starts at.
+    async fn get_events_page(
+        &self,
+        prefix: &str,
+        start_sn: u64,
+        end_sn: Option<u64>,
+        limit: usize,
+        cursor: Option<&str>,
+    ) -> DbResult<Page<SignedEvent>> {
+        let limit = limit.max(1);
+        let start = match cursor {
+            Some(cursor) => parse_sn_cursor(cursor)?.max(start_sn),
+            None => start_sn,
+        };
+        // One event past the page tells us whether another page follows
+        let last = start.saturating_add(limit as u64);
+        let last = end_sn.map_or(last, |end| end.min(last));
+        if start > last {
+            return Ok(Page::default());
+        }
+
+        let mut items = self.get_events(prefix, start, Some(last)).await?;
+        let next = if items.len() > limit {
+            items.truncate(limit);
+            Some(sn_cursor(start + limit as u64))
+        } else {
+            None
+        };
+        Ok(Page { items, next })
+    }
+
+    /// Get up to `limit` prefixes that have a KEL, continuing from `cursor`
+    ///
+    /// Prefixes are returned in an order fixed by the backend, each once.
+    async fn get_prefixes_page(&self, limit: usize, cursor: Option<&str>)
+        -> DbResult<Page<String>>;
+
     /// Get latest event for prefix
     async fn get_latest(&self, prefix: &str) -> DbResult<Option<SignedEvent>>;
 
     /// Get event by digest
+    ///
+    /// Backends may scan the prefix's KEL, so the cost can grow with its
+    /// length; callers that know the sequence number use `get_event`.
     async fn get_event_by_digest(&self, prefix: &str, digest: &str) -> DbResult<Option<SignedEvent>>;
+
+    /// Get the first-seen log entry for an accepted event
+    async fn get_first_seen(&self, prefix: &str, digest: &str) -> DbResult<Option<FirstSeen>>;
+
+    /// Get first-seen log entries for a prefix in an ordinal range
+    ///
+    /// Entries are written atomically with the KEL append, one per accepted
+    /// event, and returned in ordinal order.
+    async fn get_first_seen_range(
+        &self,
+        prefix: &str,
+        start_fn: u64,
+        end_fn: Option<u64>,
+    ) -> DbResult<Vec<FirstSeen>>;
+
+    /// Find the first event that anchors a seal with this digest
+    ///
+    /// Seals are indexed by `append_event`, so lookup does not scan KELs.
+    async fn find_anchor(&self, digest: &str) -> DbResult<Option<AnchorLocation>>;
+
+    /// Find the first event that anchors the event seal `(i, s, d)`
+    async fn find_event_seal(
+        &self,
+        prefix: &str,
+        sn: u64,
+        digest: &str,
+    ) -> DbResult<Option<AnchorLocation>>;
 }
 
 /// Key state storage
@@ -54,6 +159,11 @@ pub trait StateStore: Send + Sync {
 #[async_trait]
 pub trait ReceiptStore: Send + Sync {
     /// Add a receipt
+    ///
+    /// A witness has one receipt per event. Adding the stored receipt again
+    /// is a no-op. A receipt whose signature differs from the stored one is
+    /// rejected with `DbError::ConflictingReceipt` and recorded as a
+    /// `ReceiptConflict`; the stored receipt is kept.
     async fn add_receipt(&self, receipt: &NontransferableReceipt) -> DbResult<()>;
 
     /// Get receipts for an event
@@ -68,19 +178,86 @@ pub trait ReceiptStore: Send + Sync {
 
     /// Count receipts for an event
     async fn count_receipts(&self, event_digest: &str) -> DbResult<usize>;
+
+    /// Get the receipt conflicts recorded against a witness, by event digest
+    async fn get_receipt_conflicts(&self, witness_prefix: &str) -> DbResult<Vec<ReceiptConflict>>;
 }
 
 /// Escrow storage
 #[async_trait]
 pub trait EscrowStore: Send + Sync {
-    /// Escrow an event
-    async fn escrow_event(&self, event: &SignedEvent, reason: EscrowReason) -> DbResult<()>;
+    /// Escrow an event for the default TTL of its reason
+    async fn escrow_event(&self, event: &SignedEvent, reason: EscrowReason) -> DbResult<()> {
+        let ttl = EscrowPolicy::default().ttl(reason);
+        self.escrow_event_with_ttl(event, reason, ttl).await
+    }
+
+    /// Escrow an event until `ttl_seconds` from now
+    ///
+    /// Re-escrowing an event replaces its escrow, resetting its attempts.
+    async fn escrow_event_with_ttl(
+        &self,
+        event: &SignedEvent,
+        reason: EscrowReason,
+        ttl_seconds: u64,
+    ) -> DbResult<()>;
+
+    /// Store the attempt count and schedule of an escrowed event
+    ///
+    /// Does nothing if the event is no longer escrowed for the same reason.
+    async fn record_escrow_attempt(&self, escrowed: &EscrowedEvent) -> DbResult<()>;
+
+    /// Get every escrowed event for a prefix at once
+    ///
+    /// Only built for tests; callers page with `get_escrowed_by_prefix`.
+    #[cfg(any(test, feature = "test-util"))]
+    async fn get_escrowed(&self, prefix: &str) -> DbResult<Vec<EscrowedEvent>> {
+        crate::stream::escrowed_events_for(self, prefix, COLLECT_PAGE_SIZE)
+            .try_collect()
+            .await
+    }
+
+    /// Get every escrowed event at once
+    ///
+    /// Only built for tests; sweeps page with `get_escrowed_page`.
+    #[cfg(any(test, feature = "test-util"))]
+    async fn get_all_escrowed(&self) -> DbResult<Vec<EscrowedEvent>> {
+        crate::stream::escrowed_events(self, COLLECT_PAGE_SIZE)
+            .try_collect()
+            .await
+    }
+
+    /// Get up to `limit` escrowed events for a prefix, continuing from `cursor`
+    ///
+    /// The cursor is opaque and backend specific, with the same guarantees
+    /// as `get_escrowed_page`.
+    async fn get_escrowed_by_prefix(
+        &self,
+        prefix: &str,
+        limit: usize,
+        cursor: Option<&str>,
+    ) -> DbResult<Page<EscrowedEvent>>;
 
-    /// Get escrowed events for a prefix
-    async fn get_escrowed(&self, prefix: &str) -> DbResult<Vec<EscrowedEvent>>;
+    /// Get up to `limit` escrowed events, continuing from `cursor`
+    ///
+    /// The cursor is opaque and backend specific. Pages stay consistent
+    /// while earlier events are promoted or removed, so a sweep can act on
+    /// each page before fetching the next.
+    async fn get_escrowed_page(
+        &self,
+        limit: usize,
+        cursor: Option<&str>,
+    ) -> DbResult<Page<EscrowedEvent>>;
 
-    /// Get all escrowed events (for scheduled processing)
-    async fn get_all_escrowed(&self) -> DbResult<Vec<EscrowedEvent>>;
+    /// Get up to `limit` events escrowed for `reason`, continuing from `cursor`
+    ///
+    /// Backends that index expiry return the soonest-expiring events first.
+    async fn get_escrowed_by_reason(
+        &self,
+        reason: EscrowReason,
+        limit: usize,
+        cursor: Option<&str>,
+    ) -> DbResult<Page<EscrowedEvent>>;
 
     /// Promote escrowed event (move to KEL)
     async fn promote_escrowed(&self, event_digest: &str) -> DbResult<Option<SignedEvent>>;
@@ -89,8 +266,452 @@ pub trait EscrowStore: Send + Sync {
     async fn remove_escrowed(&self, event_digest: &str) -> DbResult<()>;
 }
 
+/// Credential schema storage
+///
+/// Schemas are content-addressed by SAID, so writes are idempotent.
+#[async_trait]
+pub trait SchemaStore: Send + Sync {
+    /// Store a verified schema (no-op if already present)
+    async fn put_schema(&self, schema: &CredentialSchema) -> DbResult<()>;
+
+    /// Get schema by SAID
+    async fn get_schema(&self, said: &str) -> DbResult<Option<CredentialSchema>>;
+}
+
+/// IPEX exchange storage
+///
+/// Exchanges are keyed by the SAID of their first message. Every accepted
+/// message is indexed to its exchange so responses can find it by `p`.
+/// Messages whose prior has not been seen yet are escrowed, keyed by SAID.
+#[async_trait]
+pub trait ExchangeStore: Send + Sync {
+    /// Store an accepted message together with the exchange state it produced
+    ///
+    /// The stored exchange must be where the message picks it up: absent
+    /// for a message without a prior, otherwise ending at the message's
+    /// prior. If another response got there first, nothing is written and
+    /// the retryable `DbError::StateConflict` is returned.
+    async fn put_exchange(
+        &self,
+        exchange: &IpexExchange,
+        message: &ExchangeMessage,
+    ) -> DbResult<()>;
+
+    /// Get exchange by identifier
+    async fn get_exchange(&self, id: &str) -> DbResult<Option<IpexExchange>>;
+
+    /// Get an accepted message by SAID
+    async fn get_message(&self, said: &str) -> DbResult<Option<ExchangeMessage>>;
+
+    /// Get the exchange an accepted message belongs to
+    async fn get_exchange_by_message(&self, said: &str) -> DbResult<Option<IpexExchange>>;
+
+    /// Escrow a message whose prior is unknown for the default TTL
+    async fn escrow_message(&self, message: &ExchangeMessage) -> DbResult<()> {
+        let ttl = EscrowPolicy::default().exchange_ttl;
+        self.escrow_message_with_ttl(message, ttl).await
+    }
+
+    /// Escrow a message whose prior is unknown until `ttl_seconds` from now
+    async fn escrow_message_with_ttl(
+        &self,
+        message: &ExchangeMessage,
+        ttl_seconds: u64,
+    ) -> DbResult<()>;
+
+    /// Get escrowed messages responding to `prior`
+    async fn get_escrowed_messages(&self, prior: &str) -> DbResult<Vec<EscrowedMessage>>;
+
+    /// Get every escrowed message at once
+    ///
+    /// Only built for tests; sweeps page with `get_escrowed_messages_page`.
+    #[cfg(any(test, feature = "test-util"))]
+    async fn get_all_escrowed_messages(&self) -> DbResult<Vec<EscrowedMessage>> {
+        crate::stream::escrowed_messages(self, COLLECT_PAGE_SIZE)
+            .try_collect()
+            .await
+    }
+
+    /// Get up to `limit` escrowed messages, continuing from `cursor`
+    ///
+    /// The cursor is opaque and backend specific. Pages stay consistent
+    /// while earlier messages are promoted or removed.
+    async fn get_escrowed_messages_page(
+        &self,
+        limit: usize,
+        cursor: Option<&str>,
+    ) -> DbResult<Page<EscrowedMessage>>;
+
+    /// Promote escrowed message (remove it from escrow for processing)
+    async fn promote_escrowed_message(&self, said: &str) -> DbResult<Option<ExchangeMessage>>;
+
+    /// Remove escrowed message
+    async fn remove_escrowed_message(&self, said: &str) -> DbResult<()>;
+}
+
+/// Rewriting stored records at their current version
+///
+/// See `crate::record`. Reads decode older records either way; migrating
+/// lets a later build drop the upgrade steps for versions no row still has.
+#[async_trait]
+pub trait MigrationStore: Send + Sync {
+    /// Rewrite every state, receipt, escrow and exchange row older than its
+    /// record's current version, in place
+    ///
+    /// Safe to run while the witness is serving: a row changed since it was
+    /// read is left for the next run rather than overwritten.
+    async fn migrate_records(&self) -> DbResult<MigrationReport>;
+}
+
+/// Counts from a migration run
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct MigrationReport {
+    /// Rows read
+    pub scanned: usize,
+    /// Rows rewritten at the current version
+    pub migrated: usize,
+    /// Outdated rows skipped because they changed while migrating
+    pub skipped: usize,
+}
+
+/// Splitting shared storage into isolated tenant namespaces
+///
+/// Several witnesses can share one set of tables, each in its own namespace.
+/// Every read, write, scan and change of a scoped handle sees only its
+/// tenant's data. A handle that was never scoped works on the default
+/// namespace, where single-tenant deployments keep their data.
+pub trait TenantStore: Sized {
+    /// Tenant this handle is scoped to, or `None` for the default namespace
+    fn tenant(&self) -> Option<&str>;
+
+    /// A handle on the same storage scoped to `tenant`
+    ///
+    /// Fails with `DbError::InvalidTenant` unless the name is valid, see
+    /// `validate_tenant`.
+    fn for_tenant(&self, tenant: &str) -> DbResult<Self>;
+}
+
+/// Longest tenant name accepted
+pub const MAX_TENANT_LEN: usize = 64;
+
+/// Check that a tenant name is 1 to `MAX_TENANT_LEN` ASCII letters, digits,
+/// `-` or `_`
+///
+/// Backends may join the tenant and a key with any other character.
+pub fn validate_tenant(tenant: &str) -> DbResult<()> {
+    let valid = !tenant.is_empty()
+        && tenant.len() <= MAX_TENANT_LEN
+        && tenant
+            .bytes()
+            .all(|b| b.is_ascii_alphanumeric() || b == b'-' || b == b'_');
+    if valid {
+        Ok(())
+    } else {
+        Err(DbError::InvalidTenant(tenant.to_string()))
+    }
+}
+
+/// Subscribing to committed changes
+///
+/// Lets watchers, mailboxes and the escrow engine react when a KEL grows
+/// instead of polling. A change is emitted once its write has committed.
+#[async_trait]
+pub trait ChangeFeed: Send + Sync {
+    /// Stream changes committed from now on, only those to one identifier
+    /// if `prefix_filter` is set
+    ///
+    /// A subscriber that falls too far behind gets `DbError::Lagged` and
+    /// should sweep what it watches with point reads. The stream ends with
+    /// an error if the feed fails; subscribe again to resume.
+    async fn subscribe(&self, prefix_filter: Option<&str>) -> DbResult<ChangeStream>;
+}
+
+/// Stream of changes from `ChangeFeed::subscribe`
+pub type ChangeStream = BoxStream<'static, DbResult<Change>>;
+
+/// A committed change to KEL, receipt or escrow storage
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Change {
+    /// An event was accepted into its KEL
+    EventAccepted {
+        prefix: String,
+        sn: u64,
+        digest: String,
+    },
+    /// A witness receipt was stored
+    ReceiptAdded {
+        prefix: String,
+        sn: u64,
+        event_digest: String,
+        witness_prefix: String,
+    },
+    /// An event entered escrow, or stayed escrowed for a new reason
+    Escrowed {
+        prefix: String,
+        sn: u64,
+        digest: String,
+        reason: EscrowReason,
+    },
+    /// An event left escrow: promoted, removed or expired
+    EscrowRemoved {
+        prefix: String,
+        sn: u64,
+        digest: String,
+    },
+}
+
+impl Change {
+    /// Identifier whose storage changed
+    pub fn prefix(&self) -> &str {
+        match self {
+            Change::EventAccepted { prefix, .. }
+            | Change::ReceiptAdded { prefix, .. }
+            | Change::Escrowed { prefix, .. }
+            | Change::EscrowRemoved { prefix, .. } => prefix,
+        }
+    }
+
+    /// Change for an accepted event
+    pub fn accepted(event: &SignedEvent) -> Self {
+        Change::EventAccepted {
+            prefix: event.event.prefix.clone(),
+            sn: event.event.sn,
+            digest: event.event.digest.clone(),
+        }
+    }
+
+    /// Change for an escrowed event
+    pub fn escrowed(event: &SignedEvent, reason: EscrowReason) -> Self {
+        Change::Escrowed {
+            prefix: event.event.prefix.clone(),
+            sn: event.event.sn,
+            digest: event.event.digest.clone(),
+            reason,
+        }
+    }
+
+    /// Change for an event leaving escrow
+    pub fn escrow_removed(event: &SignedEvent) -> Self {
+        Change::EscrowRemoved {
+            prefix: event.event.prefix.clone(),
+            sn: event.event.sn,
+            digest: event.event.digest.clone(),
+        }
+    }
+}
+
+impl From<&NontransferableReceipt> for Change {
+    fn from(receipt: &NontransferableReceipt) -> Self {
+        Change::ReceiptAdded {
+            prefix: receipt.event_prefix.clone(),
+            sn: receipt.event_sn,
+            event_digest: receipt.event_digest.clone(),
+            witness_prefix: receipt.witness_prefix.clone(),
+        }
+    }
+}
+
+/// One page of a paginated read
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Page<T> {
+    /// Items on this page
+    pub items: Vec<T>,
+    /// Continuation token for the next page (`None` on the last page)
+    ///
+    /// A backend may hand out a token whose page turns out to be empty.
+    pub next: Option<String>,
+}
+
+impl<T> Default for Page<T> {
+    fn default() -> Self {
+        Page {
+            items: Vec::new(),
+            next: None,
+        }
+    }
+}
+
+/// Encode a KEL page cursor
+fn sn_cursor(sn: u64) -> String {
+    format!("{:x}", sn)
+}
+
+/// Decode a KEL page cursor
+fn parse_sn_cursor(cursor: &str) -> DbResult<u64> {
+    u64::from_str_radix(cursor, 16)
+        .map_err(|_| DbError::InvalidCursor(cursor.to_string()))
+}
+
+/// Encode a cursor over escrows ordered by expiry then digest
+pub(crate) fn expiry_cursor(escrowed: &EscrowedEvent) -> String {
+    format!("{}#{}", escrowed.ttl, escrowed.event.event.digest)
+}
+
+/// Decode an expiry cursor into its TTL and digest
+pub(crate) fn parse_expiry_cursor(cursor: &str) -> DbResult<(u64, &str)> {
+    cursor
+        .split_once('#')
+        .and_then(|(ttl, digest)| Some((ttl.parse().ok()?, digest)))
+        .ok_or_else(|| DbError::InvalidCursor(cursor.to_string()))
+}
+
+/// Two receipts from one witness for the same event
+///
+/// Ed25519 signatures are deterministic, so an honest witness signs an
+/// event the same way every time. Two verified receipts with different
+/// signatures are evidence of witness duplicity.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ReceiptConflict {
+    /// Witness that issued both receipts
+    pub witness_prefix: String,
+    /// Prefix of the receipted event
+    pub event_prefix: String,
+    /// Sequence number of the receipted event
+    pub event_sn: u64,
+    /// Digest of the receipted event
+    pub event_digest: String,
+    /// Signature of the stored receipt, which is kept
+    pub signature: String,
+    /// Signature of the later receipt, which was rejected
+    pub conflicting_signature: String,
+    /// When the conflict was detected (ISO 8601)
+    pub datetime: String,
+}
+
+impl ReceiptConflict {
+    /// Compare a new receipt with the stored one from the same witness
+    ///
+    /// Returns `None` when the signatures match.
+    pub fn between(
+        stored: &NontransferableReceipt,
+        receipt: &NontransferableReceipt,
+    ) -> Option<Self> {
+        (stored.signature != receipt.signature).then(|| ReceiptConflict {
+            witness_prefix: stored.witness_prefix.clone(),
+            event_prefix: stored.event_prefix.clone(),
+            event_sn: stored.event_sn,
+            event_digest: stored.event_digest.clone(),
+            signature: stored.signature.clone(),
+            conflicting_signature: receipt.signature.clone(),
+            datetime: chrono::Utc::now().to_rfc3339(),
+        })
+    }
+
+    /// Key of the conflict among its witness's conflicts
+    ///
+    /// Recording the same conflict again keeps the first record.
+    pub fn key(&self) -> String {
+        format!("{}.{}", self.event_digest, self.conflicting_signature)
+    }
+
+    /// Stored and rejected receipts, for verifying the evidence
+    pub fn receipts(&self) -> (NontransferableReceipt, NontransferableReceipt) {
+        let receipt = |signature: &str| NontransferableReceipt {
+            event_digest: self.event_digest.clone(),
+            event_sn: self.event_sn,
+            event_prefix: self.event_prefix.clone(),
+            witness_prefix: self.witness_prefix.clone(),
+            signature: signature.to_string(),
+        };
+        (
+            receipt(&self.signature),
+            receipt(&self.conflicting_signature),
+        )
+    }
+}
+
+impl From<&ReceiptConflict> for DbError {
+    fn from(conflict: &ReceiptConflict) -> Self {
+        DbError::ConflictingReceipt {
+            witness: conflict.witness_prefix.clone(),
+            digest: conflict.event_digest.clone(),
+        }
+    }
+}
+
+/// First-seen log entry
+///
+/// Records the order (`fn`) and time this node first accepted an event,
+/// which settles which of two conflicting events it saw first.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FirstSeen {
+    /// Prefix of the KEL the event belongs to
+    pub prefix: String,
+    /// First-seen ordinal, monotonic per prefix from 0
+    #[serde(rename = "fn")]
+    pub ordinal: u64,
+    /// Sequence number of the event
+    pub sn: u64,
+    /// Digest of the event
+    pub digest: String,
+    /// When the event was first seen (ISO 8601)
+    pub datetime: String,
+}
+
+impl FirstSeen {
+    /// Create an entry for an event first seen now
+    pub fn new(event: &SignedEvent, ordinal: u64) -> Self {
+        Self::at(event, ordinal, &chrono::Utc::now().to_rfc3339())
+    }
+
+    /// Create an entry for an event first seen at `datetime`
+    pub fn at(event: &SignedEvent, ordinal: u64, datetime: &str) -> Self {
+        FirstSeen {
+            prefix: event.event.prefix.clone(),
+            ordinal,
+            sn: event.event.sn,
+            digest: event.event.digest.clone(),
+            datetime: datetime.to_string(),
+        }
+    }
+}
+
+/// Location of a seal anchored in a KEL
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AnchorLocation {
+    /// Prefix of the KEL that anchors the seal
+    pub prefix: String,
+    /// Sequence number of the anchoring event
+    pub sn: u64,
+    /// Digest of the anchoring event
+    pub event_digest: String,
+    /// The anchored seal
+    pub seal: Anchor,
+}
+
+impl AnchorLocation {
+    /// Get the locations of all seals anchored by an event
+    pub fn from_event(event: &SignedEvent) -> Vec<Self> {
+        event
+            .event
+            .anchors
+            .iter()
+            .map(|seal| AnchorLocation {
+                prefix: event.event.prefix.clone(),
+                sn: event.event.sn,
+                event_digest: event.event.digest.clone(),
+                seal: seal.clone(),
+            })
+            .collect()
+    }
+
+    /// Check whether the seal is the event seal for `prefix` at `sn`
+    ///
+    /// Event seals carry `s` as hex, so it is compared numerically.
+    pub fn is_event_seal(&self, prefix: &str, sn: u64) -> bool {
+        self.seal.i.as_deref() == Some(prefix)
+            && self
+                .seal
+                .s
+                .as_deref()
+                .and_then(|s| u64::from_str_radix(s, 16).ok())
+                == Some(sn)
+    }
+}
+
 /// Reasons for escrowing an event
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
 #[serde(rename_all = "snake_case")]
 pub enum EscrowReason {
     /// Missing prior events
@@ -114,6 +735,85 @@ impl std::fmt::Display for EscrowReason {
     }
 }
 
+/// Escrow TTL in seconds for reasons without their own (1 hour)
+pub const DEFAULT_ESCROW_TTL: u64 = 3600;
+
+/// How long events stay in escrow and how often they are retried
+///
+/// Out-of-order events usually resolve within minutes, while a partially
+/// signed multisig event may wait days for its other signers.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct EscrowPolicy {
+    /// TTL in seconds for reasons without their own
+    pub default_ttl: u64,
+    /// TTL in seconds per escrow reason
+    pub ttls: BTreeMap<EscrowReason, u64>,
+    /// Delay in seconds before retrying after the first failed attempt
+    pub retry_base: u64,
+    /// Longest delay in seconds between attempts
+    pub retry_max: u64,
+    /// TTL in seconds for exchange messages whose prior is unknown
+    pub exchange_ttl: u64,
+}
+
+impl Default for EscrowPolicy {
+    fn default() -> Self {
+        EscrowPolicy {
+            default_ttl: DEFAULT_ESCROW_TTL,
+            ttls: BTreeMap::from([
+                (EscrowReason::OutOfOrder, 600),
+                (EscrowReason::PartiallySigned, 3 * 86400),
+                (EscrowReason::MissingDelegator, 86400),
+            ]),
+            retry_base: 60,
+            retry_max: 3600,
+            exchange_ttl: DEFAULT_ESCROW_TTL,
+        }
+    }
+}
+
+impl EscrowPolicy {
+    /// Set the TTL for a reason
+    pub fn with_ttl(mut self, reason: EscrowReason, ttl_seconds: u64) -> Self {
+        self.ttls.insert(reason, ttl_seconds);
+        self
+    }
+
+    /// Set the TTL for reasons without their own
+    pub fn with_default_ttl(mut self, ttl_seconds: u64) -> Self {
+        self.default_ttl = ttl_seconds;
+        self
+    }
+
+    /// Set the retry backoff
+    pub fn with_retry(mut self, base_seconds: u64, max_seconds: u64) -> Self {
+        self.retry_base = base_seconds;
+        self.retry_max = max_seconds;
+        self
+    }
+
+    /// Set the TTL for escrowed exchange messages
+    pub fn with_exchange_ttl(mut self, ttl_seconds: u64) -> Self {
+        self.exchange_ttl = ttl_seconds;
+        self
+    }
+
+    /// TTL in seconds for events escrowed for `reason`
+    pub fn ttl(&self, reason: EscrowReason) -> u64 {
+        self.ttls.get(&reason).copied().unwrap_or(self.default_ttl)
+    }
+
+    /// Delay in seconds before the next attempt after `attempts` failed ones
+    ///
+    /// Doubles with each attempt up to `retry_max`.
+    pub fn retry_delay(&self, attempts: u32) -> u64 {
+        let doublings = attempts.saturating_sub(1).min(63);
+        self.retry_base
+            .saturating_mul(1u64 << doublings)
+            .min(self.retry_max)
+    }
+}
+
 /// Escrowed event with metadata
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct EscrowedEvent {
@@ -125,16 +825,69 @@ pub struct EscrowedEvent {
     pub created: String,
     /// TTL timestamp (Unix epoch seconds)
     pub ttl: u64,
+    /// Failed attempts to promote the event
+    pub attempts: u32,
+    /// When promotion was last attempted (ISO 8601)
+    pub last_attempt: Option<String>,
+    /// Earliest time to attempt promotion again (Unix epoch seconds)
+    pub next_attempt: u64,
 }
 
 impl EscrowedEvent {
-    /// Create new escrowed event
+    /// Create new escrowed event, due for its first attempt immediately
     pub fn new(event: SignedEvent, reason: EscrowReason, ttl_seconds: u64) -> Self {
         let now = chrono::Utc::now();
+        let timestamp = now.timestamp() as u64;
         EscrowedEvent {
             event,
             reason,
             created: now.to_rfc3339(),
+            ttl: timestamp + ttl_seconds,
+            attempts: 0,
+            last_attempt: None,
+            next_attempt: timestamp,
+        }
+    }
+
+    /// Check if escrow has expired
+    pub fn is_expired(&self) -> bool {
+        let now = chrono::Utc::now().timestamp() as u64;
+        now >= self.ttl
+    }
+
+    /// Check if promotion is due to be attempted
+    pub fn is_due(&self) -> bool {
+        let now = chrono::Utc::now().timestamp() as u64;
+        now >= self.next_attempt
+    }
+
+    /// Count a failed attempt and schedule the next with `policy`'s backoff
+    pub fn record_attempt(&mut self, policy: &EscrowPolicy) {
+        let now = chrono::Utc::now();
+        self.attempts = self.attempts.saturating_add(1);
+        self.last_attempt = Some(now.to_rfc3339());
+        self.next_attempt = (now.timestamp() as u64) + policy.retry_delay(self.attempts);
+    }
+}
+
+/// Escrowed exchange message with metadata
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct EscrowedMessage {
+    /// The escrowed message
+    pub message: ExchangeMessage,
+    /// When it was escrowed (ISO 8601)
+    pub created: String,
+    /// TTL timestamp (Unix epoch seconds)
+    pub ttl: u64,
+}
+
+impl EscrowedMessage {
+    /// Create new escrowed message
+    pub fn new(message: ExchangeMessage, ttl_seconds: u64) -> Self {
+        let now = chrono::Utc::now();
+        EscrowedMessage {
+            message,
+            created: now.to_rfc3339(),
             ttl: (now.timestamp() as u64) + ttl_seconds,
         }
     }
@@ -158,6 +911,7 @@ impl<T> WitnessDatabase for T where T: KelStore + StateStore + ReceiptStore + Es
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::test_support::create_test_event;
 
     #[test]
     fn test_escrow_reason_display() {
@@ -176,4 +930,89 @@ mod tests {
         let parsed: EscrowReason = serde_json::from_str(&json).unwrap();
         assert_eq!(parsed, reason);
     }
+
+    #[test]
+    fn test_escrow_policy_ttls() {
+        let policy = EscrowPolicy::default();
+        assert_eq!(policy.ttl(EscrowReason::OutOfOrder), 600);
+        assert_eq!(policy.ttl(EscrowReason::PartiallySigned), 3 * 86400);
+        assert_eq!(
+            policy.ttl(EscrowReason::MissingReceipts),
+            DEFAULT_ESCROW_TTL
+        );
+
+        let policy = policy
+            .with_ttl(EscrowReason::OutOfOrder, 120)
+            .with_default_ttl(7200);
+        assert_eq!(policy.ttl(EscrowReason::OutOfOrder), 120);
+        assert_eq!(policy.ttl(EscrowReason::MissingReceipts), 7200);
+    }
+
+    #[test]
+    fn test_escrow_retry_delay_backs_off() {
+        let policy = EscrowPolicy::default().with_retry(60, 600);
+        assert_eq!(policy.retry_delay(1), 60);
+        assert_eq!(policy.retry_delay(2), 120);
+        assert_eq!(policy.retry_delay(4), 480);
+        assert_eq!(policy.retry_delay(5), 600);
+        assert_eq!(policy.retry_delay(u32::MAX), 600);
+    }
+
+    #[test]
+    fn test_escrowed_event_attempts() {
+        let event = create_test_event("DTest123", 1, Some("EPrior".to_string()));
+        let mut escrowed = EscrowedEvent::new(event, EscrowReason::OutOfOrder, 600);
+        assert!(escrowed.is_due());
+        assert_eq!(escrowed.attempts, 0);
+
+        let policy = EscrowPolicy::default();
+        escrowed.record_attempt(&policy);
+        assert_eq!(escrowed.attempts, 1);
+        assert!(escrowed.last_attempt.is_some());
+        assert!(!escrowed.is_due());
+    }
+
+    #[test]
+    fn test_first_seen_serializes_ordinal_as_fn() {
+        let entry = FirstSeen {
+            prefix: "DTest123".to_string(),
+            ordinal: 2,
+            sn: 2,
+            digest: "EDigest".to_string(),
+            datetime: "2024-01-01T00:00:00+00:00".to_string(),
+        };
+
+        let json = serde_json::to_value(&entry).unwrap();
+        assert_eq!(json["fn"], 2);
+        assert_eq!(serde_json::from_value::<FirstSeen>(json).unwrap(), entry);
+    }
+
+    #[test]
+    fn test_anchor_location_event_seal() {
+        let location = AnchorLocation {
+            prefix: "EDelegator".to_string(),
+            sn: 3,
+            event_digest: "EAnchoring".to_string(),
+            seal: Anchor::event("EDelegate", "a", "EDelegated"),
+        };
+
+        assert!(location.is_event_seal("EDelegate", 10));
+        assert!(!location.is_event_seal("EDelegate", 0));
+        assert!(!location.is_event_seal("EOther", 10));
+    }
+
+    #[test]
+    fn test_validate_tenant() {
+        assert!(validate_tenant("community-1").is_ok());
+        assert!(validate_tenant("Tenant_A").is_ok());
+        assert!(validate_tenant(&"a".repeat(MAX_TENANT_LEN)).is_ok());
+
+        for invalid in ["", "a#b", "a/b", "witness one", "é"] {
+            assert!(matches!(
+                validate_tenant(invalid),
+                Err(DbError::InvalidTenant(_))
+            ));
+        }
+        assert!(validate_tenant(&"a".repeat(MAX_TENANT_LEN + 1)).is_err());
+    }
 }
@@ -1,6 +1,9 @@
 //! Witness configuration
 
+use crate::error::{WitnessError, WitnessResult};
+use kerihost_db::{EscrowPolicy, EscrowReason};
 use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
 
 /// Witness configuration
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -11,9 +14,25 @@ pub struct WitnessConfig {
     /// Public URL for this witness
     pub public_url: String,
 
-    /// Escrow TTL in seconds
+    /// Escrow TTL in seconds for reasons without their own
     pub escrow_ttl: u64,
 
+    /// Escrow TTL in seconds per escrow reason
+    #[serde(default = "default_escrow_ttls")]
+    pub escrow_ttls: BTreeMap<EscrowReason, u64>,
+
+    /// Escrow TTL in seconds for exchange messages whose prior is unknown
+    #[serde(default = "default_exchange_escrow_ttl")]
+    pub exchange_escrow_ttl: u64,
+
+    /// Delay in seconds before retrying an escrow after its first failed attempt
+    #[serde(default = "default_escrow_retry_base")]
+    pub escrow_retry_base: u64,
+
+    /// Longest delay in seconds between escrow attempts
+    #[serde(default = "default_escrow_retry_max")]
+    pub escrow_retry_max: u64,
+
     /// Maximum events to process per batch
     pub max_batch_size: usize,
 
@@ -23,15 +42,21 @@ pub struct WitnessConfig {
 
 impl WitnessConfig {
     /// Create config from environment variables
-    pub fn from_env() -> Self {
-        WitnessConfig {
+    ///
+    /// Fails with `WitnessError::Config` if an escrow setting is malformed.
+    pub fn from_env() -> WitnessResult<Self> {
+        Ok(WitnessConfig {
             prefix: std::env::var("WITNESS_PREFIX").unwrap_or_default(),
             public_url: std::env::var("PUBLIC_URL")
                 .unwrap_or_else(|_| "https://witness.keri.host".to_string()),
-            escrow_ttl: std::env::var("ESCROW_TTL")
-                .ok()
-                .and_then(|s| s.parse().ok())
-                .unwrap_or(3600),
+            escrow_ttl: seconds_from_env("ESCROW_TTL", 3600)?,
+            escrow_ttls: escrow_ttls_from_env()?,
+            exchange_escrow_ttl: seconds_from_env(
+                "EXCHANGE_ESCROW_TTL",
+                default_exchange_escrow_ttl(),
+            )?,
+            escrow_retry_base: seconds_from_env("ESCROW_RETRY_BASE", default_escrow_retry_base())?,
+            escrow_retry_max: seconds_from_env("ESCROW_RETRY_MAX", default_escrow_retry_max())?,
             max_batch_size: std::env::var("MAX_BATCH_SIZE")
                 .ok()
                 .and_then(|s| s.parse().ok())
@@ -40,7 +65,7 @@ impl WitnessConfig {
                 .ok()
                 .map(|s| s == "true" || s == "1")
                 .unwrap_or(true),
-        }
+        })
     }
 
     /// Create with custom values
@@ -49,6 +74,10 @@ impl WitnessConfig {
             prefix,
             public_url,
             escrow_ttl: 3600,
+            escrow_ttls: default_escrow_ttls(),
+            exchange_escrow_ttl: default_exchange_escrow_ttl(),
+            escrow_retry_base: default_escrow_retry_base(),
+            escrow_retry_max: default_escrow_retry_max(),
             max_batch_size: 100,
             strict_validation: true,
         }
@@ -60,11 +89,87 @@ impl WitnessConfig {
         self
     }
 
+    /// Builder-style method to set the escrow TTL for one reason
+    pub fn with_reason_escrow_ttl(mut self, reason: EscrowReason, ttl: u64) -> Self {
+        self.escrow_ttls.insert(reason, ttl);
+        self
+    }
+
+    /// Builder-style method to set the escrow TTL for exchange messages
+    pub fn with_exchange_escrow_ttl(mut self, ttl: u64) -> Self {
+        self.exchange_escrow_ttl = ttl;
+        self
+    }
+
+    /// Builder-style method to set the escrow retry backoff
+    pub fn with_escrow_retry(mut self, base: u64, max: u64) -> Self {
+        self.escrow_retry_base = base;
+        self.escrow_retry_max = max;
+        self
+    }
+
     /// Builder-style method to set strict validation
     pub fn with_strict_validation(mut self, strict: bool) -> Self {
         self.strict_validation = strict;
         self
     }
+
+    /// Escrow policy with the configured TTLs and retry backoff
+    pub fn escrow_policy(&self) -> EscrowPolicy {
+        let mut policy = EscrowPolicy::default()
+            .with_default_ttl(self.escrow_ttl)
+            .with_exchange_ttl(self.exchange_escrow_ttl)
+            .with_retry(self.escrow_retry_base, self.escrow_retry_max);
+        policy.ttls = self.escrow_ttls.clone();
+        policy
+    }
+}
+
+/// Default escrow TTLs per reason
+fn default_escrow_ttls() -> BTreeMap<EscrowReason, u64> {
+    EscrowPolicy::default().ttls
+}
+
+/// Default escrow TTL for exchange messages
+fn default_exchange_escrow_ttl() -> u64 {
+    EscrowPolicy::default().exchange_ttl
+}
+
+/// Default delay before the first escrow retry
+fn default_escrow_retry_base() -> u64 {
+    EscrowPolicy::default().retry_base
+}
+
+/// Default longest delay between escrow attempts
+fn default_escrow_retry_max() -> u64 {
+    EscrowPolicy::default().retry_max
+}
+
+/// Seconds from the environment variable `name`, or `default` if unset
+fn seconds_from_env(name: &str, default: u64) -> WitnessResult<u64> {
+    match std::env::var(name) {
+        Ok(s) => s
+            .parse()
+            .map_err(|e| WitnessError::Config(format!("Invalid {}: {}", name, e))),
+        Err(_) => Ok(default),
+    }
+}
+
+/// Escrow TTLs from `ESCROW_TTLS` over the defaults
+fn escrow_ttls_from_env() -> WitnessResult<BTreeMap<EscrowReason, u64>> {
+    match std::env::var("ESCROW_TTLS") {
+        Ok(json) => parse_escrow_ttls(&json),
+        Err(_) => Ok(default_escrow_ttls()),
+    }
+}
+
+/// Parse a JSON object of escrow reason to seconds over the defaults
+fn parse_escrow_ttls(json: &str) -> WitnessResult<BTreeMap<EscrowReason, u64>> {
+    let overrides: BTreeMap<EscrowReason, u64> = serde_json::from_str(json)
+        .map_err(|e| WitnessError::Config(format!("Invalid ESCROW_TTLS: {}", e)))?;
+    let mut ttls = default_escrow_ttls();
+    ttls.extend(overrides);
+    Ok(ttls)
 }
 
 impl Default for WitnessConfig {
@@ -73,6 +178,10 @@ impl Default for WitnessConfig {
             prefix: String::new(),
             public_url: "https://witness.keri.host".to_string(),
             escrow_ttl: 3600,
+            escrow_ttls: default_escrow_ttls(),
+            exchange_escrow_ttl: default_exchange_escrow_ttl(),
+            escrow_retry_base: default_escrow_retry_base(),
+            escrow_retry_max: default_escrow_retry_max(),
             max_batch_size: 100,
             strict_validation: true,
         }
@@ -100,4 +209,51 @@ mod tests {
         assert_eq!(config.escrow_ttl, 7200);
         assert!(!config.strict_validation);
     }
+
+    #[test]
+    fn test_config_escrow_policy() {
+        let config = WitnessConfig::default()
+            .with_escrow_ttl(7200)
+            .with_reason_escrow_ttl(EscrowReason::OutOfOrder, 300);
+        let policy = config.escrow_policy();
+
+        assert_eq!(policy.ttl(EscrowReason::OutOfOrder), 300);
+        assert_eq!(policy.ttl(EscrowReason::PartiallySigned), 3 * 86400);
+        assert_eq!(policy.ttl(EscrowReason::MissingReceipts), 7200);
+        assert_eq!(policy.exchange_ttl, 3600);
+
+        let policy = config
+            .with_exchange_escrow_ttl(600)
+            .with_escrow_retry(30, 900)
+            .escrow_policy();
+        assert_eq!(policy.exchange_ttl, 600);
+        assert_eq!(policy.retry_delay(1), 30);
+        assert_eq!(policy.retry_delay(10), 900);
+    }
+
+    #[test]
+    fn test_parse_escrow_ttls() {
+        let ttls = parse_escrow_ttls(r#"{"out_of_order": 120}"#).unwrap();
+        assert_eq!(ttls[&EscrowReason::OutOfOrder], 120);
+        assert_eq!(ttls[&EscrowReason::PartiallySigned], 3 * 86400);
+
+        // A malformed value is an error, not silently the defaults
+        assert!(matches!(
+            parse_escrow_ttls(r#"{"out_of_ordr": 120}"#),
+            Err(WitnessError::Config(_))
+        ));
+    }
+
+    #[test]
+    fn test_config_deserializes_without_reason_ttls() {
+        let json = r#"{
+            "prefix": "BTest123",
+            "public_url": "https://example.com",
+            "escrow_ttl": 3600,
+            "max_batch_size": 100,
+            "strict_validation": true
+        }"#;
+        let config: WitnessConfig = serde_json::from_str(json).unwrap();
+        assert_eq!(config.escrow_ttls, default_escrow_ttls());
+    }
 }
@@ -0,0 +1,350 @@
+//! Credential chain resolution against stored KELs
+//!
+//! Linked credentials are the ones a holder presents alongside the
+//! credential they back. Whether each was legitimately issued is decided
+//! from stored KELs: its SAID must be anchored in its issuer's KEL (found
+//! through `find_anchor`), and its issuer signatures must verify against the
+//! issuer's key state at the anchoring event. Delegators come from stored
+//! key states.
+//!
+//! Revocations are recorded in TELs, which are not stored here, so a
+//! credential anchored by its issuer is reported as issued.
+
+use crate::error::WitnessResult;
+use kerihost_core::{
+    linked_credentials, Acdc, CredentialResolver, CredentialStatus, SignedCredential,
+};
+use kerihost_db::history::StateHistory;
+use kerihost_db::{AnchorLocation, KelStore, StateStore};
+use std::collections::{HashMap, HashSet};
+
+/// Credential resolver over presented credentials and stored KELs
+///
+/// Chain verification is synchronous, so everything the walk can reach is
+/// loaded up front by [`StoredCredentialResolver::load`].
+#[derive(Debug, Default)]
+pub struct StoredCredentialResolver {
+    /// Reachable credentials by SAID
+    credentials: HashMap<String, Acdc>,
+    /// Registry status by credential SAID
+    statuses: HashMap<String, CredentialStatus>,
+    /// Issuance check result by credential SAID
+    issuances: HashMap<String, Result<(), String>>,
+    /// Delegator by delegated AID
+    delegators: HashMap<String, String>,
+}
+
+impl StoredCredentialResolver {
+    /// Load what verifying `credential` and its chain needs
+    ///
+    /// Edges are followed through `presented` up to `max_depth` links. Each
+    /// credential reached has its issuance checked, and the delegators of
+    /// its issuer are looked up.
+    pub async fn load<D: KelStore + StateStore + ?Sized>(
+        db: &D,
+        history: &StateHistory<D>,
+        credential: &SignedCredential,
+        presented: &[SignedCredential],
+        max_depth: usize,
+    ) -> WitnessResult<Self> {
+        let presented: HashMap<&str, &SignedCredential> = presented
+            .iter()
+            .map(|signed| (signed.credential.said.as_str(), signed))
+            .collect();
+
+        let mut resolver = StoredCredentialResolver::default();
+        let mut seen = HashSet::from([credential.credential.said.clone()]);
+        let mut level = vec![credential];
+        for depth in 0..=max_depth {
+            let mut next = Vec::new();
+            for signed in level {
+                let said = signed.credential.said.clone();
+                let anchor = db
+                    .find_anchor(&said)
+                    .await?
+                    .filter(|anchor| anchor.prefix == signed.credential.issuer);
+                let status = match anchor {
+                    Some(_) => CredentialStatus::Issued,
+                    None => CredentialStatus::Unknown,
+                };
+                let issuance = verify_issuance(history, signed, anchor).await?;
+                resolver.statuses.insert(said.clone(), status);
+                resolver.issuances.insert(said.clone(), issuance);
+                resolver
+                    .load_delegators(db, &signed.credential.issuer, max_depth)
+                    .await?;
+                resolver.credentials.insert(said, signed.credential.clone());
+
+                if depth == max_depth {
+                    continue;
+                }
+                for node in linked_credentials(&signed.credential) {
+                    if let Some(linked) = presented.get(node.as_str()) {
+                        if seen.insert(node) {
+                            next.push(*linked);
+                        }
+                    }
+                }
+            }
+            level = next;
+        }
+
+        Ok(resolver)
+    }
+
+    /// Record the delegators above `aid`, following at most `max_depth` links
+    async fn load_delegators<D: StateStore + ?Sized>(
+        &mut self,
+        db: &D,
+        aid: &str,
+        max_depth: usize,
+    ) -> WitnessResult<()> {
+        let mut current = aid.to_string();
+        for _ in 0..=max_depth {
+            if self.delegators.contains_key(&current) {
+                break;
+            }
+            let delegator = db.get_state(&current).await?.and_then(|s| s.delegator);
+            let Some(delegator) = delegator else {
+                break;
+            };
+            self.delegators.insert(current, delegator.clone());
+            current = delegator;
+        }
+        Ok(())
+    }
+}
+
+/// Check that a credential is anchored in its issuer's KEL, at `anchor`,
+/// and signed by the keys the issuer held at the anchoring event
+async fn verify_issuance<D: KelStore + ?Sized>(
+    history: &StateHistory<D>,
+    signed: &SignedCredential,
+    anchor: Option<AnchorLocation>,
+) -> WitnessResult<Result<(), String>> {
+    let Some(anchor) = anchor else {
+        return Ok(Err(format!(
+            "not anchored in the KEL of issuer {}",
+            signed.credential.issuer
+        )));
+    };
+
+    let Some(state) = history.get_state_at(&anchor.prefix, anchor.sn).await? else {
+        return Ok(Err(format!(
+            "anchoring event {} of {} not found",
+            anchor.sn, anchor.prefix
+        )));
+    };
+    Ok(signed.verify(&state).map_err(|e| e.to_string()))
+}
+
+impl CredentialResolver for StoredCredentialResolver {
+    fn resolve(&self, said: &str) -> Option<Acdc> {
+        self.credentials.get(said).cloned()
+    }
+
+    fn status(&self, credential: &Acdc) -> CredentialStatus {
+        self.statuses
+            .get(&credential.said)
+            .copied()
+            .unwrap_or(CredentialStatus::Unknown)
+    }
+
+    fn delegator(&self, aid: &str) -> Option<String> {
+        self.delegators.get(aid).cloned()
+    }
+
+    fn verify_issuance(&self, credential: &Acdc) -> Result<(), String> {
+        self.issuances
+            .get(&credential.said)
+            .cloned()
+            .unwrap_or_else(|| Err("issuance not loaded".to_string()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Witness, WitnessConfig};
+    use cesride::{Matter, Signer};
+    use kerihost_core::test_util::{inception, interaction, signer};
+    use kerihost_core::{saidify, ChainViolation, IndexedSignature};
+    use kerihost_db::InMemoryDatabase;
+    use serde_json::{json, Value};
+    use std::sync::Arc;
+
+    const COOP_SCHEMA: &str = "ECoop_Schema_00000000000000000000000000000000";
+    const LICENSE_SCHEMA: &str = "ELicense_Schema_00000000000000000000000000000";
+
+    /// Controller with a single-key KEL held by a witness
+    struct Controller {
+        signer: Signer,
+        prefix: String,
+        sn: u64,
+        latest: String,
+    }
+
+    impl Controller {
+        async fn incept(witness: &Witness<InMemoryDatabase>, seed: u8) -> Self {
+            let signer = signer(seed);
+            let prefix = signer.verfer().qb64().unwrap();
+            let icp = inception(&signer, &[witness.prefix.as_str()]);
+            witness
+                .process_notice(&icp.to_cesr().unwrap())
+                .await
+                .unwrap();
+            Controller {
+                signer,
+                prefix,
+                sn: 0,
+                latest: icp.event.digest,
+            }
+        }
+
+        /// Anchor a credential SAID in the KEL
+        async fn anchor(&mut self, witness: &Witness<InMemoryDatabase>, said: &str) {
+            self.sn += 1;
+            let ixn = interaction(&self.signer, self.sn, &self.latest, json!([{"d": said}]));
+            witness
+                .process_notice(&ixn.to_cesr().unwrap())
+                .await
+                .unwrap();
+            self.latest = ixn.event.digest;
+        }
+
+        fn issue(&self, issuee: &str, schema: &str, edges: Option<Value>) -> SignedCredential {
+            let credential = credential(&self.prefix, issuee, schema, edges);
+            let raw = SignedCredential::signed_bytes(&credential).unwrap();
+            let siger = self.signer.sign_indexed(&raw, false, 0, None).unwrap();
+            SignedCredential::new(
+                credential,
+                vec![IndexedSignature::from_siger(&siger).unwrap()],
+            )
+        }
+    }
+
+    fn credential(issuer: &str, issuee: &str, schema: &str, edges: Option<Value>) -> Acdc {
+        let attributes = saidify(&json!({"d": "", "i": issuee}), "d").unwrap();
+        let mut compact = json!({
+            "v": "ACDC10JSON000000_", "d": "", "i": issuer, "s": schema,
+            "a": attributes["d"],
+        });
+        let edges = edges.map(|e| saidify(&e, "d").unwrap());
+        if let Some(ref edges) = edges {
+            compact["e"] = edges["d"].clone();
+        }
+
+        let mut sad = saidify(&compact, "d").unwrap();
+        sad["a"] = attributes;
+        if let Some(edges) = edges {
+            sad["e"] = edges;
+        }
+        Acdc::from_value(sad).unwrap()
+    }
+
+    fn membership_edge(membership: &SignedCredential) -> Value {
+        json!({"d": "", "membership": {
+            "n": membership.credential.said, "s": COOP_SCHEMA, "o": "I2I"
+        }})
+    }
+
+    fn create_test_witness() -> Witness<InMemoryDatabase> {
+        let config =
+            WitnessConfig::new("BTest123".to_string(), "https://test.keri.host".to_string());
+        Witness::new(None, Arc::new(InMemoryDatabase::new()), config)
+    }
+
+    #[tokio::test]
+    async fn test_anchored_signed_chain_verifies() {
+        let witness = create_test_witness();
+        let mut coop = Controller::incept(&witness, 1).await;
+        let mut member = Controller::incept(&witness, 2).await;
+
+        let membership = coop.issue(&member.prefix, COOP_SCHEMA, None);
+        let license = member.issue(
+            "EApprentice",
+            LICENSE_SCHEMA,
+            Some(membership_edge(&membership)),
+        );
+        coop.anchor(&witness, &membership.credential.said).await;
+        member.anchor(&witness, &license.credential.said).await;
+
+        let proof = witness
+            .verify_credential(&license, std::slice::from_ref(&membership))
+            .await
+            .unwrap();
+        assert!(proof.is_valid(), "{:?}", proof.all_violations());
+        assert_eq!(proof.status, CredentialStatus::Issued);
+        let linked = proof.edges[0].proof.as_ref().unwrap();
+        assert_eq!(linked.said, membership.credential.said);
+    }
+
+    #[tokio::test]
+    async fn test_unanchored_or_forged_link_is_illegitimate() {
+        let witness = create_test_witness();
+        let mut coop = Controller::incept(&witness, 1).await;
+        let mut member = Controller::incept(&witness, 2).await;
+
+        // Issued by the coop but never anchored in its KEL
+        let membership = coop.issue(&member.prefix, COOP_SCHEMA, None);
+        let license = member.issue(
+            "EApprentice",
+            LICENSE_SCHEMA,
+            Some(membership_edge(&membership)),
+        );
+        member.anchor(&witness, &license.credential.said).await;
+
+        let proof = witness
+            .verify_credential(&license, std::slice::from_ref(&membership))
+            .await
+            .unwrap();
+        let violations = proof.illegitimate_delegations();
+        assert_eq!(violations.len(), 1);
+        assert!(matches!(
+            violations[0],
+            ChainViolation::IssuanceUnverified { said, .. } if *said == membership.credential.said
+        ));
+
+        // Anchored, but the signatures are not the coop's
+        coop.anchor(&witness, &membership.credential.said).await;
+        let mallory = Signer::new_with_raw(&[9u8; 32], Some(true), None).unwrap();
+        let raw = SignedCredential::signed_bytes(&membership.credential).unwrap();
+        let siger = mallory.sign_indexed(&raw, false, 0, None).unwrap();
+        let forged = SignedCredential::new(
+            membership.credential.clone(),
+            vec![IndexedSignature::from_siger(&siger).unwrap()],
+        );
+
+        let proof = witness
+            .verify_credential(&license, &[forged])
+            .await
+            .unwrap();
+        assert!(matches!(
+            proof.illegitimate_delegations()[..],
+            [ChainViolation::IssuanceUnverified { .. }]
+        ));
+        assert!(witness
+            .verify_credential(&license, &[membership])
+            .await
+            .unwrap()
+            .is_valid());
+    }
+
+    #[tokio::test]
+    async fn test_anchor_in_another_kel_does_not_count() {
+        let witness = create_test_witness();
+        let coop = Controller::incept(&witness, 1).await;
+        let mut member = Controller::incept(&witness, 2).await;
+
+        // The member anchors a credential the coop supposedly issued
+        let membership = coop.issue(&member.prefix, COOP_SCHEMA, None);
+        member.anchor(&witness, &membership.credential.said).await;
+
+        let proof = witness.verify_credential(&membership, &[]).await.unwrap();
+        assert_eq!(proof.status, CredentialStatus::Unknown);
+        assert!(proof
+            .violations
+            .iter()
+            .any(|v| matches!(v, ChainViolation::IssuanceUnverified { .. })));
+    }
+}
@@ -31,6 +31,10 @@ pub enum WitnessError {
     #[error("CESR error: {0}")]
     Cesr(String),
 
+    /// Tenant not configured on this deployment
+    #[error("Unknown tenant: {0}")]
+    UnknownTenant(String),
+
     /// Configuration error
     #[error("Configuration error: {0}")]
     Config(String),
@@ -0,0 +1,550 @@
+//! IPEX exchange processing
+//!
+//! Tracks IPEX conversations by their SAID chain. Messages arrive as CESR
+//! and are only accepted once the sender's signatures verify against the
+//! sender's key state. A message whose prior has not been seen yet is
+//! escrowed (escrow is state, not error) and replayed as soon as the prior
+//! arrives, or by the scheduled escrow sweep.
+
+use crate::error::{WitnessError, WitnessResult};
+use futures::TryStreamExt;
+use kerihost_core::{ExchangeMessage, IpexExchange, SignedExchange};
+use kerihost_db::{stream, DbError, EscrowPolicy, ExchangeStore, StateStore};
+use std::sync::Arc;
+use tracing::{debug, info, warn};
+
+/// Attempts to apply a message before a lost race is reported as an error
+const MAX_APPLY_ATTEMPTS: usize = 5;
+
+/// Escrowed messages the sweep fetches per page
+const ESCROW_PAGE_SIZE: usize = 100;
+
+/// Result of processing an IPEX message
+#[derive(Debug, Clone, PartialEq)]
+pub enum IpexResult {
+    /// Message accepted; exchange state after any escrowed responses were applied
+    Accepted { exchange: IpexExchange },
+    /// Prior message unknown, message escrowed
+    Escrowed { prior: String },
+    /// Message already accepted
+    Duplicate,
+}
+
+/// IPEX exchange processor
+pub struct IpexProcessor<D: ExchangeStore + StateStore> {
+    db: Arc<D>,
+    escrow_policy: EscrowPolicy,
+}
+
+impl<D: ExchangeStore + StateStore> IpexProcessor<D> {
+    /// Create new IPEX processor
+    pub fn new(db: Arc<D>) -> Self {
+        IpexProcessor {
+            db,
+            escrow_policy: EscrowPolicy::default(),
+        }
+    }
+
+    /// Builder-style method to set how long messages stay in escrow
+    pub fn with_escrow_policy(mut self, policy: EscrowPolicy) -> Self {
+        self.escrow_policy = policy;
+        self
+    }
+
+    /// Process a signed `exn` message in CESR
+    pub async fn process(&self, raw: &[u8]) -> WitnessResult<IpexResult> {
+        let signed = SignedExchange::from_cesr(raw)?;
+        self.verify(&signed).await?;
+        self.process_message(signed.message).await
+    }
+
+    /// Verify a message against its sender's current key state
+    async fn verify(&self, signed: &SignedExchange) -> WitnessResult<()> {
+        let sender = &signed.message.sender;
+        let state = self.db.get_state(sender).await?.ok_or_else(|| {
+            WitnessError::Validation(format!("Unknown exchange message sender {}", sender))
+        })?;
+        signed.verify(&state)?;
+        Ok(())
+    }
+
+    /// Process an `exn` message whose signatures have been verified,
+    /// re-reading the exchange after losing a race to another response
+    async fn process_message(&self, message: ExchangeMessage) -> WitnessResult<IpexResult> {
+        let mut attempt = 1;
+        loop {
+            match self.try_process_message(&message).await {
+                Err(WitnessError::Database(DbError::StateConflict(e)))
+                    if attempt < MAX_APPLY_ATTEMPTS =>
+                {
+                    debug!(said = %message.said, attempt, error = %e, "Lost exchange race, retrying");
+                    attempt += 1;
+                }
+                result => return result,
+            }
+        }
+    }
+
+    /// Apply a message to the exchange as currently stored
+    async fn try_process_message(&self, message: &ExchangeMessage) -> WitnessResult<IpexResult> {
+        if self.db.get_message(&message.said).await?.is_some() {
+            debug!(said = %message.said, "Duplicate exchange message");
+            return Ok(IpexResult::Duplicate);
+        }
+
+        let exchange = match message.prior {
+            None => IpexExchange::start(message)?,
+            Some(ref prior) => match self.db.get_exchange_by_message(prior).await? {
+                Some(mut exchange) => {
+                    exchange.advance(message)?;
+                    exchange
+                }
+                None => {
+                    info!(said = %message.said, prior = %prior, "Exchange message escrowed");
+                    self.db
+                        .escrow_message_with_ttl(message, self.escrow_policy.exchange_ttl)
+                        .await?;
+                    return Ok(IpexResult::Escrowed {
+                        prior: prior.clone(),
+                    });
+                }
+            },
+        };
+
+        self.db.put_exchange(&exchange, message).await?;
+        info!(
+            exchange = %exchange.id,
+            state = %exchange.state,
+            said = %message.said,
+            "Exchange message accepted"
+        );
+
+        let exchange = self.promote_responses(exchange).await?;
+        Ok(IpexResult::Accepted { exchange })
+    }
+
+    /// Apply escrowed responses to the latest message, following the chain
+    async fn promote_responses(&self, mut exchange: IpexExchange) -> WitnessResult<IpexExchange> {
+        loop {
+            let last = exchange.last_said().to_string();
+            let mut advanced = false;
+
+            for escrowed in self.db.get_escrowed_messages(&last).await? {
+                let Some(message) = self
+                    .db
+                    .promote_escrowed_message(&escrowed.message.said)
+                    .await?
+                else {
+                    continue;
+                };
+
+                // Only one response can extend the chain; others are invalid
+                if advanced {
+                    warn!(said = %message.said, "Competing exchange response dropped");
+                    continue;
+                }
+                let mut next = exchange.clone();
+                if let Err(e) = next.advance(&message) {
+                    warn!(said = %message.said, error = %e, "Escrowed exchange message rejected");
+                    continue;
+                }
+                match self.db.put_exchange(&next, &message).await {
+                    Ok(()) => {
+                        info!(exchange = %next.id, said = %message.said, "Escrowed exchange message promoted");
+                        exchange = next;
+                        advanced = true;
+                    }
+                    // Another writer moved the exchange on; it or the sweep
+                    // picks up the remaining responses
+                    Err(DbError::StateConflict(e)) => {
+                        debug!(said = %message.said, error = %e, "Lost exchange race, message re-escrowed");
+                        self.db
+                            .escrow_message_with_ttl(&message, self.escrow_policy.exchange_ttl)
+                            .await?;
+                        let stored = self.db.get_exchange(&exchange.id).await?;
+                        return Ok(stored.unwrap_or(exchange));
+                    }
+                    Err(e) => return Err(e.into()),
+                }
+            }
+
+            if !advanced {
+                return Ok(exchange);
+            }
+        }
+    }
+
+    /// Sweep escrowed messages: drop expired ones and replay those whose prior is now known
+    ///
+    /// Returns the number of messages promoted.
+    pub async fn process_escrow(&self) -> WitnessResult<usize> {
+        let mut promoted = 0;
+
+        // One page at a time, so a large backlog stays within memory
+        let mut escrows = std::pin::pin!(stream::escrowed_messages(
+            self.db.as_ref(),
+            ESCROW_PAGE_SIZE
+        ));
+        while let Some(escrowed) = escrows.try_next().await? {
+            let said = escrowed.message.said.clone();
+
+            if escrowed.is_expired() {
+                self.db.remove_escrowed_message(&said).await?;
+                continue;
+            }
+
+            let Some(ref prior) = escrowed.message.prior else {
+                continue;
+            };
+            if self.db.get_message(prior).await?.is_none() {
+                continue;
+            }
+
+            if let Some(message) = self.db.promote_escrowed_message(&said).await? {
+                if matches!(
+                    self.process_message(message).await,
+                    Ok(IpexResult::Accepted { .. })
+                ) {
+                    promoted += 1;
+                }
+            }
+        }
+
+        Ok(promoted)
+    }
+
+    /// Get exchange by identifier
+    pub async fn get_exchange(&self, id: &str) -> WitnessResult<Option<IpexExchange>> {
+        Ok(self.db.get_exchange(id).await?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use cesride::{Matter, Signer};
+    use kerihost_core::test_util::{key_state, signer};
+    use kerihost_core::{CoreError, IndexedSignature, IpexVerb};
+    use kerihost_db::InMemoryDatabase;
+    use serde_json::json;
+
+    const HOLDER: &str = "EHolder_AID";
+    const ISSUER: &str = "EIssuer_AID";
+
+    /// One party in a local exchange, with its own key and database
+    struct Party {
+        signer: Signer,
+        aid: String,
+        db: Arc<InMemoryDatabase>,
+        ipex: IpexProcessor<InMemoryDatabase>,
+    }
+
+    impl Party {
+        fn new(seed: u8) -> Self {
+            let signer = signer(seed);
+            let aid = signer.verfer().qb64().unwrap();
+            let db = Arc::new(InMemoryDatabase::new());
+            Party {
+                signer,
+                aid,
+                ipex: IpexProcessor::new(Arc::clone(&db)),
+                db,
+            }
+        }
+
+        /// Record the key state of each party, as resolved from their KELs
+        async fn knows(&self, parties: &[&Party]) {
+            for party in parties {
+                self.db.put_state(&key_state(&party.signer)).await.unwrap();
+            }
+        }
+
+        fn sign(&self, message: ExchangeMessage) -> SignedExchange {
+            let raw = message.to_json().unwrap();
+            let siger = self
+                .signer
+                .sign_indexed(raw.as_bytes(), false, 0, None)
+                .unwrap();
+            SignedExchange::new(message, vec![IndexedSignature::from_siger(&siger).unwrap()])
+        }
+
+        fn message(
+            &self,
+            to: &Party,
+            verb: IpexVerb,
+            prior: Option<&SignedExchange>,
+        ) -> ExchangeMessage {
+            let embeds = matches!(verb, IpexVerb::Offer | IpexVerb::Grant)
+                .then(|| json!({"acdc": {"d": "ECredential_SAID"}}));
+            ExchangeMessage::new(
+                &self.aid,
+                Some(&to.aid),
+                &verb.route(),
+                prior.map(|p| p.message.said.as_str()),
+                json!({"m": verb.to_string()}),
+                embeds,
+            )
+            .unwrap()
+        }
+
+        /// Create and sign a message, record it locally, and return it for delivery
+        async fn send(
+            &self,
+            to: &Party,
+            verb: IpexVerb,
+            prior: Option<&SignedExchange>,
+        ) -> SignedExchange {
+            let signed = self.sign(self.message(to, verb, prior));
+            self.deliver(&signed.to_cesr().unwrap()).await.unwrap();
+            signed
+        }
+
+        async fn deliver(&self, raw: &[u8]) -> WitnessResult<IpexResult> {
+            self.ipex.process(raw).await
+        }
+
+        async fn receive(&self, signed: &SignedExchange) -> IpexResult {
+            self.deliver(&signed.to_cesr().unwrap()).await.unwrap()
+        }
+    }
+
+    /// Holder and issuer, each knowing both key states
+    async fn parties() -> (Party, Party) {
+        let holder = Party::new(1);
+        let issuer = Party::new(2);
+        holder.knows(&[&holder, &issuer]).await;
+        issuer.knows(&[&holder, &issuer]).await;
+        (holder, issuer)
+    }
+
+    fn state(result: &IpexResult) -> IpexVerb {
+        match result {
+            IpexResult::Accepted { exchange } => exchange.state,
+            other => panic!("Expected accepted, got {:?}", other),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_two_party_full_flow() {
+        let (holder, issuer) = parties().await;
+
+        let apply = holder.send(&issuer, IpexVerb::Apply, None).await;
+        assert_eq!(state(&issuer.receive(&apply).await), IpexVerb::Apply);
+
+        let offer = issuer.send(&holder, IpexVerb::Offer, Some(&apply)).await;
+        assert_eq!(state(&holder.receive(&offer).await), IpexVerb::Offer);
+
+        let agree = holder.send(&issuer, IpexVerb::Agree, Some(&offer)).await;
+        assert_eq!(state(&issuer.receive(&agree).await), IpexVerb::Agree);
+
+        let grant = issuer.send(&holder, IpexVerb::Grant, Some(&agree)).await;
+        assert_eq!(state(&holder.receive(&grant).await), IpexVerb::Grant);
+
+        let admit = holder.send(&issuer, IpexVerb::Admit, Some(&grant)).await;
+        assert_eq!(state(&issuer.receive(&admit).await), IpexVerb::Admit);
+
+        // Both parties converge on the same completed exchange
+        let id = &apply.message.said;
+        let at_holder = holder.ipex.get_exchange(id).await.unwrap().unwrap();
+        let at_issuer = issuer.ipex.get_exchange(id).await.unwrap().unwrap();
+        assert_eq!(at_holder, at_issuer);
+        assert!(at_issuer.is_complete());
+        assert_eq!(at_issuer.chain.len(), 5);
+        assert_eq!(at_issuer.credential.as_deref(), Some("ECredential_SAID"));
+    }
+
+    #[tokio::test]
+    async fn test_out_of_order_messages_escrowed() {
+        let (holder, issuer) = parties().await;
+        let observer = Party::new(3);
+        observer.knows(&[&holder, &issuer]).await;
+
+        let apply = holder.send(&issuer, IpexVerb::Apply, None).await;
+        let offer = issuer.send(&holder, IpexVerb::Offer, Some(&apply)).await;
+        let agree = holder.send(&issuer, IpexVerb::Agree, Some(&offer)).await;
+
+        // Deliver in reverse order
+        let result = observer.receive(&agree).await;
+        assert!(
+            matches!(result, IpexResult::Escrowed { ref prior } if *prior == offer.message.said)
+        );
+        let result = observer.receive(&offer).await;
+        assert!(matches!(result, IpexResult::Escrowed { .. }));
+
+        // The first message releases the whole chain
+        let result = observer.receive(&apply).await;
+        assert_eq!(state(&result), IpexVerb::Agree);
+        assert!(observer
+            .db
+            .get_all_escrowed_messages()
+            .await
+            .unwrap()
+            .is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_escrow_ttl_from_policy() {
+        let (holder, issuer) = parties().await;
+        let mut observer = Party::new(3);
+        observer.ipex = IpexProcessor::new(Arc::clone(&observer.db))
+            .with_escrow_policy(EscrowPolicy::default().with_exchange_ttl(60));
+        observer.knows(&[&holder, &issuer]).await;
+
+        let apply = holder.send(&issuer, IpexVerb::Apply, None).await;
+        let offer = issuer.send(&holder, IpexVerb::Offer, Some(&apply)).await;
+        let now = chrono::Utc::now().timestamp() as u64;
+        observer.receive(&offer).await;
+
+        let escrowed = observer.db.get_all_escrowed_messages().await.unwrap();
+        assert!(escrowed[0].ttl >= now + 60);
+        assert!(escrowed[0].ttl < now + EscrowPolicy::default().exchange_ttl);
+    }
+
+    #[tokio::test]
+    async fn test_process_escrow_sweep() {
+        let db = Arc::new(InMemoryDatabase::new());
+        let ipex = IpexProcessor::new(Arc::clone(&db));
+
+        let apply =
+            ExchangeMessage::new(HOLDER, Some(ISSUER), "/ipex/apply", None, json!({}), None)
+                .unwrap();
+        let offer = ExchangeMessage::new(
+            ISSUER,
+            Some(HOLDER),
+            "/ipex/offer",
+            Some(&apply.said),
+            json!({}),
+            None,
+        )
+        .unwrap();
+
+        // Simulate the prior landing without triggering promotion
+        db.escrow_message(&offer).await.unwrap();
+        let exchange = IpexExchange::start(&apply).unwrap();
+        db.put_exchange(&exchange, &apply).await.unwrap();
+
+        assert_eq!(ipex.process_escrow().await.unwrap(), 1);
+        let exchange = ipex.get_exchange(&apply.said).await.unwrap().unwrap();
+        assert_eq!(exchange.state, IpexVerb::Offer);
+    }
+
+    #[tokio::test]
+    async fn test_duplicate_message() {
+        let (holder, issuer) = parties().await;
+
+        let apply = holder.send(&issuer, IpexVerb::Apply, None).await;
+        issuer.receive(&apply).await;
+
+        assert_eq!(issuer.receive(&apply).await, IpexResult::Duplicate);
+    }
+
+    #[tokio::test]
+    async fn test_invalid_transition_rejected() {
+        let (holder, issuer) = parties().await;
+
+        let apply = holder.send(&issuer, IpexVerb::Apply, None).await;
+        let admit = issuer.sign(issuer.message(&holder, IpexVerb::Admit, Some(&apply)));
+
+        let result = holder.deliver(&admit.to_cesr().unwrap()).await;
+        assert!(matches!(
+            result,
+            Err(WitnessError::Core(CoreError::InvalidTransition { .. }))
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_competing_responses_one_accepted() {
+        let (holder, issuer) = parties().await;
+
+        let apply = holder.send(&issuer, IpexVerb::Apply, None).await;
+        issuer.receive(&apply).await;
+        let offer = issuer.sign(issuer.message(&holder, IpexVerb::Offer, Some(&apply)));
+        let spurn = issuer.sign(issuer.message(&holder, IpexVerb::Spurn, Some(&apply)));
+
+        let (offered, spurned) = tokio::join!(holder.receive(&offer), async {
+            holder.deliver(&spurn.to_cesr().unwrap()).await
+        });
+        assert_eq!(state(&offered), IpexVerb::Offer);
+        assert!(spurned.is_err());
+
+        let exchange = holder
+            .ipex
+            .get_exchange(&apply.message.said)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(exchange.chain, vec![apply.message.said, offer.message.said]);
+    }
+
+    #[tokio::test]
+    async fn test_forged_response_rejected() {
+        let (holder, issuer) = parties().await;
+        let mallory = Party::new(9);
+
+        let apply = holder.send(&issuer, IpexVerb::Apply, None).await;
+
+        // A spurn claiming to be from the issuer, signed with another key
+        let spurn = mallory.sign(issuer.message(&holder, IpexVerb::Spurn, Some(&apply)));
+        let result = holder.deliver(&spurn.to_cesr().unwrap()).await;
+        assert!(matches!(
+            result,
+            Err(WitnessError::Core(CoreError::ThresholdNotMet {
+                have: 0,
+                need: 1
+            }))
+        ));
+
+        let exchange = holder
+            .ipex
+            .get_exchange(&apply.message.said)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(exchange.state, IpexVerb::Apply);
+        assert!(holder
+            .db
+            .get_message(&spurn.message.said)
+            .await
+            .unwrap()
+            .is_none());
+    }
+
+    #[tokio::test]
+    async fn test_unsigned_response_rejected() {
+        let (holder, issuer) = parties().await;
+
+        let apply = holder.send(&issuer, IpexVerb::Apply, None).await;
+        let offer = issuer.message(&holder, IpexVerb::Offer, Some(&apply));
+
+        let result = holder.deliver(offer.to_json().unwrap().as_bytes()).await;
+        assert!(matches!(
+            result,
+            Err(WitnessError::Core(CoreError::ThresholdNotMet {
+                have: 0,
+                need: 1
+            }))
+        ));
+        assert!(holder
+            .db
+            .get_escrowed_messages(&apply.message.said)
+            .await
+            .unwrap()
+            .is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_unknown_sender_rejected() {
+        let (_holder, issuer) = parties().await;
+        let stranger = Party::new(5);
+
+        let apply = stranger.sign(stranger.message(&issuer, IpexVerb::Apply, None));
+        let result = issuer.deliver(&apply.to_cesr().unwrap()).await;
+        assert!(matches!(result, Err(WitnessError::Validation(_))));
+        assert!(issuer
+            .db
+            .get_message(&apply.message.said)
+            .await
+            .unwrap()
+            .is_none());
+    }
+}
@@ -5,6 +5,10 @@
 //! - Receipt generation
 //! - Escrow handling
 //! - OOBI generation and resolution
+//! - Credential schema registry
+//! - Credential chain verification against stored KELs
+//! - IPEX credential exchange
+//! - Per-tenant witnesses sharing one deployment
 //!
 //! # KERI-Honest Design
 //!
@@ -15,14 +19,22 @@
 //! - Explicit confidence qualifiers
 
 pub mod config;
+pub mod credentials;
 pub mod error;
 pub mod escrow;
+pub mod ipex;
 pub mod oobi;
 pub mod processor;
 pub mod receipt_generator;
+pub mod schema;
+pub mod tenants;
 pub mod witness;
 
 pub use config::*;
+pub use credentials::*;
 pub use error::*;
+pub use ipex::*;
 pub use processor::*;
+pub use schema::*;
+pub use tenants::*;
 pub use witness::*;
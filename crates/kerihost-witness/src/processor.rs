@@ -5,13 +5,41 @@ use kerihost_core::{
     ConfidenceLevel, CoreError, EventValidator, HonestMetadata, KeyState, NontransferableReceipt,
     SignedEvent, ValidationResult,
 };
-use kerihost_db::{EscrowReason, WitnessDatabase};
+use kerihost_db::{EscrowPolicy, EscrowReason, WitnessDatabase};
 use serde::{Deserialize, Serialize};
+use std::collections::hash_map::RandomState;
+use std::hash::{BuildHasher, Hasher};
 use std::sync::Arc;
+use std::time::Duration;
+use tracing::debug;
+
+/// Attempts to commit an event before a lost race or a transient storage
+/// failure is reported as an error
+const MAX_COMMIT_ATTEMPTS: usize = 5;
+
+/// Backoff before the first retry after a transient storage failure,
+/// doubled for each one after
+const RETRY_BASE_DELAY: Duration = Duration::from_millis(50);
+
+/// Longest backoff between retries
+const RETRY_MAX_DELAY: Duration = Duration::from_secs(1);
+
+/// Backoff before retry `attempt` (from 1), jittered over its upper half so
+/// writers that failed together do not retry together
+fn retry_delay(attempt: usize) -> Duration {
+    let ceiling = RETRY_BASE_DELAY
+        .saturating_mul(1 << (attempt - 1).min(16))
+        .min(RETRY_MAX_DELAY);
+    // Every RandomState is freshly keyed, which is random enough here
+    let random = RandomState::new().build_hasher().finish();
+    let fraction = (random >> 11) as f64 / (1u64 << 53) as f64;
+    ceiling.mul_f64(0.5 + fraction / 2.0)
+}
 
 /// Result of processing an event
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(tag = "status", rename_all = "snake_case")]
+#[allow(clippy::large_enum_variant)]
 pub enum ProcessResult {
     /// Event was accepted and stored
     Accepted {
@@ -48,6 +76,8 @@ pub struct EventProcessor<D: WitnessDatabase> {
     strict_validation: bool,
     /// Witness prefix for authorization checks
     witness_prefix: Option<String>,
+    /// TTLs for escrowed events
+    escrow_policy: EscrowPolicy,
 }
 
 impl<D: WitnessDatabase> EventProcessor<D> {
@@ -57,6 +87,7 @@ impl<D: WitnessDatabase> EventProcessor<D> {
             db,
             strict_validation,
             witness_prefix: None,
+            escrow_policy: EscrowPolicy::default(),
         }
     }
 
@@ -66,9 +97,16 @@ impl<D: WitnessDatabase> EventProcessor<D> {
             db,
             strict_validation,
             witness_prefix: Some(witness_prefix),
+            escrow_policy: EscrowPolicy::default(),
         }
     }
 
+    /// Builder-style method to set how long events stay in escrow
+    pub fn with_escrow_policy(mut self, policy: EscrowPolicy) -> Self {
+        self.escrow_policy = policy;
+        self
+    }
+
     /// Process raw CESR bytes
     pub async fn process(&self, raw: &[u8]) -> WitnessResult<ProcessResult> {
         // Parse the event
@@ -80,7 +118,75 @@ impl<D: WitnessDatabase> EventProcessor<D> {
     }
 
     /// Process a parsed signed event
+    ///
+    /// If another writer commits to the same prefix between reading the
+    /// state and committing, the state is re-read and the event re-validated.
+    /// Throttling and other transient storage failures are retried the same
+    /// way after a jittered exponential backoff.
     pub async fn process_signed_event(&self, event: SignedEvent) -> WitnessResult<ProcessResult> {
+        self.process_with_retries(&event, None).await
+    }
+
+    /// Process an event replayed from another node's export
+    ///
+    /// Validation is the same as for a fresh event, but the event is recorded
+    /// as first seen at `first_seen_at` (RFC 3339), and the witness
+    /// authorization check is skipped: the exporting node already accepted
+    /// the KEL, and a restored or migrated witness may have a new prefix.
+    pub async fn process_replayed(
+        &self,
+        event: &SignedEvent,
+        first_seen_at: &str,
+    ) -> WitnessResult<ProcessResult> {
+        self.process_with_retries(event, Some(first_seen_at)).await
+    }
+
+    /// Process an event, re-validating after lost commit races and
+    /// transient storage failures
+    async fn process_with_retries(
+        &self,
+        event: &SignedEvent,
+        first_seen_at: Option<&str>,
+    ) -> WitnessResult<ProcessResult> {
+        let mut attempt = 1;
+        loop {
+            match self.try_process(event, first_seen_at).await {
+                Err(WitnessError::Database(e))
+                    if e.is_retryable() && attempt < MAX_COMMIT_ATTEMPTS =>
+                {
+                    if e.is_transient() {
+                        let delay = retry_delay(attempt);
+                        debug!(
+                            prefix = %event.event.prefix,
+                            sn = event.event.sn,
+                            attempt,
+                            delay_ms = delay.as_millis() as u64,
+                            error = %e,
+                            "Transient storage failure, retrying"
+                        );
+                        tokio::time::sleep(delay).await;
+                    } else {
+                        debug!(
+                            prefix = %event.event.prefix,
+                            sn = event.event.sn,
+                            attempt,
+                            error = %e,
+                            "Lost commit race, re-validating"
+                        );
+                    }
+                    attempt += 1;
+                }
+                result => return result,
+            }
+        }
+    }
+
+    /// Validate an event against the current state and commit it
+    async fn try_process(
+        &self,
+        event: &SignedEvent,
+        first_seen_at: Option<&str>,
+    ) -> WitnessResult<ProcessResult> {
         let prefix = &event.event.prefix;
         let sn = event.event.sn;
 
@@ -89,16 +195,21 @@ impl<D: WitnessDatabase> EventProcessor<D> {
 
         // Validate the event
         let validation_result = if self.strict_validation {
-            EventValidator::validate(&event, current_state.as_ref())
+            EventValidator::validate(event, current_state.as_ref())
         } else {
             // Lenient validation - skip signature verification
-            self.lenient_validate(&event, current_state.as_ref())
+            self.lenient_validate(event, current_state.as_ref())
         };
 
         match validation_result {
             Ok(ValidationResult::Valid) => {
                 // Check witness authorization for non-inception events
-                if let Some(ref witness_prefix) = self.witness_prefix {
+                // Replayed events were authorized by the node that exported them
+                let witness_prefix = self
+                    .witness_prefix
+                    .as_ref()
+                    .filter(|_| first_seen_at.is_none());
+                if let Some(witness_prefix) = witness_prefix {
                     if sn > 0 {
                         // For non-inception: witness must be in the identifier's witness list
                         let authorized = if let Some(ref state) = current_state {
@@ -123,9 +234,6 @@ impl<D: WitnessDatabase> EventProcessor<D> {
                     }
                 }
 
-                // Store the event
-                self.db.append_event(&event).await?;
-
                 // Compute new state
                 let new_state = if sn == 0 {
                     KeyState::from_inception(&event.event)?
@@ -135,8 +243,15 @@ impl<D: WitnessDatabase> EventProcessor<D> {
                     current.apply(&event.event)?
                 };
 
-                // Store the new state
-                self.db.put_state(&new_state).await?;
+                // Store the event and its state together
+                match first_seen_at {
+                    Some(datetime) => {
+                        self.db
+                            .commit_event_seen_at(event, &new_state, datetime)
+                            .await?
+                    }
+                    None => self.db.commit_event(event, &new_state).await?,
+                }
 
                 Ok(ProcessResult::Accepted {
                     receipt: None, // Witness generates receipt separately
@@ -145,33 +260,30 @@ impl<D: WitnessDatabase> EventProcessor<D> {
             }
             Ok(ValidationResult::OutOfOrder { .. }) => {
                 // Escrow the event
+                let reason = EscrowReason::OutOfOrder;
                 self.db
-                    .escrow_event(&event, EscrowReason::OutOfOrder)
+                    .escrow_event_with_ttl(event, reason, self.escrow_policy.ttl(reason))
                     .await?;
 
-                Ok(ProcessResult::Escrowed {
-                    reason: EscrowReason::OutOfOrder,
-                })
+                Ok(ProcessResult::Escrowed { reason })
             }
             Ok(ValidationResult::PartiallySigned { .. }) => {
                 // Escrow the event
+                let reason = EscrowReason::PartiallySigned;
                 self.db
-                    .escrow_event(&event, EscrowReason::PartiallySigned)
+                    .escrow_event_with_ttl(event, reason, self.escrow_policy.ttl(reason))
                     .await?;
 
-                Ok(ProcessResult::Escrowed {
-                    reason: EscrowReason::PartiallySigned,
-                })
+                Ok(ProcessResult::Escrowed { reason })
             }
             Ok(ValidationResult::MissingDelegator) => {
                 // Escrow the event
+                let reason = EscrowReason::MissingDelegator;
                 self.db
-                    .escrow_event(&event, EscrowReason::MissingDelegator)
+                    .escrow_event_with_ttl(event, reason, self.escrow_policy.ttl(reason))
                     .await?;
 
-                Ok(ProcessResult::Escrowed {
-                    reason: EscrowReason::MissingDelegator,
-                })
+                Ok(ProcessResult::Escrowed { reason })
             }
             Ok(ValidationResult::Duplicate) => Ok(ProcessResult::Duplicate),
             Err(e) => Err(WitnessError::Validation(e.to_string())),
@@ -246,7 +358,7 @@ impl<D: WitnessDatabase> EventProcessor<D> {
 mod tests {
     use super::*;
     use kerihost_core::{EventType, IndexedSignature, KeyEvent, Threshold};
-    use kerihost_db::InMemoryDatabase;
+    use kerihost_db::{DbError, InMemoryDatabase, StateStore};
 
     fn create_test_db() -> Arc<InMemoryDatabase> {
         Arc::new(InMemoryDatabase::new())
@@ -281,6 +393,7 @@ mod tests {
                 index: 0,
                 signature: "AASig".to_string(),
             }],
+            attachments: Vec::new(),
         }
     }
 
@@ -388,6 +501,85 @@ mod tests {
         assert!(matches!(result, ProcessResult::Duplicate));
     }
 
+    #[tokio::test(flavor = "multi_thread", worker_threads = 8)]
+    async fn test_concurrent_submissions_single_winner() {
+        const ROUNDS: u64 = 10;
+        const TASKS: usize = 16;
+
+        let db = create_test_db();
+        let processor = Arc::new(EventProcessor::new(db.clone(), false));
+
+        let icp = create_test_event("DTest123", 0, None);
+        processor.process_signed_event(icp.clone()).await.unwrap();
+        let mut prior = icp.event.digest;
+
+        // Each round, many tasks race competing events for the same sn
+        for sn in 1..=ROUNDS {
+            let barrier = Arc::new(tokio::sync::Barrier::new(TASKS));
+            let handles: Vec<_> = (0..TASKS)
+                .map(|i| {
+                    let mut event = create_test_event("DTest123", sn, Some(prior.clone()));
+                    event.event.digest = format!("EDigestDTest123_{}_{}", sn, i);
+                    let processor = Arc::clone(&processor);
+                    let barrier = Arc::clone(&barrier);
+                    tokio::spawn(async move {
+                        barrier.wait().await;
+                        processor.process_signed_event(event).await
+                    })
+                })
+                .collect();
+
+            let mut winners = Vec::new();
+            for handle in handles {
+                match handle.await.unwrap().unwrap() {
+                    ProcessResult::Accepted { state, .. } => winners.push(state),
+                    ProcessResult::Duplicate => {}
+                    other => panic!("Unexpected result: {:?}", other),
+                }
+            }
+
+            assert_eq!(winners.len(), 1, "exactly one event wins sn {}", sn);
+            let state = db.get_state("DTest123").await.unwrap().unwrap();
+            assert_eq!(state.sn, sn);
+            assert_eq!(state.latest_digest, winners[0].latest_digest);
+            prior = state.latest_digest;
+        }
+
+        assert_eq!(db.event_count("DTest123").await, ROUNDS as usize + 1);
+    }
+
+    #[tokio::test]
+    async fn test_transient_failures_are_retried() {
+        let db = create_test_db();
+        let processor = EventProcessor::new(Arc::clone(&db), false);
+
+        db.fail_commits(MAX_COMMIT_ATTEMPTS - 1);
+        let icp = create_test_event("DTest123", 0, None);
+        let result = processor.process_signed_event(icp).await.unwrap();
+        assert!(matches!(result, ProcessResult::Accepted { .. }));
+
+        // Retries are bounded
+        db.fail_commits(MAX_COMMIT_ATTEMPTS);
+        let ixn = create_test_event("DTest123", 1, Some("EDigestDTest123_0".to_string()));
+        let result = processor.process_signed_event(ixn.clone()).await;
+        assert!(matches!(
+            result,
+            Err(WitnessError::Database(DbError::Transient(_)))
+        ));
+        assert_eq!(db.get_state("DTest123").await.unwrap().unwrap().sn, 0);
+    }
+
+    #[test]
+    fn test_retry_delay_grows_within_bounds() {
+        for attempt in 1..=10 {
+            let ceiling = RETRY_BASE_DELAY
+                .saturating_mul(1 << (attempt - 1))
+                .min(RETRY_MAX_DELAY);
+            let delay = retry_delay(attempt);
+            assert!(delay >= ceiling / 2 && delay <= ceiling, "{:?}", delay);
+        }
+    }
+
     #[tokio::test]
     async fn test_process_result_metadata() {
         let state = KeyState {
@@ -80,6 +80,7 @@ mod tests {
                 index: 0,
                 signature: "AASig".to_string(),
             }],
+            attachments: Vec::new(),
         }
     }
 
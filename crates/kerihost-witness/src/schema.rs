@@ -0,0 +1,181 @@
+//! Credential schema registry
+//!
+//! Schemas are stored by SAID after their `$id` has been verified, and served
+//! back through data OOBIs (`/oobi/{said}`). Credentials are validated against
+//! the registered schema they reference.
+
+use crate::error::{WitnessError, WitnessResult};
+use kerihost_core::{Acdc, CredentialSchema, SchemaValidation};
+use kerihost_db::SchemaStore;
+use std::sync::Arc;
+use tracing::info;
+
+/// Registry of SAIDified credential schemas
+pub struct SchemaRegistry<D: SchemaStore> {
+    db: Arc<D>,
+}
+
+impl<D: SchemaStore> SchemaRegistry<D> {
+    /// Create new schema registry
+    pub fn new(db: Arc<D>) -> Self {
+        SchemaRegistry { db }
+    }
+
+    /// Register a schema from raw JSON
+    ///
+    /// The schema SAID is verified before storing. Registering the same
+    /// schema twice is a no-op.
+    pub async fn register(&self, raw: &[u8]) -> WitnessResult<CredentialSchema> {
+        let schema = CredentialSchema::from_json(raw)?;
+        self.db.put_schema(&schema).await?;
+
+        info!(said = %schema.said, title = ?schema.title, "Schema registered");
+        Ok(schema)
+    }
+
+    /// Get schema by SAID
+    pub async fn get(&self, said: &str) -> WitnessResult<Option<CredentialSchema>> {
+        Ok(self.db.get_schema(said).await?)
+    }
+
+    /// Parse a credential and validate it against its registered schema
+    ///
+    /// A credential submitted with its attribute block compacted to a SAID
+    /// is reported as `SchemaValidation::AttributesUnverified`, never as
+    /// valid.
+    pub async fn validate_credential(&self, raw: &[u8]) -> WitnessResult<(Acdc, SchemaValidation)> {
+        let credential = Acdc::from_json(raw)?;
+
+        let schema = self.get(&credential.schema).await?.ok_or_else(|| {
+            WitnessError::Validation(format!("Unknown schema {}", credential.schema))
+        })?;
+        let validation = schema.validate(&credential)?;
+
+        Ok((credential, validation))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use kerihost_core::{saidify, CoreError};
+    use kerihost_db::InMemoryDatabase;
+    use serde_json::{json, Value};
+
+    fn create_test_schema() -> Value {
+        saidify(
+            &json!({
+                "$id": "",
+                "$schema": "http://json-schema.org/draft-07/schema#",
+                "title": "Membership",
+                "type": "object",
+                "properties": {
+                    "a": {
+                        "oneOf": [
+                            {"type": "string"},
+                            {
+                                "type": "object",
+                                "properties": {"d": {"type": "string"}, "role": {"type": "string"}},
+                                "required": ["d", "role"]
+                            }
+                        ]
+                    }
+                },
+                "required": ["a"]
+            }),
+            "$id",
+        )
+        .unwrap()
+    }
+
+    fn create_test_credential(schema: &str, attributes: Value) -> Vec<u8> {
+        let block = saidify(&attributes, "d").unwrap();
+        let mut credential = saidify(
+            &json!({
+                "v": "ACDC10JSON000000_",
+                "d": "",
+                "i": "EIssuer",
+                "s": schema,
+                "a": block["d"],
+            }),
+            "d",
+        )
+        .unwrap();
+        credential["a"] = block;
+        serde_json::to_vec(&credential).unwrap()
+    }
+
+    fn create_test_registry() -> SchemaRegistry<InMemoryDatabase> {
+        SchemaRegistry::new(Arc::new(InMemoryDatabase::new()))
+    }
+
+    #[tokio::test]
+    async fn test_register_and_get() {
+        let registry = create_test_registry();
+        let raw = serde_json::to_vec(&create_test_schema()).unwrap();
+
+        let schema = registry.register(&raw).await.unwrap();
+        let fetched = registry.get(&schema.said).await.unwrap().unwrap();
+        assert_eq!(fetched.title.as_deref(), Some("Membership"));
+    }
+
+    #[tokio::test]
+    async fn test_register_rejects_bad_said() {
+        let registry = create_test_registry();
+        let mut schema = create_test_schema();
+        schema["title"] = json!("Tampered");
+        let raw = serde_json::to_vec(&schema).unwrap();
+
+        let result = registry.register(&raw).await;
+        assert!(matches!(
+            result,
+            Err(WitnessError::Core(CoreError::SaidMismatch { .. }))
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_validate_credential() {
+        let registry = create_test_registry();
+        let raw = serde_json::to_vec(&create_test_schema()).unwrap();
+        let schema = registry.register(&raw).await.unwrap();
+
+        let valid = create_test_credential(&schema.said, json!({"d": "", "role": "member"}));
+        let (_, validation) = registry.validate_credential(&valid).await.unwrap();
+        assert_eq!(validation, SchemaValidation::Valid);
+
+        let invalid = create_test_credential(&schema.said, json!({"d": "", "rank": 1}));
+        let result = registry.validate_credential(&invalid).await;
+        assert!(matches!(
+            result,
+            Err(WitnessError::Core(CoreError::SchemaViolation(_)))
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_validate_compacted_credential_unverified() {
+        let registry = create_test_registry();
+        let raw = serde_json::to_vec(&create_test_schema()).unwrap();
+        let schema = registry.register(&raw).await.unwrap();
+
+        // The compacted block hides attributes the schema would reject
+        let invalid = create_test_credential(&schema.said, json!({"d": "", "rank": 1}));
+        let mut compacted: Value = serde_json::from_slice(&invalid).unwrap();
+        compacted["a"] = compacted["a"]["d"].clone();
+        let raw = serde_json::to_vec(&compacted).unwrap();
+
+        let (_, validation) = registry.validate_credential(&raw).await.unwrap();
+        assert_eq!(validation, SchemaValidation::AttributesUnverified);
+    }
+
+    #[tokio::test]
+    async fn test_validate_credential_unknown_schema() {
+        let registry = create_test_registry();
+        let credential = create_test_credential(
+            "EUnknown_Schema_00000000000000000000000000000",
+            json!({"d": "", "role": "member"}),
+        );
+
+        let result = registry.validate_credential(&credential).await;
+        assert!(matches!(result, Err(WitnessError::Validation(_))));
+    }
+}
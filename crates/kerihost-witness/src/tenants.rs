@@ -0,0 +1,362 @@
+//! Witnesses for the tenants of a shared deployment
+//!
+//! One deployment can host many witnesses, each with its own seed, over a
+//! single set of tables. [`WitnessFactory`] scopes the database to each
+//! tenant's namespace (see `kerihost_db::TenantStore`) and builds that
+//! tenant's witness the first time it is asked for.
+//!
+//! Seeds never appear in the tenant configuration itself: each tenant names
+//! a Secrets Manager secret holding its seed, loaded once when the factory
+//! is created.
+
+use crate::config::WitnessConfig;
+use crate::error::{WitnessError, WitnessResult};
+use crate::witness::Witness;
+use async_trait::async_trait;
+use aws_sdk_secretsmanager::error::DisplayErrorContext;
+use cesride::Signer;
+use kerihost_db::{validate_tenant, TenantStore, WitnessDatabase};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::sync::{Arc, Mutex};
+
+/// Request header naming the tenant a request is for
+pub const TENANT_HEADER: &str = "x-kerihost-tenant";
+
+/// Identity of one tenant's witness
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct TenantConfig {
+    /// Name or ARN of the secret holding the qualified Base64 Ed25519 seed
+    /// of the witness signer
+    ///
+    /// Without one the witness uses the deployment's prefix and cannot
+    /// issue receipts.
+    #[serde(default)]
+    pub seed_secret: Option<String>,
+
+    /// Public URL, in place of the deployment's
+    #[serde(default)]
+    pub public_url: Option<String>,
+}
+
+/// Store of secret strings, by name or ARN
+#[async_trait]
+pub trait SecretSource: Send + Sync {
+    /// Get the string value of a secret
+    async fn secret_string(&self, id: &str) -> WitnessResult<String>;
+}
+
+#[async_trait]
+impl SecretSource for aws_sdk_secretsmanager::Client {
+    async fn secret_string(&self, id: &str) -> WitnessResult<String> {
+        let output = self
+            .get_secret_value()
+            .secret_id(id)
+            .send()
+            .await
+            .map_err(|e| {
+                WitnessError::Config(format!(
+                    "Cannot read secret {}: {}",
+                    id,
+                    DisplayErrorContext(e)
+                ))
+            })?;
+        output
+            .secret_string
+            .ok_or_else(|| WitnessError::Config(format!("Secret {} has no string value", id)))
+    }
+}
+
+/// A configured tenant, with its seed loaded
+#[derive(Debug, Clone, Default)]
+struct Tenant {
+    /// Qualified Base64 Ed25519 seed of the witness signer
+    seed: Option<String>,
+    /// Public URL, in place of the deployment's
+    public_url: Option<String>,
+}
+
+/// Builds one witness per tenant over a shared database
+///
+/// Requests without a tenant go to the default namespace's witness, built
+/// from the deployment's config alone.
+pub struct WitnessFactory<D: WitnessDatabase> {
+    /// Unscoped database
+    db: Arc<D>,
+    /// Deployment configuration
+    config: WitnessConfig,
+    /// Configured tenants
+    tenants: BTreeMap<String, Tenant>,
+    /// Witnesses built so far, by tenant
+    witnesses: Mutex<HashMap<Option<String>, Arc<Witness<D>>>>,
+}
+
+impl<D: WitnessDatabase + TenantStore> WitnessFactory<D> {
+    /// Create a factory over an unscoped database
+    pub fn new(db: D, config: WitnessConfig) -> Self {
+        WitnessFactory {
+            db: Arc::new(db),
+            config,
+            tenants: BTreeMap::new(),
+            witnesses: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Create with the tenants configured in the environment
+    ///
+    /// `WITNESS_TENANTS` holds a JSON object from tenant name to
+    /// `TenantConfig`. Without it the factory has no tenants. Seeds are
+    /// read from AWS Secrets Manager.
+    pub async fn from_env(db: D, config: WitnessConfig) -> WitnessResult<Self> {
+        let tenants: BTreeMap<String, TenantConfig> = match std::env::var("WITNESS_TENANTS") {
+            Ok(json) => serde_json::from_str(&json)
+                .map_err(|e| WitnessError::Config(format!("Invalid WITNESS_TENANTS: {}", e)))?,
+            Err(_) => BTreeMap::new(),
+        };
+
+        let aws_config = aws_config::defaults(aws_config::BehaviorVersion::latest())
+            .load()
+            .await;
+        let secrets = aws_sdk_secretsmanager::Client::new(&aws_config);
+        Self::with_tenants(db, config, tenants, &secrets).await
+    }
+
+    /// Create with `tenants`, loading each tenant's seed from `secrets`
+    pub async fn with_tenants<S: SecretSource>(
+        db: D,
+        config: WitnessConfig,
+        tenants: BTreeMap<String, TenantConfig>,
+        secrets: &S,
+    ) -> WitnessResult<Self> {
+        let mut factory = Self::new(db, config);
+        for (tenant, tenant_config) in tenants {
+            validate_tenant(&tenant).map_err(|e| WitnessError::Config(e.to_string()))?;
+            let seed = match tenant_config.seed_secret {
+                Some(ref id) => Some(secrets.secret_string(id).await?.trim().to_string()),
+                None => None,
+            };
+            factory = factory.with_tenant(&tenant, seed, tenant_config.public_url);
+        }
+        Ok(factory)
+    }
+
+    /// Add a tenant with its seed, if it signs receipts, and public URL
+    pub fn with_tenant(
+        mut self,
+        tenant: &str,
+        seed: Option<String>,
+        public_url: Option<String>,
+    ) -> Self {
+        self.tenants
+            .insert(tenant.to_string(), Tenant { seed, public_url });
+        self
+    }
+
+    /// Names of the configured tenants, in order
+    pub fn tenants(&self) -> impl Iterator<Item = &str> {
+        self.tenants.keys().map(String::as_str)
+    }
+
+    /// Get the witness for a tenant, or for the default namespace
+    ///
+    /// Fails with `WitnessError::UnknownTenant` for a tenant that is not
+    /// configured, so requests can never open a namespace of their own.
+    pub fn witness(&self, tenant: Option<&str>) -> WitnessResult<Arc<Witness<D>>> {
+        let key = tenant.map(str::to_string);
+        if let Some(witness) = self.lock().get(&key) {
+            return Ok(Arc::clone(witness));
+        }
+
+        let witness = Arc::new(self.build(tenant)?);
+        // A racing build for the same tenant is identical; keep the first
+        let mut witnesses = self.lock();
+        Ok(Arc::clone(witnesses.entry(key).or_insert(witness)))
+    }
+
+    /// Build a tenant's witness over its namespace
+    fn build(&self, tenant: Option<&str>) -> WitnessResult<Witness<D>> {
+        let Some(tenant) = tenant else {
+            return Ok(Witness::new(
+                None,
+                Arc::clone(&self.db),
+                self.config.clone(),
+            ));
+        };
+        let configured = self
+            .tenants
+            .get(tenant)
+            .ok_or_else(|| WitnessError::UnknownTenant(tenant.to_string()))?;
+
+        let signer = configured
+            .seed
+            .as_deref()
+            .map(|seed| Signer::new_with_qb64(seed, Some(false)))
+            .transpose()
+            .map_err(|e| WitnessError::Config(format!("Invalid seed for {}: {}", tenant, e)))?;
+        let mut config = self.config.clone();
+        if let Some(ref public_url) = configured.public_url {
+            config.public_url = public_url.clone();
+        }
+
+        let db = Arc::new(self.db.for_tenant(tenant)?);
+        Ok(Witness::new(signer, db, config))
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, HashMap<Option<String>, Arc<Witness<D>>>> {
+        self.witnesses.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::processor::ProcessResult;
+    use cesride::Matter;
+    use futures::StreamExt;
+    use kerihost_core::test_util::{inception, signer};
+    use kerihost_db::InMemoryDatabase;
+
+    fn seed(byte: u8) -> String {
+        Signer::new_with_raw(&[byte; 32], Some(false), None)
+            .unwrap()
+            .qb64()
+            .unwrap()
+    }
+
+    fn create_test_factory() -> WitnessFactory<InMemoryDatabase> {
+        let config =
+            WitnessConfig::new("BTest123".to_string(), "https://test.keri.host".to_string());
+        WitnessFactory::new(InMemoryDatabase::new(), config)
+            .with_tenant(
+                "alpha",
+                Some(seed(1)),
+                Some("https://alpha.keri.host".to_string()),
+            )
+            .with_tenant("beta", Some(seed(2)), None)
+    }
+
+    #[test]
+    fn test_factory_builds_each_tenant_once() {
+        let factory = create_test_factory();
+        assert_eq!(factory.tenants().collect::<Vec<_>>(), vec!["alpha", "beta"]);
+
+        let alpha = factory.witness(Some("alpha")).unwrap();
+        let beta = factory.witness(Some("beta")).unwrap();
+        let default = factory.witness(None).unwrap();
+        assert!(Arc::ptr_eq(
+            &alpha,
+            &factory.witness(Some("alpha")).unwrap()
+        ));
+        assert_ne!(alpha.prefix, beta.prefix);
+        assert_eq!(default.prefix, "BTest123");
+        assert_eq!(alpha.introduce_url(), "https://alpha.keri.host/introduce");
+        assert_eq!(beta.introduce_url(), "https://test.keri.host/introduce");
+
+        assert!(matches!(
+            factory.witness(Some("gamma")),
+            Err(WitnessError::UnknownTenant(_))
+        ));
+    }
+
+    #[test]
+    fn test_factory_rejects_bad_seed() {
+        let factory =
+            create_test_factory().with_tenant("broken", Some("not a seed".to_string()), None);
+        assert!(matches!(
+            factory.witness(Some("broken")),
+            Err(WitnessError::Config(_))
+        ));
+    }
+
+    /// Secrets held in memory
+    struct StaticSecrets(BTreeMap<String, String>);
+
+    #[async_trait]
+    impl SecretSource for StaticSecrets {
+        async fn secret_string(&self, id: &str) -> WitnessResult<String> {
+            self.0
+                .get(id)
+                .cloned()
+                .ok_or_else(|| WitnessError::Config(format!("No secret {}", id)))
+        }
+    }
+
+    #[tokio::test]
+    async fn test_factory_loads_seeds_from_secrets() {
+        let config =
+            WitnessConfig::new("BTest123".to_string(), "https://test.keri.host".to_string());
+        let secrets = StaticSecrets(BTreeMap::from([(
+            "kerihost/tenants/alpha".to_string(),
+            format!("{}\n", seed(1)),
+        )]));
+        let tenants: BTreeMap<String, TenantConfig> = serde_json::from_str(
+            r#"{"alpha": {"seed_secret": "kerihost/tenants/alpha"}, "beta": {}}"#,
+        )
+        .unwrap();
+
+        let factory = WitnessFactory::with_tenants(
+            InMemoryDatabase::new(),
+            config.clone(),
+            tenants,
+            &secrets,
+        )
+        .await
+        .unwrap();
+        let alpha = factory.witness(Some("alpha")).unwrap();
+        assert_eq!(
+            alpha.prefix,
+            create_test_factory().witness(Some("alpha")).unwrap().prefix
+        );
+        assert_eq!(factory.witness(Some("beta")).unwrap().prefix, "BTest123");
+
+        // A secret that cannot be read fails the whole configuration
+        let tenants = BTreeMap::from([(
+            "gamma".to_string(),
+            TenantConfig {
+                seed_secret: Some("kerihost/tenants/gamma".to_string()),
+                public_url: None,
+            },
+        )]);
+        let result =
+            WitnessFactory::with_tenants(InMemoryDatabase::new(), config, tenants, &secrets).await;
+        assert!(matches!(result, Err(WitnessError::Config(_))));
+    }
+
+    #[test]
+    fn test_tenant_config_rejects_plaintext_seed() {
+        let result = serde_json::from_str::<TenantConfig>(&format!(r#"{{"seed": "{}"}}"#, seed(1)));
+        assert!(result.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_tenant_witnesses_are_isolated() {
+        let factory = create_test_factory();
+        let alpha = factory.witness(Some("alpha")).unwrap();
+        let beta = factory.witness(Some("beta")).unwrap();
+
+        let icp = inception(&signer(7), &[alpha.prefix.as_str()]);
+        let result = alpha.process_notice(&icp.to_cesr().unwrap()).await.unwrap();
+        assert!(matches!(result, ProcessResult::Accepted { .. }));
+
+        let prefix = &icp.event.prefix;
+        assert!(beta.get_state(prefix).await.unwrap().is_none());
+        assert!(factory
+            .witness(None)
+            .unwrap()
+            .get_state(prefix)
+            .await
+            .unwrap()
+            .is_none());
+
+        // Each tenant exports only its own KELs
+        let exported: Vec<Vec<u8>> = alpha.export(10).map(|m| m.unwrap()).collect().await;
+        assert_eq!(exported.len(), 1);
+        assert_eq!(beta.export(10).count().await, 0);
+
+        // and an export imports into another tenant's namespace
+        let summary = beta.import(&exported.concat()).await.unwrap();
+        assert_eq!(summary.accepted, 1);
+        assert_eq!(beta.get_state(prefix).await.unwrap().unwrap().sn, 0);
+    }
+}
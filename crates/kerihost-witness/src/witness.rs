@@ -1,13 +1,46 @@
 //! Core Witness implementation
 
 use crate::config::WitnessConfig;
+use crate::credentials::StoredCredentialResolver;
 use crate::error::{WitnessError, WitnessResult};
+use crate::ipex::IpexProcessor;
 use crate::processor::{EventProcessor, ProcessResult};
+use crate::schema::SchemaRegistry;
 use cesride::{Matter, Signer};
-use kerihost_core::{KeyState, NontransferableReceipt, SignedEvent};
-use kerihost_db::{EscrowedEvent, WitnessDatabase};
+use chrono::{DateTime, Utc};
+use futures::stream::{Stream, StreamExt, TryStreamExt};
+use kerihost_core::{
+    ChainVerifier, KeyState, NontransferableReceipt, ProofNode, Receipt, ReplayEvent,
+    SignedCredential, SignedEvent, DEFAULT_MAX_CHAIN_DEPTH,
+};
+use kerihost_db::history::StateHistory;
+use kerihost_db::{
+    stream, AnchorLocation, DbError, EscrowedEvent, ExchangeStore, Page, ReceiptConflict,
+    SchemaStore, WitnessDatabase,
+};
 use std::sync::Arc;
 
+/// Escrowed events fetched per page when collecting them all
+const ESCROW_PAGE_SIZE: usize = 100;
+
+/// Counts from importing a replay stream
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ImportSummary {
+    /// Events accepted into a KEL
+    pub accepted: usize,
+    /// Events already in their KEL
+    pub duplicates: usize,
+    /// Events escrowed rather than accepted
+    pub escrowed: usize,
+    /// Receipts verified and stored
+    pub receipts: usize,
+    /// Receipts dropped because their signature did not verify
+    pub invalid_receipts: usize,
+    /// Verified receipts rejected because their witness already receipted
+    /// the event with a different signature
+    pub conflicting_receipts: usize,
+}
+
 /// KERI Witness
 ///
 /// Processes key events, validates them, stores them, and issues receipts.
@@ -22,6 +55,8 @@ pub struct Witness<D: WitnessDatabase> {
     config: WitnessConfig,
     /// Event processor
     processor: EventProcessor<D>,
+    /// Historical key states
+    history: StateHistory<D>,
 }
 
 impl<D: WitnessDatabase> Witness<D> {
@@ -37,7 +72,10 @@ impl<D: WitnessDatabase> Witness<D> {
             Arc::clone(&db),
             config.strict_validation,
             prefix.clone(),
-        );
+        )
+        .with_escrow_policy(config.escrow_policy());
+
+        let history = StateHistory::new(Arc::clone(&db));
 
         Witness {
             prefix,
@@ -45,6 +83,7 @@ impl<D: WitnessDatabase> Witness<D> {
             db,
             config,
             processor,
+            history,
         }
     }
 
@@ -100,7 +139,35 @@ impl<D: WitnessDatabase> Witness<D> {
         }
     }
 
+    /// Get key state for an identifier as of the event at `sn`
+    pub async fn get_state_at(&self, prefix: &str, sn: u64) -> WitnessResult<Option<KeyState>> {
+        let state = self.history.get_state_at(prefix, sn).await?;
+        self.with_receipts(state).await
+    }
+
+    /// Get key state for an identifier as this witness knew it at `datetime`
+    pub async fn get_state_as_of(
+        &self,
+        prefix: &str,
+        datetime: DateTime<Utc>,
+    ) -> WitnessResult<Option<KeyState>> {
+        let state = self.history.get_state_as_of(prefix, datetime).await?;
+        self.with_receipts(state).await
+    }
+
+    /// Enrich a state with the receipt count of its latest event
+    async fn with_receipts(&self, state: Option<KeyState>) -> WitnessResult<Option<KeyState>> {
+        match state {
+            Some(state) => {
+                let receipt_count = self.db.count_receipts(&state.latest_digest).await?;
+                Ok(Some(state.with_receipts(receipt_count as u32)))
+            }
+            None => Ok(None),
+        }
+    }
+
     /// Get KEL events for an identifier
+    #[deprecated(note = "loads the whole range at once; use `get_kel_page`")]
     pub async fn get_kel(
         &self,
         prefix: &str,
@@ -110,6 +177,21 @@ impl<D: WitnessDatabase> Witness<D> {
         Ok(self.db.get_events(prefix, start, end).await?)
     }
 
+    /// Get up to `limit` KEL events, continuing from a previous page's `next`
+    pub async fn get_kel_page(
+        &self,
+        prefix: &str,
+        start: u64,
+        end: Option<u64>,
+        limit: usize,
+        cursor: Option<&str>,
+    ) -> WitnessResult<Page<SignedEvent>> {
+        Ok(self
+            .db
+            .get_events_page(prefix, start, end, limit, cursor)
+            .await?)
+    }
+
     /// Get receipts for an event
     pub async fn get_receipts(
         &self,
@@ -118,11 +200,111 @@ impl<D: WitnessDatabase> Witness<D> {
         Ok(self.db.get_receipts(event_digest).await?)
     }
 
+    /// Get the receipt conflicts recorded against a witness
+    pub async fn get_receipt_conflicts(
+        &self,
+        witness_prefix: &str,
+    ) -> WitnessResult<Vec<ReceiptConflict>> {
+        Ok(self.db.get_receipt_conflicts(witness_prefix).await?)
+    }
+
+    /// Find the event that anchors a seal digest
+    pub async fn find_anchor(&self, digest: &str) -> WitnessResult<Option<AnchorLocation>> {
+        Ok(self.db.find_anchor(digest).await?)
+    }
+
+    /// Find the event that anchors the event seal `(i, s, d)`
+    pub async fn find_event_seal(
+        &self,
+        prefix: &str,
+        sn: u64,
+        digest: &str,
+    ) -> WitnessResult<Option<AnchorLocation>> {
+        Ok(self.db.find_event_seal(prefix, sn, digest).await?)
+    }
+
+    /// Export every KEL as a CESR replay stream
+    ///
+    /// Yields one message per event, each KEL in first-seen order, carrying
+    /// the controller signatures, stored receipts and first-seen data.
+    /// Concatenated, the messages are a stream `import` accepts.
+    pub fn export(
+        &self,
+        page_size: usize,
+    ) -> impl Stream<Item = WitnessResult<Vec<u8>>> + Send + '_ {
+        stream::replay_all(self.db.as_ref(), page_size).map(|replayed| Ok(replayed?.to_cesr()?))
+    }
+
+    /// Import a CESR replay stream, such as one from `export`
+    ///
+    /// Every event is validated as a fresh one would be and keeps the
+    /// first-seen datetime the stream carries. Receipts are stored only once
+    /// their signature verifies and their event is in the KEL; one that
+    /// conflicts with a stored receipt is recorded as such. Stops at the
+    /// first event that fails to parse or validate; events before it stay
+    /// imported.
+    pub async fn import(&self, stream: &[u8]) -> WitnessResult<ImportSummary> {
+        let mut summary = ImportSummary::default();
+        for replayed in ReplayEvent::parse_stream(stream) {
+            let replayed = replayed?;
+            let event = &replayed.signed;
+            let first_seen_at = match replayed.first_seen {
+                Some(ref first_seen) => first_seen.datetime.clone(),
+                None => Utc::now().to_rfc3339(),
+            };
+
+            let result = self
+                .processor
+                .process_replayed(event, &first_seen_at)
+                .await
+                .map_err(|e| {
+                    WitnessError::Validation(format!(
+                        "Import stopped at {} sn {}: {}",
+                        event.event.prefix, event.event.sn, e
+                    ))
+                })?;
+            match result {
+                ProcessResult::Accepted { .. } => summary.accepted += 1,
+                ProcessResult::Duplicate => summary.duplicates += 1,
+                ProcessResult::Escrowed { .. } => {
+                    summary.escrowed += 1;
+                    continue;
+                }
+            }
+
+            // A duplicate sn may carry a different event than the stored one
+            let stored = self
+                .db
+                .get_event_by_digest(&event.event.prefix, &event.event.digest)
+                .await?;
+            if stored.is_none() {
+                continue;
+            }
+            for receipt in &replayed.receipts {
+                if !receipt.verify(&event.event.raw).unwrap_or(false) {
+                    summary.invalid_receipts += 1;
+                    continue;
+                }
+                match self.db.add_receipt(receipt).await {
+                    Ok(()) => summary.receipts += 1,
+                    Err(DbError::ConflictingReceipt { .. }) => summary.conflicting_receipts += 1,
+                    Err(e) => return Err(e.into()),
+                }
+            }
+        }
+        Ok(summary)
+    }
+
     /// Get OOBI URL for this witness
     pub fn oobi_url(&self) -> String {
         format!("{}/oobi/{}", self.config.public_url, self.prefix)
     }
 
+    /// Get data OOBI URL for a schema served by this witness
+    pub fn schema_oobi_url(&self, said: &str) -> String {
+        format!("{}/oobi/{}", self.config.public_url, said)
+    }
+
     /// Get introduction URL
     pub fn introduce_url(&self) -> String {
         format!("{}/introduce", self.config.public_url)
@@ -145,9 +327,35 @@ impl<D: WitnessDatabase> Witness<D> {
         }
     }
 
+    /// Get up to `limit` escrowed events for a prefix, continuing from a
+    /// previous page's `next` (for change-triggered processing)
+    pub async fn get_escrowed_by_prefix(
+        &self,
+        prefix: &str,
+        limit: usize,
+        cursor: Option<&str>,
+    ) -> WitnessResult<Page<EscrowedEvent>> {
+        Ok(self
+            .db
+            .get_escrowed_by_prefix(prefix, limit, cursor)
+            .await?)
+    }
+
     /// Get all escrowed events (for scheduled processing)
+    #[deprecated(note = "loads every escrow at once; use `get_escrowed_page`")]
     pub async fn get_all_escrowed(&self) -> WitnessResult<Vec<EscrowedEvent>> {
-        Ok(self.db.get_all_escrowed().await?)
+        Ok(stream::escrowed_events(self.db.as_ref(), ESCROW_PAGE_SIZE)
+            .try_collect()
+            .await?)
+    }
+
+    /// Get up to `limit` escrowed events, continuing from a previous page's `next`
+    pub async fn get_escrowed_page(
+        &self,
+        limit: usize,
+        cursor: Option<&str>,
+    ) -> WitnessResult<Page<EscrowedEvent>> {
+        Ok(self.db.get_escrowed_page(limit, cursor).await?)
     }
 
     /// Check if an escrowed event can be promoted
@@ -206,17 +414,69 @@ impl<D: WitnessDatabase> Witness<D> {
         }
     }
 
+    /// Record a failed promotion attempt, backing off the next one
+    ///
+    /// Returns the escrow with its updated schedule.
+    pub async fn record_escrow_attempt(
+        &self,
+        escrowed: &EscrowedEvent,
+    ) -> WitnessResult<EscrowedEvent> {
+        let mut escrowed = escrowed.clone();
+        escrowed.record_attempt(&self.config.escrow_policy());
+        self.db.record_escrow_attempt(&escrowed).await?;
+        Ok(escrowed)
+    }
+
     /// Remove an escrowed event
     pub async fn remove_escrowed(&self, event_digest: &str) -> WitnessResult<()> {
         Ok(self.db.remove_escrowed(event_digest).await?)
     }
+
+    /// Verify a credential and the chain of credentials it links to
+    ///
+    /// `presented` holds the linked credentials disclosed with it. Each
+    /// credential in the chain must be anchored in its issuer's stored KEL
+    /// and signed by the issuer's keys at the anchoring event.
+    pub async fn verify_credential(
+        &self,
+        credential: &SignedCredential,
+        presented: &[SignedCredential],
+    ) -> WitnessResult<ProofNode> {
+        let resolver = StoredCredentialResolver::load(
+            self.db.as_ref(),
+            &self.history,
+            credential,
+            presented,
+            DEFAULT_MAX_CHAIN_DEPTH,
+        )
+        .await?;
+        Ok(ChainVerifier::new(&resolver).verify(&credential.credential))
+    }
+}
+
+impl<D: WitnessDatabase + SchemaStore> Witness<D> {
+    /// Get the credential schema registry backed by this witness's database
+    pub fn schema_registry(&self) -> SchemaRegistry<D> {
+        SchemaRegistry::new(Arc::clone(&self.db))
+    }
+}
+
+impl<D: WitnessDatabase + ExchangeStore> Witness<D> {
+    /// Get the IPEX exchange processor backed by this witness's database
+    pub fn ipex(&self) -> IpexProcessor<D> {
+        IpexProcessor::new(Arc::clone(&self.db)).with_escrow_policy(self.config.escrow_policy())
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use kerihost_core::test_util::{inception, interaction, signer};
     use kerihost_core::{EventType, IndexedSignature, KeyEvent, Threshold};
-    use kerihost_db::InMemoryDatabase;
+    use kerihost_db::{
+        CachedDatabase, EscrowReason, FirstSeen, InMemoryDatabase, KelStore, ReceiptStore,
+        StateStore,
+    };
 
     fn create_test_db() -> Arc<InMemoryDatabase> {
         Arc::new(InMemoryDatabase::new())
@@ -255,6 +515,7 @@ mod tests {
                 index: 0,
                 signature: "AASig".to_string(),
             }],
+            attachments: Vec::new(),
         }
     }
 
@@ -299,6 +560,15 @@ mod tests {
 
         let state = witness.get_state("DNotExist").await.unwrap();
         assert!(state.is_none());
+
+        let state = witness.get_state_at("DNotExist", 0).await.unwrap();
+        assert!(state.is_none());
+
+        let state = witness
+            .get_state_as_of("DNotExist", Utc::now())
+            .await
+            .unwrap();
+        assert!(state.is_none());
     }
 
     #[tokio::test]
@@ -318,8 +588,12 @@ mod tests {
         let config = create_test_config();
         let witness: Witness<InMemoryDatabase> = Witness::new(None, db, config);
 
-        let kel = witness.get_kel("DNotExist", 0, None).await.unwrap();
-        assert!(kel.is_empty());
+        let page = witness
+            .get_kel_page("DNotExist", 0, None, 10, None)
+            .await
+            .unwrap();
+        assert!(page.items.is_empty());
+        assert!(page.next.is_none());
     }
 
     #[tokio::test]
@@ -343,4 +617,217 @@ mod tests {
 
         assert!(matches!(result, Err(WitnessError::MissingSigner)));
     }
+
+    #[tokio::test]
+    async fn test_witness_export_import_roundtrip() {
+        let source_db = create_test_db();
+        let source =
+            Witness::from_seed(&[1u8; 32], Arc::clone(&source_db), create_test_config()).unwrap();
+
+        // A real, witnessed KEL: inception plus one interaction
+        let controller = signer(7);
+        let key = controller.verfer().qb64().unwrap();
+        let icp = inception(&controller, &[source.prefix.as_str()]);
+        let ixn = interaction(&controller, 1, &icp.event.digest, serde_json::json!([]));
+        for event in [&icp, &ixn] {
+            let result = source
+                .process_notice(&event.to_cesr().unwrap())
+                .await
+                .unwrap();
+            assert!(matches!(result, ProcessResult::Accepted { .. }));
+            let receipt = source.generate_receipt(event).unwrap();
+            source_db.add_receipt(&receipt).await.unwrap();
+        }
+
+        // A stored receipt that signs the wrong event is not carried over
+        let other = Signer::new_with_raw(&[9u8; 32], Some(false), None).unwrap();
+        let mut forged = source.generate_receipt(&icp).unwrap();
+        forged.event_digest = ixn.event.digest.clone();
+        forged.event_sn = 1;
+        forged.witness_prefix = other.verfer().qb64().unwrap();
+        forged.signature = other
+            .sign_unindexed(&icp.event.raw)
+            .unwrap()
+            .qb64()
+            .unwrap();
+        source_db.add_receipt(&forged).await.unwrap();
+
+        let exported: Vec<Vec<u8>> = source.export(10).map(|m| m.unwrap()).collect().await;
+        assert_eq!(exported.len(), 2);
+        let stream = exported.concat();
+
+        let target_db = create_test_db();
+        let target =
+            Witness::from_seed(&[2u8; 32], Arc::clone(&target_db), create_test_config()).unwrap();
+        let summary = target.import(&stream).await.unwrap();
+        assert_eq!(
+            summary,
+            ImportSummary {
+                accepted: 2,
+                duplicates: 0,
+                escrowed: 0,
+                receipts: 2,
+                invalid_receipts: 1,
+                conflicting_receipts: 0,
+            }
+        );
+
+        let kel = target
+            .get_kel_page(&key, 0, None, 10, None)
+            .await
+            .unwrap()
+            .items;
+        assert_eq!(kel.len(), 2);
+        assert_eq!(kel[1].event.digest, ixn.event.digest);
+        assert_eq!(target.get_state(&key).await.unwrap().unwrap().sn, 1);
+        let receipts = target.get_receipts(&ixn.event.digest).await.unwrap();
+        assert_eq!(receipts.len(), 1);
+        assert_eq!(receipts[0].witness_prefix, source.prefix);
+
+        // First-seen times survive to the microsecond
+        for event in [&icp, &ixn] {
+            let digest = &event.event.digest;
+            let seen = |entry: Option<FirstSeen>| {
+                DateTime::parse_from_rfc3339(&entry.unwrap().datetime).unwrap()
+            };
+            let before = seen(source_db.get_first_seen(&key, digest).await.unwrap());
+            let after = seen(target_db.get_first_seen(&key, digest).await.unwrap());
+            assert_eq!((before - after).num_microseconds(), Some(0));
+        }
+
+        // Importing again changes nothing
+        let summary = target.import(&stream).await.unwrap();
+        assert_eq!(summary.accepted, 0);
+        assert_eq!(summary.duplicates, 2);
+        let kel = target.get_kel_page(&key, 0, None, 10, None).await.unwrap();
+        assert_eq!(kel.items.len(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_witness_import_records_conflicting_receipt() {
+        let source_db = create_test_db();
+        let source =
+            Witness::from_seed(&[1u8; 32], Arc::clone(&source_db), create_test_config()).unwrap();
+        let controller = signer(7);
+        let icp = inception(&controller, &[source.prefix.as_str()]);
+        source
+            .process_notice(&icp.to_cesr().unwrap())
+            .await
+            .unwrap();
+        let receipt = source.generate_receipt(&icp).unwrap();
+        source_db.add_receipt(&receipt).await.unwrap();
+        let exported: Vec<Vec<u8>> = source.export(10).map(|m| m.unwrap()).collect().await;
+
+        // The target already holds a different receipt from the same witness
+        let target_db = create_test_db();
+        let mut stored = receipt.clone();
+        stored.signature = "0BOtherSignature".to_string();
+        target_db.add_receipt(&stored).await.unwrap();
+
+        let target =
+            Witness::from_seed(&[2u8; 32], Arc::clone(&target_db), create_test_config()).unwrap();
+        let summary = target.import(&exported.concat()).await.unwrap();
+        assert_eq!(summary.accepted, 1);
+        assert_eq!(summary.receipts, 0);
+        assert_eq!(summary.conflicting_receipts, 1);
+
+        let receipts = target.get_receipts(&icp.event.digest).await.unwrap();
+        assert_eq!(receipts[0].signature, stored.signature);
+        let conflicts = target.get_receipt_conflicts(&source.prefix).await.unwrap();
+        assert_eq!(conflicts.len(), 1);
+        assert_eq!(conflicts[0].conflicting_signature, receipt.signature);
+    }
+
+    #[tokio::test]
+    async fn test_witness_import_stops_at_invalid_event() {
+        let controller = signer(7);
+        let key = controller.verfer().qb64().unwrap();
+        let icp = inception(&controller, &[]);
+        // Chains to a prior event that is not the inception
+        let prior = "EAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAA";
+        let ixn = interaction(&controller, 1, prior, serde_json::json!([]));
+        let stream = [icp.to_cesr().unwrap(), ixn.to_cesr().unwrap()].concat();
+
+        let db = create_test_db();
+        let witness =
+            Witness::from_seed(&[2u8; 32], Arc::clone(&db), create_test_config()).unwrap();
+        let result = witness.import(&stream).await;
+        assert!(
+            matches!(result, Err(WitnessError::Validation(_))),
+            "{:?}",
+            result
+        );
+        assert_eq!(db.get_state(&key).await.unwrap().unwrap().sn, 0);
+    }
+
+    #[tokio::test]
+    async fn test_witness_escrow_ttl_and_attempts() {
+        let db = create_test_db();
+        let config = create_test_config()
+            .with_strict_validation(false)
+            .with_reason_escrow_ttl(EscrowReason::OutOfOrder, 120);
+        let witness: Witness<InMemoryDatabase> = Witness::new(None, db.clone(), config);
+
+        let now = Utc::now().timestamp() as u64;
+        let event = create_test_event("DTest123", 5, Some("EPrior".to_string()));
+        witness.processor.process_signed_event(event).await.unwrap();
+
+        let escrowed = witness
+            .get_escrowed_page(10, None)
+            .await
+            .unwrap()
+            .items
+            .remove(0);
+        assert_eq!(escrowed.reason, EscrowReason::OutOfOrder);
+        assert!(escrowed.ttl >= now + 120 && escrowed.ttl <= now + 180);
+        assert!(escrowed.is_due());
+        assert!(!witness.can_promote(&escrowed).await.unwrap());
+
+        // A failed attempt defers the next one
+        let updated = witness.record_escrow_attempt(&escrowed).await.unwrap();
+        assert_eq!(updated.attempts, 1);
+        assert!(!updated.is_due());
+        let stored = witness
+            .get_escrowed_page(10, None)
+            .await
+            .unwrap()
+            .items
+            .remove(0);
+        assert_eq!(stored.attempts, 1);
+        assert_eq!(stored.next_attempt, updated.next_attempt);
+    }
+
+    #[tokio::test]
+    async fn test_witnesses_share_backend_through_caches() {
+        // Two processes, each with its own cache over the same storage
+        let backend = InMemoryDatabase::new();
+        let first = Witness::from_seed(
+            &[1u8; 32],
+            Arc::new(CachedDatabase::new(backend.clone())),
+            create_test_config(),
+        )
+        .unwrap();
+        let second = Witness::from_seed(
+            &[1u8; 32],
+            Arc::new(CachedDatabase::new(backend.clone())),
+            create_test_config(),
+        )
+        .unwrap();
+
+        let controller = signer(7);
+        let key = controller.verfer().qb64().unwrap();
+        let icp = inception(&controller, &[first.prefix.as_str()]);
+        let ixn = interaction(&controller, 1, &icp.event.digest, serde_json::json!([]));
+
+        first.process_notice(&icp.to_cesr().unwrap()).await.unwrap();
+        assert_eq!(second.get_state(&key).await.unwrap().unwrap().sn, 0);
+        let result = first.process_notice(&ixn.to_cesr().unwrap()).await.unwrap();
+        assert!(matches!(result, ProcessResult::Accepted { .. }));
+
+        // The second cache is stale; its commit loses and the retry re-reads
+        let result = second.process_notice(&ixn.to_cesr().unwrap()).await.unwrap();
+        assert!(matches!(result, ProcessResult::Duplicate));
+        assert_eq!(second.get_state(&key).await.unwrap().unwrap().sn, 1);
+        assert_eq!(backend.event_count(&key).await, 2);
+    }
 }
@@ -14,6 +14,11 @@ const app = new cdk.App();
 const domainName = app.node.tryGetContext("domainName") || "api.keri.host";
 const hostedZoneId = app.node.tryGetContext("hostedZoneId");
 
+// Tenant witnesses, e.g. -c tenants='{"acme":{"seedSecretName":"kerihost/tenants/acme"}}'
+const tenantsContext = app.node.tryGetContext("tenants");
+const tenants =
+  typeof tenantsContext === "string" ? JSON.parse(tenantsContext) : tenantsContext;
+
 if (!hostedZoneId) {
   throw new Error(
     "hostedZoneId is required. Pass via CLI: -c hostedZoneId=Z0070723WLKQKTOACN5H"
@@ -55,6 +60,7 @@ const witnessStack = new WitnessStack(app, "KerihostWitnessStack", {
   api: apiStack.api,
   publicUrl: `${apiStack.customDomainUrl}/${basePath}`,
   basePath,
+  tenants,
   description: "KERI Host Witness Service - Lambdas and API routes",
 });
 
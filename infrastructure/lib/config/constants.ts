@@ -10,9 +10,16 @@
  */
 export const TABLE_SLUGS = {
   KEL: "kel",
+  FEL: "first-seen",
+  ANCHORS: "anchors",
   STATES: "states",
   RECEIPTS: "receipts",
-  ESCROWS: "escrows",
+  RECEIPT_CONFLICTS: "receipt-conflicts",
+  // Renamed when the escrow keys changed: a named table cannot be replaced
+  // in place, and escrows expire within their TTL so none need migrating
+  ESCROWS: "escrow-events",
+  SCHEMAS: "schemas",
+  EXCHANGES: "exchanges",
 } as const;
 
 /**
@@ -36,7 +43,9 @@ export const SECRET_NAMES = {
  * GSI names (slugs appended to table name)
  */
 export const GSI_SLUGS = {
+  ESCROWS_BY_DIGEST: "by-digest",
   ESCROWS_BY_REASON: "by-reason",
+  EXCHANGES_BY_PRIOR: "by-prior",
 } as const;
 
 /**
@@ -6,7 +6,8 @@ import { TABLE_SLUGS, GSI_SLUGS, SECRET_NAMES } from "../config/constants";
 
 /**
  * DataStack contains all persistent data resources:
- * - DynamoDB tables for KEL, states, receipts, and escrows
+ * - DynamoDB tables for KEL, first-seen log, anchored seals, states, receipts,
+ *   receipt conflicts, escrows, credential schemas, and IPEX exchanges
  * - Reference to witness seed secret
  *
  * This stack is the foundation layer that other stacks depend on.
@@ -18,9 +19,14 @@ import { TABLE_SLUGS, GSI_SLUGS, SECRET_NAMES } from "../config/constants";
 export class DataStack extends cdk.Stack {
   public readonly tables: {
     kel: dynamodb.Table;
+    fel: dynamodb.Table;
+    anchors: dynamodb.Table;
     states: dynamodb.Table;
     receipts: dynamodb.Table;
+    receiptConflicts: dynamodb.Table;
     escrows: dynamodb.Table;
+    schemas: dynamodb.Table;
+    exchanges: dynamodb.Table;
   };
 
   public readonly witnessSeed: secretsmanager.ISecret;
@@ -37,12 +43,36 @@ export class DataStack extends cdk.Stack {
 
     // KEL (Key Event Log) Table
     // PK: aid (AID/prefix), SK: sn (zero-padded sequence number)
+    // The KEL, receipts and escrows streams feed DynamoDbChangeFeed; the
+    // KEL and receipts streams also trigger the escrow check Lambda
     const kelTable = new dynamodb.Table(this, "KelTable", {
       tableName: resourceName(TABLE_SLUGS.KEL),
       partitionKey: { name: "aid", type: dynamodb.AttributeType.STRING },
       sortKey: { name: "sn", type: dynamodb.AttributeType.STRING },
       billingMode: dynamodb.BillingMode.PAY_PER_REQUEST,
       pointInTimeRecoverySpecification: { pointInTimeRecoveryEnabled: true },
+      stream: dynamodb.StreamViewType.NEW_AND_OLD_IMAGES,
+    });
+
+    // First-Seen Event Log Table
+    // PK: aid (AID/prefix), SK: fn (first-seen ordinal)
+    // Each entry also has a digest item, PK: aid (AID/prefix/digest), SK: fn = 0
+    const felTable = new dynamodb.Table(this, "FelTable", {
+      tableName: resourceName(TABLE_SLUGS.FEL),
+      partitionKey: { name: "aid", type: dynamodb.AttributeType.STRING },
+      sortKey: { name: "fn", type: dynamodb.AttributeType.NUMBER },
+      billingMode: dynamodb.BillingMode.PAY_PER_REQUEST,
+      pointInTimeRecoverySpecification: { pointInTimeRecoveryEnabled: true },
+    });
+
+    // Anchors Table (index of seals anchored in KEL events)
+    // PK: digest (seal digest), SK: location (aid#sn of anchoring event)
+    const anchorsTable = new dynamodb.Table(this, "AnchorsTable", {
+      tableName: resourceName(TABLE_SLUGS.ANCHORS),
+      partitionKey: { name: "digest", type: dynamodb.AttributeType.STRING },
+      sortKey: { name: "location", type: dynamodb.AttributeType.STRING },
+      billingMode: dynamodb.BillingMode.PAY_PER_REQUEST,
+      pointInTimeRecoverySpecification: { pointInTimeRecoveryEnabled: true },
     });
 
     // States Table (current key state for each identifier)
@@ -63,24 +93,78 @@ export class DataStack extends cdk.Stack {
       },
       sortKey: { name: "witness_aid", type: dynamodb.AttributeType.STRING },
       billingMode: dynamodb.BillingMode.PAY_PER_REQUEST,
+      stream: dynamodb.StreamViewType.NEW_AND_OLD_IMAGES,
     });
 
+    // Receipt Conflicts Table (rejected receipts: witness duplicity evidence)
+    // PK: witness_aid, SK: conflict (event_digest.conflicting_signature)
+    const receiptConflictsTable = new dynamodb.Table(
+      this,
+      "ReceiptConflictsTable",
+      {
+        tableName: resourceName(TABLE_SLUGS.RECEIPT_CONFLICTS),
+        partitionKey: {
+          name: "witness_aid",
+          type: dynamodb.AttributeType.STRING,
+        },
+        sortKey: { name: "conflict", type: dynamodb.AttributeType.STRING },
+        billingMode: dynamodb.BillingMode.PAY_PER_REQUEST,
+      }
+    );
+
     // Escrows Table (events waiting for conditions to be met)
-    // PK: aid, SK: reason#digest
+    // PK: aid, SK: sn_digest (zero-padded sn#digest, one item per event)
     // TTL enabled for automatic expiration
     const escrowsTable = new dynamodb.Table(this, "EscrowsTable", {
       tableName: resourceName(TABLE_SLUGS.ESCROWS),
       partitionKey: { name: "aid", type: dynamodb.AttributeType.STRING },
-      sortKey: { name: "reason_digest", type: dynamodb.AttributeType.STRING },
+      sortKey: { name: "sn_digest", type: dynamodb.AttributeType.STRING },
       billingMode: dynamodb.BillingMode.PAY_PER_REQUEST,
       timeToLiveAttribute: "ttl",
+      stream: dynamodb.StreamViewType.NEW_AND_OLD_IMAGES,
     });
 
-    // Global Secondary Index for escrows - to query all escrows regardless of aid
+    // GSI for escrows by event digest - promotion and removal look up the
+    // table key here instead of scanning
+    escrowsTable.addGlobalSecondaryIndex({
+      indexName: `${TABLE_SLUGS.ESCROWS}-${GSI_SLUGS.ESCROWS_BY_DIGEST}`,
+      partitionKey: { name: "digest", type: dynamodb.AttributeType.STRING },
+      projectionType: dynamodb.ProjectionType.KEYS_ONLY,
+    });
+
+    // GSI for escrows by reason, soonest-expiring first
     escrowsTable.addGlobalSecondaryIndex({
       indexName: `${TABLE_SLUGS.ESCROWS}-${GSI_SLUGS.ESCROWS_BY_REASON}`,
       partitionKey: { name: "reason", type: dynamodb.AttributeType.STRING },
-      sortKey: { name: "created", type: dynamodb.AttributeType.NUMBER },
+      sortKey: { name: "ttl", type: dynamodb.AttributeType.NUMBER },
+      projectionType: dynamodb.ProjectionType.ALL,
+    });
+
+    // Schemas Table (SAIDified credential schemas)
+    // PK: said (schema SAID, content-addressed so items are immutable)
+    const schemasTable = new dynamodb.Table(this, "SchemasTable", {
+      tableName: resourceName(TABLE_SLUGS.SCHEMAS),
+      partitionKey: { name: "said", type: dynamodb.AttributeType.STRING },
+      billingMode: dynamodb.BillingMode.PAY_PER_REQUEST,
+      pointInTimeRecoverySpecification: { pointInTimeRecoveryEnabled: true },
+    });
+
+    // Exchanges Table (IPEX exchanges, messages, and escrowed messages)
+    // PK: said, SK: kind (exchange | message | escrow)
+    // TTL enabled for escrowed message expiration
+    const exchangesTable = new dynamodb.Table(this, "ExchangesTable", {
+      tableName: resourceName(TABLE_SLUGS.EXCHANGES),
+      partitionKey: { name: "said", type: dynamodb.AttributeType.STRING },
+      sortKey: { name: "kind", type: dynamodb.AttributeType.STRING },
+      billingMode: dynamodb.BillingMode.PAY_PER_REQUEST,
+      timeToLiveAttribute: "ttl",
+    });
+
+    // Sparse GSI - only escrowed messages carry a prior
+    exchangesTable.addGlobalSecondaryIndex({
+      indexName: `${TABLE_SLUGS.EXCHANGES}-${GSI_SLUGS.EXCHANGES_BY_PRIOR}`,
+      partitionKey: { name: "prior", type: dynamodb.AttributeType.STRING },
+      sortKey: { name: "said", type: dynamodb.AttributeType.STRING },
       projectionType: dynamodb.ProjectionType.ALL,
     });
 
@@ -101,9 +185,14 @@ export class DataStack extends cdk.Stack {
 
     this.tables = {
       kel: kelTable,
+      fel: felTable,
+      anchors: anchorsTable,
       states: statesTable,
       receipts: receiptsTable,
+      receiptConflicts: receiptConflictsTable,
       escrows: escrowsTable,
+      schemas: schemasTable,
+      exchanges: exchangesTable,
     };
 
     this.witnessSeed = witnessSeedSecret;
@@ -118,6 +207,18 @@ export class DataStack extends cdk.Stack {
       exportName: `${this.stackName}-KelTableName`,
     });
 
+    new cdk.CfnOutput(this, "FelTableName", {
+      value: felTable.tableName,
+      description: "DynamoDB table for First-Seen Event Log",
+      exportName: `${this.stackName}-FelTableName`,
+    });
+
+    new cdk.CfnOutput(this, "AnchorsTableName", {
+      value: anchorsTable.tableName,
+      description: "DynamoDB table for Anchored Seals",
+      exportName: `${this.stackName}-AnchorsTableName`,
+    });
+
     new cdk.CfnOutput(this, "StatesTableName", {
       value: statesTable.tableName,
       description: "DynamoDB table for Key States",
@@ -130,10 +231,28 @@ export class DataStack extends cdk.Stack {
       exportName: `${this.stackName}-ReceiptsTableName`,
     });
 
+    new cdk.CfnOutput(this, "ReceiptConflictsTableName", {
+      value: receiptConflictsTable.tableName,
+      description: "DynamoDB table for Witness Receipt Conflicts",
+      exportName: `${this.stackName}-ReceiptConflictsTableName`,
+    });
+
     new cdk.CfnOutput(this, "EscrowsTableName", {
       value: escrowsTable.tableName,
       description: "DynamoDB table for Escrowed Events",
       exportName: `${this.stackName}-EscrowsTableName`,
     });
+
+    new cdk.CfnOutput(this, "SchemasTableName", {
+      value: schemasTable.tableName,
+      description: "DynamoDB table for Credential Schemas",
+      exportName: `${this.stackName}-SchemasTableName`,
+    });
+
+    new cdk.CfnOutput(this, "ExchangesTableName", {
+      value: exchangesTable.tableName,
+      description: "DynamoDB table for IPEX Exchanges",
+      exportName: `${this.stackName}-ExchangesTableName`,
+    });
   }
 }
@@ -2,6 +2,8 @@ import * as cdk from "aws-cdk-lib";
 import * as dynamodb from "aws-cdk-lib/aws-dynamodb";
 import * as events from "aws-cdk-lib/aws-events";
 import * as targets from "aws-cdk-lib/aws-events-targets";
+import * as lambda from "aws-cdk-lib/aws-lambda";
+import * as lambdaEventSources from "aws-cdk-lib/aws-lambda-event-sources";
 import * as apigateway from "aws-cdk-lib/aws-apigateway";
 import * as secretsmanager from "aws-cdk-lib/aws-secretsmanager";
 import { Construct } from "constructs";
@@ -9,15 +11,37 @@ import { RustFunction } from "cargo-lambda-cdk";
 import * as path from "path";
 import { LAMBDA_SLUGS } from "../config/constants";
 
+/**
+ * One tenant witness hosted by the deployment
+ */
+export interface WitnessTenant {
+  /**
+   * Name of the Secrets Manager secret holding the tenant's witness seed
+   * (must be created manually before deployment). Without one the tenant
+   * cannot issue receipts.
+   */
+  seedSecretName?: string;
+
+  /**
+   * Public URL, in place of the deployment's
+   */
+  publicUrl?: string;
+}
+
 export interface WitnessStackProps extends cdk.StackProps {
   /**
    * DynamoDB tables from DataStack
    */
   tables: {
     kel: dynamodb.ITable;
+    fel: dynamodb.ITable;
+    anchors: dynamodb.ITable;
     states: dynamodb.ITable;
     receipts: dynamodb.ITable;
+    receiptConflicts: dynamodb.ITable;
     escrows: dynamodb.ITable;
+    schemas: dynamodb.ITable;
+    exchanges: dynamodb.ITable;
   };
 
   /**
@@ -39,6 +63,11 @@ export interface WitnessStackProps extends cdk.StackProps {
    * Base path for witness routes (e.g., 'witness')
    */
   basePath: string;
+
+  /**
+   * Tenant witnesses, by tenant name
+   */
+  tenants?: Record<string, WitnessTenant>;
 }
 
 /**
@@ -46,6 +75,7 @@ export interface WitnessStackProps extends cdk.StackProps {
  * - 4 Rust Lambda functions (process, query, oobi, escrow-check)
  * - API routes under /{basePath}/...
  * - EventBridge schedule for escrow processing
+ * - KEL and receipts stream triggers for escrow processing
  *
  * API Gateway and DNS are managed by ApiStack.
  * Resource names are derived from stack name: {StackName}-{slug}
@@ -54,7 +84,7 @@ export class WitnessStack extends cdk.Stack {
   constructor(scope: Construct, id: string, props: WitnessStackProps) {
     super(scope, id, props);
 
-    const { tables, witnessSeed, api, publicUrl, basePath } = props;
+    const { tables, witnessSeed, api, publicUrl, basePath, tenants = {} } = props;
 
     // Helper to create resource name: {StackName}-{slug}
     const resourceName = (slug: string) => `${this.stackName}-${slug}`;
@@ -65,13 +95,30 @@ export class WitnessStack extends cdk.Stack {
 
     const lambdaEnv = {
       KEL_TABLE: tables.kel.tableName,
+      FEL_TABLE: tables.fel.tableName,
+      ANCHORS_TABLE: tables.anchors.tableName,
       STATES_TABLE: tables.states.tableName,
       RECEIPTS_TABLE: tables.receipts.tableName,
+      RECEIPT_CONFLICTS_TABLE: tables.receiptConflicts.tableName,
       ESCROWS_TABLE: tables.escrows.tableName,
+      SCHEMAS_TABLE: tables.schemas.tableName,
+      EXCHANGES_TABLE: tables.exchanges.tableName,
       WITNESS_PREFIX: "BWitness_Kerihost_001", // Default prefix if no signer
       PUBLIC_URL: publicUrl,
       STRICT_VALIDATION: "false", // Lenient mode by default
       RUST_LOG: "info",
+      // Tenants name their seed secrets; seeds are read at Lambda init
+      WITNESS_TENANTS: JSON.stringify(
+        Object.fromEntries(
+          Object.entries(tenants).map(([name, tenant]) => [
+            name,
+            {
+              seed_secret: tenant.seedSecretName,
+              public_url: tenant.publicUrl,
+            },
+          ])
+        )
+      ),
     };
 
     // Path to workspace root (relative to infrastructure)
@@ -81,7 +128,7 @@ export class WitnessStack extends cdk.Stack {
     // Lambda Functions (Rust via cargo-lambda-cdk)
     // =======================================================================
 
-    // Process Lambda - POST /process
+    // Process Lambda - POST /process, POST /schema, POST /exchange
     const processLambda = new RustFunction(this, "ProcessLambda", {
       manifestPath: path.join(workspaceRoot, "Cargo.toml"),
       binaryName: "witness-process",
@@ -114,7 +161,7 @@ export class WitnessStack extends cdk.Stack {
       architecture: cdk.aws_lambda.Architecture.X86_64,
     });
 
-    // Escrow Check Lambda - Scheduled
+    // Escrow Check Lambda - Scheduled and triggered by table streams
     const escrowCheckLambda = new RustFunction(this, "EscrowCheckLambda", {
       manifestPath: path.join(workspaceRoot, "Cargo.toml"),
       binaryName: "witness-escrow-check",
@@ -131,28 +178,56 @@ export class WitnessStack extends cdk.Stack {
 
     // Process Lambda needs read/write to all tables
     tables.kel.grantReadWriteData(processLambda);
+    tables.fel.grantReadWriteData(processLambda);
+    tables.anchors.grantReadWriteData(processLambda);
     tables.states.grantReadWriteData(processLambda);
     tables.receipts.grantReadWriteData(processLambda);
+    tables.receiptConflicts.grantReadWriteData(processLambda);
     tables.escrows.grantReadWriteData(processLambda);
+    tables.schemas.grantReadWriteData(processLambda);
+    tables.exchanges.grantReadWriteData(processLambda);
 
     // Query Lambda only needs read access
     tables.kel.grantReadData(queryLambda);
+    tables.fel.grantReadData(queryLambda);
+    tables.anchors.grantReadData(queryLambda);
     tables.states.grantReadData(queryLambda);
     tables.receipts.grantReadData(queryLambda);
+    tables.receiptConflicts.grantReadData(queryLambda);
 
-    // OOBI Lambda needs to read states and receipts
+    // OOBI Lambda needs to read states, receipts and schemas
     tables.states.grantReadData(oobiLambda);
     tables.receipts.grantReadData(oobiLambda);
+    tables.schemas.grantReadData(oobiLambda);
 
     // Escrow Check Lambda needs read/write to escrows and read/write to KEL/states
     tables.kel.grantReadWriteData(escrowCheckLambda);
+    tables.fel.grantReadWriteData(escrowCheckLambda);
+    tables.anchors.grantReadWriteData(escrowCheckLambda);
     tables.states.grantReadWriteData(escrowCheckLambda);
+    tables.receipts.grantReadData(escrowCheckLambda);
     tables.escrows.grantReadWriteData(escrowCheckLambda);
+    tables.exchanges.grantReadWriteData(escrowCheckLambda);
 
     // Grant secret read access to lambdas that need signing capability
     witnessSeed.grantRead(processLambda);
     witnessSeed.grantRead(escrowCheckLambda);
 
+    // Every Lambda builds tenant witnesses, whose prefixes come from their seeds
+    for (const [name, tenant] of Object.entries(tenants)) {
+      if (!tenant.seedSecretName) {
+        continue;
+      }
+      const tenantSeed = secretsmanager.Secret.fromSecretNameV2(
+        this,
+        `TenantSeed-${name}`,
+        tenant.seedSecretName
+      );
+      for (const lambda of [processLambda, queryLambda, oobiLambda, escrowCheckLambda]) {
+        tenantSeed.grantRead(lambda);
+      }
+    }
+
     // =======================================================================
     // API Routes (attached to shared API Gateway)
     // =======================================================================
@@ -180,6 +255,20 @@ export class WitnessStack extends cdk.Stack {
       new apigateway.LambdaIntegration(processLambda, { proxy: true })
     );
 
+    // POST /{basePath}/schema - Register credential schemas
+    const schemaResource = witnessResource.addResource("schema");
+    schemaResource.addMethod(
+      "POST",
+      new apigateway.LambdaIntegration(processLambda, { proxy: true })
+    );
+
+    // POST /{basePath}/exchange - Submit IPEX exchange messages
+    const exchangeResource = witnessResource.addResource("exchange");
+    exchangeResource.addMethod(
+      "POST",
+      new apigateway.LambdaIntegration(processLambda, { proxy: true })
+    );
+
     // POST /{basePath}/query - Query KEL, state, receipts
     const queryResource = witnessResource.addResource("query");
     queryResource.addMethod(
@@ -187,7 +276,7 @@ export class WitnessStack extends cdk.Stack {
       new apigateway.LambdaIntegration(queryLambda, { proxy: true })
     );
 
-    // GET /{basePath}/oobi/{id} - Resolve OOBI for identifier
+    // GET /{basePath}/oobi/{id} - Resolve OOBI for identifier or schema SAID
     const oobiResource = witnessResource.addResource("oobi");
     const oobiIdResource = oobiResource.addResource("{id}");
     oobiIdResource.addMethod(
@@ -215,6 +304,20 @@ export class WitnessStack extends cdk.Stack {
       description: "Scheduled check for promotable escrowed events",
     });
 
+    // Accepted events and new receipts re-check their identifier's escrows
+    // right away; the schedule above still retries and expires the rest
+    for (const table of [tables.kel, tables.receipts]) {
+      escrowCheckLambda.addEventSource(
+        new lambdaEventSources.DynamoEventSource(table, {
+          startingPosition: lambda.StartingPosition.LATEST,
+          batchSize: 100,
+          maxBatchingWindow: cdk.Duration.seconds(1),
+          bisectBatchOnError: true,
+          retryAttempts: 3,
+        })
+      );
+    }
+
     // =======================================================================
     // Outputs
     // =======================================================================
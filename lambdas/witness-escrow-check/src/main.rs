@@ -1,121 +1,329 @@
-//! Lambda handler for scheduled escrow processing
+//! Lambda handler for escrow processing
 //!
 //! This handler is triggered by CloudWatch Events to process escrowed events.
 //! It checks if escrow conditions are now satisfied and promotes events accordingly.
+//! Escrowed IPEX exchange messages are swept in the same run. Every
+//! tenant's escrows are checked by its own witness, after the default one.
+//!
+//! It is also subscribed to the KEL and receipts table streams. An accepted
+//! event or a new receipt re-checks only the escrows of the identifier it
+//! belongs to, so they are promoted without waiting for the next sweep.
+//! The schedule still retries what the streams do not cover, such as
+//! backed-off attempts and expiry.
 
-use aws_lambda_events::cloudwatch_events::CloudWatchEvent;
-use kerihost_db::DynamoDbDatabase;
-use kerihost_witness::{Witness, WitnessConfig};
+use aws_lambda_events::dynamodb::{Event as StreamEvent, EventRecord};
+use aws_sdk_dynamodb::types::AttributeValue;
+use kerihost_db::dynamodb::{Operation, StreamRecord, TableConfig};
+use kerihost_db::{Change, DynamoDbDatabase, EscrowedEvent, InstrumentedDatabase};
+use kerihost_witness::{Witness, WitnessConfig, WitnessFactory};
 use lambda_runtime::{service_fn, Error, LambdaEvent};
-use std::sync::Arc;
+use serde::de::IgnoredAny;
+use serde::Deserialize;
+use std::collections::{BTreeSet, HashMap};
 use tokio::sync::OnceCell;
 use tracing::{info, warn, error};
 
-/// Global witness instance
-static WITNESS: OnceCell<Witness<DynamoDbDatabase>> = OnceCell::const_new();
+/// Escrowed events fetched per page
+const ESCROW_PAGE_SIZE: usize = 100;
+
+/// Database the witnesses use, with every call traced
+type Db = InstrumentedDatabase<DynamoDbDatabase>;
+
+/// Global witness factory (initialized once)
+static WITNESSES: OnceCell<WitnessFactory<Db>> = OnceCell::const_new();
 
-/// Initialize the witness
-async fn init_witness() -> Witness<DynamoDbDatabase> {
-    let db = Arc::new(DynamoDbDatabase::from_env().await);
-    let config = WitnessConfig::from_env();
-    Witness::new(None, db, config)
+/// Initialize the witness factory
+async fn init_witnesses() -> WitnessFactory<Db> {
+    let db = InstrumentedDatabase::new(DynamoDbDatabase::from_env().await);
+    let config = WitnessConfig::from_env().expect("Invalid witness configuration");
+    WitnessFactory::from_env(db, config)
+        .await
+        .expect("Invalid tenant configuration")
 }
 
-/// Get or initialize the witness
-async fn get_witness() -> &'static Witness<DynamoDbDatabase> {
-    WITNESS.get_or_init(init_witness).await
+/// Get or initialize the witness factory
+async fn get_witnesses() -> &'static WitnessFactory<Db> {
+    WITNESSES.get_or_init(init_witnesses).await
+}
+
+/// What invoked the handler
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum Trigger {
+    /// Records from the KEL and receipts table streams
+    Changes(StreamEvent),
+    /// The scheduled sweep, whose CloudWatch event carries nothing it reads
+    Schedule(IgnoredAny),
 }
 
 /// Lambda handler
-async fn handler(
-    _event: LambdaEvent<CloudWatchEvent>,
-) -> Result<(), Error> {
-    let witness = get_witness().await;
+async fn handler(event: LambdaEvent<Trigger>) -> Result<(), Error> {
+    match event.payload {
+        Trigger::Changes(event) => handle_changes(event.records).await,
+        Trigger::Schedule(_) => sweep().await,
+    }
+}
 
-    info!("Starting escrow check");
+/// Check every tenant's escrows
+async fn sweep() -> Result<(), Error> {
+    let witnesses = get_witnesses().await;
 
-    // Get all escrowed events
-    let escrowed = match witness.get_all_escrowed().await {
-        Ok(e) => e,
-        Err(e) => {
-            error!(error = %e, "Failed to get escrowed events");
-            return Err(e.into());
+    // One tenant's failure does not hold up the others' escrows
+    let mut failed = None;
+    for tenant in std::iter::once(None).chain(witnesses.tenants().map(Some)) {
+        let result = match witnesses.witness(tenant) {
+            Ok(witness) => check_escrows(&witness, tenant.unwrap_or("default")).await,
+            Err(e) => Err(e.into()),
+        };
+        if let Err(e) = result {
+            error!(tenant = tenant.unwrap_or("default"), error = %e, "Escrow check failed");
+            failed = Some(e);
         }
-    };
+    }
 
-    info!(count = escrowed.len(), "Found escrowed events");
+    failed.map_or(Ok(()), Err)
+}
 
-    let mut promoted = 0;
-    let mut expired = 0;
-    let mut kept = 0;
+/// Check the escrows of every identifier whose KEL or receipts changed
+///
+/// A failure fails the batch, so the event source mapping retries it;
+/// checking an identifier's escrows again is harmless.
+async fn handle_changes(records: Vec<EventRecord>) -> Result<(), Error> {
+    let witnesses = get_witnesses().await;
+    let tables = TableConfig::from_env();
 
-    for item in escrowed {
-        // Check if expired
-        if item.is_expired() {
-            info!(
-                digest = %item.event.event.digest,
-                reason = %item.reason,
-                "Removing expired escrow"
-            );
-
-            if let Err(e) = witness.remove_escrowed(&item.event.event.digest).await {
-                warn!(error = %e, "Failed to remove expired escrow");
-            } else {
-                expired += 1;
-            }
+    // Each identifier is checked once however many of its records arrived
+    let mut affected = BTreeSet::new();
+    for record in &records {
+        let Some(table) = record.event_source_arn.as_deref().and_then(table_name) else {
+            warn!(event_id = %record.event_id, "Stream record without a table ARN");
+            continue;
+        };
+        let Some(stream_record) = stream_record(record) else {
             continue;
+        };
+        // Only accepted events and new receipts can make escrows promotable
+        if let Some((
+            tenant,
+            change @ (Change::EventAccepted { .. } | Change::ReceiptAdded { .. }),
+        )) = tables.change_from_stream(table, &stream_record)?
+        {
+            affected.insert((tenant, change.prefix().to_string()));
         }
+    }
 
-        // Check if can be promoted
-        match witness.can_promote(&item).await {
-            Ok(true) => {
-                info!(
-                    digest = %item.event.event.digest,
-                    reason = %item.reason,
-                    "Promoting escrowed event"
-                );
-
-                match witness.promote_escrowed(&item).await {
-                    Ok(_) => {
-                        promoted += 1;
-                    }
-                    Err(e) => {
-                        warn!(
-                            error = %e,
-                            digest = %item.event.event.digest,
-                            "Failed to promote escrowed event"
-                        );
-                    }
-                }
-            }
-            Ok(false) => {
-                kept += 1;
-            }
+    let mut counts = EscrowCounts::default();
+    for (tenant, prefix) in affected {
+        let witness = match witnesses.witness(tenant.as_deref()) {
+            Ok(witness) => witness,
             Err(e) => {
+                // Retrying cannot help a namespace this deployment does not serve
                 warn!(
+                    tenant = tenant.as_deref().unwrap_or("default"),
                     error = %e,
-                    digest = %item.event.event.digest,
-                    "Failed to check escrow promotion"
+                    "Skipping changes for unknown tenant"
                 );
-                kept += 1;
+                continue;
+            }
+        };
+        let mut cursor = None;
+        loop {
+            let page = witness
+                .get_escrowed_by_prefix(&prefix, ESCROW_PAGE_SIZE, cursor.as_deref())
+                .await?;
+            for item in page.items {
+                check_escrow(&witness, item, &mut counts).await;
+            }
+            cursor = page.next;
+            if cursor.is_none() {
+                break;
             }
         }
     }
 
     info!(
-        promoted = promoted,
-        expired = expired,
-        kept = kept,
+        records = records.len(),
+        promoted = counts.promoted,
+        expired = counts.expired,
+        kept = counts.kept,
+        waiting = counts.waiting,
+        "Change-triggered escrow check completed"
+    );
+    Ok(())
+}
+
+/// Get the table a stream's ARN belongs to
+///
+/// Stream ARNs have the form `arn:aws:dynamodb:...:table/<name>/stream/<label>`.
+fn table_name(stream_arn: &str) -> Option<&str> {
+    stream_arn.split('/').nth(1)
+}
+
+/// Convert a Lambda stream record into the form the change feed reads,
+/// keeping the string and number attributes changes are built from
+fn stream_record(record: &EventRecord) -> Option<StreamRecord> {
+    let operation = match record.event_name.as_str() {
+        "INSERT" => Operation::Insert,
+        "MODIFY" => Operation::Modify,
+        "REMOVE" => Operation::Remove,
+        _ => return None,
+    };
+    let convert = |item: &serde_dynamo::Item| -> HashMap<_, _> {
+        item.iter()
+            .filter_map(|(name, value)| match value {
+                serde_dynamo::AttributeValue::S(s) => {
+                    Some((name.clone(), AttributeValue::S(s.clone())))
+                }
+                serde_dynamo::AttributeValue::N(n) => {
+                    Some((name.clone(), AttributeValue::N(n.clone())))
+                }
+                _ => None,
+            })
+            .collect()
+    };
+    // Lambda delivers a missing image as an empty one
+    let image = |item: &serde_dynamo::Item| (!item.is_empty()).then(|| convert(item));
+    Some(StreamRecord {
+        operation,
+        keys: convert(&record.change.keys),
+        new_image: image(&record.change.new_image),
+        old_image: image(&record.change.old_image),
+    })
+}
+
+/// Outcomes of an escrow check
+#[derive(Default)]
+struct EscrowCounts {
+    promoted: usize,
+    expired: usize,
+    kept: usize,
+    waiting: usize,
+}
+
+/// Check one tenant's escrowed events and exchange messages
+async fn check_escrows(witness: &Witness<Db>, tenant: &str) -> Result<(), Error> {
+    info!(tenant = tenant, "Starting escrow check");
+
+    let mut counts = EscrowCounts::default();
+
+    // Sweep one page at a time so large backlogs stay within memory
+    let mut cursor = None;
+    loop {
+        let page = match witness
+            .get_escrowed_page(ESCROW_PAGE_SIZE, cursor.as_deref())
+            .await
+        {
+            Ok(page) => page,
+            Err(e) => {
+                error!(error = %e, "Failed to get escrowed events");
+                return Err(e.into());
+            }
+        };
+
+        info!(count = page.items.len(), "Found escrowed events");
+
+        for item in page.items {
+            check_escrow(witness, item, &mut counts).await;
+        }
+
+        cursor = page.next;
+        if cursor.is_none() {
+            break;
+        }
+    }
+
+    info!(
+        tenant = tenant,
+        promoted = counts.promoted,
+        expired = counts.expired,
+        kept = counts.kept,
+        waiting = counts.waiting,
         "Escrow check completed"
     );
 
+    match witness.ipex().process_escrow().await {
+        Ok(promoted) => info!(promoted = promoted, "Exchange escrow check completed"),
+        Err(e) => warn!(error = %e, "Failed to process exchange escrow"),
+    }
+
     Ok(())
 }
 
+/// Remove an escrowed event if expired, or promote it if it is due and its
+/// conditions are now satisfied
+async fn check_escrow(witness: &Witness<Db>, item: EscrowedEvent, counts: &mut EscrowCounts) {
+    // Check if expired
+    if item.is_expired() {
+        info!(
+            digest = %item.event.event.digest,
+            reason = %item.reason,
+            "Removing expired escrow"
+        );
+
+        if let Err(e) = witness.remove_escrowed(&item.event.event.digest).await {
+            warn!(error = %e, "Failed to remove expired escrow");
+        } else {
+            counts.expired += 1;
+        }
+        return;
+    }
+
+    // Skip escrows still backing off from a failed attempt
+    if !item.is_due() {
+        counts.waiting += 1;
+        return;
+    }
+
+    // Check if can be promoted
+    match witness.can_promote(&item).await {
+        Ok(true) => {
+            info!(
+                digest = %item.event.event.digest,
+                reason = %item.reason,
+                "Promoting escrowed event"
+            );
+
+            match witness.promote_escrowed(&item).await {
+                Ok(_) => {
+                    counts.promoted += 1;
+                }
+                Err(e) => {
+                    warn!(
+                        error = %e,
+                        digest = %item.event.event.digest,
+                        "Failed to promote escrowed event"
+                    );
+                }
+            }
+        }
+        Ok(false) => {
+            if let Err(e) = witness.record_escrow_attempt(&item).await {
+                warn!(
+                    error = %e,
+                    digest = %item.event.event.digest,
+                    "Failed to record escrow attempt"
+                );
+            }
+            counts.kept += 1;
+        }
+        Err(e) => {
+            warn!(
+                error = %e,
+                digest = %item.event.event.digest,
+                "Failed to check escrow promotion"
+            );
+            counts.kept += 1;
+        }
+    }
+}
+
 #[tokio::main]
 async fn main() -> Result<(), Error> {
     tracing_subscriber::fmt()
-        .with_max_level(tracing::Level::INFO)
+        .with_env_filter(
+            tracing_subscriber::EnvFilter::try_from_default_env()
+                .unwrap_or_else(|_| tracing_subscriber::EnvFilter::new("info")),
+        )
         .with_target(false)
         .json()
         .init();
@@ -125,6 +333,70 @@ async fn main() -> Result<(), Error> {
 
 #[cfg(test)]
 mod tests {
-    // Integration tests would go here
-    // Unit tests are limited since this is primarily integration logic
+    use super::*;
+
+    #[test]
+    fn test_stream_trigger_parses_records() {
+        let payload = serde_json::json!({
+            "Records": [{
+                "awsRegion": "us-east-1",
+                "eventID": "1",
+                "eventName": "INSERT",
+                "eventSource": "aws:dynamodb",
+                "eventSourceARN": "arn:aws:dynamodb:us-east-1:123456789012:table/kerihost-kel/stream/2024-01-01T00:00:00.000",
+                "dynamodb": {
+                    "ApproximateCreationDateTime": 1700000000,
+                    "Keys": {"aid": {"S": "DTest123"}, "sn": {"S": "0000000000000001"}},
+                    "NewImage": {
+                        "aid": {"S": "DTest123"},
+                        "sn": {"S": "0000000000000001"},
+                        "digest": {"S": "EDigest"},
+                        "raw": {"B": "AA=="}
+                    },
+                    "SizeBytes": 64,
+                    "StreamViewType": "NEW_AND_OLD_IMAGES"
+                }
+            }]
+        });
+        let Trigger::Changes(event) = serde_json::from_value(payload).unwrap() else {
+            panic!("Stream records parsed as a scheduled trigger");
+        };
+        let record = &event.records[0];
+        let table = table_name(record.event_source_arn.as_deref().unwrap()).unwrap();
+        assert_eq!(table, "kerihost-kel");
+
+        let stream_record = stream_record(record).unwrap();
+        assert_eq!(stream_record.operation, Operation::Insert);
+        assert!(stream_record.old_image.is_none());
+        let config = TableConfig::new("kerihost-kel", "states", "receipts", "escrows");
+        assert_eq!(
+            config.change_from_stream(table, &stream_record).unwrap(),
+            Some((
+                None,
+                Change::EventAccepted {
+                    prefix: "DTest123".to_string(),
+                    sn: 1,
+                    digest: "EDigest".to_string(),
+                }
+            ))
+        );
+    }
+
+    #[test]
+    fn test_scheduled_trigger() {
+        let payload = serde_json::json!({
+            "version": "0",
+            "id": "1",
+            "detail-type": "Scheduled Event",
+            "source": "aws.events",
+            "time": "2024-01-01T00:00:00Z",
+            "region": "us-east-1",
+            "resources": [],
+            "detail": {}
+        });
+        assert!(matches!(
+            serde_json::from_value(payload).unwrap(),
+            Trigger::Schedule(_)
+        ));
+    }
 }
@@ -1,38 +1,66 @@
 //! Lambda handler for OOBI endpoints
 //!
 //! GET /introduce - Get witness OOBI
-//! GET /oobi/{id} - Resolve OOBI for an identifier
+//! GET /oobi/{id} - Resolve OOBI for an identifier, or serve a schema by SAID
 
 use aws_lambda_events::apigw::{ApiGatewayProxyRequest, ApiGatewayProxyResponse};
 use aws_lambda_events::encodings::Body;
 use aws_lambda_events::http::HeaderMap;
-use kerihost_db::DynamoDbDatabase;
-use kerihost_witness::{oobi::Oobi, Witness, WitnessConfig};
+use kerihost_db::{DynamoDbDatabase, InstrumentedDatabase};
+use kerihost_witness::{oobi::Oobi, WitnessConfig, WitnessFactory, TENANT_HEADER};
 use lambda_runtime::{service_fn, Error, LambdaEvent};
 use serde_json::json;
-use std::sync::Arc;
 use tokio::sync::OnceCell;
 use tracing::info;
 
-/// Global witness instance
-static WITNESS: OnceCell<Witness<DynamoDbDatabase>> = OnceCell::const_new();
+/// Database the witnesses use, with every call traced
+type Db = InstrumentedDatabase<DynamoDbDatabase>;
 
-/// Initialize the witness
-async fn init_witness() -> Witness<DynamoDbDatabase> {
-    let db = Arc::new(DynamoDbDatabase::from_env().await);
-    let config = WitnessConfig::from_env();
-    Witness::new(None, db, config)
+/// Global witness factory (initialized once)
+static WITNESSES: OnceCell<WitnessFactory<Db>> = OnceCell::const_new();
+
+/// Initialize the witness factory
+async fn init_witnesses() -> WitnessFactory<Db> {
+    let db = InstrumentedDatabase::new(DynamoDbDatabase::from_env().await);
+    let config = WitnessConfig::from_env().expect("Invalid witness configuration");
+    WitnessFactory::from_env(db, config)
+        .await
+        .expect("Invalid tenant configuration")
+}
+
+/// Get or initialize the witness factory
+async fn get_witnesses() -> &'static WitnessFactory<Db> {
+    WITNESSES.get_or_init(init_witnesses).await
 }
 
-/// Get or initialize the witness
-async fn get_witness() -> &'static Witness<DynamoDbDatabase> {
-    WITNESS.get_or_init(init_witness).await
+/// Tenant a request is for: the `tenant` path parameter, else the tenant
+/// header, else none for the default witness
+fn request_tenant(request: &ApiGatewayProxyRequest) -> Option<&str> {
+    request
+        .path_parameters
+        .get("tenant")
+        .map(String::as_str)
+        .or_else(|| {
+            request
+                .headers
+                .get(TENANT_HEADER)
+                .and_then(|value| value.to_str().ok())
+        })
 }
 
 /// Create API Gateway response
 fn response(status: i64, body: serde_json::Value) -> ApiGatewayProxyResponse {
+    response_with_type(status, body, "application/json")
+}
+
+/// Create API Gateway response with a specific content type
+fn response_with_type(
+    status: i64,
+    body: serde_json::Value,
+    content_type: &str,
+) -> ApiGatewayProxyResponse {
     let mut headers = HeaderMap::new();
-    headers.insert("content-type", "application/json".parse().unwrap());
+    headers.insert("content-type", content_type.parse().unwrap());
     headers.insert("access-control-allow-origin", "*".parse().unwrap());
 
     ApiGatewayProxyResponse {
@@ -48,8 +76,20 @@ fn response(status: i64, body: serde_json::Value) -> ApiGatewayProxyResponse {
 async fn handler(
     event: LambdaEvent<ApiGatewayProxyRequest>,
 ) -> Result<ApiGatewayProxyResponse, Error> {
-    let witness = get_witness().await;
     let now = chrono::Utc::now().to_rfc3339();
+    let tenant = request_tenant(&event.payload);
+    let witness = match get_witnesses().await.witness(tenant) {
+        Ok(witness) => witness,
+        Err(e) => {
+            return Ok(response(
+                404,
+                json!({
+                    "error": e.to_string(),
+                    "asOf": now
+                }),
+            ));
+        }
+    };
 
     let full_path = event.payload.path.as_deref().unwrap_or("/");
 
@@ -102,6 +142,27 @@ async fn handler(
         let prefix = parts[0];
         info!(prefix = %prefix, "Resolving OOBI");
 
+        // Data OOBI: schemas are served verbatim so the SAID can be verified
+        match witness.schema_registry().get(prefix).await {
+            Ok(Some(schema)) => {
+                return Ok(response_with_type(
+                    200,
+                    schema.schema,
+                    "application/schema+json",
+                ));
+            }
+            Ok(None) => {}
+            Err(e) => {
+                return Ok(response(
+                    500,
+                    json!({
+                        "error": e.to_string(),
+                        "asOf": now
+                    }),
+                ));
+            }
+        }
+
         // Get state for the identifier
         match witness.get_state(prefix).await {
             Ok(Some(state)) => {
@@ -150,7 +211,10 @@ async fn handler(
 #[tokio::main]
 async fn main() -> Result<(), Error> {
     tracing_subscriber::fmt()
-        .with_max_level(tracing::Level::INFO)
+        .with_env_filter(
+            tracing_subscriber::EnvFilter::try_from_default_env()
+                .unwrap_or_else(|_| tracing_subscriber::EnvFilter::new("info")),
+        )
         .with_target(false)
         .json()
         .init();
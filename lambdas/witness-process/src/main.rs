@@ -1,6 +1,8 @@
 //! Lambda handler for processing KERI events
 //!
 //! POST /process - Receive and process KERI events
+//! POST /schema - Register a SAIDified credential schema
+//! POST /exchange - Process an IPEX `exn` message
 //!
 //! This handler:
 //! 1. Parses incoming CESR-encoded events
@@ -10,27 +12,48 @@
 use aws_lambda_events::apigw::{ApiGatewayProxyRequest, ApiGatewayProxyResponse};
 use aws_lambda_events::encodings::Body;
 use aws_lambda_events::http::HeaderMap;
-use kerihost_db::DynamoDbDatabase;
-use kerihost_witness::{ProcessResult, Witness, WitnessConfig};
+use kerihost_db::{DynamoDbDatabase, InstrumentedDatabase};
+use kerihost_witness::{
+    IpexResult, ProcessResult, WitnessConfig, WitnessError, WitnessFactory, TENANT_HEADER,
+};
 use lambda_runtime::{service_fn, Error, LambdaEvent};
 use serde_json::json;
-use std::sync::Arc;
 use tokio::sync::OnceCell;
 use tracing::{info, error};
 
-/// Global witness instance (initialized once)
-static WITNESS: OnceCell<Witness<DynamoDbDatabase>> = OnceCell::const_new();
+/// Database the witnesses use, with every call traced
+type Db = InstrumentedDatabase<DynamoDbDatabase>;
 
-/// Initialize the witness
-async fn init_witness() -> Witness<DynamoDbDatabase> {
-    let db = Arc::new(DynamoDbDatabase::from_env().await);
-    let config = WitnessConfig::from_env();
-    Witness::new(None, db, config)
+/// Global witness factory (initialized once)
+static WITNESSES: OnceCell<WitnessFactory<Db>> = OnceCell::const_new();
+
+/// Initialize the witness factory
+async fn init_witnesses() -> WitnessFactory<Db> {
+    let db = InstrumentedDatabase::new(DynamoDbDatabase::from_env().await);
+    let config = WitnessConfig::from_env().expect("Invalid witness configuration");
+    WitnessFactory::from_env(db, config)
+        .await
+        .expect("Invalid tenant configuration")
+}
+
+/// Get or initialize the witness factory
+async fn get_witnesses() -> &'static WitnessFactory<Db> {
+    WITNESSES.get_or_init(init_witnesses).await
 }
 
-/// Get or initialize the witness
-async fn get_witness() -> &'static Witness<DynamoDbDatabase> {
-    WITNESS.get_or_init(init_witness).await
+/// Tenant a request is for: the `tenant` path parameter, else the tenant
+/// header, else none for the default witness
+fn request_tenant(request: &ApiGatewayProxyRequest) -> Option<&str> {
+    request
+        .path_parameters
+        .get("tenant")
+        .map(String::as_str)
+        .or_else(|| {
+            request
+                .headers
+                .get(TENANT_HEADER)
+                .and_then(|value| value.to_str().ok())
+        })
 }
 
 /// Create API Gateway response
@@ -48,12 +71,34 @@ fn response(status: i64, body: serde_json::Value) -> ApiGatewayProxyResponse {
     }
 }
 
+/// Status for a failed request: storage failures are the server's fault,
+/// and retryable ones are reported as unavailable
+fn error_status(error: &WitnessError) -> i64 {
+    match error {
+        WitnessError::Database(e) if e.is_retryable() => 503,
+        WitnessError::Database(_) => 500,
+        _ => 400,
+    }
+}
+
 /// Lambda handler
 async fn handler(
     event: LambdaEvent<ApiGatewayProxyRequest>,
 ) -> Result<ApiGatewayProxyResponse, Error> {
-    let witness = get_witness().await;
     let now = chrono::Utc::now().to_rfc3339();
+    let tenant = request_tenant(&event.payload);
+    let witness = match get_witnesses().await.witness(tenant) {
+        Ok(witness) => witness,
+        Err(e) => {
+            return Ok(response(
+                404,
+                json!({
+                    "error": e.to_string(),
+                    "asOf": now
+                }),
+            ));
+        }
+    };
 
     // Get request body
     let body = match &event.payload.body {
@@ -87,6 +132,82 @@ async fn handler(
         body.into_bytes()
     };
 
+    // Handle schema registration
+    let path = event.payload.path.as_deref().unwrap_or("/");
+    if path.ends_with("/schema") {
+        return Ok(match witness.schema_registry().register(&raw).await {
+            Ok(schema) => {
+                info!(said = %schema.said, "Schema registered");
+
+                response(
+                    200,
+                    json!({
+                        "status": "registered",
+                        "said": schema.said,
+                        "oobi": witness.schema_oobi_url(&schema.said),
+                        "asOf": now
+                    }),
+                )
+            }
+            Err(e) => {
+                error!(error = %e, "Failed to register schema");
+
+                response(
+                    error_status(&e),
+                    json!({
+                        "error": e.to_string(),
+                        "asOf": now
+                    }),
+                )
+            }
+        });
+    }
+
+    // Handle IPEX exchange messages
+    if path.ends_with("/exchange") {
+        return Ok(match witness.ipex().process(&raw).await {
+            Ok(IpexResult::Accepted { exchange }) => {
+                info!(exchange = %exchange.id, state = %exchange.state, "Exchange message accepted");
+
+                response(
+                    200,
+                    json!({
+                        "status": "accepted",
+                        "exchange": exchange,
+                        "asOf": now
+                    }),
+                )
+            }
+            Ok(IpexResult::Escrowed { prior }) => response(
+                202,
+                json!({
+                    "status": "escrowed",
+                    "reason": "missing_prior",
+                    "prior": prior,
+                    "asOf": now
+                }),
+            ),
+            Ok(IpexResult::Duplicate) => response(
+                200,
+                json!({
+                    "status": "duplicate",
+                    "asOf": now
+                }),
+            ),
+            Err(e) => {
+                error!(error = %e, "Failed to process exchange message");
+
+                response(
+                    error_status(&e),
+                    json!({
+                        "error": e.to_string(),
+                        "asOf": now
+                    }),
+                )
+            }
+        });
+    }
+
     // Process the event
     match witness.process_notice(&raw).await {
         Ok(ProcessResult::Accepted { receipt, state }) => {
@@ -143,7 +264,7 @@ async fn handler(
             error!(error = %e, "Failed to process event");
 
             Ok(response(
-                400,
+                error_status(&e),
                 json!({
                     "error": e.to_string(),
                     "asOf": now
@@ -197,9 +318,12 @@ fn base64_decode(s: &str) -> Result<Vec<u8>, String> {
 
 #[tokio::main]
 async fn main() -> Result<(), Error> {
-    // Initialize tracing
+    // Initialize tracing; RUST_LOG=info,kerihost_db=debug adds storage call timings
     tracing_subscriber::fmt()
-        .with_max_level(tracing::Level::INFO)
+        .with_env_filter(
+            tracing_subscriber::EnvFilter::try_from_default_env()
+                .unwrap_or_else(|_| tracing_subscriber::EnvFilter::new("info")),
+        )
         .with_target(false)
         .json()
         .init();
@@ -210,6 +334,7 @@ async fn main() -> Result<(), Error> {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use kerihost_db::DbError;
 
     #[test]
     fn test_base64_decode() {
@@ -218,6 +343,16 @@ mod tests {
         assert_eq!(String::from_utf8(decoded).unwrap(), "Hello World");
     }
 
+    #[test]
+    fn test_error_status() {
+        let invalid = WitnessError::Validation("bad schema".to_string());
+        let throttled = WitnessError::Database(DbError::Throttled("slow down".to_string()));
+        let corrupt = WitnessError::Database(DbError::Corruption("bad row".to_string()));
+        assert_eq!(error_status(&invalid), 400);
+        assert_eq!(error_status(&throttled), 503);
+        assert_eq!(error_status(&corrupt), 500);
+    }
+
     #[test]
     fn test_response_format() {
         let resp = response(200, json!({"status": "ok"}));
@@ -3,35 +3,63 @@
 //! POST /query - Query KEL, state, or receipts
 //!
 //! This handler supports:
-//! - state: Get current key state for an identifier
-//! - kel: Get events from the KEL
+//! - state: Get current key state for an identifier, or its state at event
+//!   `sn` or `as_of` an RFC 3339 datetime
+//! - kel: Get a page of events from the KEL; pass the returned `next`
+//!   back as `cursor` to fetch the following page
 //! - receipts: Get receipts for an event
+//! - anchor: Find the event anchoring a seal digest (or event seal)
 
 use aws_lambda_events::apigw::{ApiGatewayProxyRequest, ApiGatewayProxyResponse};
 use aws_lambda_events::encodings::Body;
 use aws_lambda_events::http::HeaderMap;
-use kerihost_db::DynamoDbDatabase;
-use kerihost_witness::{Witness, WitnessConfig};
+use kerihost_db::{DbError, DynamoDbDatabase, InstrumentedDatabase};
+use kerihost_witness::{WitnessConfig, WitnessFactory, TENANT_HEADER, WitnessError};
 use lambda_runtime::{service_fn, Error, LambdaEvent};
 use serde::Deserialize;
 use serde_json::json;
-use std::sync::Arc;
 use tokio::sync::OnceCell;
 use tracing::{info, error};
 
-/// Global witness instance
-static WITNESS: OnceCell<Witness<DynamoDbDatabase>> = OnceCell::const_new();
+/// Events per KEL page when the request sets no limit
+const DEFAULT_KEL_PAGE_SIZE: usize = 100;
 
-/// Initialize the witness
-async fn init_witness() -> Witness<DynamoDbDatabase> {
-    let db = Arc::new(DynamoDbDatabase::from_env().await);
-    let config = WitnessConfig::from_env();
-    Witness::new(None, db, config)
+/// Largest KEL page a request may ask for
+const MAX_KEL_PAGE_SIZE: usize = 1000;
+
+/// Database the witnesses use, with every call traced
+type Db = InstrumentedDatabase<DynamoDbDatabase>;
+
+/// Global witness factory (initialized once)
+static WITNESSES: OnceCell<WitnessFactory<Db>> = OnceCell::const_new();
+
+/// Initialize the witness factory
+async fn init_witnesses() -> WitnessFactory<Db> {
+    let db = InstrumentedDatabase::new(DynamoDbDatabase::from_env().await);
+    let config = WitnessConfig::from_env().expect("Invalid witness configuration");
+    WitnessFactory::from_env(db, config)
+        .await
+        .expect("Invalid tenant configuration")
+}
+
+/// Get or initialize the witness factory
+async fn get_witnesses() -> &'static WitnessFactory<Db> {
+    WITNESSES.get_or_init(init_witnesses).await
 }
 
-/// Get or initialize the witness
-async fn get_witness() -> &'static Witness<DynamoDbDatabase> {
-    WITNESS.get_or_init(init_witness).await
+/// Tenant a request is for: the `tenant` path parameter, else the tenant
+/// header, else none for the default witness
+fn request_tenant(request: &ApiGatewayProxyRequest) -> Option<&str> {
+    request
+        .path_parameters
+        .get("tenant")
+        .map(String::as_str)
+        .or_else(|| {
+            request
+                .headers
+                .get(TENANT_HEADER)
+                .and_then(|value| value.to_str().ok())
+        })
 }
 
 /// Query request
@@ -42,12 +70,26 @@ struct QueryRequest {
     query_type: String,
     /// Identifier prefix
     prefix: Option<String>,
+    /// Sequence number of the event to get state at (for state query)
+    sn: Option<u64>,
+    /// Datetime to get state as of (for state query)
+    as_of: Option<String>,
     /// Event digest (for receipts query)
     event_digest: Option<String>,
     /// Start sequence number (for kel query)
     start_sn: Option<u64>,
     /// End sequence number (for kel query)
     end_sn: Option<u64>,
+    /// Page size (for kel query)
+    limit: Option<usize>,
+    /// Continuation token from a previous page (for kel query)
+    cursor: Option<String>,
+    /// Seal digest (for anchor query)
+    digest: Option<String>,
+    /// Sealed event prefix (for event seal anchor query)
+    seal_prefix: Option<String>,
+    /// Sealed event sequence number (for event seal anchor query)
+    seal_sn: Option<u64>,
 }
 
 /// Create API Gateway response
@@ -69,8 +111,20 @@ fn response(status: i64, body: serde_json::Value) -> ApiGatewayProxyResponse {
 async fn handler(
     event: LambdaEvent<ApiGatewayProxyRequest>,
 ) -> Result<ApiGatewayProxyResponse, Error> {
-    let witness = get_witness().await;
     let now = chrono::Utc::now().to_rfc3339();
+    let tenant = request_tenant(&event.payload);
+    let witness = match get_witnesses().await.witness(tenant) {
+        Ok(witness) => witness,
+        Err(e) => {
+            return Ok(response(
+                404,
+                json!({
+                    "error": e.to_string(),
+                    "asOf": now
+                }),
+            ));
+        }
+    };
 
     // Parse query request
     let query: QueryRequest = match &event.payload.body {
@@ -104,7 +158,29 @@ async fn handler(
                 }
             };
 
-            match witness.get_state(&prefix).await {
+            let state = match (query.sn, query.as_of) {
+                (Some(sn), _) => witness.get_state_at(&prefix, sn).await,
+                (None, Some(as_of)) => match chrono::DateTime::parse_from_rfc3339(&as_of) {
+                    Ok(datetime) => {
+                        witness
+                            .get_state_as_of(&prefix, datetime.with_timezone(&chrono::Utc))
+                            .await
+                    }
+                    Err(_) => {
+                        return Ok(response(
+                            400,
+                            json!({
+                                "error": "Invalid as_of datetime for state query",
+                                "as_of": as_of,
+                                "asOf": now
+                            }),
+                        ));
+                    }
+                },
+                (None, None) => witness.get_state(&prefix).await,
+            };
+
+            match state {
                 Ok(Some(state)) => {
                     info!(prefix = %prefix, "State query successful");
                     Ok(response(200, json!({ "state": state, "asOf": now })))
@@ -148,19 +224,35 @@ async fn handler(
 
             let start = query.start_sn.unwrap_or(0);
             let end = query.end_sn;
+            let limit = query
+                .limit
+                .unwrap_or(DEFAULT_KEL_PAGE_SIZE)
+                .clamp(1, MAX_KEL_PAGE_SIZE);
 
-            match witness.get_kel(&prefix, start, end).await {
-                Ok(events) => {
-                    info!(prefix = %prefix, count = %events.len(), "KEL query successful");
+            match witness
+                .get_kel_page(&prefix, start, end, limit, query.cursor.as_deref())
+                .await
+            {
+                Ok(page) => {
+                    info!(prefix = %prefix, count = %page.items.len(), "KEL query successful");
                     Ok(response(
                         200,
                         json!({
-                            "events": events,
-                            "count": events.len(),
+                            "events": page.items,
+                            "count": page.items.len(),
+                            "next": page.next,
                             "asOf": now
                         }),
                     ))
                 }
+                Err(WitnessError::Database(DbError::InvalidCursor(cursor))) => Ok(response(
+                    400,
+                    json!({
+                        "error": "Invalid cursor for kel query",
+                        "cursor": cursor,
+                        "asOf": now
+                    }),
+                )),
                 Err(e) => {
                     error!(error = %e, "KEL query failed");
                     Ok(response(
@@ -213,6 +305,69 @@ async fn handler(
             }
         }
 
+        "anchor" => {
+            let digest = match query.digest {
+                Some(d) => d,
+                None => {
+                    return Ok(response(
+                        400,
+                        json!({
+                            "error": "Missing digest for anchor query",
+                            "asOf": now
+                        }),
+                    ));
+                }
+            };
+
+            let result = match (query.seal_prefix, query.seal_sn) {
+                (Some(prefix), Some(sn)) => witness.find_event_seal(&prefix, sn, &digest).await,
+                (None, None) => witness.find_anchor(&digest).await,
+                _ => {
+                    return Ok(response(
+                        400,
+                        json!({
+                            "error": "Event seal query needs both seal_prefix and seal_sn",
+                            "asOf": now
+                        }),
+                    ));
+                }
+            };
+
+            match result {
+                Ok(Some(location)) => {
+                    info!(digest = %digest, prefix = %location.prefix, sn = %location.sn, "Anchor query successful");
+                    Ok(response(
+                        200,
+                        json!({
+                            "prefix": location.prefix,
+                            "sn": location.sn,
+                            "eventDigest": location.event_digest,
+                            "seal": location.seal,
+                            "asOf": now
+                        }),
+                    ))
+                }
+                Ok(None) => Ok(response(
+                    404,
+                    json!({
+                        "error": "Seal not anchored",
+                        "digest": digest,
+                        "asOf": now
+                    }),
+                )),
+                Err(e) => {
+                    error!(error = %e, "Anchor query failed");
+                    Ok(response(
+                        500,
+                        json!({
+                            "error": e.to_string(),
+                            "asOf": now
+                        }),
+                    ))
+                }
+            }
+        }
+
         _ => Ok(response(
             400,
             json!({
@@ -226,7 +381,10 @@ async fn handler(
 #[tokio::main]
 async fn main() -> Result<(), Error> {
     tracing_subscriber::fmt()
-        .with_max_level(tracing::Level::INFO)
+        .with_env_filter(
+            tracing_subscriber::EnvFilter::try_from_default_env()
+                .unwrap_or_else(|_| tracing_subscriber::EnvFilter::new("info")),
+        )
         .with_target(false)
         .json()
         .init();
@@ -246,6 +404,18 @@ mod tests {
         assert_eq!(query.prefix, Some("DTest123".to_string()));
     }
 
+    #[test]
+    fn test_query_request_historical_state() {
+        let json = r#"{"query_type": "state", "prefix": "DTest123", "sn": 42}"#;
+        let query: QueryRequest = serde_json::from_str(json).unwrap();
+        assert_eq!(query.sn, Some(42));
+        assert!(query.as_of.is_none());
+
+        let json = r#"{"query_type": "state", "prefix": "DTest123", "as_of": "2026-01-01T00:00:00Z"}"#;
+        let query: QueryRequest = serde_json::from_str(json).unwrap();
+        assert_eq!(query.as_of.as_deref(), Some("2026-01-01T00:00:00Z"));
+    }
+
     #[test]
     fn test_query_request_kel() {
         let json = r#"{"query_type": "kel", "prefix": "DTest123", "start_sn": 0, "end_sn": 10}"#;
@@ -253,5 +423,23 @@ mod tests {
         assert_eq!(query.query_type, "kel");
         assert_eq!(query.start_sn, Some(0));
         assert_eq!(query.end_sn, Some(10));
+        assert!(query.cursor.is_none());
+    }
+
+    #[test]
+    fn test_query_request_kel_page() {
+        let json = r#"{"query_type": "kel", "prefix": "DTest123", "limit": 500, "cursor": "1f4"}"#;
+        let query: QueryRequest = serde_json::from_str(json).unwrap();
+        assert_eq!(query.limit, Some(500));
+        assert_eq!(query.cursor.as_deref(), Some("1f4"));
+    }
+
+    #[test]
+    fn test_query_request_anchor() {
+        let json = r#"{"query_type": "anchor", "digest": "EDelegated", "seal_prefix": "EDelegate", "seal_sn": 0}"#;
+        let query: QueryRequest = serde_json::from_str(json).unwrap();
+        assert_eq!(query.query_type, "anchor");
+        assert_eq!(query.digest, Some("EDelegated".to_string()));
+        assert_eq!(query.seal_sn, Some(0));
     }
 }
@@ -0,0 +1,37 @@
+#!/usr/bin/env bash
+# Run the storage conformance suites against PostgreSQL and DynamoDB Local
+# in throwaway containers. Backend tests fail instead of skipping when their
+# server is not configured, so the suites cannot pass without running.
+set -euo pipefail
+
+POSTGRES_PORT="${POSTGRES_PORT:-55432}"
+DYNAMODB_PORT="${DYNAMODB_PORT:-58000}"
+POSTGRES_CONTAINER="kerihost-test-postgres"
+DYNAMODB_CONTAINER="kerihost-test-dynamodb"
+
+cleanup() {
+  docker rm -f "$POSTGRES_CONTAINER" "$DYNAMODB_CONTAINER" >/dev/null 2>&1 || true
+}
+trap cleanup EXIT
+cleanup
+
+docker run -d --name "$POSTGRES_CONTAINER" \
+  -e POSTGRES_HOST_AUTH_METHOD=trust -e POSTGRES_DB=kerihost_test \
+  -p "$POSTGRES_PORT:5432" postgres:15 >/dev/null
+docker run -d --name "$DYNAMODB_CONTAINER" \
+  -p "$DYNAMODB_PORT:8000" amazon/dynamodb-local -jar DynamoDBLocal.jar -inMemory >/dev/null
+
+echo "Waiting for PostgreSQL..."
+until docker exec "$POSTGRES_CONTAINER" pg_isready -U postgres -d kerihost_test >/dev/null 2>&1; do
+  sleep 1
+done
+echo "Waiting for DynamoDB Local..."
+until curl -s -o /dev/null "http://localhost:$DYNAMODB_PORT"; do
+  sleep 1
+done
+
+export KERIHOST_TEST_REQUIRE_BACKENDS=1
+export KERIHOST_TEST_POSTGRES_URL="postgres://postgres@localhost:$POSTGRES_PORT/kerihost_test"
+export KERIHOST_TEST_DYNAMODB_ENDPOINT="http://localhost:$DYNAMODB_PORT"
+
+cargo test -p kerihost-db --features sqlite,postgres,redb,prometheus "$@"